@@ -1,6 +1,23 @@
-use clap::{Arg, ArgAction, ArgMatches, Command};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::parser::ValueSource;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::tmux::QueryScope;
+use crate::config::graph::GraphFormat;
+use crate::config::loader::{self, LoadOptions};
+use crate::config::missing_dirs::MissingDirsPolicy;
+use crate::config::sizes::ClientSize;
+use crate::config::templates;
+use crate::config::{MergeStrategy, RestartPolicy};
+use crate::logging::Level;
+use crate::plugin::Action;
+use crate::shell_init::Shell;
+use crate::tmux::import::{Relativize, SessionOrder};
+use crate::tmux::layout::Sizes;
+use crate::tmux::{QueryScope, TmuxSocket};
+use crate::ui::ColorChoice;
+use crate::ExitCode;
 
 #[derive(Debug)]
 pub enum Subcommand<'a> {
@@ -8,10 +25,31 @@ pub enum Subcommand<'a> {
     Export(ExportOpts<'a>),
     DumpCommand(DumpCommandOps<'a>),
     DumpConfig(DumpConfigOps<'a>),
+    Check(CheckOpts<'a>),
+    Save(SaveOpts<'a>),
+    Restore(RestoreOpts<'a>),
+    Popup(PopupOpts<'a>),
+    Respawn(RespawnOpts<'a>),
+    Supervise(SuperviseOpts<'a>),
+    ReadOnly(ReadOnlyOpts<'a>),
+    Focus(FocusOpts<'a>),
+    Swap(SwapOpts<'a>),
+    Kill(KillOpts<'a>),
+    Rebalance(RebalanceOpts<'a>),
+    Plugin(PluginOpts<'a>),
+    ShellInit(ShellInitOpts<'a>),
+    ConvertLayout(ConvertLayoutOpts<'a>),
+    Man(ManOpts<'a>),
+    Edit(EditOpts<'a>),
+    Init(InitOpts<'a>),
+    Snapshot(SnapshotOpts<'a>),
+    Pick(PickOpts<'a>),
+    Preview(PreviewOpts<'a>),
 }
 
 impl Subcommand<'_> {
     pub fn from_matches(matches: &ArgMatches) -> Option<Subcommand<'_>> {
+        let output = OutputFormat::from_matches(matches);
         match matches.subcommand() {
             None => None,
             Some(("create", sub_matches)) => {
@@ -21,11 +59,70 @@ impl Subcommand<'_> {
                 DumpCommandOps::from_matches(sub_matches),
             )),
             Some(("dump-config", sub_matches)) => Some(Subcommand::DumpConfig(
-                DumpConfigOps::from_matches(sub_matches),
+                DumpConfigOps::from_matches(sub_matches, output),
             )),
-            Some(("export", sub_matches)) => {
-                Some(Subcommand::Export(ExportOpts::from_matches(sub_matches)))
+            Some(("save", sub_matches)) => {
+                Some(Subcommand::Save(SaveOpts::from_matches(sub_matches)))
             }
+            Some(("restore", sub_matches)) => {
+                Some(Subcommand::Restore(RestoreOpts::from_matches(sub_matches)))
+            }
+            Some(("popup", sub_matches)) => {
+                Some(Subcommand::Popup(PopupOpts::from_matches(sub_matches)))
+            }
+            Some(("respawn", sub_matches)) => {
+                Some(Subcommand::Respawn(RespawnOpts::from_matches(sub_matches)))
+            }
+            Some(("supervise", sub_matches)) => Some(Subcommand::Supervise(
+                SuperviseOpts::from_matches(sub_matches),
+            )),
+            Some(("read-only", sub_matches)) => Some(Subcommand::ReadOnly(
+                ReadOnlyOpts::from_matches(sub_matches),
+            )),
+            Some(("focus", sub_matches)) => {
+                Some(Subcommand::Focus(FocusOpts::from_matches(sub_matches)))
+            }
+            Some(("swap", sub_matches)) => {
+                Some(Subcommand::Swap(SwapOpts::from_matches(sub_matches)))
+            }
+            Some(("kill", sub_matches)) => {
+                Some(Subcommand::Kill(KillOpts::from_matches(sub_matches)))
+            }
+            Some(("rebalance", sub_matches)) => Some(Subcommand::Rebalance(
+                RebalanceOpts::from_matches(sub_matches),
+            )),
+            Some(("plugin", sub_matches)) => {
+                Some(Subcommand::Plugin(PluginOpts::from_matches(sub_matches)))
+            }
+            Some(("shell-init", sub_matches)) => Some(Subcommand::ShellInit(
+                ShellInitOpts::from_matches(sub_matches),
+            )),
+            Some(("convert-layout", sub_matches)) => Some(Subcommand::ConvertLayout(
+                ConvertLayoutOpts::from_matches(sub_matches, output),
+            )),
+            Some(("man", sub_matches)) => Some(Subcommand::Man(ManOpts::from_matches(sub_matches))),
+            Some(("init", sub_matches)) => {
+                Some(Subcommand::Init(InitOpts::from_matches(sub_matches)))
+            }
+            Some(("snapshot", sub_matches)) => Some(Subcommand::Snapshot(
+                SnapshotOpts::from_matches(sub_matches),
+            )),
+            Some(("pick", sub_matches)) => {
+                Some(Subcommand::Pick(PickOpts::from_matches(sub_matches)))
+            }
+            Some(("edit", sub_matches)) => {
+                Some(Subcommand::Edit(EditOpts::from_matches(sub_matches)))
+            }
+            Some(("preview", sub_matches)) => {
+                Some(Subcommand::Preview(PreviewOpts::from_matches(sub_matches)))
+            }
+            Some(("check", sub_matches)) => {
+                Some(Subcommand::Check(CheckOpts::from_matches(sub_matches)))
+            }
+            Some(("export", sub_matches)) => Some(Subcommand::Export(ExportOpts::from_matches(
+                sub_matches,
+                output,
+            ))),
             _ => unreachable!("undefined subcommand"),
         }
     }
@@ -34,8 +131,37 @@ impl Subcommand<'_> {
 #[derive(Debug)]
 pub struct CreateOpts<'a> {
     pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Create all windows in the current session (see `--here`)
+    pub here: bool,
+    /// Session to add the top-level windows to (`Some(None)`: the current one)
+    pub into_session: Option<Option<&'a str>>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
     pub session_select_mode: SessionSelectModeOption,
-    pub ignore_existing_sessions: bool,
+    /// Detach other clients when attaching (`--detach-others`)
+    pub detach_others: bool,
+    pub on_conflict: Option<ConflictStrategy>,
+    pub update_existing_sessions: bool,
+    /// Skip top-level windows whose name exists in the session they're
+    /// added to (`--ignore-existing-windows`)
+    pub ignore_existing_windows: bool,
+    /// Select running sessions instead of creating them (`--attach-if-exists`)
+    pub attach_if_exists: bool,
+    pub missing_dirs: MissingDirsPolicy,
+    /// Target to select instead of the config's (`--select-target`)
+    pub select_target: Option<&'a str>,
+    /// Run every tmux command on its own and report the results
+    pub sequential: bool,
+    /// Print the resolved config before creating it (`--print-config`)
+    pub print_config: bool,
+    /// Only print the resolved config (`--resolve-only`)
+    pub resolve_only: bool,
+    /// Shell command to run after the layout was created (overrides
+    /// `on_created` of the config)
+    pub post_cmd: Option<&'a str>,
     pub tmux_args: Vec<&'a str>,
 }
 
@@ -43,12 +169,30 @@ impl CreateOpts<'_> {
     fn from_matches(matches: &ArgMatches) -> CreateOpts<'_> {
         CreateOpts {
             config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
             session_select_mode: SessionSelectModeOption::from_arg(
                 matches
                     .get_one::<String>("session-select-mode")
                     .map(|s| s.as_str()),
             ),
-            ignore_existing_sessions: matches.get_flag("ignore-existing-sessions"),
+            detach_others: matches.get_flag("detach-others"),
+            on_conflict: ConflictStrategy::from_matches(matches),
+            missing_dirs: missing_dirs_from_matches(matches),
+            select_target: matches
+                .get_one::<String>("select-target")
+                .map(|s| s.as_str()),
+            update_existing_sessions: matches.get_flag("update-existing-sessions")
+                || matches.get_flag("ignore-existing-windows"),
+            ignore_existing_windows: matches.get_flag("ignore-existing-windows"),
+            attach_if_exists: matches.get_flag("attach-if-exists"),
+            into_session: into_session_from_matches(matches),
+            here: matches.get_flag("here"),
+            sequential: matches.get_flag("sequential"),
+            print_config: matches.get_flag("print-config"),
+            resolve_only: matches.get_flag("resolve-only"),
+            post_cmd: matches.get_one::<String>("post-cmd").map(|s| s.as_str()),
             tmux_args: matches
                 .get_many::<String>("tmux args")
                 .into_iter()
@@ -62,15 +206,81 @@ impl CreateOpts<'_> {
 #[derive(Debug)]
 pub struct ExportOpts<'a> {
     pub scope: QueryScope,
+    /// Names of the windows to export as top-level windows (`--windows`)
+    pub windows: Option<Vec<&'a str>>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    /// Directory to capture the pane contents into (`--include-history`),
+    /// relative to the config file with `--append`
+    pub history_dir: Option<PathBuf>,
+    /// Record the PIDs and foreground commands of panes
+    pub include_processes: bool,
+    /// Record the alerts (bell, activity, silence) of windows
+    pub include_alerts: bool,
+    pub relativize: Relativize,
+    pub sizes: Sizes,
+    /// Order of the exported sessions (`--sort`)
+    pub sort: SessionOrder,
+    /// Strip commands and home paths (see `Config::redact`)
+    pub redact: bool,
+    /// Sort sessions by name and normalize sizes (see `Config::canonicalize`)
+    pub canonical: bool,
     pub format: ConfigFormat,
+    /// Config file to add the exported sessions/windows to (`--append`)
+    pub append: Option<&'a str>,
+    /// Config file to compare the export with (`--diff-against`)
+    pub diff_against: Option<&'a str>,
     pub tmux_args: Vec<&'a str>,
 }
 
 impl ExportOpts<'_> {
-    fn from_matches(matches: &ArgMatches) -> ExportOpts<'_> {
+    fn from_matches(matches: &ArgMatches, output: OutputFormat) -> ExportOpts<'_> {
+        let windows = matches
+            .get_many::<String>("windows")
+            .map(|names| names.map(|s| s.as_str()).collect::<Vec<_>>());
+        let scope = match matches.get_one::<QueryScope>("scope").unwrap() {
+            // Windows are picked from the current session by default.
+            QueryScope::AllSessions if windows.is_some() => QueryScope::CurrentSession,
+            scope => scope.clone(),
+        };
+        let append = matches.get_one::<String>("append").map(|s| s.as_str());
+
         ExportOpts {
-            scope: QueryScope::from_arg(matches.get_one::<String>("scope").map(|s| s.as_str())),
-            format: ConfigFormat::from_arg(matches.get_one::<String>("format").map(|s| s.as_str())),
+            scope,
+            windows,
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            history_dir: matches.get_flag("include-history").then(|| {
+                let history_dir = matches.get_one::<String>("history-dir").unwrap();
+                // Next to the config the history files are referenced from
+                match append.and_then(|path| Path::new(path).parent()) {
+                    Some(config_dir) => config_dir.join(history_dir),
+                    None => PathBuf::from(history_dir),
+                }
+            }),
+            include_processes: matches.get_flag("include-processes"),
+            include_alerts: matches.get_flag("include-alerts"),
+            relativize: match matches.get_one::<String>("relativize").unwrap().as_str() {
+                "home" => Relativize::Home,
+                "session" => Relativize::Session,
+                "none" => Relativize::None,
+                _ => unreachable!("undefined Relativize"),
+            },
+            sizes: sizes_from_matches(matches),
+            sort: match matches.get_one::<String>("sort").unwrap().as_str() {
+                "id" => SessionOrder::Id,
+                "name" => SessionOrder::Name,
+                "attached-first" => SessionOrder::AttachedFirst,
+                _ => unreachable!("undefined SessionOrder"),
+            },
+            redact: matches.get_flag("redact"),
+            canonical: matches.get_flag("canonical"),
+            format: ConfigFormat::from_matches(matches, output),
+            append,
+            diff_against: matches
+                .get_one::<String>("diff-against")
+                .map(|s| s.as_str()),
             tmux_args: matches
                 .get_many::<String>("tmux args")
                 .into_iter()
@@ -84,8 +294,30 @@ impl ExportOpts<'_> {
 #[derive(Debug)]
 pub struct DumpCommandOps<'a> {
     pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Create all windows in the current session (see `--here`)
+    pub here: bool,
+    /// Session to add the top-level windows to (`Some(None)`: the current one)
+    pub into_session: Option<Option<&'a str>>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
     pub session_select_mode: SessionSelectModeOption,
-    pub ignore_existing_sessions: bool,
+    /// Detach other clients when attaching (`--detach-others`)
+    pub detach_others: bool,
+    pub on_conflict: Option<ConflictStrategy>,
+    pub update_existing_sessions: bool,
+    /// Skip top-level windows whose name exists in the session they're
+    /// added to (`--ignore-existing-windows`)
+    pub ignore_existing_windows: bool,
+    /// Select running sessions instead of creating them (`--attach-if-exists`)
+    pub attach_if_exists: bool,
+    pub missing_dirs: MissingDirsPolicy,
+    /// Target to select instead of the config's (`--select-target`)
+    pub select_target: Option<&'a str>,
+    /// Print a separate command line per session
+    pub split_per_session: bool,
     pub tmux_args: Vec<&'a str>,
 }
 
@@ -93,12 +325,27 @@ impl DumpCommandOps<'_> {
     fn from_matches(matches: &ArgMatches) -> DumpCommandOps<'_> {
         DumpCommandOps {
             config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
             session_select_mode: SessionSelectModeOption::from_arg(
                 matches
                     .get_one::<String>("session-select-mode")
                     .map(|s| s.as_str()),
             ),
-            ignore_existing_sessions: matches.get_flag("ignore-existing-sessions"),
+            detach_others: matches.get_flag("detach-others"),
+            on_conflict: ConflictStrategy::from_matches(matches),
+            missing_dirs: missing_dirs_from_matches(matches),
+            select_target: matches
+                .get_one::<String>("select-target")
+                .map(|s| s.as_str()),
+            update_existing_sessions: matches.get_flag("update-existing-sessions")
+                || matches.get_flag("ignore-existing-windows"),
+            ignore_existing_windows: matches.get_flag("ignore-existing-windows"),
+            attach_if_exists: matches.get_flag("attach-if-exists"),
+            into_session: into_session_from_matches(matches),
+            here: matches.get_flag("here"),
+            split_per_session: matches.get_flag("split-per-session"),
             tmux_args: matches
                 .get_many::<String>("tmux args")
                 .into_iter()
@@ -112,166 +359,1988 @@ impl DumpCommandOps<'_> {
 #[derive(Debug)]
 pub struct DumpConfigOps<'a> {
     pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
     pub format: ConfigFormat,
 }
 
 impl DumpConfigOps<'_> {
-    fn from_matches(matches: &ArgMatches) -> DumpConfigOps<'_> {
+    fn from_matches(matches: &ArgMatches, output: OutputFormat) -> DumpConfigOps<'_> {
         DumpConfigOps {
             config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
-            format: ConfigFormat::from_arg(matches.get_one::<String>("format").map(|s| s.as_str())),
+            load: load_options_from_matches(matches),
+            format: ConfigFormat::from_matches(matches, output),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum ConfigFormat {
-    Yaml,
-    Toml,
+#[derive(Debug)]
+pub struct CheckOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
 }
 
-impl ConfigFormat {
-    fn from_arg(arg: Option<&str>) -> ConfigFormat {
-        match arg {
-            Some("yaml") | None => ConfigFormat::Yaml,
-            Some("toml") => ConfigFormat::Toml,
-            _ => unreachable!("undefined ConfigFormat"),
+impl CheckOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> CheckOpts<'_> {
+        CheckOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
         }
     }
 }
 
-impl QueryScope {
-    fn from_arg(arg: Option<&str>) -> QueryScope {
-        match arg {
-            Some("all") => QueryScope::AllSessions,
-            Some("session") => QueryScope::CurrentSession,
-            Some("window") => QueryScope::CurrentWindow,
-            _ => unreachable!("undefined ExportScope"),
+#[derive(Debug)]
+pub struct PreviewOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Client size to render for (default: size of the terminal)
+    pub size: Option<ClientSize>,
+}
+
+impl PreviewOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> PreviewOpts<'_> {
+        PreviewOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            size: matches.get_one::<ClientSize>("size").copied(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-pub enum SessionSelectModeOption {
-    #[default]
-    Auto,
-    Attach,
-    Switch,
-    Detached,
+#[derive(Debug)]
+pub struct EditOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    pub profile: Option<&'a str>,
+    /// Edit the layout interactively instead of in $EDITOR
+    pub tui: bool,
 }
 
-impl SessionSelectModeOption {
-    fn from_arg(arg: Option<&str>) -> SessionSelectModeOption {
-        match arg {
-            Some("auto") | None => SessionSelectModeOption::Auto,
-            Some("attach") => SessionSelectModeOption::Attach,
-            Some("switch") => SessionSelectModeOption::Switch,
-            Some("detached") => SessionSelectModeOption::Detached,
-            _ => unreachable!("undefined AttachOption"),
+impl EditOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> EditOpts<'_> {
+        EditOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            profile: matches.get_one::<String>("profile").map(|s| s.as_str()),
+            tui: matches.get_flag("tui"),
         }
     }
 }
 
-pub fn app() -> Command {
-    let config_arg = Arg::new("config")
-        .help(
-            "Config file path. If not given the config file is searched for at:\n\
-              - ./tmux-layout.{yaml,yml,toml}\n\
-              - ~/tmux-layout.{yaml,yml,toml}\n",
-        )
-        .required(false)
-        .short('c')
-        .long("config")
-        .num_args(1)
-        .value_name("FILE")
-        .required(false);
+#[derive(Debug)]
+pub struct InitOpts<'a> {
+    pub template: &'a str,
+    pub global: bool,
+    pub from_current_window: bool,
+    pub force: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
 
-    let format_arg = Arg::new("format")
-        .help("Export config format")
-        .required(false)
-        .short('f')
-        .long("format")
-        .num_args(1)
-        .value_name("FORMAT")
-        .value_parser(["yaml", "toml"])
-        .default_value("yaml");
+impl InitOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> InitOpts<'_> {
+        InitOpts {
+            template: matches.get_one::<String>("template").unwrap(),
+            global: matches.get_flag("global"),
+            from_current_window: matches.get_flag("from-current-window"),
+            force: matches.get_flag("force"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
 
-    let session_select_mode_arg = Arg::new("session-select-mode")
-        .help(
-            "Session select mode:\n\
-                - switch: switch existing client to selected (or last created) session\n\
-                - attach: attach to selected (or last created) session\n\
-                - detached: don't attach/switch to any session\n\
-                - auto: switch when there is a tmux client, \
-                  attach when running from a TTY, \
-                  detached otherwise\n",
-        )
-        .short('m')
-        .long("session-select-mode")
-        .num_args(1)
-        .value_name("MODE")
-        .value_parser(["auto", "attach", "switch", "detached"])
-        .default_value("auto")
-        .required(false);
+#[derive(Debug)]
+pub struct SnapshotOpts<'a> {
+    pub profile: &'a str,
+    pub scope: QueryScope,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
 
-    let ignore_existing_sessions_arg = Arg::new("ignore-existing-sessions")
-        .help("Don't create already existing tmux sessions")
-        .short('i')
-        .long("ignore-existing-sessions")
-        .action(ArgAction::SetTrue)
-        .required(false);
+impl SnapshotOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> SnapshotOpts<'_> {
+        SnapshotOpts {
+            profile: matches.get_one::<String>("profile").unwrap(),
+            scope: matches.get_one::<QueryScope>("scope").unwrap().clone(),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
 
-    let tmux_args = Arg::new("tmux args")
-        .required(false)
-        .last(true)
-        .num_args(0..);
+#[derive(Debug)]
+pub struct PickOpts<'a> {
+    /// Profile to open without picking (`tmux-layout pick <profile>`)
+    pub profile: Option<&'a str>,
+    pub finder: Option<&'a str>,
+    /// How the default config file is looked up and loaded
+    pub load: LoadOptions,
+    pub session_select_mode: SessionSelectModeOption,
+    /// Detach other clients when attaching (`--detach-others`)
+    pub detach_others: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
 
-    Command::new("tmux-layout")
-        .version("0.1.0")
-        .author("Daniel Strittmatter <github@smattr.de>")
-        .about("Starts tmux sessions in pre-defined layouts")
-        .subcommand(
-            Command::new("create")
-                .about("Create tmux layout from config file")
-                .arg(&config_arg)
-                .arg(&session_select_mode_arg)
-                .arg(&ignore_existing_sessions_arg)
-                .arg(&tmux_args),
-        )
-        .subcommand(
-            Command::new("dump-command")
-                .about("Dump tmux command to stdout")
-                .arg(&config_arg)
-                .arg(&session_select_mode_arg)
-                .arg(&ignore_existing_sessions_arg)
-                .arg(&tmux_args),
-        )
-        .subcommand(
-            Command::new("dump-config")
-                .arg(&config_arg)
-                .about("Dump config to stdout")
-                .arg(&format_arg),
-        )
-        .subcommand(
-            Command::new("export")
-                .about("Exports running tmux sessions into tmux-layout config file format")
-                .arg(
-                    Arg::new("scope")
-                        .help("Export scope")
-                        .required(false)
-                        .short('s')
-                        .long("scope")
-                        .num_args(1)
-                        .value_name("SCOPE")
-                        .value_parser(["all", "session", "window"])
-                        .default_value("all"),
-                )
-                .arg(&format_arg)
-                .arg(&tmux_args),
-        )
+impl PickOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> PickOpts<'_> {
+        PickOpts {
+            profile: matches.get_one::<String>("profile").map(|s| s.as_str()),
+            finder: matches.get_one::<String>("finder").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            session_select_mode: SessionSelectModeOption::from_arg(
+                matches
+                    .get_one::<String>("session-select-mode")
+                    .map(|s| s.as_str()),
+            ),
+            detach_others: matches.get_flag("detach-others"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
 }
 
-#[test]
-fn verify_cli() {
-    app().debug_assert();
+#[derive(Debug)]
+pub struct SaveOpts<'a> {
+    pub output: Option<&'a str>,
+    pub interval: Duration,
+    pub keep: usize,
+    pub once: bool,
+    /// Write the snapshot canonically (see `Config::canonicalize`)
+    pub canonical: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl SaveOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> SaveOpts<'_> {
+        SaveOpts {
+            output: matches.get_one::<String>("output").map(|s| s.as_str()),
+            interval: *matches.get_one::<Duration>("interval").unwrap(),
+            keep: *matches.get_one::<usize>("keep").unwrap(),
+            once: matches.get_flag("once"),
+            canonical: matches.get_flag("canonical"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RestoreOpts<'a> {
+    pub from: Option<&'a str>,
+    pub session_select_mode: SessionSelectModeOption,
+    /// Detach other clients when attaching (`--detach-others`)
+    pub detach_others: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl RestoreOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> RestoreOpts<'_> {
+        RestoreOpts {
+            from: matches.get_one::<String>("from").map(|s| s.as_str()),
+            session_select_mode: SessionSelectModeOption::from_arg(
+                matches
+                    .get_one::<String>("session-select-mode")
+                    .map(|s| s.as_str()),
+            ),
+            detach_others: matches.get_flag("detach-others"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PopupOpts<'a> {
+    pub name: &'a str,
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl PopupOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> PopupOpts<'_> {
+        PopupOpts {
+            name: matches.get_one::<String>("name").unwrap(),
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RespawnOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Name of the only window to respawn (`--window`)
+    pub window: Option<&'a str>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl RespawnOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> RespawnOpts<'_> {
+        RespawnOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            window: matches.get_one::<String>("window").map(|s| s.as_str()),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SuperviseOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Time between checks for exited panes (`--interval`)
+    pub interval: Duration,
+    /// Policy of panes without `restart` (`--restart`)
+    pub restart: RestartPolicy,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl SuperviseOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> SuperviseOpts<'_> {
+        SuperviseOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            interval: *matches.get_one::<Duration>("interval").unwrap(),
+            restart: match matches.get_one::<String>("restart").unwrap().as_str() {
+                "never" => RestartPolicy::Never,
+                "always" => RestartPolicy::Always,
+                "on-failure" => RestartPolicy::OnFailure,
+                _ => unreachable!("undefined RestartPolicy"),
+            },
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadOnlyOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Enable the input again (`--off`)
+    pub off: bool,
+    /// Name of the only window to apply to (`--window`)
+    pub window: Option<&'a str>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl ReadOnlyOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> ReadOnlyOpts<'_> {
+        ReadOnlyOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            off: matches.get_flag("off"),
+            window: matches.get_one::<String>("window").map(|s| s.as_str()),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FocusOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    pub role: &'a str,
+    /// Name of the only window to look for the role in (`--window`)
+    pub window: Option<&'a str>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl FocusOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> FocusOpts<'_> {
+        FocusOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            role: matches.get_one::<String>("role").unwrap(),
+            window: matches.get_one::<String>("window").map(|s| s.as_str()),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KillOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Names of the sessions to kill (default: all running sessions of the
+    /// config)
+    pub sessions: Vec<&'a str>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl KillOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> KillOpts<'_> {
+        KillOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            sessions: matches
+                .get_many::<String>("session")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SwapOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    pub roles: [&'a str; 2],
+    /// Name of the only window to look for the roles in (`--window`)
+    pub window: Option<&'a str>,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl SwapOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> SwapOpts<'_> {
+        SwapOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            roles: [
+                matches.get_one::<String>("role").unwrap(),
+                matches.get_one::<String>("other role").unwrap(),
+            ],
+            window: matches.get_one::<String>("window").map(|s| s.as_str()),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RebalanceOpts<'a> {
+    pub config_path: Option<&'a str>,
+    /// How the config file is looked up and loaded
+    pub load: LoadOptions,
+    /// Name of the only window to rebalance (`--window`)
+    pub window: Option<&'a str>,
+    /// Install a `client-resized` hook rebalancing again (`--hook`)
+    pub hook: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl RebalanceOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> RebalanceOpts<'_> {
+        RebalanceOpts {
+            config_path: matches.get_one::<String>("config").map(|s| s.as_str()),
+            load: load_options_from_matches(matches),
+            window: matches.get_one::<String>("window").map(|s| s.as_str()),
+            hook: matches.get_flag("hook"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PluginOpts<'a> {
+    /// Key of each action, `None` if unbound (`--<action>-key`)
+    pub keys: Vec<(Action, Option<&'a str>)>,
+    /// Bind the keys in the running server (`--apply`) instead of printing
+    /// the tmux config lines
+    pub apply: bool,
+    pub socket: Option<TmuxSocket>,
+    /// Retries of tmux invocations failing for transient reasons (`--retries`)
+    pub retries: u32,
+    pub tmux_args: Vec<&'a str>,
+}
+
+impl PluginOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> PluginOpts<'_> {
+        PluginOpts {
+            keys: Action::ALL
+                .iter()
+                .map(|&action| {
+                    let key = matches.get_one::<String>(plugin_key_id(action)).unwrap();
+                    (action, Some(key.as_str()).filter(|key| *key != "none"))
+                })
+                .collect(),
+            apply: matches.get_flag("apply"),
+            socket: socket_from_matches(matches),
+            retries: retries_from_matches(matches),
+            tmux_args: matches
+                .get_many::<String>("tmux args")
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect(),
+        }
+    }
+}
+
+/// ID and long flag of the key argument of `action`
+fn plugin_key_id(action: Action) -> &'static str {
+    match action {
+        Action::Save => "save-key",
+        Action::Restore => "restore-key",
+        Action::Snapshot => "snapshot-key",
+        Action::Pick => "pick-key",
+    }
+}
+
+#[derive(Debug)]
+pub struct ShellInitOpts<'a> {
+    pub shell: Shell,
+    /// Name of the defined function (`--name`)
+    pub name: &'a str,
+}
+
+impl ShellInitOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> ShellInitOpts<'_> {
+        ShellInitOpts {
+            shell: match matches.get_one::<String>("shell").unwrap().as_str() {
+                "bash" => Shell::Bash,
+                "zsh" => Shell::Zsh,
+                "fish" => Shell::Fish,
+                _ => unreachable!("undefined Shell"),
+            },
+            name: matches.get_one::<String>("name").unwrap(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConvertLayoutOpts<'a> {
+    /// Layout string as printed by tmux (`#{window_layout}`)
+    pub layout: &'a str,
+    pub sizes: Sizes,
+    pub format: ConfigFormat,
+}
+
+impl ConvertLayoutOpts<'_> {
+    fn from_matches(matches: &ArgMatches, output: OutputFormat) -> ConvertLayoutOpts<'_> {
+        ConvertLayoutOpts {
+            layout: matches.get_one::<String>("layout").unwrap(),
+            sizes: sizes_from_matches(matches),
+            format: ConfigFormat::from_matches(matches, output),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ManOpts<'a> {
+    /// Directory to write the pages of all subcommands to (`--dir`) instead
+    /// of printing the main page
+    pub dir: Option<&'a str>,
+}
+
+impl ManOpts<'_> {
+    fn from_matches(matches: &ArgMatches) -> ManOpts<'_> {
+        ManOpts {
+            dir: matches.get_one::<String>("dir").map(|s| s.as_str()),
+        }
+    }
+}
+
+/// Parses durations like `90`, `30s`, `5m` or `1h`.
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let (value, unit_secs) = match arg.char_indices().last() {
+        Some((i, 's')) => (&arg[..i], 1),
+        Some((i, 'm')) => (&arg[..i], 60),
+        Some((i, 'h')) => (&arg[..i], 60 * 60),
+        _ => (arg, 1),
+    };
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration '{}' (expected e.g. 30s, 5m, 1h)", arg))?;
+    if value == 0 {
+        return Err("duration must be positive".to_string());
+    }
+    Ok(Duration::from_secs(value * unit_secs))
+}
+
+fn into_session_from_matches(matches: &ArgMatches) -> Option<Option<&str>> {
+    // Given without a name, the option's value is empty.
+    matches
+        .get_one::<String>("into-session")
+        .map(|name| Some(name.as_str()).filter(|name| !name.is_empty()))
+}
+
+fn sizes_from_matches(matches: &ArgMatches) -> Sizes {
+    match matches.get_one::<String>("sizes").unwrap().as_str() {
+        "percent" => Sizes::Percent,
+        "cells" => Sizes::Cells,
+        "none" => Sizes::None,
+        _ => unreachable!("undefined Sizes"),
+    }
+}
+
+fn load_options_from_matches(matches: &ArgMatches) -> LoadOptions {
+    LoadOptions {
+        ascend: !matches.get_flag("no-ascend"),
+        config_dirs: match matches.get_many::<String>("config-dir") {
+            Some(dirs) => Some(dirs.map(PathBuf::from).collect()),
+            None => std::env::var_os(loader::CONFIG_DIR_ENV)
+                .map(|dirs| std::env::split_paths(&dirs).collect()),
+        },
+        merge_strategy: match matches
+            .get_one::<String>("merge-strategy")
+            .map(String::as_str)
+        {
+            Some("merge") => MergeStrategy::Merge,
+            Some("dedup") => MergeStrategy::DedupByName,
+            Some("overwrite") => MergeStrategy::Overwrite,
+            _ => MergeStrategy::Append,
+        },
+    }
+}
+
+fn retries_from_matches(matches: &ArgMatches) -> u32 {
+    matches
+        .get_one::<u32>("retries")
+        .copied()
+        .unwrap_or_default()
+}
+
+fn socket_from_matches(matches: &ArgMatches) -> Option<TmuxSocket> {
+    if let Some(name) = matches.get_one::<String>("socket-name") {
+        return Some(TmuxSocket::Name(name.clone()));
+    }
+    matches
+        .get_one::<String>("socket-path")
+        .map(|path| TmuxSocket::Path(path.clone()))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+    /// Graph of the layout tree (`dump-config` only)
+    Graph(GraphFormat),
+}
+
+impl ConfigFormat {
+    /// The `--format` given, or JSON with `--output json`
+    fn from_matches(matches: &ArgMatches, output: OutputFormat) -> ConfigFormat {
+        let format_given = matches.value_source("format") == Some(ValueSource::CommandLine);
+        match output {
+            OutputFormat::Json if !format_given => ConfigFormat::Json,
+            _ => ConfigFormat::from_arg(matches.get_one::<String>("format").map(|s| s.as_str())),
+        }
+    }
+
+    fn from_arg(arg: Option<&str>) -> ConfigFormat {
+        match arg {
+            Some("yaml") | None => ConfigFormat::Yaml,
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            Some("dot") => ConfigFormat::Graph(GraphFormat::Dot),
+            Some("mermaid") => ConfigFormat::Graph(GraphFormat::Mermaid),
+            _ => unreachable!("undefined ConfigFormat"),
+        }
+    }
+}
+
+/// Logging options shared by all subcommands
+#[derive(Debug)]
+pub struct LogOpts<'a> {
+    pub level: Level,
+    pub log_file: Option<&'a str>,
+}
+
+impl LogOpts<'_> {
+    pub fn from_matches(matches: &ArgMatches) -> LogOpts<'_> {
+        LogOpts {
+            level: Level::from_verbosity(matches.get_flag("quiet"), matches.get_count("verbose")),
+            log_file: matches.get_one::<String>("log-file").map(|s| s.as_str()),
+        }
+    }
+}
+
+/// List of the exit codes for `--help`
+fn exit_codes_help() -> String {
+    let codes = ExitCode::ALL
+        .iter()
+        .map(|code| format!("  {}  {}", *code as i32, code.description()))
+        .collect::<Vec<_>>();
+    format!("Exit codes:\n  0  success\n{}", codes.join("\n"))
+}
+
+/// When to color the output (`--color`, `--no-color`)
+pub fn color_choice_from_matches(matches: &ArgMatches) -> ColorChoice {
+    if matches.get_flag("no-color") {
+        return ColorChoice::Never;
+    }
+    match matches.get_one::<String>("color").map(|s| s.as_str()) {
+        Some("auto") | None => ColorChoice::Auto,
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ => unreachable!("undefined ColorChoice"),
+    }
+}
+
+/// Format of the results and errors printed by all subcommands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_matches(matches: &ArgMatches) -> OutputFormat {
+        match matches.get_one::<String>("output").map(|s| s.as_str()) {
+            Some("text") | None => OutputFormat::Text,
+            Some("json") => OutputFormat::Json,
+            _ => unreachable!("undefined OutputFormat"),
+        }
+    }
+}
+
+/// Parses export scopes: `all`, `session`, `window` (the current ones),
+/// `session=NAME` and `window=SESSION:WINDOW`.
+fn parse_query_scope(arg: &str) -> Result<QueryScope, String> {
+    match arg.split_once('=') {
+        None => match arg {
+            "all" => Ok(QueryScope::AllSessions),
+            "session" => Ok(QueryScope::CurrentSession),
+            "window" => Ok(QueryScope::CurrentWindow),
+            _ => Err("expected all, session, window, session=NAME or window=SESSION:WINDOW".into()),
+        },
+        Some(("session", name)) if !name.is_empty() => Ok(QueryScope::Session(name.to_string())),
+        Some(("window", target)) => match target.split_once(':') {
+            Some((session, window)) if !session.is_empty() && !window.is_empty() => {
+                Ok(QueryScope::Window {
+                    session: session.to_string(),
+                    window: window.to_string(),
+                })
+            }
+            _ => Err("expected window=SESSION:WINDOW".into()),
+        },
+        _ => Err("expected session=NAME or window=SESSION:WINDOW".into()),
+    }
+}
+
+/// What to do with configured sessions whose name is already taken by a
+/// running tmux session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Don't create the session
+    Skip,
+    /// Abort before running any tmux command
+    Error,
+    /// Create the session under a name with a numeric suffix
+    Rename,
+    /// Create the session anew, killing the running one once the client
+    /// switched to the new one
+    Replace,
+}
+
+fn missing_dirs_from_matches(matches: &ArgMatches) -> MissingDirsPolicy {
+    match matches.get_one::<String>("missing-dirs").unwrap().as_str() {
+        "error" => MissingDirsPolicy::Error,
+        "warn" => MissingDirsPolicy::Warn,
+        "create" => MissingDirsPolicy::Create,
+        "fallback-home" => MissingDirsPolicy::FallbackHome,
+        _ => unreachable!("undefined MissingDirsPolicy"),
+    }
+}
+
+impl ConflictStrategy {
+    fn from_matches(matches: &ArgMatches) -> Option<ConflictStrategy> {
+        // `--ignore-existing-windows` refines `-i` to update existing sessions
+        if matches.get_flag("ignore-existing-windows") {
+            return None;
+        }
+        if matches.get_flag("ignore-existing-sessions") {
+            return Some(ConflictStrategy::Skip);
+        }
+        match matches.get_one::<String>("on-conflict").map(|s| s.as_str()) {
+            None => None,
+            Some("skip") => Some(ConflictStrategy::Skip),
+            Some("error") => Some(ConflictStrategy::Error),
+            Some("rename") => Some(ConflictStrategy::Rename),
+            Some("replace") => Some(ConflictStrategy::Replace),
+            _ => unreachable!("undefined ConflictStrategy"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SessionSelectModeOption {
+    #[default]
+    Auto,
+    Attach,
+    Switch,
+    Detached,
+}
+
+impl SessionSelectModeOption {
+    fn from_arg(arg: Option<&str>) -> SessionSelectModeOption {
+        match arg {
+            Some("auto") | None => SessionSelectModeOption::Auto,
+            Some("attach") => SessionSelectModeOption::Attach,
+            Some("switch") => SessionSelectModeOption::Switch,
+            Some("detached") => SessionSelectModeOption::Detached,
+            _ => unreachable!("undefined AttachOption"),
+        }
+    }
+}
+
+pub fn app() -> Command {
+    let config_arg = Arg::new("config")
+        .help(
+            "Config file path. If not given the first config file found is used, in order:\n\
+              - .tmux-layout.{yaml,yml,toml} in the current directory and each of its \
+             parents (only the current one with --no-ascend)\n\
+              - ~/.tmux-layout.{yaml,yml,toml}\n\
+              - ~/.config/tmux-layout/config.{yaml,yml,toml}\n\
+             --config-dir (or TMUX_LAYOUT_CONFIG_DIR) replaces the last two entries, \
+             -v prints the search order.\n",
+        )
+        .required(false)
+        .short('c')
+        .long("config")
+        .num_args(1)
+        .value_name("FILE")
+        .required(false);
+
+    let format_arg = Arg::new("format")
+        .help("Export config format")
+        .required(false)
+        .short('f')
+        .long("format")
+        .num_args(1)
+        .value_name("FORMAT")
+        .value_parser(["yaml", "toml", "json"])
+        .default_value("yaml");
+
+    let sizes_arg = Arg::new("sizes")
+        .help(
+            "How to write the sizes of splits:\n\
+                - percent: percentages, adapting to the terminal size\n\
+                - cells: cells, exact on terminals of the same size\n\
+                - none: no sizes, every split halves the pane\n",
+        )
+        .long("sizes")
+        .num_args(1)
+        .value_name("UNIT")
+        .value_parser(["percent", "cells", "none"])
+        .default_value("percent");
+
+    let canonical_arg = Arg::new("canonical")
+        .help(
+            "Write the snapshot deterministically for tracking it in git: sessions ordered \
+             by name (windows keep their order), normalized sizes, a single trailing newline",
+        )
+        .long("canonical")
+        .action(ArgAction::SetTrue);
+
+    let session_select_mode_arg = Arg::new("session-select-mode")
+        .help(
+            "Session select mode:\n\
+                - switch: switch existing client to selected (or last created) session\n\
+                - attach: attach to selected (or last created) session\n\
+                - detached: don't attach/switch to any session\n\
+                - auto: switch when running inside tmux or there is a tmux client, \
+                  attach when running from a TTY, \
+                  detached otherwise\n",
+        )
+        .short('m')
+        .long("session-select-mode")
+        .num_args(1)
+        .value_name("MODE")
+        .value_parser(["auto", "attach", "switch", "detached"])
+        .default_value("auto")
+        .required(false);
+
+    let select_target_arg = Arg::new("select-target")
+        .help(
+            "Select this pane (SESSION:WINDOW.PANE), window (SESSION:WINDOW) or \
+             session after creating, instead of `selected_target`/`selected_session` \
+             of the config",
+        )
+        .long("select-target")
+        .num_args(1)
+        .value_name("TARGET")
+        .required(false);
+
+    let detach_others_arg = Arg::new("detach-others")
+        .help(
+            "Detach other clients from the session when attaching to it \
+             (`attach-session -d`, like `detach_others` of the config)",
+        )
+        .long("detach-others")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let ignore_existing_sessions_arg = Arg::new("ignore-existing-sessions")
+        .help("Don't create already existing tmux sessions (same as `--on-conflict skip`)")
+        .short('i')
+        .long("ignore-existing-sessions")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let into_session_arg = Arg::new("into-session")
+        .help(
+            "Add the top-level windows to the existing session NAME \
+             (default: the session of the current client)",
+        )
+        .long("into-session")
+        .num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value("")
+        .value_name("NAME")
+        .required(false);
+
+    let here_arg = Arg::new("here")
+        .help(
+            "Create the windows of all sessions (and the top-level windows) \
+             as new windows of the current tmux session",
+        )
+        .long("here")
+        .action(ArgAction::SetTrue)
+        .conflicts_with_all([
+            "into-session",
+            "on-conflict",
+            "update-existing-sessions",
+            "attach-if-exists",
+        ]);
+
+    let update_existing_sessions_arg = Arg::new("update-existing-sessions")
+        .help(
+            "Add missing windows (matched by name) to already existing tmux sessions \
+             instead of creating them",
+        )
+        .short('u')
+        .long("update-existing-sessions")
+        .action(ArgAction::SetTrue)
+        .conflicts_with_all(["ignore-existing-sessions", "on-conflict"])
+        .required(false);
+
+    let ignore_existing_windows_arg = Arg::new("ignore-existing-windows")
+        .help(
+            "Only create the windows missing (by name) from already existing tmux sessions, \
+             including top-level windows added to an existing session",
+        )
+        .long("ignore-existing-windows")
+        .action(ArgAction::SetTrue)
+        .conflicts_with_all(["update-existing-sessions", "on-conflict"])
+        .required(false);
+
+    let attach_if_exists_arg = Arg::new("attach-if-exists")
+        .help(
+            "Select already existing tmux sessions instead of creating them \
+             (like `new-session -A`, see `attach_if_exists` of sessions)",
+        )
+        .short('A')
+        .long("attach-if-exists")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let on_conflict_arg = Arg::new("on-conflict")
+        .help(
+            "What to do with sessions which already exist:\n\
+                - skip: don't create the session\n\
+                - error: abort without running any tmux command\n\
+                - rename: append a numeric suffix to the session name\n\
+                - replace: kill the existing session and create it anew\n",
+        )
+        .long("on-conflict")
+        .num_args(1)
+        .value_name("STRATEGY")
+        .value_parser(["skip", "error", "rename", "replace"])
+        .conflicts_with("ignore-existing-sessions")
+        .required(false);
+
+    let missing_dirs_arg = Arg::new("missing-dirs")
+        .help(
+            "What to do with configured directories which don't exist:\n\
+                - error: abort without running any tmux command\n\
+                - warn: print a warning (tmux falls back to another directory)\n\
+                - create: create the directory (including parents)\n\
+                - fallback-home: use the home directory instead\n",
+        )
+        .long("missing-dirs")
+        .num_args(1)
+        .value_name("POLICY")
+        .value_parser(["error", "warn", "create", "fallback-home"])
+        .default_value("warn");
+
+    let socket_name_arg = Arg::new("socket-name")
+        .help("Name of the tmux server socket (passed as `-L` to every tmux invocation)")
+        .short('L')
+        .long("socket-name")
+        .num_args(1)
+        .value_name("NAME")
+        .conflicts_with("socket-path")
+        .required(false);
+
+    let socket_path_arg = Arg::new("socket-path")
+        .help("Path of the tmux server socket (passed as `-S` to every tmux invocation)")
+        .short('S')
+        .long("socket-path")
+        .num_args(1)
+        .value_name("PATH")
+        .required(false);
+
+    let role_window_arg = Arg::new("window")
+        .help("Only look for the roles in the window with this name")
+        .short('w')
+        .long("window")
+        .num_args(1)
+        .value_name("NAME");
+
+    let tmux_args = Arg::new("tmux args")
+        .required(false)
+        .last(true)
+        .num_args(0..);
+
+    Command::new("tmux-layout")
+        .version("0.1.0")
+        .author("Daniel Strittmatter <github@smattr.de>")
+        .about("Starts tmux sessions in pre-defined layouts")
+        .after_help(exit_codes_help())
+        .arg(
+            Arg::new("output")
+                .help(
+                    "Output format (given before the subcommand). `json` prints results \
+                     (e.g. of `check` and `export`, unless `--format` is given) and errors \
+                     as JSON on STDOUT",
+                )
+                .long("output")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("color")
+                .help(
+                    "When to color messages and results. `auto` colors them on terminals \
+                     unless NO_COLOR is set",
+                )
+                .long("color")
+                .num_args(1)
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-color")
+                .help("Same as --color never")
+                .long("no-color")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .help("Only print errors")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .global(true),
+        )
+        .arg(
+            Arg::new("verbose")
+                .help("Print executed tmux commands (-v) and their results (-vv)")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-ascend")
+                .help(
+                    "Look for the default config file only in the current directory \
+                     (not in its parents) and the home directory",
+                )
+                .long("no-ascend")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("config-dir")
+                .help(
+                    "Look for the default config file in DIR instead of the home and user \
+                     config directory, after the current directory (may be repeated, an \
+                     empty DIR stands for the default directories) [env: TMUX_LAYOUT_CONFIG_DIR]",
+                )
+                .long("config-dir")
+                .num_args(1)
+                .value_name("DIR")
+                .action(ArgAction::Append)
+                .global(true),
+        )
+        .arg(
+            Arg::new("merge-strategy")
+                .help(
+                    "How sessions from included files are combined with sessions of the \
+                     same name:\n\
+                        - append: keep both (unless one of them has `merge: true`)\n\
+                        - merge: add the windows of the included session to the existing one\n\
+                        - dedup: drop included sessions and windows whose name exists\n\
+                        - overwrite: replace sessions and windows of the same name\n",
+                )
+                .long("merge-strategy")
+                .num_args(1)
+                .value_name("STRATEGY")
+                .value_parser(["append", "merge", "dedup", "overwrite"])
+                .default_value("append")
+                .global(true),
+        )
+        .arg(
+            Arg::new("retries")
+                .help(
+                    "Retry tmux invocations losing their connection to the server (e.g. \
+                     right after `kill-server`) up to N times with increasing delays. A server \
+                     which isn't running isn't waited for",
+                )
+                .long("retries")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-file")
+                .help("Append all log messages (with timestamps) to FILE")
+                .long("log-file")
+                .num_args(1)
+                .value_name("FILE")
+                .global(true),
+        )
+        .subcommand(
+            Command::new("create")
+                .about("Create tmux layout from config file")
+                .arg(&config_arg)
+                .arg(&session_select_mode_arg)
+                .arg(&detach_others_arg)
+                .arg(&ignore_existing_sessions_arg)
+                .arg(&update_existing_sessions_arg)
+                .arg(&ignore_existing_windows_arg)
+                .arg(&attach_if_exists_arg)
+                .arg(&into_session_arg)
+                .arg(&here_arg)
+                .arg(&on_conflict_arg)
+                .arg(&missing_dirs_arg)
+                .arg(&select_target_arg)
+                .arg(
+                    Arg::new("sequential")
+                        .help(
+                            "Run every tmux command in a separate tmux invocation \
+                             (continuing after failures) and print which sessions \
+                             and windows were created successfully",
+                        )
+                        .long("sequential")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("print-config")
+                        .help(
+                            "Print the resolved config (with includes merged, defaults and \
+                             templates applied and sizes resolved, but secrets unresolved) \
+                             before creating it",
+                        )
+                        .long("print-config")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("resolve-only")
+                        .help(
+                            "Print the resolved config (like --print-config) without cloning \
+                             repos or creating anything",
+                        )
+                        .long("resolve-only")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("post-cmd")
+                        .help(
+                            "Run this shell command after tmux created the layout \
+                             (before attaching), instead of `on_created` of the config",
+                        )
+                        .long("post-cmd")
+                        .num_args(1)
+                        .value_name("COMMAND"),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("dump-command")
+                .about("Dump tmux command to stdout")
+                .arg(&config_arg)
+                .arg(&session_select_mode_arg)
+                .arg(&detach_others_arg)
+                .arg(&ignore_existing_sessions_arg)
+                .arg(&update_existing_sessions_arg)
+                .arg(&ignore_existing_windows_arg)
+                .arg(&attach_if_exists_arg)
+                .arg(&into_session_arg)
+                .arg(&here_arg)
+                .arg(&on_conflict_arg)
+                .arg(&missing_dirs_arg)
+                .arg(&select_target_arg)
+                .arg(
+                    Arg::new("split-per-session")
+                        .help(
+                            "Print one command line per session (preceded by the top-level \
+                             windows and followed by selecting the session) instead of a \
+                             single chained command",
+                        )
+                        .long("split-per-session")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("dump-config")
+                .arg(&config_arg)
+                .about("Dump config to stdout")
+                .arg(
+                    format_arg
+                        .clone()
+                        .help("Export config format (dot and mermaid render the layout tree)")
+                        .value_parser(["yaml", "toml", "json", "dot", "mermaid"]),
+                ),
+        )
+        .subcommand(
+            Command::new("save")
+                .about("Periodically export all tmux sessions into a snapshot file")
+                .arg(
+                    Arg::new("output")
+                        .help(
+                            "Snapshot file path \
+                             [default: ~/.local/state/tmux-layout/autosave.yaml]",
+                        )
+                        .long("output")
+                        .visible_alias("file")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .help("Time between snapshots (e.g. 30s, 5m, 1h)")
+                        .long("interval")
+                        .num_args(1)
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .default_value("5m"),
+                )
+                .arg(
+                    Arg::new("keep")
+                        .help("Number of previous snapshots to keep as <FILE>.1, <FILE>.2, ...")
+                        .long("keep")
+                        .num_args(1)
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::new("once")
+                        .help("Save a single snapshot and exit (e.g. for systemd timers)")
+                        .long("once")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(&canonical_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Recreate sessions from the newest snapshot which are not running anymore")
+                .arg(
+                    Arg::new("from")
+                        .help(
+                            "Snapshot file to restore \
+                             [default: newest of ~/.local/state/tmux-layout/autosave.yaml*]",
+                        )
+                        .long("from")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .required(false),
+                )
+                .arg(&session_select_mode_arg)
+                .arg(&detach_others_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("popup")
+                .about("Open a popup defined in the config file (requires tmux >= 3.2)")
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the popup")
+                        .required(true)
+                        .value_name("NAME"),
+                )
+                .arg(&config_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("read-only")
+                .about(
+                    "Disable input to the running panes with `read_only` in the config \
+                     (again, e.g. after respawning), or enable it with --off",
+                )
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("off")
+                        .help("Enable input to the panes again")
+                        .long("off")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("window")
+                        .help("Only apply to the panes of the window with this name")
+                        .short('w')
+                        .long("window")
+                        .num_args(1)
+                        .value_name("NAME"),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("focus")
+                .about("Select the running pane with the given `role` in the config")
+                .arg(
+                    Arg::new("role")
+                        .help("Role of the pane")
+                        .required(true)
+                        .value_name("ROLE"),
+                )
+                .arg(&config_arg)
+                .arg(&role_window_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("swap")
+                .about("Swap the running panes with the given `role`s in the config")
+                .arg(
+                    Arg::new("role")
+                        .help("Role of the first pane")
+                        .required(true)
+                        .value_name("ROLE"),
+                )
+                .arg(
+                    Arg::new("other role")
+                        .help("Role of the second pane")
+                        .required(true)
+                        .value_name("OTHER_ROLE"),
+                )
+                .arg(&config_arg)
+                .arg(&role_window_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("kill")
+                .about(
+                    "Kill the running sessions of the config, running their `on_kill` \
+                     commands first",
+                )
+                .arg(
+                    Arg::new("session")
+                        .help("Sessions to kill (default: all sessions of the config)")
+                        .num_args(0..)
+                        .value_name("SESSION"),
+                )
+                .arg(&config_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("rebalance")
+                .about(
+                    "Re-apply the split sizes of the config to the running windows at their \
+                     current size, e.g. after the terminal was resized",
+                )
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("window")
+                        .help("Only rebalance the window with this name")
+                        .short('w')
+                        .long("window")
+                        .num_args(1)
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new("hook")
+                        .help("Also rebalance whenever a client of the sessions is resized")
+                        .long("hook")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("plugin")
+                .about(
+                    "Print tmux key bindings running tmux-layout (or bind them with --apply, \
+                     as the TPM entry point tmux-layout.tmux does)",
+                )
+                .args(Action::ALL.iter().map(|&action| {
+                    let help = match action {
+                        Action::Save => "Key saving a snapshot of all sessions (save --once)",
+                        Action::Restore => "Key restoring the newest snapshot (restore)",
+                        Action::Snapshot => {
+                            "Key prompting for a profile to export the current session into"
+                        }
+                        Action::Pick => "Key picking a layout in a popup (requires tmux >= 3.2)",
+                    };
+                    Arg::new(plugin_key_id(action))
+                        .help(format!("{}, `none` to leave unbound", help))
+                        .long(plugin_key_id(action))
+                        .num_args(1)
+                        .value_name("KEY")
+                        .default_value(action.default_key().unwrap_or("none"))
+                }))
+                .arg(
+                    Arg::new("apply")
+                        .help("Bind the keys in the running tmux server")
+                        .long("apply")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("shell-init")
+                .about(
+                    "Print a shell function opening profiles (`tl <profile>`, with \
+                     completion) for the shell's startup file",
+                )
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell to define the function for")
+                        .required(true)
+                        .value_name("SHELL")
+                        .value_parser(["bash", "zsh", "fish"]),
+                )
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the function")
+                        .long("name")
+                        .num_args(1)
+                        .value_name("NAME")
+                        .default_value("tl"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert-layout")
+                .about("Convert a tmux layout string into the layout of a config window")
+                .arg(
+                    Arg::new("layout")
+                        .help(
+                            "Layout as printed by `tmux list-windows -F '#{window_layout}'`, \
+                             e.g. 'b25d,80x24,0,0{40x24,0,0,1,39x24,41,0,2}'",
+                        )
+                        .required(true)
+                        .value_name("LAYOUT"),
+                )
+                .arg(&sizes_arg)
+                .arg(&format_arg),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Print the man page (roff), including a reference of the config format")
+                .arg(
+                    Arg::new("dir")
+                        .help(
+                            "Write the man pages of tmux-layout and all subcommands to DIR \
+                             instead",
+                        )
+                        .long("dir")
+                        .num_args(1)
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            Command::new("respawn")
+                .about(
+                    "Restart the commands of running panes as configured (matching panes \
+                     by position), killing whatever runs in them",
+                )
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("window")
+                        .help("Only respawn the panes of the window with this name")
+                        .short('w')
+                        .long("window")
+                        .num_args(1)
+                        .value_name("NAME"),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("supervise")
+                .about(
+                    "Keep running and restart the configured panes whose process exits, \
+                     according to their restart policy",
+                )
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("interval")
+                        .help("Time between checks for exited panes (e.g. 2s, 1m)")
+                        .long("interval")
+                        .num_args(1)
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .default_value("2s"),
+                )
+                .arg(
+                    Arg::new("restart")
+                        .help("Restart policy of panes without `restart` in the config")
+                        .long("restart")
+                        .num_args(1)
+                        .value_name("POLICY")
+                        .value_parser(["never", "always", "on-failure"])
+                        .default_value("never"),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Write a starter config file from a template")
+                .arg(
+                    Arg::new("template")
+                        .help("Template to start from")
+                        .short('t')
+                        .long("template")
+                        .value_name("TEMPLATE")
+                        .value_parser(PossibleValuesParser::new(
+                            templates::TEMPLATES
+                                .iter()
+                                .map(|t| PossibleValue::new(t.name).help(t.description)),
+                        ))
+                        .default_value("minimal"),
+                )
+                .arg(
+                    Arg::new("global")
+                        .help("Write ~/.config/tmux-layout/config.yaml instead of ./.tmux-layout.yaml")
+                        .short('g')
+                        .long("global")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("from-current-window")
+                        .help("Pre-fill the config by exporting the current tmux window")
+                        .short('w')
+                        .long("from-current-window")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("template"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .help("Overwrite an existing config file")
+                        .long("force")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("snapshot")
+                .about("Export the current window or session into a named profile")
+                .arg(
+                    Arg::new("profile")
+                        .help(
+                            "Name of the profile in ~/.config/tmux-layout/profiles. \
+                             Sessions/windows of the same name are replaced, \
+                             everything else is kept.",
+                        )
+                        .required(true)
+                        .value_name("PROFILE"),
+                )
+                .arg(
+                    Arg::new("scope")
+                        .help(
+                            "Snapshot scope: session, window (the current ones), \
+                             session=NAME or window=SESSION:WINDOW",
+                        )
+                        .short('s')
+                        .long("scope")
+                        .num_args(1)
+                        .value_name("SCOPE")
+                        .value_parser(|arg: &str| match parse_query_scope(arg)? {
+                            QueryScope::AllSessions => Err("use session or window".to_string()),
+                            scope => Ok(scope),
+                        })
+                        .default_value("window"),
+                )
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("pick")
+                .about("Pick a profile, config or running session in a fuzzy finder and open it")
+                .arg(
+                    Arg::new("profile")
+                        .help(
+                            "Open this profile in ~/.config/tmux-layout/profiles right away \
+                             (selecting its sessions if they're running)",
+                        )
+                        .required(false)
+                        .value_name("PROFILE")
+                        .conflicts_with("finder"),
+                )
+                .arg(
+                    Arg::new("finder")
+                        .help(
+                            "Fuzzy finder command reading candidates from STDIN and \
+                             printing the selection [default: $TMUX_LAYOUT_FINDER or fzf]",
+                        )
+                        .long("finder")
+                        .num_args(1)
+                        .value_name("COMMAND")
+                        .required(false),
+                )
+                .arg(&session_select_mode_arg)
+                .arg(&detach_others_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Open the config file in $EDITOR and validate it afterwards")
+                .arg(
+                    Arg::new("profile")
+                        .help(
+                            "Name of a profile in ~/.config/tmux-layout/profiles \
+                             (created if missing)",
+                        )
+                        .required(false)
+                        .value_name("PROFILE")
+                        .conflicts_with("config"),
+                )
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("tui")
+                        .long("tui")
+                        .help(
+                            "Split, resize and label panes in an interactive editor \
+                             (requires the 'tui' feature)",
+                        )
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Validate config file without running tmux")
+                .arg(&config_arg),
+        )
+        .subcommand(
+            Command::new("preview")
+                .about("Render the layout of every window as ASCII art without running tmux")
+                .arg(&config_arg)
+                .arg(
+                    Arg::new("size")
+                        .help("Size of the client to render for (default: size of the terminal)")
+                        .long("size")
+                        .num_args(1)
+                        .value_name("WIDTHxHEIGHT")
+                        .value_parser(|s: &str| {
+                            ClientSize::parse(s).ok_or("expected WIDTHxHEIGHT, e.g. 200x50")
+                        }),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Exports running tmux sessions into tmux-layout config file format")
+                .arg(
+                    Arg::new("scope")
+                        .help(
+                            "Export scope: all, session, window (the current ones), \
+                             session=NAME or window=SESSION:WINDOW",
+                        )
+                        .required(false)
+                        .short('s')
+                        .long("scope")
+                        .num_args(1)
+                        .value_name("SCOPE")
+                        .value_parser(parse_query_scope)
+                        .default_value("all"),
+                )
+                .arg(
+                    Arg::new("windows")
+                        .help(
+                            "Export only the given windows (by name) of the current \
+                             session (or the session given by --scope session=NAME) \
+                             as top-level windows",
+                        )
+                        .short('w')
+                        .long("windows")
+                        .num_args(1)
+                        .value_name("NAMES")
+                        .value_delimiter(','),
+                )
+                .arg(
+                    Arg::new("include-history")
+                        .help(
+                            "Capture the contents of each pane into a file below --history-dir. \
+                             Panes replay their file when the layout is created.",
+                        )
+                        .long("include-history")
+                        .action(ArgAction::SetTrue)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("history-dir")
+                        .help(
+                            "Directory for captured pane contents, relative to the config file \
+                             with --append (else to the current directory)",
+                        )
+                        .long("history-dir")
+                        .num_args(1)
+                        .value_name("DIR")
+                        .default_value("tmux-layout-history")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("include-processes")
+                        .help(
+                            "Record the PID of each pane and the command line of the program \
+                             running in its foreground (Linux only)",
+                        )
+                        .long("include-processes")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("include-alerts")
+                        .help(
+                            "Record the bell, activity and silence alerts of windows, so that \
+                             create keeps monitoring them",
+                        )
+                        .long("include-alerts")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("relativize")
+                        .help(
+                            "How to write working directories:\n\
+                                - home: like session, other paths below $HOME start with ~\n\
+                                - session: relative to the session or window cwd where possible\n\
+                                - none: absolute paths only\n",
+                        )
+                        .long("relativize")
+                        .num_args(1)
+                        .value_name("BASE")
+                        .value_parser(["home", "session", "none"])
+                        .default_value("session"),
+                )
+                .arg(&sizes_arg)
+                .arg(
+                    Arg::new("sort")
+                        .help(
+                            "Order of the exported sessions:\n\
+                                - id: by creation\n\
+                                - name: by name\n\
+                                - attached-first: sessions with attached clients first, \
+                                  each by creation\n",
+                        )
+                        .long("sort")
+                        .num_args(1)
+                        .value_name("ORDER")
+                        .value_parser(["id", "name", "attached-first"])
+                        .default_value("id"),
+                )
+                .arg(
+                    Arg::new("redact")
+                        .help(
+                            "Strip shell commands and keys to send, and write paths below \
+                             $HOME with ~ (for sharing the config publicly)",
+                        )
+                        .long("redact")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["include-history", "include-processes"]),
+                )
+                .arg(canonical_arg.clone().help(
+                    "Write the config deterministically for tracking it in git: sessions \
+                     ordered by name (overriding --sort, windows keep their order), normalized \
+                     sizes, a single trailing newline. With --append, only the added entries \
+                     are normalized",
+                ))
+                .arg(
+                    Arg::new("append")
+                        .help(
+                            "Add the exported sessions (or windows) to this config file \
+                             instead of printing them, replacing entries of the same name",
+                        )
+                        .long("append")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .conflicts_with("format"),
+                )
+                .arg(
+                    Arg::new("diff-against")
+                        .help(
+                            "Compare the export with this config file and print only the \
+                             differences as a unified diff. Exits with 0 if there are none and \
+                             with 9 otherwise (unlike diff, as 1 stands for errors). With \
+                             --output json it prints {\"changed\": BOOL, \"diff\": TEXT}",
+                        )
+                        .long("diff-against")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .conflicts_with("append"),
+                )
+                .arg(&format_arg)
+                .arg(&socket_name_arg)
+                .arg(&socket_path_arg)
+                .arg(&tmux_args),
+        )
+}
+
+#[test]
+fn verify_cli() {
+    app().debug_assert();
+}
+
+#[test]
+fn test_parse_query_scope() {
+    assert_eq!(parse_query_scope("all"), Ok(QueryScope::AllSessions));
+    assert_eq!(
+        parse_query_scope("session=dev"),
+        Ok(QueryScope::Session("dev".to_string()))
+    );
+    assert_eq!(
+        parse_query_scope("window=dev:editor"),
+        Ok(QueryScope::Window {
+            session: "dev".to_string(),
+            window: "editor".to_string()
+        })
+    );
+    assert!(parse_query_scope("window=dev").is_err());
+    assert!(parse_query_scope("session=").is_err());
+    assert!(parse_query_scope("pane").is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    assert!(parse_duration("0").is_err());
+    assert!(parse_duration("5d").is_err());
+    assert!(parse_duration("m").is_err());
+}
+
+#[test]
+fn test_output_options() {
+    let subcommand = |args: &[&str]| {
+        let args = ["tmux-layout"].iter().chain(args);
+        let matches = app().try_get_matches_from(args).unwrap();
+        match Subcommand::from_matches(&matches).unwrap() {
+            Subcommand::Save(opts) => format!("{:?}", opts.output),
+            Subcommand::Export(opts) => format!("{:?}", opts.format),
+            _ => unreachable!(),
+        }
+    };
+
+    assert_eq!(
+        subcommand(&["save", "--output", "snap.yaml"]),
+        r#"Some("snap.yaml")"#
+    );
+    assert_eq!(
+        subcommand(&["save", "--file", "snap.yaml"]),
+        r#"Some("snap.yaml")"#
+    );
+    assert_eq!(
+        subcommand(&["--output", "json", "save", "--output", "snap.yaml"]),
+        r#"Some("snap.yaml")"#
+    );
+    assert_eq!(subcommand(&["export"]), "Yaml");
+    assert_eq!(subcommand(&["--output", "json", "export"]), "Json");
+    assert_eq!(
+        subcommand(&["--output", "json", "export", "--format", "toml"]),
+        "Toml"
+    );
+}
+
+#[test]
+fn test_history_dir() {
+    let history_dir = |args: &[&str]| {
+        let args = ["tmux-layout", "export"].iter().chain(args);
+        let matches = app().try_get_matches_from(args).unwrap();
+        match Subcommand::from_matches(&matches).unwrap() {
+            Subcommand::Export(opts) => opts.history_dir,
+            _ => unreachable!(),
+        }
+    };
+
+    assert_eq!(history_dir(&[]), None);
+    assert_eq!(
+        history_dir(&["--include-history"]),
+        Some(PathBuf::from("tmux-layout-history"))
+    );
+    assert_eq!(
+        history_dir(&["--include-history", "--append", "/layouts/dev.yaml"]),
+        Some(PathBuf::from("/layouts/tmux-layout-history"))
+    );
+    assert_eq!(
+        history_dir(&[
+            "--include-history",
+            "--history-dir",
+            "/tmp/history",
+            "--append",
+            "dev.yaml"
+        ]),
+        Some(PathBuf::from("/tmp/history"))
+    );
 }
@@ -0,0 +1,88 @@
+use super::{ConfigIncludes, ConfigL, Split};
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Normalizes the config so the same layouts always serialize the same
+    /// (`export --canonical`): sessions are ordered by name and split sizes
+    /// are written as `N%` or `N`.
+    pub fn canonicalize(&mut self) {
+        self.sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        let session_windows = self.sessions.iter_mut().flat_map(|s| &mut s.windows);
+        for window in session_windows.chain(&mut self.windows) {
+            let layout_splits = window.layouts.iter_mut().map(|l| &mut l.root_split);
+            for root_split in std::iter::once(&mut window.root_split).chain(layout_splits) {
+                normalize_sizes(root_split);
+            }
+        }
+    }
+}
+
+fn normalize_sizes(split: &mut Split) {
+    match split {
+        Split::Pane(_) => {}
+        Split::H { left, right } => {
+            for part in [left, right] {
+                normalize_size(&mut part.width);
+                normalize_sizes(&mut part.split);
+            }
+        }
+        Split::V { top, bottom } => {
+            for part in [top, bottom] {
+                normalize_size(&mut part.height);
+                normalize_sizes(&mut part.split);
+            }
+        }
+    }
+}
+
+/// Writes sizes like ` 040 %` as `40%`, leaving unparsable ones alone
+fn normalize_size(size: &mut Option<String>) {
+    let Some(value) = size else {
+        return;
+    };
+    let normalized = match value.trim().strip_suffix('%') {
+        Some(percentage) => percentage.trim().parse::<u32>().map(|p| format!("{}%", p)),
+        None => value.trim().parse::<u32>().map(|cells| cells.to_string()),
+    };
+    if let Ok(normalized) = normalized {
+        *value = normalized;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+
+    #[test]
+    fn test_canonicalize() {
+        let load = |config_str: &str| serde_yaml::from_str::<Config>(config_str).unwrap();
+        let mut config = load(
+            r#"
+            sessions:
+              - name: web
+                windows:
+                  - left: { width: " 040 %" }
+                    right:
+                      top: { height: "12 " }
+                      bottom: { height: half }
+              - name: api
+                windows: []
+            "#,
+        );
+        config.canonicalize();
+
+        let expected = load(
+            r#"
+            sessions:
+              - name: api
+                windows: []
+              - name: web
+                windows:
+                  - left: { width: 40% }
+                    right:
+                      top: { height: "12" }
+                      bottom: { height: half }
+            "#,
+        );
+        assert_eq!(config, expected);
+    }
+}
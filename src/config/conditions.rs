@@ -0,0 +1,154 @@
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{ConfigIncludes, ConfigL};
+
+/// Conditions under which a session or window is created (all given ones
+/// must hold), e.g. `when: { hostname: "work-*", os: linux }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct When {
+    /// Glob pattern for the host name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Operating system as reported by Rust (`linux`, `macos`, `freebsd`, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// Glob patterns for environment variables (unset variables match `""`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+/// The machine conditions are evaluated against
+#[derive(Debug, Clone)]
+pub struct Host {
+    pub hostname: String,
+    pub os: String,
+}
+
+impl Host {
+    pub fn current() -> Host {
+        Host {
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+            os: std::env::consts::OS.to_string(),
+        }
+    }
+}
+
+impl When {
+    pub fn matches(&self, host: &Host) -> Result<bool, glob::PatternError> {
+        if let Some(hostname) = &self.hostname {
+            if !Pattern::new(hostname)?.matches(&host.hostname) {
+                return Ok(false);
+            }
+        }
+        if let Some(os) = &self.os {
+            if !os.eq_ignore_ascii_case(&host.os) {
+                return Ok(false);
+            }
+        }
+        for (var, pattern) in &self.env {
+            let value = std::env::var(var).unwrap_or_default();
+            if !Pattern::new(pattern)?.matches(&value) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn is_enabled(when: &mut Option<When>, host: &Host) -> Result<bool, glob::PatternError> {
+    match when.take() {
+        None => Ok(true),
+        Some(when) => when.matches(host),
+    }
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Removes all sessions and windows whose `when` conditions don't hold
+    /// on `host` (and clears the conditions of the remaining ones).
+    pub fn apply_conditions(&mut self, host: &Host) -> Result<(), glob::PatternError> {
+        let mut result = Ok(());
+        let mut retain = |when: &mut Option<When>| match is_enabled(when, host) {
+            Ok(enabled) => enabled,
+            Err(err) => {
+                result = Err(err);
+                false
+            }
+        };
+
+        self.sessions
+            .retain_mut(|session| retain(&mut session.when));
+        for session in &mut self.sessions {
+            session
+                .windows
+                .retain_mut(|window| retain(&mut window.when));
+        }
+        self.windows.retain_mut(|window| retain(&mut window.when));
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{Config, PartialConfig};
+
+    #[test]
+    fn test_apply_conditions() {
+        let mut config: Config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            sessions:
+              - name: work
+                when: { hostname: "work-*" }
+                windows:
+                  - name: a
+              - name: both
+                windows:
+                  - name: linux
+                    when: { os: Linux }
+                  - name: mac
+                    when: { os: macos }
+                  - name: unset-env
+                    when: { env: { TMUX_LAYOUT_UNSET_TEST_VAR: "" } }
+            windows:
+              - name: home
+                when: { hostname: "home-?", os: linux }
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+
+        let host = Host {
+            hostname: "work-laptop".to_string(),
+            os: "linux".to_string(),
+        };
+        config.apply_conditions(&host).unwrap();
+
+        let sessions = config
+            .sessions
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(sessions, ["work", "both"]);
+        let windows = config.sessions[1]
+            .windows
+            .iter()
+            .map(|w| w.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(windows, ["linux", "unset-env"]);
+        assert!(config.sessions[1].windows[0].when.is_none());
+        assert!(config.windows.is_empty());
+
+        config.windows.push(crate::config::Window {
+            when: Some(When {
+                hostname: Some("[".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert!(config.apply_conditions(&host).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ConfigIncludes, ConfigL, Key, Split, Window};
+
+type Cwd = crate::cwd::Cwd<'static>;
+
+/// Settings merged into every session, window and pane of the same config
+/// file unless they are set explicitly.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Defaults {
+    /// Base directory for sessions and top-level windows. Relative cwds of
+    /// sessions and top-level windows are resolved against it.
+    #[serde(skip_serializing_if = "Cwd::is_empty")]
+    pub cwd: Cwd,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_keys: Option<Vec<Key>>,
+    /// Used by panes without `wrapper`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<String>,
+    /// Width of the right part of horizontal splits without any size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<String>,
+    /// Height of the bottom part of vertical splits without any size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<String>,
+}
+
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn apply_to_window(&self, window: &mut Window) {
+        let shell = window.shell.take();
+        let direnv = window.direnv.take();
+        let wrapper = window.wrapper.take();
+        let layout_splits = window.layouts.iter_mut().map(|l| &mut l.root_split);
+        for root_split in std::iter::once(&mut window.root_split).chain(layout_splits) {
+            for pane in root_split.pane_iter_mut() {
+                if pane.shell.is_none() {
+                    pane.shell.clone_from(&shell);
+                }
+                if pane.direnv.is_none() {
+                    pane.direnv = direnv;
+                }
+                if pane.wrapper.is_none() {
+                    pane.wrapper.clone_from(&wrapper);
+                }
+            }
+        }
+        self.apply_to_split(&mut window.root_split);
+        for layout in &mut window.layouts {
+            self.apply_to_split(&mut layout.root_split);
+        }
+    }
+
+    fn apply_to_split(&self, split: &mut Split) {
+        match split {
+            Split::Pane(pane) => {
+                if pane.shell_command.is_none() {
+                    pane.shell_command.clone_from(&self.shell_command);
+                }
+                if pane.send_keys.is_none() {
+                    pane.send_keys.clone_from(&self.send_keys);
+                }
+                if pane.wrapper.is_none() {
+                    pane.wrapper.clone_from(&self.wrapper);
+                }
+            }
+            Split::H { left, right } => {
+                if left.width.is_none() && right.width.is_none() {
+                    right.width.clone_from(&self.width);
+                }
+                self.apply_to_split(&mut left.split);
+                self.apply_to_split(&mut right.split);
+            }
+            Split::V { top, bottom } => {
+                if top.height.is_none() && bottom.height.is_none() {
+                    bottom.height.clone_from(&self.height);
+                }
+                self.apply_to_split(&mut top.split);
+                self.apply_to_split(&mut bottom.split);
+            }
+        }
+    }
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Merges the `defaults` block into the sessions and windows of this
+    /// config and clears it afterwards. Session- and window-level pane
+    /// settings (like `shell`, `direnv` and `wrapper`) are pushed down into
+    /// the panes as well.
+    pub fn apply_defaults(&mut self) {
+        let defaults = std::mem::take(&mut self.defaults);
+
+        for session in &mut self.sessions {
+            session.cwd = defaults.cwd.joined(&session.cwd).into_owned();
+            let direnv = session.direnv.take();
+            let wrapper = session.wrapper.take();
+            for window in &mut session.windows {
+                if window.direnv.is_none() {
+                    window.direnv = direnv;
+                }
+                if window.wrapper.is_none() {
+                    window.wrapper.clone_from(&wrapper);
+                }
+                defaults.apply_to_window(window);
+            }
+        }
+        for window in &mut self.windows {
+            window.cwd = defaults.cwd.joined(&window.cwd).into_owned();
+            defaults.apply_to_window(window);
+        }
+        for repo in &mut self.repos {
+            repo.path = defaults.cwd.joined(&repo.path).into_owned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Config, PartialConfig};
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            defaults:
+              cwd: /base
+              shell_command: fish
+              width: 30%
+            sessions:
+              - name: a
+                cwd: project
+                windows:
+                  - left: {}
+                    right:
+                      top: {}
+                      bottom: {}
+            windows:
+              - cwd: /elsewhere
+                shell: zsh
+                left: {}
+                right: { shell: bash }
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+        config.apply_defaults();
+
+        assert!(config.defaults.is_empty());
+
+        let session = &config.sessions[0];
+        assert_eq!(session.cwd, "/base/project");
+
+        let expected_window = serde_yaml::from_str::<Config>(
+            r#"
+            windows:
+              - left: { shell_command: fish }
+                right:
+                  width: 30%
+                  top: { shell_command: fish }
+                  bottom: { shell_command: fish }
+            "#,
+        )
+        .unwrap()
+        .windows
+        .remove(0);
+        assert_eq!(session.windows[0], expected_window);
+
+        let window = &config.windows[0];
+        assert_eq!(window.cwd, "/elsewhere");
+        assert_eq!(window.shell, None);
+        let shells = window
+            .root_split
+            .pane_iter()
+            .map(|p| p.shell.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(shells, vec![Some("zsh"), Some("bash")]);
+    }
+
+    #[test]
+    fn test_wrapper_inheritance() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            defaults:
+              wrapper: nix develop -c
+            sessions:
+              - name: a
+                wrapper: poetry run
+                windows:
+                  - left: {}
+                    right: { wrapper: "" }
+              - name: b
+                windows:
+                  - wrapper: docker compose exec app
+                    left: {}
+                    right: {}
+                  - {}
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+        config.apply_defaults();
+
+        let wrappers = config
+            .sessions
+            .iter()
+            .flat_map(|s| &s.windows)
+            .flat_map(|w| w.root_split.pane_iter().map(|p| p.wrapper.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            wrappers,
+            [
+                Some("poetry run"),
+                Some(""),
+                Some("docker compose exec app"),
+                Some("docker compose exec app"),
+                Some("nix develop -c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_direnv_inheritance() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            sessions:
+              - name: a
+                direnv: true
+                windows:
+                  - left: {}
+                    right: { direnv: false }
+                  - direnv: false
+                    left: {}
+                    right: { direnv: true }
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+        config.apply_defaults();
+
+        let session = &config.sessions[0];
+        assert_eq!(session.direnv, None);
+        let direnv = session
+            .windows
+            .iter()
+            .map(|w| {
+                w.root_split
+                    .pane_iter()
+                    .map(|p| p.direnv)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            direnv,
+            [[Some(true), Some(false)], [Some(false), Some(true)]]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Graph export of the session/window/split tree for documentation
+//! (`dump-config --format dot|mermaid`).
+
+use super::{Config, Pane, Split, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz `digraph`
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// Renders all sessions (and top-level windows) of `config` as a graph with
+/// a node per session, window, split and pane. Edges into parts of splits
+/// are labeled with the side and its size.
+pub fn to_graph(config: &Config, format: GraphFormat) -> String {
+    let mut graph = Graph::default();
+    for session in &config.sessions {
+        let session_node = graph.node(format!("session {}", session.name));
+        for window in &session.windows {
+            let window_node = graph.window(window);
+            graph.edge(session_node, window_node, None);
+        }
+    }
+    for window in &config.windows {
+        graph.window(window);
+    }
+    match format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::Mermaid => graph.to_mermaid(),
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    /// Lines of the label of every node (the index is the node id)
+    nodes: Vec<Vec<String>>,
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+impl Graph {
+    fn node(&mut self, label: String) -> usize {
+        self.nodes.push(vec![label]);
+        self.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<String>) {
+        self.edges.push((from, to, label));
+    }
+
+    fn window(&mut self, window: &Window) -> usize {
+        let name = window.name.as_deref().unwrap_or("(unnamed)");
+        let window_node = self.node(format!("window {}", name));
+        let mut pane_index = 0;
+        let split_node = self.split(&window.root_split, &mut pane_index);
+        self.edge(window_node, split_node, None);
+        window_node
+    }
+
+    fn split(&mut self, split: &Split, pane_index: &mut usize) -> usize {
+        let (label, first, second) = match split {
+            Split::Pane(pane) => {
+                let node = self.pane(pane, *pane_index);
+                *pane_index += 1;
+                return node;
+            }
+            Split::H { left, right } => (
+                "split left/right",
+                (&left.split, part_label("left", &left.width)),
+                (&right.split, part_label("right", &right.width)),
+            ),
+            Split::V { top, bottom } => (
+                "split top/bottom",
+                (&top.split, part_label("top", &top.height)),
+                (&bottom.split, part_label("bottom", &bottom.height)),
+            ),
+        };
+        let split_node = self.node(label.to_string());
+        for (part, part_label) in [first, second] {
+            let part_node = self.split(part, pane_index);
+            self.edge(split_node, part_node, Some(part_label));
+        }
+        split_node
+    }
+
+    fn pane(&mut self, pane: &Pane, index: usize) -> usize {
+        let active = if pane.active { "*" } else { "" };
+        let mut label = vec![format!("pane {}{}", index, active)];
+        if let Some(command) = &pane.shell_command {
+            label.push(command.clone());
+        }
+        if let Some(cwd) = pane.cwd.to_path() {
+            label.push(cwd.to_string_lossy().into_owned());
+        }
+        self.nodes.push(label);
+        self.nodes.len() - 1
+    }
+
+    fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph layout {\n    node [shape=box];\n");
+        for (id, label) in self.nodes.iter().enumerate() {
+            let label = label.iter().map(|l| escape(l)).collect::<Vec<_>>();
+            dot += &format!("    n{} [label=\"{}\"];\n", id, label.join("\\n"));
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(label) => {
+                    dot += &format!("    n{} -> n{} [label=\"{}\"];\n", from, to, escape(label))
+                }
+                None => dot += &format!("    n{} -> n{};\n", from, to),
+            }
+        }
+        dot + "}\n"
+    }
+
+    fn to_mermaid(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "#amp;")
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;")
+                .replace('|', "#124;")
+        };
+        let mut mermaid = String::from("graph TD\n");
+        for (id, label) in self.nodes.iter().enumerate() {
+            let label = label.iter().map(|l| escape(l)).collect::<Vec<_>>();
+            mermaid += &format!("    n{}[\"{}\"]\n", id, label.join("<br/>"));
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(label) => {
+                    mermaid += &format!("    n{} -->|{}| n{}\n", from, escape(label), to)
+                }
+                None => mermaid += &format!("    n{} --> n{}\n", from, to),
+            }
+        }
+        mermaid
+    }
+}
+
+fn part_label(side: &str, size: &Option<String>) -> String {
+    match size {
+        Some(size) => format!("{} {}", side, size),
+        None => side.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_graph() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            sessions:
+              - name: dev
+                windows:
+                  - name: code
+                    left:
+                      width: 60%
+                      shell_command: nvim
+                      active: true
+                    right:
+                      cwd: /var/log
+            "#,
+        )
+        .unwrap();
+
+        let dot = to_graph(&config, GraphFormat::Dot);
+        assert_eq!(
+            dot,
+            r#"digraph layout {
+    node [shape=box];
+    n0 [label="session dev"];
+    n1 [label="window code"];
+    n2 [label="split left/right"];
+    n3 [label="pane 0*\nnvim"];
+    n4 [label="pane 1\n/var/log"];
+    n2 -> n3 [label="left 60%"];
+    n2 -> n4 [label="right"];
+    n1 -> n2;
+    n0 -> n1;
+}
+"#
+        );
+
+        let mermaid = to_graph(&config, GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("graph TD\n    n0[\"session dev\"]\n"));
+        assert!(mermaid.contains("    n3[\"pane 0*<br/>nvim\"]\n"));
+        assert!(mermaid.contains("    n2 -->|left 60%| n3\n"));
+    }
+}
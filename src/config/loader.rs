@@ -1,22 +1,62 @@
 use shellexpand::LookupError;
 use std::env::VarError;
+use std::io::Read;
 use std::os::unix::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 use thiserror::Error;
 
-use crate::show_warning;
+use crate::{logging, show_warning};
 
-use super::{Config, PartialConfig};
+use super::{Config, Host, MergeStrategy, PartialConfig};
 
-pub fn load_config_at(path: &Path) -> Result<Config, Error> {
+/// How config files are looked up and combined with the files they include
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Whether [`find_default_config_file`] looks into the parent directories
+    /// of the current directory (`--no-ascend` disables it)
+    pub ascend: bool,
+    /// Directories [`find_default_config_file`] searches instead of the home
+    /// and the user's config directory (`--config-dir` or
+    /// `$TMUX_LAYOUT_CONFIG_DIR`). An empty entry stands for these default
+    /// directories, e.g. `/team/layouts:` searches them after `/team/layouts`.
+    pub config_dirs: Option<Vec<PathBuf>>,
+    /// How sessions and windows from included files are combined with those
+    /// of the same name from the including file (`--merge-strategy`)
+    pub merge_strategy: MergeStrategy,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            ascend: true,
+            config_dirs: None,
+            merge_strategy: MergeStrategy::default(),
+        }
+    }
+}
+
+pub fn load_config_at(path: &Path, options: &LoadOptions) -> Result<Config, Error> {
     let partial_config = load_partial_config_at(path)?;
     let mut config = Config {
+        defaults: partial_config.defaults,
+        templates: partial_config.templates,
         selected_session: partial_config.selected_session,
+        selected_target: partial_config.selected_target,
+        on_created: partial_config.on_created,
+        detach_others: partial_config.detach_others,
+        socket: partial_config.socket,
         sessions: partial_config.sessions,
         windows: partial_config.windows,
+        repos: partial_config.repos,
         ..Default::default()
     };
+    resolve_own_entries(&mut config, path.parent().unwrap()).map_err(|message| {
+        Error::ParseError {
+            path: path.to_owned(),
+            message,
+        }
+    })?;
 
     for included_path in partial_config.includes.0 {
         let included_path = shellexpand::full(&included_path)?;
@@ -25,26 +65,83 @@ pub fn load_config_at(path: &Path) -> Result<Config, Error> {
             .unwrap()
             .join(Path::new(included_path.as_ref()));
 
-        let mut included_config = load_config_at(&included_path)?;
-        // Merge sessions and windows
-        config.sessions.append(&mut included_config.sessions);
-        config.windows.append(&mut included_config.windows);
-
-        // Merge selected session
-        if let Some(select_session) = included_config.selected_session {
-            if config.selected_session.is_none() {
-                config.selected_session = Some(select_session);
-            } else {
-                show_warning(&format!(
-                    "ignoring selected session \"{}\" from {:?}",
-                    select_session, included_path
-                ))
+        let included_config = load_config_at(&included_path, options)?;
+        let strategy = options.merge_strategy;
+        if strategy != MergeStrategy::Overwrite {
+            let ignored = [
+                (
+                    "selected session",
+                    &config.selected_session,
+                    &included_config.selected_session,
+                ),
+                (
+                    "selected target",
+                    &config.selected_target,
+                    &included_config.selected_target,
+                ),
+                (
+                    "on_created hook",
+                    &config.on_created,
+                    &included_config.on_created,
+                ),
+                ("socket", &config.socket, &included_config.socket),
+            ];
+            for (what, existing, included) in ignored {
+                if let (Some(_), Some(included)) = (existing, included) {
+                    show_warning(&format!(
+                        "ignoring {} \"{}\" from {:?}",
+                        what, included, included_path
+                    ))
+                }
             }
         }
+        config.merge(included_config, strategy);
     }
     Ok(config)
 }
 
+/// Loads a config without includes (e.g. from STDIN) from `reader`, guessing
+/// its format. Relative repo paths are resolved against `base_dir`.
+pub fn load_config_from_reader(mut reader: impl Read, base_dir: &Path) -> Result<Config, Error> {
+    let parse_error = |message: String| Error::ReaderParseError { message };
+    let mut config_bytes = Vec::new();
+    reader
+        .read_to_end(&mut config_bytes)
+        .map_err(|err| parse_error(err.to_string()))?;
+    let config_str = std::str::from_utf8(&config_bytes)
+        .map_err(|err| parse_error(format!("UTF-8 error: {}", err)))?;
+
+    // Guess format
+    let partial_config: PartialConfig = if config_str.starts_with("[[") {
+        toml::from_str(config_str).map_err(|err| parse_error(err.to_string()))?
+    } else {
+        serde_yaml::from_str(config_str)
+            .or_else(|_| toml::from_str(config_str))
+            .map_err(|err| parse_error(err.to_string()))?
+    };
+    let mut config = partial_config
+        .into_config()
+        .map_err(|_| parse_error("file includes aren't supported here".to_string()))?;
+    resolve_own_entries(&mut config, base_dir).map_err(parse_error)?;
+    Ok(config)
+}
+
+/// Resolves the entries a config file declares itself (before merging its
+/// includes): applies templates, expands repos (with relative paths resolved
+/// against `base_dir`) and session names, drops entries whose `when`
+/// conditions don't match this host and applies the defaults.
+fn resolve_own_entries(config: &mut Config, base_dir: &Path) -> Result<(), String> {
+    config.apply_templates()?;
+    config.expand_repos(base_dir);
+    config.expand_session_names(&chrono::Local::now())?;
+    config
+        .apply_conditions(&Host::current())
+        .map_err(|err| format!("invalid pattern in `when`: {}", err))?;
+    // Defaults only apply to the file they are declared in.
+    config.apply_defaults();
+    Ok(())
+}
+
 pub fn load_partial_config_at(path: &Path) -> Result<PartialConfig, Error> {
     let config_bytes = fs::read(path).map_err(|error| Error::Io {
         path: path.to_owned(),
@@ -74,32 +171,256 @@ pub fn load_partial_config_at(path: &Path) -> Result<PartialConfig, Error> {
     }
 }
 
-pub fn find_default_config_file() -> Option<PathBuf> {
-    const BASENAME: &str = ".tmux-layout";
-    const EXTS: [&str; 3] = ["yaml", "yml", "toml"];
+/// Extensions of config files
+pub const EXTS: [&str; 3] = ["yaml", "yml", "toml"];
 
+/// Environment variable with the directories searched for the default config
+/// file (separated by `:`, see [`LoadOptions::config_dirs`])
+pub const CONFIG_DIR_ENV: &str = "TMUX_LAYOUT_CONFIG_DIR";
+
+const BASENAME: &str = ".tmux-layout";
+
+/// Looks for `.tmux-layout.{yaml,yml,toml}` in the current directory and its
+/// parents (up to the root, like `.git` discovery), then in the home
+/// directory, and finally for `config.{yaml,yml,toml}` in the user's config
+/// directory (see [`LoadOptions`] for skipping the parents and replacing the
+/// latter two). The search order is logged at debug level (`-v`).
+pub fn find_default_config_file(options: &LoadOptions) -> Option<PathBuf> {
     let current_dir = std::env::current_dir().ok()?;
-    let home_dir = dirs::home_dir()?;
+    let home_dir = dirs::home_dir();
+    let user_config_dir = dirs::config_dir().map(|dir| dir.join("tmux-layout"));
+    let locations = search_locations(
+        &current_dir,
+        options.ascend,
+        options.config_dirs.as_deref(),
+        home_dir.as_deref(),
+        user_config_dir.as_deref(),
+    );
 
-    for dir in &[current_dir, home_dir] {
-        for ext in &EXTS {
-            let file_path = dir.join(format!("{}.{}", BASENAME, ext));
-            if file_path.exists() {
-                return Some(file_path);
-            }
+    let order = locations
+        .iter()
+        .map(|(dir, basename)| dir.join(format!("{}.*", basename)).display().to_string())
+        .collect::<Vec<_>>();
+    logging::debug(&format!("config search order: {}", order.join(", ")));
+
+    locations.iter().find_map(|(dir, basename)| {
+        EXTS.iter()
+            .map(|ext| dir.join(format!("{}.{}", basename, ext)))
+            .find(|path| path.exists())
+    })
+}
+
+/// Directories (with the base name of config files in them) in the order
+/// [`find_default_config_file`] searches them
+fn search_locations(
+    current_dir: &Path,
+    ascend: bool,
+    config_dirs: Option<&[PathBuf]>,
+    home_dir: Option<&Path>,
+    user_config_dir: Option<&Path>,
+) -> Vec<(PathBuf, &'static str)> {
+    let mut locations = if ascend {
+        current_dir
+            .ancestors()
+            .map(|dir| (dir.to_owned(), BASENAME))
+            .collect()
+    } else {
+        vec![(current_dir.to_owned(), BASENAME)]
+    };
+    let default_locations = home_dir
+        .map(|dir| (dir.to_owned(), BASENAME))
+        .into_iter()
+        .chain(user_config_dir.map(|dir| (dir.to_owned(), "config")));
+    let Some(config_dirs) = config_dirs else {
+        locations.extend(default_locations);
+        return locations;
+    };
+
+    let mut default_locations = Some(default_locations);
+    for dir in config_dirs {
+        if dir.as_os_str().is_empty() {
+            locations.extend(default_locations.take().into_iter().flatten());
+        } else {
+            locations.push((dir.clone(), BASENAME));
+            locations.push((dir.clone(), "config"));
         }
     }
+    locations
+}
+
+/// Path of the config file in the user's config directory, i.e.
+/// `$XDG_CONFIG_HOME/tmux-layout/config.yaml`.
+pub fn global_config_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("tmux-layout").join("config.yaml"))
+}
+
+/// Directory of named config files ("profiles"), i.e.
+/// `$XDG_CONFIG_HOME/tmux-layout/profiles`.
+pub fn profiles_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("tmux-layout").join("profiles"))
+}
+
+/// Looks up the config file of the profile `name` in [`profiles_dir`].
+pub fn find_profile(name: &str) -> Option<PathBuf> {
+    let dir = profiles_dir()?;
+    EXTS.iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.exists())
+}
+
+/// Lists all profiles (name and config file path), sorted by name.
+pub fn list_profiles() -> Vec<(String, PathBuf)> {
+    let Some(entries) = profiles_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut profiles = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let ext = path.extension()?.to_str()?;
+            if !EXTS.contains(&ext) {
+                return None;
+            }
+            Some((path.file_stem()?.to_str()?.to_string(), path))
+        })
+        .collect::<Vec<_>>();
+    profiles.sort();
+    profiles
+}
 
-    None
+/// Path of the profile `name`: the existing config file if there is one,
+/// a new YAML file in [`profiles_dir`] otherwise.
+pub fn profile_path(name: &str) -> Option<PathBuf> {
+    find_profile(name).or_else(|| Some(profiles_dir()?.join(format!("{}.yaml", name))))
 }
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("failed to load config file at {path:?}: {error}")]
     Io { path: PathBuf, error: io::Error },
     #[error("failed to parse config file at {path:?}: {message}")]
     ParseError { path: PathBuf, message: String },
+    #[error("failed to parse config: {message}")]
+    ReaderParseError { message: String },
     #[error("unsupported config format (supported: YAML, TOML)")]
     UnsupportedFormat,
     #[error("variable lookup error: {0}")]
     LookupError(#[from] LookupError<VarError>),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_search_locations() {
+        let locations = |ascend, config_dirs: Option<&[PathBuf]>| {
+            search_locations(
+                Path::new("/src/repo"),
+                ascend,
+                config_dirs,
+                Some(Path::new("/home/me")),
+                Some(Path::new("/home/me/.config/tmux-layout")),
+            )
+            .into_iter()
+            .map(|(dir, basename)| dir.join(basename).display().to_string())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            locations(true, None),
+            [
+                "/src/repo/.tmux-layout",
+                "/src/.tmux-layout",
+                "/.tmux-layout",
+                "/home/me/.tmux-layout",
+                "/home/me/.config/tmux-layout/config",
+            ]
+        );
+        let team_dir = [PathBuf::from("/team")];
+        assert_eq!(
+            locations(false, Some(&team_dir)),
+            [
+                "/src/repo/.tmux-layout",
+                "/team/.tmux-layout",
+                "/team/config",
+            ]
+        );
+        let team_dir_then_defaults = [PathBuf::from("/team"), PathBuf::new()];
+        assert_eq!(
+            locations(false, Some(&team_dir_then_defaults)),
+            [
+                "/src/repo/.tmux-layout",
+                "/team/.tmux-layout",
+                "/team/config",
+                "/home/me/.tmux-layout",
+                "/home/me/.config/tmux-layout/config",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_config_from_reader() {
+        let config_str = r#"
+            sessions:
+              - name: here
+                windows: [{}]
+              - name: elsewhere
+                when: { hostname: "no-such-host-*" }
+                windows: [{}]
+            repos:
+              - path: tmp
+            "#;
+        let config = load_config_from_reader(config_str.as_bytes(), Path::new("/src")).unwrap();
+        let sessions = config
+            .sessions
+            .iter()
+            .map(|s| (s.name.as_str(), s.cwd.to_path()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sessions,
+            [("here", None), ("tmp", Some(Path::new("/src/tmp")))]
+        );
+
+        let toml_str = "[[sessions]]\nname = \"dev\"\n[[sessions.windows]]\n";
+        let config = load_config_from_reader(toml_str.as_bytes(), Path::new("/")).unwrap();
+        assert_eq!(config.sessions[0].name, "dev");
+
+        let err = load_config_from_reader("includes: [other.yaml]".as_bytes(), Path::new("/"));
+        assert!(matches!(err, Err(Error::ReaderParseError { .. })));
+    }
+
+    #[test]
+    fn test_load_includes_with_merge_strategy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.yaml");
+        fs::write(
+            &path,
+            "includes: [included.yaml]\nsessions:\n  - name: dev\n    windows: [{name: edit}]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("included.yaml"),
+            "sessions:\n  - name: dev\n    windows: [{name: logs}]\n",
+        )
+        .unwrap();
+
+        let window_names = |merge_strategy| {
+            let options = LoadOptions {
+                merge_strategy,
+                ..Default::default()
+            };
+            let config = load_config_at(&path, &options).unwrap();
+            config
+                .sessions
+                .iter()
+                .map(|session| {
+                    let names = session.windows.iter().flat_map(|w| w.name.as_deref());
+                    names.collect::<Vec<_>>().join(",")
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(window_names(MergeStrategy::Append), ["edit", "logs"]);
+        assert_eq!(window_names(MergeStrategy::Merge), ["edit,logs"]);
+        assert_eq!(window_names(MergeStrategy::Overwrite), ["logs"]);
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Config, ConfigIncludes, ConfigL, Session, Window};
+
+/// How [`ConfigL::merge`] combines sessions (and top-level windows) with
+/// entries of the same name (`--merge-strategy` for included files)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep both, unless one of two sessions has `merge: true`
+    #[default]
+    Append,
+    /// Always add the windows of a session to the existing one
+    Merge,
+    /// Drop sessions and windows whose name exists already
+    DedupByName,
+    /// Replace sessions and windows of the same name
+    Overwrite,
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Adds the sessions, top-level windows and repos of `other`, combined
+    /// with entries of the same name according to `strategy`.
+    /// `selected_session`, `selected_target`, `on_created` and `socket` of
+    /// `other` only apply if unset here (or with `Overwrite`), its defaults and
+    /// templates are ignored (see [`Config::apply_defaults`]). Returns the
+    /// number of sessions and windows combined with existing ones.
+    pub fn merge(&mut self, other: Config, strategy: MergeStrategy) -> usize {
+        let mut combined = 0;
+        for session in other.sessions {
+            combined += usize::from(self.merge_session(session, strategy));
+        }
+        for window in other.windows {
+            combined += usize::from(self.merge_window(window, strategy));
+        }
+        self.repos.extend(other.repos);
+
+        let overwrite = strategy == MergeStrategy::Overwrite;
+        let merge_option = |option: &mut Option<String>, other: Option<String>| {
+            if other.is_some() && (overwrite || option.is_none()) {
+                *option = other;
+            }
+        };
+        merge_option(&mut self.selected_session, other.selected_session);
+        merge_option(&mut self.selected_target, other.selected_target);
+        merge_option(&mut self.on_created, other.on_created);
+        merge_option(&mut self.socket, other.socket);
+        self.detach_others |= other.detach_others;
+        combined
+    }
+
+    fn merge_session(&mut self, session: Session, strategy: MergeStrategy) -> bool {
+        let existing = self.sessions.iter_mut().find(|existing| {
+            existing.name == session.name
+                && (strategy != MergeStrategy::Append || existing.merge || session.merge)
+        });
+        let Some(existing) = existing else {
+            self.sessions.push(session);
+            return false;
+        };
+        match strategy {
+            MergeStrategy::Append | MergeStrategy::Merge => append_session(existing, session),
+            MergeStrategy::DedupByName => {}
+            MergeStrategy::Overwrite => *existing = session,
+        }
+        true
+    }
+
+    fn merge_window(&mut self, window: Window, strategy: MergeStrategy) -> bool {
+        match strategy {
+            MergeStrategy::Append | MergeStrategy::Merge => {
+                self.windows.push(window);
+                false
+            }
+            MergeStrategy::DedupByName => {
+                let exists =
+                    window.name.is_some() && self.windows.iter().any(|w| w.name == window.name);
+                if !exists {
+                    self.windows.push(window);
+                }
+                exists
+            }
+            MergeStrategy::Overwrite => self.upsert_window(window),
+        }
+    }
+}
+
+/// Appends the windows, popups, raw commands and `on_kill` commands of
+/// `session` to `existing`,
+/// everything else is kept from `existing` where set
+fn append_session(existing: &mut Session, session: Session) {
+    // Windows keep their cwd relative to their own session.
+    let session_cwd = &session.cwd;
+    existing
+        .windows
+        .extend(session.windows.into_iter().map(|mut window| {
+            window.cwd = session_cwd.joined(&window.cwd).into_owned();
+            window
+        }));
+    existing.popups.extend(session.popups);
+    existing.raw_commands.extend(session.raw_commands);
+    existing.on_kill.extend(session.on_kill);
+    if existing.status.is_empty() {
+        existing.status = session.status;
+    }
+    if existing.default_command.is_none() {
+        existing.default_command = session.default_command;
+    }
+    if existing.default_terminal.is_none() {
+        existing.default_terminal = session.default_terminal;
+    }
+    if existing.match_by.is_none() {
+        existing.match_by = session.match_by;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_merge() {
+        let load = |config_str: &str| serde_yaml::from_str::<Config>(config_str).unwrap();
+        let config = || {
+            load(
+                r#"
+            sessions:
+              - name: dev
+                merge: true
+                cwd: /src
+                windows:
+                  - name: editor
+              - name: ops
+                windows:
+                  - name: logs
+            windows:
+              - name: scratch
+            "#,
+            )
+        };
+        let other = || {
+            load(
+                r#"
+                selected_session: ops
+                sessions:
+                  - name: dev
+                    cwd: /src/app
+                    windows:
+                      - name: server
+                        cwd: server
+                      - name: tests
+                  - name: ops
+                    windows:
+                      - name: monitor
+                windows:
+                  - name: scratch
+                    cwd: /tmp
+                "#,
+            )
+        };
+        fn names(config: &Config) -> (Vec<&str>, Vec<&str>) {
+            let sessions = config.sessions.iter().map(|s| s.name.as_str());
+            let windows = config.windows.iter().map(|w| w.name.as_deref().unwrap());
+            (sessions.collect(), windows.collect())
+        }
+
+        let mut appended = config();
+        assert_eq!(appended.merge(other(), MergeStrategy::Append), 1);
+        assert_eq!(
+            names(&appended),
+            (vec!["dev", "ops", "ops"], vec!["scratch", "scratch"])
+        );
+        assert_eq!(appended.selected_session.as_deref(), Some("ops"));
+        let dev_windows = appended.sessions[0]
+            .windows
+            .iter()
+            .map(|w| (w.name.as_deref().unwrap(), w.cwd.to_path()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dev_windows,
+            [
+                ("editor", None),
+                ("server", Some(Path::new("/src/app/server"))),
+                ("tests", Some(Path::new("/src/app"))),
+            ]
+        );
+
+        let mut merged = config();
+        assert_eq!(merged.merge(other(), MergeStrategy::Merge), 2);
+        assert_eq!(
+            names(&merged),
+            (vec!["dev", "ops"], vec!["scratch", "scratch"])
+        );
+        assert_eq!(merged.sessions[1].windows.len(), 2);
+
+        let mut deduped = config();
+        assert_eq!(deduped.merge(other(), MergeStrategy::DedupByName), 3);
+        assert_eq!(deduped.sessions, config().sessions);
+        assert_eq!(deduped.windows, config().windows);
+
+        let mut overwritten = config();
+        assert_eq!(overwritten.merge(other(), MergeStrategy::Overwrite), 3);
+        assert_eq!(overwritten.sessions, other().sessions);
+        assert_eq!(overwritten.windows, other().windows);
+    }
+}
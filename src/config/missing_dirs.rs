@@ -0,0 +1,180 @@
+//! Handling of configured working directories which don't exist (yet).
+//!
+//! tmux silently falls back to another directory when a `-c` directory is
+//! missing, so `create` checks the resolved cwds of all sessions, windows and
+//! panes before running tmux and acts according to `--missing-dirs`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::{Config, RootSplit};
+
+type Cwd = crate::cwd::Cwd<'static>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingDirsPolicy {
+    /// Abort before running any tmux command
+    Error,
+    /// Report missing directories and let tmux fall back
+    #[default]
+    Warn,
+    /// Create missing directories (including parents)
+    Create,
+    /// Use the home directory instead
+    FallbackHome,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("directory '{}' does not exist", .0.display())]
+    Missing(PathBuf),
+    #[error("failed to create directory '{}': {}", .0.display(), .1)]
+    Create(PathBuf, io::Error),
+    #[error("cannot determine home directory")]
+    NoHomeDir,
+}
+
+/// Checks the resolved cwds of all sessions, windows and panes of `config`
+/// and handles missing directories according to `policy`. With
+/// `FallbackHome`, the cwds of `config` are replaced.
+pub fn handle_missing_dirs(config: &mut Config, policy: MissingDirsPolicy) -> Result<(), Error> {
+    handle_missing_dirs_with(config, policy, dirs::home_dir().as_deref())
+}
+
+/// Like [`handle_missing_dirs`] with the home directory `home_dir`
+fn handle_missing_dirs_with(
+    config: &mut Config,
+    policy: MissingDirsPolicy,
+    home_dir: Option<&Path>,
+) -> Result<(), Error> {
+    let mut checker = Checker {
+        policy,
+        home_dir,
+        reported: HashSet::new(),
+    };
+
+    for session in &mut config.sessions {
+        let session_cwd = checker.check(&mut session.cwd, &Cwd::default())?;
+        for window in &mut session.windows {
+            let window_cwd = checker.check(&mut window.cwd, &session_cwd)?;
+            checker.check_panes(&mut window.root_split, &window_cwd)?;
+        }
+    }
+    for window in &mut config.windows {
+        let window_cwd = checker.check(&mut window.cwd, &Cwd::default())?;
+        checker.check_panes(&mut window.root_split, &window_cwd)?;
+    }
+
+    Ok(())
+}
+
+struct Checker<'a> {
+    policy: MissingDirsPolicy,
+    home_dir: Option<&'a Path>,
+    /// Missing directories already reported (or created)
+    reported: HashSet<PathBuf>,
+}
+
+impl Checker<'_> {
+    fn check_panes(&mut self, root_split: &mut RootSplit, window_cwd: &Cwd) -> Result<(), Error> {
+        for pane in root_split.pane_iter_mut() {
+            self.check(&mut pane.cwd, window_cwd)?;
+        }
+        Ok(())
+    }
+
+    /// Checks `cwd` (resolved against `parent_cwd`) and returns the resolved
+    /// cwd to resolve children against.
+    fn check(&mut self, cwd: &mut Cwd, parent_cwd: &Cwd) -> Result<Cwd, Error> {
+        let resolved = parent_cwd.joined(cwd).into_owned();
+        let path = match resolved.to_path() {
+            Some(path) if !cwd.is_empty() && !path.is_dir() => path.to_owned(),
+            _ => return Ok(resolved),
+        };
+        let first_report = self.reported.insert(path.clone());
+
+        match self.policy {
+            MissingDirsPolicy::Error => Err(Error::Missing(path)),
+            MissingDirsPolicy::Warn => {
+                if first_report {
+                    crate::show_warning(&format!("directory '{}' does not exist", path.display()));
+                }
+                Ok(resolved)
+            }
+            MissingDirsPolicy::Create => {
+                if first_report {
+                    fs::create_dir_all(&path).map_err(|err| Error::Create(path.clone(), err))?;
+                    crate::show_info(&format!("created directory '{}'", path.display()));
+                }
+                Ok(resolved)
+            }
+            MissingDirsPolicy::FallbackHome => {
+                let home_dir = self.home_dir.ok_or(Error::NoHomeDir)?;
+                if first_report {
+                    crate::show_warning(&format!(
+                        "directory '{}' does not exist, using '{}' instead",
+                        path.display(),
+                        home_dir.display()
+                    ));
+                }
+                *cwd = home_dir.to_path_buf().into();
+                Ok(cwd.clone())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::PartialConfig;
+
+    fn load(config_str: &str) -> Config {
+        serde_yaml::from_str::<PartialConfig>(config_str)
+            .unwrap()
+            .into_config()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_handle_missing_dirs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let sess_dir = tmp_dir.path().join("sess");
+        let config_str = format!(
+            "
+            sessions:
+              - name: sess
+                cwd: {}
+                windows:
+                  - cwd: win
+                    left:
+                      cwd: left
+                    right: {{}}
+            ",
+            sess_dir.display()
+        );
+
+        let mut config = load(&config_str);
+        let err = handle_missing_dirs(&mut config, MissingDirsPolicy::Error).unwrap_err();
+        assert!(matches!(err, Error::Missing(path) if path == sess_dir));
+
+        let home_dir = tmp_dir.path().join("home");
+        let mut config = load(&config_str);
+        let policy = MissingDirsPolicy::FallbackHome;
+        handle_missing_dirs_with(&mut config, policy, Some(&home_dir)).unwrap();
+        let session = &config.sessions[0];
+        assert_eq!(session.cwd.to_path(), Some(home_dir.as_path()));
+        assert_eq!(session.windows[0].cwd.to_path(), Some(home_dir.as_path()));
+        let mut config = load(&config_str);
+        let err = handle_missing_dirs_with(&mut config, policy, None).unwrap_err();
+        assert!(matches!(err, Error::NoHomeDir));
+
+        let mut config = load(&config_str);
+        handle_missing_dirs(&mut config, MissingDirsPolicy::Create).unwrap();
+        assert!(sess_dir.join("win").join("left").is_dir());
+        handle_missing_dirs(&mut config, MissingDirsPolicy::Error).unwrap();
+    }
+}
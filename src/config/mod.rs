@@ -1,7 +1,30 @@
 mod model;
 pub use model::*;
 
+mod conditions;
+pub use conditions::*;
+
+mod defaults;
+pub use defaults::*;
+
 mod includes;
 pub use includes::*;
 
+mod merge;
+pub use merge::MergeStrategy;
+
+mod canonical;
+pub mod graph;
 pub mod loader;
+pub mod missing_dirs;
+mod named_templates;
+mod names;
+pub use named_templates::Templates;
+mod redact;
+mod repos;
+pub mod rewrite;
+pub mod secrets;
+pub use secrets::Key;
+pub mod sizes;
+pub mod templates;
+pub mod validate;
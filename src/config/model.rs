@@ -1,6 +1,11 @@
+use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
 
+use super::conditions::When;
+use super::defaults::Defaults;
 use super::includes::*;
+use super::named_templates::Templates;
+use super::secrets::Key;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub type Config = ConfigL<NoIncludes>;
@@ -13,21 +18,153 @@ type Cwd = crate::cwd::Cwd<'static>;
 pub struct ConfigL<Includes: ConfigIncludes> {
     #[serde(default, skip_serializing_if = "ConfigIncludes::is_empty")]
     pub includes: Includes,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+    #[serde(default, skip_serializing_if = "Templates::is_empty")]
+    pub templates: Templates,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub selected_session: Option<String>,
+    /// Pane (`session:window.pane`) or window (`session:window`) to select
+    /// after creating, instead of `selected_session`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_target: Option<String>,
+    /// Shell command run by `create` after tmux created the layout (see
+    /// `--post-cmd`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_created: Option<String>,
+    /// Detach other clients when attaching to the selected session (see
+    /// `--detach-others`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detach_others: bool,
+    /// Name of the tmux server socket (`-L`) all subcommands talk to when
+    /// run with this config, unless `-L`/`-S` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub sessions: Vec<Session>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub windows: Vec<Window>,
+    /// Git checkouts expanded into `sessions` (or `windows`) when loading
+    #[serde(default, skip_serializing)]
+    pub repos: Vec<Repo>,
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// The target to select after creating: `selected_target`, or else
+    /// `selected_session`
+    pub fn target_to_select(&self) -> Option<&str> {
+        self.selected_target
+            .as_deref()
+            .or(self.selected_session.as_deref())
+    }
+
+    /// The session of [`Self::target_to_select`]
+    pub fn session_to_select(&self) -> Option<&str> {
+        self.target_to_select().map(|target| {
+            target
+                .split_once(':')
+                .map_or(target, |(session, _)| session)
+        })
+    }
+
+    /// Iterates all popups along with their working directories (resolved
+    /// against the enclosing session and window).
+    pub fn popups(&self) -> impl Iterator<Item = (&Popup, crate::cwd::Cwd<'_>)> {
+        let session_popups = self.sessions.iter().flat_map(|session| {
+            let window_popups = session.windows.iter().flat_map(move |window| {
+                window.popups.iter().map(move |p| {
+                    (
+                        p,
+                        session.cwd.joined(&window.cwd).joined(&p.cwd).into_owned(),
+                    )
+                })
+            });
+            session
+                .popups
+                .iter()
+                .map(move |p| (p, session.cwd.joined(&p.cwd)))
+                .chain(window_popups)
+        });
+        let window_popups = self.windows.iter().flat_map(|window| {
+            window
+                .popups
+                .iter()
+                .map(move |p| (p, window.cwd.joined(&p.cwd)))
+        });
+        session_popups.chain(window_popups)
+    }
+
+    /// Adds `session`, replacing an existing session of the same name.
+    /// Returns whether a session was replaced.
+    pub fn upsert_session(&mut self, session: Session) -> bool {
+        match self.sessions.iter_mut().find(|s| s.name == session.name) {
+            Some(existing) => {
+                *existing = session;
+                true
+            }
+            None => {
+                self.sessions.push(session);
+                false
+            }
+        }
+    }
+
+    /// Adds the top-level `window`, replacing an existing window of the same
+    /// name. Unnamed windows are always appended. Returns whether a window
+    /// was replaced.
+    pub fn upsert_window(&mut self, window: Window) -> bool {
+        let existing = window.name.as_ref().and_then(|name| {
+            self.windows
+                .iter_mut()
+                .find(|w| w.name.as_ref() == Some(name))
+        });
+        match existing {
+            Some(existing) => {
+                *existing = window;
+                true
+            }
+            None => {
+                self.windows.push(window);
+                false
+            }
+        }
+    }
+
+    /// Removes the sessions which are running already (according to
+    /// `exists`) and have `attach_if_exists` set (or all running ones with
+    /// `all`). The first of them is selected unless a target is selected
+    /// already. Returns the names of the removed sessions.
+    pub fn skip_attachable_sessions(
+        &mut self,
+        all: bool,
+        exists: impl Fn(&str) -> bool,
+    ) -> Vec<String> {
+        let (attachable, sessions) = std::mem::take(&mut self.sessions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|s| (all || s.attach_if_exists) && exists(&s.name));
+        self.sessions = sessions;
+        let names = attachable.into_iter().map(|s| s.name).collect::<Vec<_>>();
+        if self.target_to_select().is_none() {
+            self.selected_session = names.first().cloned();
+        }
+        names
+    }
 }
 
 impl PartialConfig {
     pub fn into_config(self) -> Result<Config, UnresolvedIncludes> {
         if self.includes.is_empty() {
             Ok(Config {
+                defaults: self.defaults,
+                templates: self.templates,
                 selected_session: self.selected_session,
+                selected_target: self.selected_target,
+                on_created: self.on_created,
+                detach_others: self.detach_others,
+                socket: self.socket,
                 sessions: self.sessions,
                 windows: self.windows,
+                repos: self.repos,
                 includes: NoIncludes,
             })
         } else {
@@ -36,28 +173,245 @@ impl PartialConfig {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Session {
+    /// May contain `{date:<format>}` and (with `count`) `{i}` placeholders
     pub name: String,
+    /// Creates this many copies of the session (numbered via `{i}`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    /// Merge sessions of the same name from included files into this one
+    /// (or this one into the including file's session) instead of creating
+    /// a duplicate
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub merge: bool,
+    /// If the session is running already, select it instead of creating it
+    /// (like `new-session -A`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub attach_if_exists: bool,
+    /// How the windows are matched to the windows of the running session
+    /// (default: `name`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_by: Option<MatchBy>,
+    /// Only create the session if these conditions hold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<When>,
     #[serde(skip_serializing_if = "Cwd::is_empty")]
     pub cwd: Cwd,
     pub windows: Vec<Window>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub popups: Vec<Popup>,
+    #[serde(default, skip_serializing_if = "Status::is_empty")]
+    pub status: Status,
+    /// Sets the `default-command` session option (the command run by new
+    /// panes instead of the default shell)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_command: Option<String>,
+    /// Sets the `default-terminal` session option (`TERM` in new panes)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_terminal: Option<String>,
+    /// Default of `direnv` for the windows of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direnv: Option<bool>,
+    /// Default of `wrapper` for the windows of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<String>,
+    /// tmux commands (split into arguments like by a shell) run after the
+    /// windows of the session are created, for options the config doesn't
+    /// cover. Without `-t` they apply to the session's current window.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_commands: Vec<String>,
+    /// Shell commands run (in the session's cwd) before the session is
+    /// killed by `tmux-layout kill` or `--on-conflict replace`, e.g. to stop
+    /// services started by `on_created`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_kill: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+/// Status line options of a session (see the `status*` options of tmux)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Status {
+    /// Shows (`true`) or hides (`false`) the status line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Number of status lines (1 to 5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<StatusPosition>,
+    /// Format string of the left part of the status line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<String>,
+    /// Format string of the right part of the status line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<String>,
+}
+
+impl Status {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Value of the `status` option, if set
+    pub fn option_value(&self) -> Option<String> {
+        match (self.enabled, self.lines) {
+            (Some(false), _) => Some("off".to_string()),
+            (_, Some(lines)) if lines > 1 => Some(lines.to_string()),
+            (Some(true), _) | (None, Some(_)) => Some("on".to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Rows taken by the status line(s), assuming tmux's default of one line
+    /// unless configured otherwise
+    pub fn height(&self) -> u32 {
+        match self.enabled {
+            Some(false) => 0,
+            _ => self.lines.unwrap_or(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Alert {
+    Activity,
+    Bell,
+    /// Only re-armed with `monitor_silence`, which has the interval
+    Silence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusPosition {
+    Top,
+    Bottom,
+}
+
+impl StatusPosition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StatusPosition::Top => "top",
+            StatusPosition::Bottom => "bottom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Window {
+    /// Name of a window template (see [`Templates`]) providing the fields
+    /// this window doesn't set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Only create the window if these conditions hold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<When>,
     #[serde(skip_serializing_if = "Cwd::is_empty")]
     pub cwd: Cwd,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub active: bool,
+    /// Create the window without selecting it (`new-window -d`), so that it
+    /// never takes the focus from the current window
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub background: bool,
+    /// Shell for all panes of the window without their own `shell`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Default of `direnv` for the panes of the window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direnv: Option<bool>,
+    /// Default of `wrapper` for the panes of the window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub popups: Vec<Popup>,
+    /// Sets the `monitor-activity` window option
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor_activity: Option<bool>,
+    /// Sets the `monitor-silence` window option (in seconds, 0 disables it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor_silence: Option<u32>,
+    /// Sets the `monitor-bell` window option
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor_bell: Option<bool>,
+    /// Alerts the window had when it was exported (see `export
+    /// --include-alerts`). tmux can't raise them again, but `create` turns
+    /// on the corresponding `monitor-*` options unless they are set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<Alert>,
+    /// Alternative layouts by terminal width. `create` uses the widest one
+    /// fitting the terminal instead of the window's own splits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layouts: Vec<LayoutVariant>,
+    /// tmux commands (split into arguments like by a shell) run once the
+    /// window is set up. Without `-t` they apply to the window and its
+    /// active pane, unless it's a `background` window.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_commands: Vec<String>,
+    #[serde(flatten)]
+    pub root_split: RootSplit,
+}
+
+/// A window layout for terminals at least `min_width` columns wide
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LayoutVariant {
+    #[serde(default)]
+    pub min_width: u32,
     #[serde(flatten)]
     pub root_split: RootSplit,
 }
 
+/// A git checkout which is expanded into a session (or top-level window) of
+/// its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Repo {
+    /// Directory of the checkout (the cwd of the session or window)
+    pub path: Cwd,
+    /// Clone URL, used by `create` if `path` doesn't exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Name of the session or window (default: last component of `path`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, rename = "as")]
+    pub expand_as: RepoExpansion,
+    /// Windows of the session (or the top-level windows) created for the
+    /// repo, with cwds relative to `path`. A single window by default.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<Window>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoExpansion {
+    #[default]
+    Session,
+    Window,
+}
+
+/// A floating window launched on demand via `tmux-layout popup <name>`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Popup {
+    pub name: String,
+    #[serde(skip_serializing_if = "Cwd::is_empty")]
+    pub cwd: Cwd,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<String>,
+    #[serde(
+        default = "serialization::default_true",
+        skip_serializing_if = "serialization::is_true"
+    )]
+    pub border: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(from = "serialization::SplitMap", into = "serialization::SplitMap")]
+#[serde(try_from = "serialization::SplitMap", into = "serialization::SplitMap")]
 pub enum Split {
     Pane(Pane),
     H { left: HSplitPart, right: HSplitPart },
@@ -69,6 +423,49 @@ impl Split {
         RootSplit(self)
     }
 
+    /// Side by side panes of equal width running `commands` (an empty
+    /// command starts a plain shell).
+    pub fn even(commands: Vec<String>) -> Split {
+        let weights = vec![1; commands.len()];
+        Split::weighted(commands, &weights)
+    }
+
+    /// Side by side panes running `commands` with widths proportional to
+    /// `weights` (one per command).
+    pub fn weighted(commands: Vec<String>, weights: &[u32]) -> Split {
+        let mut panes = commands
+            .into_iter()
+            .map(|command| {
+                Split::Pane(Pane {
+                    shell_command: Some(command).filter(|c| !c.is_empty()),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let Some(mut split) = panes.pop() else {
+            return Split::default();
+        };
+        // Nest from the right: each left pane takes its share of the
+        // remaining width.
+        for (i, pane) in panes.into_iter().enumerate().rev() {
+            let remaining: u32 = weights[i..].iter().sum();
+            split = Split::H {
+                left: HSplitPart {
+                    width: Some(format!("{}%", 100 * weights[i] / remaining)),
+                    split: Box::new(pane),
+                    ..Default::default()
+                },
+                right: HSplitPart {
+                    width: None,
+                    split: Box::new(split),
+                    ..Default::default()
+                },
+            };
+        }
+        split
+    }
+
     pub fn single_pane(&self) -> Option<&Pane> {
         match self {
             Split::Pane(pane) => Some(pane),
@@ -83,11 +480,11 @@ impl Split {
         }
     }
 
-    pub fn pane_iter(&self) -> Panes {
+    pub fn pane_iter(&self) -> Panes<'_> {
         Panes::new(self)
     }
 
-    pub fn pane_iter_mut(&mut self) -> PanesMut {
+    pub fn pane_iter_mut(&mut self) -> PanesMut<'_> {
         PanesMut::new(self)
     }
 }
@@ -99,7 +496,7 @@ impl Default for Split {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(from = "serialization::SplitMap", into = "serialization::SplitMap")]
+#[serde(try_from = "serialization::SplitMap", into = "serialization::SplitMap")]
 #[repr(transparent)]
 pub struct RootSplit(Split);
 
@@ -121,6 +518,12 @@ impl DerefMut for RootSplit {
 pub struct HSplitPart {
     #[serde(skip_serializing_if = "serialization::is_default_size")]
     pub width: Option<String>,
+    /// Minimum width in cells (see [`super::sizes`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    /// Maximum width in cells
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
     #[serde(flatten)]
     pub split: Box<Split>,
 }
@@ -129,6 +532,12 @@ pub struct HSplitPart {
 pub struct VSplitPart {
     #[serde(skip_serializing_if = "serialization::is_default_size")]
     pub height: Option<String>,
+    /// Minimum height in cells (see [`super::sizes`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u32>,
+    /// Maximum height in cells
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<u32>,
     #[serde(flatten)]
     pub split: Box<Split>,
 }
@@ -138,10 +547,174 @@ pub struct Pane {
     pub cwd: Cwd,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub active: bool,
+    /// Shell to start instead of tmux's `default-shell`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub shell_command: Option<String>,
+    /// Drop to a shell when `shell_command` exits instead of closing the pane
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_open: bool,
+    /// Run `shell_command` via `direnv exec` in the pane's cwd, so it gets
+    /// the environment of the project's `.envrc`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direnv: Option<bool>,
+    /// Command prefix running `shell_command` (in the pane's shell), e.g.
+    /// `nix develop -c` or `poetry run`. Empty for none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_keys: Option<Vec<Key>>,
+    /// Index of the pane of the same window to type `send_keys` into instead
+    /// of this one. They are sent once all panes of the window exist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_keys_target: Option<u32>,
+    /// Only send `send_keys` once this condition holds (boxed as it's rarely
+    /// set, keeping panes small)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for: Option<Box<WaitFor>>,
+    /// Seconds to defer the `shell_command` and `send_keys` by (before
+    /// waiting for `wait_for`), to stagger heavy processes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u32>,
+    /// When `supervise` restarts the pane after its process exits (default:
+    /// the `--restart` policy of `supervise`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Disable input from clients once the pane is set up (`select-pane
+    /// -d`), e.g. for presentations. Re-enabled with `read-only --off`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Clear the screen and scrollback once the startup output is printed:
+    /// after a `shell_command` with `keep_open` and after `send_keys`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clear: bool,
+    /// File with captured pane contents, replayed when the pane starts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_file: Option<String>,
+    /// Label of the pane for `focus` and `swap`, e.g. `editor`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Background color of the pane (`select-pane -P bg=<color>`), e.g.
+    /// `colour236` or `#202040`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    /// Color of the borders between the panes of the window (its
+    /// `pane-border-style`, as tmux has no border styles per pane)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_color: Option<String>,
+    /// Name of a pane template (see [`Templates`]) providing the fields this
+    /// pane doesn't set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Process running in the pane when it was exported (see `export
+    /// --include-processes`). Only recorded, `create` doesn't start it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<Box<Process>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Process {
+    /// PID of the first process of the pane (usually the shell)
+    pub pid: u32,
+    /// Command line of the program in the foreground (if not the shell)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+/// Condition the `send_keys` of a pane wait for (polled once a second by
+/// tmux in the background). All given conditions must hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitFor {
+    /// TCP port accepting connections on `host`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Host of `port` (default: `localhost`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Text appearing on the screen of `pane`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Index of the pane of the window showing `text` (default: the pane
+    /// the keys are sent to)
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub send_keys: Option<Vec<String>>,
+    pub pane: Option<u32>,
+    /// Seconds to wait before giving up on sending the keys (default: 30)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+}
+
+/// When a pane whose process exited is restarted by `supervise`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    Always,
+    /// Only after a non-zero exit status or a signal
+    OnFailure,
+}
+
+/// How the windows of a session are matched to the windows of the running
+/// session, e.g. by `--update-existing-sessions`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchBy {
+    /// Named windows by name, unnamed ones by position
+    #[default]
+    Name,
+    /// By position, ignoring the names
+    Index,
+    /// By name, or else by position unless the running window there matches
+    /// another window by name, so renamed windows still match
+    NameThenIndex,
+}
+
+impl MatchBy {
+    /// Position in `running` (the names of the running windows ordered by
+    /// index) of the window matching each of `windows`. Each running window
+    /// matches one window at most, those matching by name take precedence.
+    pub fn match_windows<S: AsRef<str>>(
+        self,
+        windows: &[Window],
+        running: &[S],
+    ) -> Vec<Option<usize>> {
+        let mut taken = HashSet::new();
+        let mut matches = windows
+            .iter()
+            .map(|window| {
+                let name = window.name.as_deref().filter(|_| self != MatchBy::Index)?;
+                let position = (0..running.len())
+                    .find(|p| running[*p].as_ref() == name && !taken.contains(p))?;
+                taken.insert(position);
+                Some(position)
+            })
+            .collect::<Vec<_>>();
+
+        for (position, (window, matched)) in windows.iter().zip(&mut matches).enumerate() {
+            let by_position = match self {
+                MatchBy::Name => window.name.is_none(),
+                MatchBy::Index => true,
+                MatchBy::NameThenIndex => matched.is_none(),
+            };
+            if by_position && position < running.len() && taken.insert(position) {
+                *matched = Some(position);
+            }
+        }
+        matches
+    }
+}
+
+impl RestartPolicy {
+    /// Whether a pane whose process exited with `exit_status` (`None` if it
+    /// was killed by a signal) is restarted
+    pub fn restarts(self, exit_status: Option<i32>) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => exit_status != Some(0),
+        }
+    }
 }
 
 /// Iterates panes in tmux index order.
@@ -210,6 +783,8 @@ impl<'a> Iterator for PanesMut<'a> {
 
 pub(super) mod serialization {
     use super::*;
+    use std::convert::TryFrom;
+
     #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     pub(super) struct SplitMap {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -225,33 +800,178 @@ pub(super) mod serialization {
         #[serde(default, skip_serializing_if = "std::ops::Not::not")]
         pub active: bool,
         #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) shell: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub(super) shell_command: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub(super) keep_open: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) direnv: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) wrapper: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) send_keys: Option<Vec<Key>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) send_keys_target: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) wait_for: Option<Box<WaitFor>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) delay: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) restart: Option<RestartPolicy>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub(super) read_only: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub(super) clear: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) history_file: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub(super) send_keys: Option<Vec<String>>,
+        pub(super) role: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) background: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) border_color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) template: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub(super) process: Option<Box<Process>>,
+        /// Shorthand for side by side panes running these commands
+        #[serde(default, skip_serializing)]
+        pub(super) panes: Option<Vec<String>>,
+        /// Relative sizes of the parts of the split, e.g. `1:2`
+        #[serde(default, skip_serializing)]
+        pub(super) ratio: Option<String>,
+    }
+
+    /// Largest part of a ratio
+    const MAX_RATIO_PART: u32 = 10_000;
+
+    /// Parses a ratio like `30:70` into its (positive) parts.
+    /// Parts are limited to [`MAX_RATIO_PART`], so the sizes can't overflow.
+    fn parse_ratio(ratio: &str, parts: usize) -> Result<Vec<u32>, String> {
+        let weights = ratio
+            .split(':')
+            .map(|part| {
+                let weight = part.trim().parse::<u32>().ok();
+                weight.filter(|w| (1..=MAX_RATIO_PART).contains(w))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                format!(
+                    "invalid ratio '{}' (expected e.g. 1:2, with parts up to {})",
+                    ratio, MAX_RATIO_PART
+                )
+            })?;
+        if weights.len() != parts {
+            return Err(format!(
+                "ratio '{}' has {} parts, but the split has {}",
+                ratio,
+                weights.len(),
+                parts
+            ));
+        }
+        Ok(weights)
     }
 
-    impl From<SplitMap> for Split {
-        fn from(map: SplitMap) -> Self {
+    /// Size (in percent) of the first part of a binary split with `ratio`
+    fn first_part_size(
+        ratio: Option<&str>,
+        sizes: [&Option<String>; 2],
+    ) -> Result<Option<String>, String> {
+        let Some(ratio) = ratio else {
+            return Ok(None);
+        };
+        if sizes.iter().any(|size| size.is_some()) {
+            return Err("ratio can't be combined with sizes of the parts".to_string());
+        }
+        let weights = parse_ratio(ratio, 2)?;
+        Ok(Some(format!(
+            "{}%",
+            100 * weights[0] / (weights[0] + weights[1])
+        )))
+    }
+
+    impl TryFrom<SplitMap> for Split {
+        type Error = String;
+
+        fn try_from(mut map: SplitMap) -> Result<Self, String> {
+            if let Some(commands) = map.panes.take() {
+                let splits = map.left.is_some()
+                    || map.right.is_some()
+                    || map.top.is_some()
+                    || map.bottom.is_some();
+                let ratio = map.ratio.take();
+                if splits || map.into_pane() != Pane::default() {
+                    return Err(
+                        "panes can't be combined with left/right, top/bottom or pane options \
+                         (e.g. shell_command, cwd or active)"
+                            .to_string(),
+                    );
+                }
+                return Ok(match ratio {
+                    None => Split::even(commands),
+                    Some(ratio) => {
+                        let weights = parse_ratio(&ratio, commands.len())?;
+                        Split::weighted(commands, &weights)
+                    }
+                });
+            }
+
             if map.left.is_some() || map.right.is_some() {
-                return Split::H {
-                    left: map.left.unwrap_or_default(),
-                    right: map.right.unwrap_or_default(),
-                };
+                let mut left = map.left.unwrap_or_default();
+                let right = map.right.unwrap_or_default();
+                if let Some(width) =
+                    first_part_size(map.ratio.as_deref(), [&left.width, &right.width])?
+                {
+                    left.width = Some(width);
+                }
+                return Ok(Split::H { left, right });
             }
 
             if map.top.is_some() || map.bottom.is_some() {
-                return Split::V {
-                    top: map.top.unwrap_or_default(),
-                    bottom: map.bottom.unwrap_or_default(),
-                };
+                let mut top = map.top.unwrap_or_default();
+                let bottom = map.bottom.unwrap_or_default();
+                if let Some(height) =
+                    first_part_size(map.ratio.as_deref(), [&top.height, &bottom.height])?
+                {
+                    top.height = Some(height);
+                }
+                return Ok(Split::V { top, bottom });
             }
 
-            Split::Pane(Pane {
-                cwd: map.cwd,
-                active: map.active,
-                shell_command: map.shell_command,
-                send_keys: map.send_keys,
-            })
+            if map.ratio.is_some() {
+                return Err("ratio requires a split (left/right, top/bottom or panes)".to_string());
+            }
+
+            Ok(Split::Pane(map.into_pane()))
+        }
+    }
+
+    impl SplitMap {
+        /// The pane options of the map (ignoring any splits)
+        fn into_pane(self) -> Pane {
+            Pane {
+                cwd: self.cwd,
+                active: self.active,
+                shell: self.shell,
+                shell_command: self.shell_command,
+                keep_open: self.keep_open,
+                direnv: self.direnv,
+                wrapper: self.wrapper,
+                send_keys: self.send_keys,
+                send_keys_target: self.send_keys_target,
+                wait_for: self.wait_for,
+                delay: self.delay,
+                restart: self.restart,
+                read_only: self.read_only,
+                clear: self.clear,
+                history_file: self.history_file,
+                role: self.role,
+                background: self.background,
+                border_color: self.border_color,
+                template: self.template,
+                process: self.process,
+            }
         }
     }
 
@@ -261,8 +981,24 @@ pub(super) mod serialization {
                 Split::Pane(pane) => Self {
                     cwd: pane.cwd,
                     active: pane.active,
+                    shell: pane.shell,
                     shell_command: pane.shell_command,
+                    keep_open: pane.keep_open,
+                    direnv: pane.direnv,
+                    wrapper: pane.wrapper,
                     send_keys: pane.send_keys,
+                    send_keys_target: pane.send_keys_target,
+                    wait_for: pane.wait_for,
+                    delay: pane.delay,
+                    restart: pane.restart,
+                    read_only: pane.read_only,
+                    clear: pane.clear,
+                    history_file: pane.history_file,
+                    role: pane.role,
+                    background: pane.background,
+                    border_color: pane.border_color,
+                    template: pane.template,
+                    process: pane.process,
                     ..Default::default()
                 },
                 Split::H { left, right } => Self {
@@ -292,12 +1028,22 @@ pub(super) mod serialization {
         }
     }
 
-    impl From<SplitMap> for RootSplit {
-        fn from(map: SplitMap) -> Self {
-            Split::from(map).into_root()
+    impl TryFrom<SplitMap> for RootSplit {
+        type Error = String;
+
+        fn try_from(map: SplitMap) -> Result<Self, String> {
+            Split::try_from(map).map(Split::into_root)
         }
     }
 
+    pub(super) fn default_true() -> bool {
+        true
+    }
+
+    pub(super) fn is_true(b: &bool) -> bool {
+        *b
+    }
+
     pub(super) fn is_default_size(size: &Option<String>) -> bool {
         match size {
             None => true,
@@ -310,7 +1056,102 @@ pub(super) mod serialization {
 mod test {
     use crate::config::{model::Cwd, HSplitPart, Pane, Session, Split, VSplitPart, Window};
 
-    use super::PartialConfig;
+    use super::{Config, MatchBy, PartialConfig, RestartPolicy};
+
+    #[test]
+    fn test_panes_shorthand() {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+            windows:
+              - panes: ["npm run dev", "npm test -- --watch", ""]
+            "#,
+        )
+        .unwrap();
+
+        let expected = serde_yaml::from_str::<Config>(
+            r#"
+            windows:
+              - left:
+                  width: 33%
+                  shell_command: npm run dev
+                right:
+                  left:
+                    width: 50%
+                    shell_command: npm test -- --watch
+                  right: {}
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+        assert_eq!(Split::even(vec![]), Split::default());
+
+        for invalid in [
+            "{ panes: [a, b], left: {}, right: {} }",
+            "{ panes: [a, b], bottom: {} }",
+            "{ panes: [a, b], shell_command: c }",
+            "{ left: { panes: [a, b], cwd: src }, right: {} }",
+            "{ top: { panes: [a, b], active: true }, bottom: {} }",
+            "{ panes: [a, b], send_keys: [x] }",
+        ] {
+            let config_str = format!("windows: [{}]", invalid);
+            assert!(
+                serde_yaml::from_str::<Config>(&config_str).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_ratio() {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+            windows:
+              - ratio: "1:3"
+                left: {}
+                right:
+                  ratio: 30:70
+                  top: {}
+                  bottom: {}
+              - panes: [a, b, c]
+                ratio: 1:2:1
+            "#,
+        )
+        .unwrap();
+
+        let expected = serde_yaml::from_str::<Config>(
+            r#"
+            windows:
+              - left: { width: 25% }
+                right:
+                  top: { height: 30% }
+                  bottom: {}
+              - left: { width: 25%, shell_command: a }
+                right:
+                  left: { width: 66%, shell_command: b }
+                  right: { shell_command: c }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+
+        for invalid in [
+            "{ ratio: 1:2, left: { width: 10% }, right: {} }",
+            "{ ratio: 1:0, left: {}, right: {} }",
+            "{ ratio: 1:2:3, top: {}, bottom: {} }",
+            "{ ratio: a:b, panes: [x, y] }",
+            "{ ratio: 1:2 }",
+            "{ ratio: '50000000:1', left: {}, right: {} }",
+            "{ ratio: '4294967295:1', panes: [x, y] }",
+        ] {
+            let config_str = format!("windows: [{}]", invalid);
+            assert!(
+                serde_yaml::from_str::<Config>(&config_str).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
 
     #[test]
     fn test_single_window_config() {
@@ -324,8 +1165,15 @@ mod test {
             config,
             PartialConfig {
                 includes: Default::default(),
+                defaults: Default::default(),
+                templates: Default::default(),
                 selected_session: None,
+                selected_target: None,
+                on_created: None,
+                detach_others: false,
+                socket: None,
                 sessions: vec![],
+                repos: vec![],
                 windows: vec![Window {
                     name: Some("A new window".to_string()),
                     cwd: "/tmp".into(),
@@ -338,6 +1186,7 @@ mod test {
                                 shell_command: Some("bash".to_string()),
                                 ..Default::default()
                             })),
+                            ..Default::default()
                         },
                         right: HSplitPart {
                             width: None,
@@ -347,15 +1196,31 @@ mod test {
                                     .into_owned()
                                     .into(),
                                 ..Default::default()
-                            }))
+                            })),
+                            ..Default::default()
                         }
                     }
                     .into_root(),
+                    ..Default::default()
                 }],
             }
         );
     }
 
+    #[test]
+    fn test_selected_target() {
+        let mut config = Config {
+            selected_session: Some("sess1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.target_to_select(), Some("sess1"));
+        assert_eq!(config.session_to_select(), Some("sess1"));
+
+        config.selected_target = Some("sess2:editor.1".to_string());
+        assert_eq!(config.target_to_select(), Some("sess2:editor.1"));
+        assert_eq!(config.session_to_select(), Some("sess2"));
+    }
+
     #[test]
     fn test_layout_config_yaml() {
         let config_str = include_str!(concat!(
@@ -443,10 +1308,12 @@ mod test {
                             cwd: shellexpand::full("$JAVA_HOME").unwrap().into_owned().into(),
                             ..Default::default()
                         })),
+                        ..Default::default()
                     },
                     right: HSplitPart::default(),
                 }
                 .into_root(),
+                ..Default::default()
             }
         );
 
@@ -464,9 +1331,10 @@ mod test {
                         left: HSplitPart {
                             width: Some("20%".to_string()),
                             split: Box::new(Split::Pane(Pane {
-                                send_keys: Some(vec!["ls -al".to_string(), "ENTER".to_string()]),
+                                send_keys: Some(vec!["ls -al".into(), "ENTER".into()]),
                                 ..Default::default()
                             })),
+                            ..Default::default()
                         },
                         right: HSplitPart {
                             width: None,
@@ -474,10 +1342,13 @@ mod test {
                                 shell_command: Some("bash".to_string()),
                                 ..Default::default()
                             }),),
+                            ..Default::default()
                         }
                     }
                     .into_root(),
+                    ..Default::default()
                 }],
+                ..Default::default()
             }
         );
     }
@@ -494,8 +1365,15 @@ mod test {
             config,
             PartialConfig {
                 includes: Default::default(),
+                defaults: Default::default(),
+                templates: Default::default(),
                 selected_session: Some("sess1".to_string()),
+                selected_target: None,
+                on_created: None,
+                detach_others: false,
+                socket: None,
                 windows: vec![],
+                repos: vec![],
                 sessions: vec![
                     Session {
                         name: "sess1".to_string(),
@@ -515,6 +1393,7 @@ mod test {
                                                     cwd: "projects".into(),
                                                     ..Default::default()
                                                 })),
+                                                ..Default::default()
                                             },
                                             bottom: VSplitPart {
                                                 height: None,
@@ -522,8 +1401,10 @@ mod test {
                                                     cwd: "scratch".into(),
                                                     ..Default::default()
                                                 })),
+                                                ..Default::default()
                                             },
-                                        })
+                                        }),
+                                        ..Default::default()
                                     },
                                     right: HSplitPart {
                                         width: None,
@@ -531,22 +1412,26 @@ mod test {
                                             top: VSplitPart {
                                                 height: None,
                                                 split: Box::new(Split::Pane(Pane::default())),
+                                                ..Default::default()
                                             },
                                             bottom: VSplitPart {
                                                 height: None,
                                                 split: Box::new(Split::Pane(Pane {
                                                     cwd: "projects/tmux-layout".into(),
                                                     send_keys: Some(vec![
-                                                        "g".to_string(),
-                                                        "ENTER".to_string()
+                                                        "g".into(),
+                                                        "ENTER".into()
                                                     ]),
                                                     ..Default::default()
                                                 })),
+                                                ..Default::default()
                                             },
-                                        })
+                                        }),
+                                        ..Default::default()
                                     }
                                 }
                                 .into_root(),
+                                ..Default::default()
                             },
                             Window {
                                 name: Some("win2".to_string()),
@@ -562,15 +1447,19 @@ mod test {
                                                 .into(),
                                             ..Default::default()
                                         })),
+                                        ..Default::default()
                                     },
                                     right: HSplitPart {
                                         width: None,
                                         split: Box::new(Split::Pane(Pane::default())),
+                                        ..Default::default()
                                     }
                                 }
                                 .into_root(),
+                                ..Default::default()
                             },
-                        ]
+                        ],
+                        ..Default::default()
                     },
                     Session {
                         name: "sess2".to_string(),
@@ -583,12 +1472,10 @@ mod test {
                                 left: HSplitPart {
                                     width: None,
                                     split: Box::new(Split::Pane(Pane {
-                                        send_keys: Some(vec![
-                                            "ls -al".to_string(),
-                                            "ENTER".to_string()
-                                        ]),
+                                        send_keys: Some(vec!["ls -al".into(), "ENTER".into()]),
                                         ..Default::default()
                                     })),
+                                    ..Default::default()
                                 },
                                 right: HSplitPart {
                                     width: Some("120".to_string()),
@@ -596,10 +1483,13 @@ mod test {
                                         shell_command: Some("bash".to_string()),
                                         ..Default::default()
                                     })),
+                                    ..Default::default()
                                 },
                             }
                             .into_root(),
+                            ..Default::default()
                         }],
+                        ..Default::default()
                     }
                 ],
             }
@@ -645,4 +1535,145 @@ mod test {
 
         assert_eq!(config, parsed);
     }
+
+    #[test]
+    fn test_upsert() {
+        let mut config = serde_yaml::from_str::<Config>(
+            r#"
+            sessions:
+              - name: a
+                windows: [{ name: w1 }]
+            windows:
+              - name: w1
+              - cwd: /tmp
+            "#,
+        )
+        .unwrap();
+
+        let session = Session {
+            name: "a".to_string(),
+            ..Default::default()
+        };
+        assert!(config.upsert_session(session.clone()));
+        assert_eq!(config.sessions, vec![session]);
+        assert!(!config.upsert_session(Session {
+            name: "b".to_string(),
+            ..Default::default()
+        }));
+        assert_eq!(config.sessions.len(), 2);
+
+        let window = Window {
+            name: Some("w1".to_string()),
+            active: true,
+            ..Default::default()
+        };
+        assert!(config.upsert_window(window.clone()));
+        assert_eq!(config.windows[0], window);
+        assert!(!config.upsert_window(Window::default()));
+        assert_eq!(config.windows.len(), 3);
+    }
+
+    #[test]
+    fn test_skip_attachable_sessions() {
+        let load = || {
+            serde_yaml::from_str::<Config>(
+                r#"
+            sessions:
+              - name: new
+                windows: []
+              - name: running
+                windows: []
+              - name: dev
+                attach_if_exists: true
+                windows: []
+            "#,
+            )
+            .unwrap()
+        };
+        let running = |name: &str| name != "new";
+        let session_names = |config: &Config| {
+            config
+                .sessions
+                .iter()
+                .map(|s| s.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut attached = load();
+        assert_eq!(attached.skip_attachable_sessions(false, running), ["dev"]);
+        assert_eq!(session_names(&attached), ["new", "running"]);
+        assert_eq!(attached.session_to_select(), Some("dev"));
+
+        let mut config = load();
+        config.selected_target = Some("new:1".to_string());
+        assert_eq!(
+            config.skip_attachable_sessions(true, running),
+            ["running", "dev"]
+        );
+        assert_eq!(session_names(&config), ["new"]);
+        assert_eq!(config.session_to_select(), Some("new"));
+    }
+
+    #[test]
+    fn test_match_windows() {
+        let windows = serde_yaml::from_str::<Vec<Window>>(
+            "[{ name: editor }, { name: renamed }, {}, { name: shell }]",
+        )
+        .unwrap();
+        let running = ["editor", "logs", "shell"];
+        assert_eq!(
+            MatchBy::Name.match_windows(&windows, &running),
+            [Some(0), None, None, Some(2)]
+        );
+        assert_eq!(
+            MatchBy::Index.match_windows(&windows, &running),
+            [Some(0), Some(1), Some(2), None]
+        );
+        assert_eq!(
+            MatchBy::NameThenIndex.match_windows(&windows, &running),
+            [Some(0), Some(1), None, Some(2)]
+        );
+
+        // Each running window matches once
+        let windows =
+            serde_yaml::from_str::<Vec<Window>>("[{ name: shell }, {}, { name: shell }, {}]")
+                .unwrap();
+        let running = ["shell", "logs", "shell"];
+        assert_eq!(
+            MatchBy::Name.match_windows(&windows, &running),
+            [Some(0), Some(1), Some(2), None]
+        );
+        let running = ["shell", "logs"];
+        assert_eq!(
+            MatchBy::Name.match_windows(&windows, &running),
+            [Some(0), Some(1), None, None]
+        );
+        assert_eq!(
+            MatchBy::NameThenIndex.match_windows(&windows, &running),
+            [Some(0), Some(1), None, None]
+        );
+    }
+
+    #[test]
+    fn test_restart_policy() {
+        let window = serde_yaml::from_str::<Window>(
+            "{ left: { restart: on-failure }, right: { restart: always } }",
+        )
+        .unwrap();
+        let policies = window
+            .root_split
+            .pane_iter()
+            .map(|pane| pane.restart)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            policies,
+            [Some(RestartPolicy::OnFailure), Some(RestartPolicy::Always)]
+        );
+
+        assert!(!RestartPolicy::Never.restarts(Some(1)));
+        assert!(RestartPolicy::Always.restarts(Some(0)));
+        assert!(!RestartPolicy::OnFailure.restarts(Some(0)));
+        assert!(RestartPolicy::OnFailure.restarts(Some(2)));
+        assert!(RestartPolicy::OnFailure.restarts(None));
+    }
 }
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ConfigIncludes, ConfigL, Pane, Split, Window};
+
+/// Windows and panes referenced by name (`template: <name>`) from windows
+/// and panes of the same config file, which override the template's fields
+/// they set explicitly. Unlike YAML anchors, this also works in TOML.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Templates {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub windows: BTreeMap<String, Window>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub panes: BTreeMap<String, Pane>,
+}
+
+impl Templates {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn apply_to_window(&self, window: &mut Window) -> Result<(), String> {
+        self.apply_window_template(window, &mut Vec::new())?;
+        let layout_splits = window.layouts.iter_mut().map(|l| &mut l.root_split);
+        for root_split in std::iter::once(&mut window.root_split).chain(layout_splits) {
+            for pane in root_split.pane_iter_mut() {
+                self.apply_pane_template(pane, &mut Vec::new())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the (resolved) window template of `window` into it. `stack`
+    /// holds the templates being resolved to detect cycles.
+    fn apply_window_template(
+        &self,
+        window: &mut Window,
+        stack: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let Some(name) = window.template.take() else {
+            return Ok(());
+        };
+        check_cycle(stack, &name)?;
+        let mut template = self
+            .windows
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("unknown window template '{}'", name))?;
+        stack.push(name);
+        self.apply_window_template(&mut template, stack)?;
+        stack.pop();
+
+        merge_option(&mut window.name, template.name);
+        merge_option(&mut window.when, template.when);
+        if window.cwd.is_empty() {
+            window.cwd = template.cwd;
+        }
+        window.active |= template.active;
+        window.background |= template.background;
+        merge_option(&mut window.shell, template.shell);
+        merge_option(&mut window.direnv, template.direnv);
+        merge_option(&mut window.wrapper, template.wrapper);
+        if window.popups.is_empty() {
+            window.popups = template.popups;
+        }
+        merge_option(&mut window.monitor_activity, template.monitor_activity);
+        merge_option(&mut window.monitor_silence, template.monitor_silence);
+        merge_option(&mut window.monitor_bell, template.monitor_bell);
+        if window.alerts.is_empty() {
+            window.alerts = template.alerts;
+        }
+        if window.layouts.is_empty() {
+            window.layouts = template.layouts;
+        }
+        if window.raw_commands.is_empty() {
+            window.raw_commands = template.raw_commands;
+        }
+        match (&mut *window.root_split, &*template.root_split) {
+            (Split::Pane(pane), Split::Pane(template_pane)) => {
+                merge_pane(pane, template_pane.clone())
+            }
+            (Split::Pane(pane), _) if *pane == Pane::default() => {
+                window.root_split = template.root_split
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_pane_template(&self, pane: &mut Pane, stack: &mut Vec<String>) -> Result<(), String> {
+        let Some(name) = pane.template.take() else {
+            return Ok(());
+        };
+        check_cycle(stack, &name)?;
+        let mut template = self
+            .panes
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("unknown pane template '{}'", name))?;
+        stack.push(name);
+        self.apply_pane_template(&mut template, stack)?;
+        stack.pop();
+
+        merge_pane(pane, template);
+        Ok(())
+    }
+}
+
+fn check_cycle(stack: &[String], name: &str) -> Result<(), String> {
+    if stack.iter().any(|n| n == name) {
+        let cycle = stack
+            .iter()
+            .map(String::as_str)
+            .chain([name])
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "templates reference each other: {}",
+            cycle.join(" -> ")
+        ));
+    }
+    Ok(())
+}
+
+fn merge_option<T>(value: &mut Option<T>, template: Option<T>) {
+    if value.is_none() {
+        *value = template;
+    }
+}
+
+fn merge_pane(pane: &mut Pane, template: Pane) {
+    if pane.cwd.is_empty() {
+        pane.cwd = template.cwd;
+    }
+    pane.active |= template.active;
+    merge_option(&mut pane.shell, template.shell);
+    merge_option(&mut pane.direnv, template.direnv);
+    merge_option(&mut pane.wrapper, template.wrapper);
+    merge_option(&mut pane.shell_command, template.shell_command);
+    pane.keep_open |= template.keep_open;
+    merge_option(&mut pane.send_keys, template.send_keys);
+    merge_option(&mut pane.send_keys_target, template.send_keys_target);
+    merge_option(&mut pane.wait_for, template.wait_for);
+    merge_option(&mut pane.delay, template.delay);
+    merge_option(&mut pane.restart, template.restart);
+    pane.read_only |= template.read_only;
+    pane.clear |= template.clear;
+    merge_option(&mut pane.history_file, template.history_file);
+    merge_option(&mut pane.role, template.role);
+    merge_option(&mut pane.background, template.background);
+    merge_option(&mut pane.border_color, template.border_color);
+    merge_option(&mut pane.template, template.template);
+    merge_option(&mut pane.process, template.process);
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Merges the templates referenced by windows and panes of this config
+    /// into them and clears the `templates` block afterwards.
+    pub fn apply_templates(&mut self) -> Result<(), String> {
+        let templates = std::mem::take(&mut self.templates);
+
+        let session_windows = self.sessions.iter_mut().flat_map(|s| &mut s.windows);
+        let repo_windows = self.repos.iter_mut().flat_map(|r| &mut r.windows);
+        for window in session_windows.chain(&mut self.windows).chain(repo_windows) {
+            templates.apply_to_window(window)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{PartialConfig, Split};
+
+    #[test]
+    fn test_apply_templates() {
+        let load = |config_str: &str| serde_yaml::from_str::<PartialConfig>(config_str).unwrap();
+        let mut config = load(
+            r#"
+            templates:
+              windows:
+                editor:
+                  name: editor
+                  monitor_activity: true
+                  left:
+                    template: nvim
+                  right:
+                    width: 30%
+                    template: shell
+                editor-here:
+                  template: editor
+                  cwd: /src
+              panes:
+                nvim:
+                  shell_command: nvim
+                  active: true
+                shell:
+                  shell_command: fish
+            windows:
+              - template: editor-here
+                name: code
+              - template: editor
+                shell_command: vim
+            "#,
+        );
+        config.apply_templates().unwrap();
+
+        assert!(config.templates.is_empty());
+        let code = &config.windows[0];
+        assert_eq!(code.name.as_deref(), Some("code"));
+        assert_eq!(code.cwd.to_path(), Some(std::path::Path::new("/src")));
+        assert_eq!(code.monitor_activity, Some(true));
+        let Split::H { left, right } = &*code.root_split else {
+            panic!("expected a horizontal split")
+        };
+        assert_eq!(
+            left.split.single_pane().unwrap().shell_command.as_deref(),
+            Some("nvim")
+        );
+        assert!(left.split.single_pane().unwrap().active);
+        assert_eq!(right.width.as_deref(), Some("30%"));
+        assert_eq!(
+            right.split.single_pane().unwrap().shell_command.as_deref(),
+            Some("fish")
+        );
+
+        // A window with its own panes keeps them.
+        let vim = config.windows[1].root_split.single_pane().unwrap();
+        assert_eq!(vim.shell_command.as_deref(), Some("vim"));
+
+        let mut config = load(
+            r#"
+            templates:
+              windows:
+                a: { template: b }
+                b: { template: a }
+            windows:
+              - template: a
+            "#,
+        );
+        assert_eq!(
+            config.apply_templates(),
+            Err("templates reference each other: a -> b -> a".to_string())
+        );
+    }
+}
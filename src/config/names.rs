@@ -0,0 +1,110 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+use super::{ConfigIncludes, ConfigL};
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Stamps out sessions with a `count` (`worker-{i}`, starting at 1) and
+    /// fills in the current date in session names (`standup-{date:%Y%m%d}`,
+    /// `{date}` is `{date:%Y-%m-%d}`). Other `{...}` are kept as they are.
+    pub fn expand_session_names(&mut self, now: &DateTime<Local>) -> Result<(), String> {
+        let mut sessions = Vec::with_capacity(self.sessions.len());
+        for session in self.sessions.drain(..) {
+            let Some(count) = session.count else {
+                let name = expand_name(&session.name, None, now)?;
+                sessions.push(super::Session { name, ..session });
+                continue;
+            };
+            if count > 1 && !session.name.contains("{i}") {
+                return Err(format!(
+                    "session '{}' has `count: {}` but no `{{i}}` in its name",
+                    session.name, count
+                ));
+            }
+            for i in 1..=count {
+                sessions.push(super::Session {
+                    name: expand_name(&session.name, Some(i), now)?,
+                    count: None,
+                    ..session.clone()
+                });
+            }
+        }
+        self.sessions = sessions;
+        Ok(())
+    }
+}
+
+fn expand_name(name: &str, index: Option<u32>, now: &DateTime<Local>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(start) = rest.find('{') {
+        expanded += &rest[..start];
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let placeholder = &rest[1..end];
+        match (placeholder, placeholder.strip_prefix("date:"), index) {
+            ("i", _, Some(i)) => expanded += &i.to_string(),
+            ("date", _, _) => expanded += &now.format("%Y-%m-%d").to_string(),
+            (_, Some(format), _) => {
+                let items = StrftimeItems::new(format).collect::<Vec<_>>();
+                if items.contains(&Item::Error) {
+                    return Err(format!("invalid date format '{}' in session name", format));
+                }
+                expanded += &now.format_with_items(items.into_iter()).to_string();
+            }
+            _ => expanded += &rest[..=end],
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(expanded + rest)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::config::PartialConfig;
+
+    #[test]
+    fn test_expand_session_names() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            sessions:
+              - name: standup-{date:%Y%m%d}
+                windows: [{}]
+              - name: worker-{i}
+                count: 3
+                windows: [{}]
+              - name: "{date}-{other}"
+                windows: [{}]
+            "#,
+        )
+        .unwrap();
+        let now = chrono::Local
+            .with_ymd_and_hms(2024, 3, 9, 12, 0, 0)
+            .unwrap();
+        config.expand_session_names(&now).unwrap();
+
+        let names = config
+            .sessions
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "standup-20240309",
+                "worker-1",
+                "worker-2",
+                "worker-3",
+                "2024-03-09-{other}"
+            ]
+        );
+        assert!(config.sessions.iter().all(|s| s.count.is_none()));
+
+        config.sessions[0].name = "nightly-{date:%Q}".to_string();
+        assert!(config.expand_session_names(&now).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use super::{ConfigIncludes, ConfigL, Popup, RootSplit, Window};
+
+type Cwd = crate::cwd::Cwd<'static>;
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Prepares the config for sharing (`export --redact`): strips commands,
+    /// keys, captured history and processes, and writes paths below `home` with `~`.
+    pub fn redact(&mut self, home: Option<&Path>) {
+        let redact_cwd = |cwd: &mut Cwd| {
+            if let Some(home_cwd) = home.and_then(|home| cwd.home_relative(home)) {
+                *cwd = home_cwd;
+            }
+        };
+
+        redact_cwd(&mut self.defaults.cwd);
+        self.defaults.shell_command = None;
+        self.defaults.send_keys = None;
+        for session in &mut self.sessions {
+            redact_cwd(&mut session.cwd);
+            session.default_command = None;
+            session.raw_commands.clear();
+            redact_popups(&mut session.popups, redact_cwd);
+            for window in &mut session.windows {
+                redact_window(window, redact_cwd);
+            }
+        }
+        for window in &mut self.windows {
+            redact_window(window, redact_cwd);
+        }
+    }
+}
+
+fn redact_window(window: &mut Window, redact_cwd: impl Fn(&mut Cwd) + Copy) {
+    redact_cwd(&mut window.cwd);
+    window.raw_commands.clear();
+    redact_popups(&mut window.popups, redact_cwd);
+    let layout_splits = window.layouts.iter_mut().map(|l| &mut l.root_split);
+    for root_split in std::iter::once(&mut window.root_split).chain(layout_splits) {
+        redact_panes(root_split, redact_cwd);
+    }
+}
+
+fn redact_panes(root_split: &mut RootSplit, redact_cwd: impl Fn(&mut Cwd)) {
+    for pane in root_split.pane_iter_mut() {
+        redact_cwd(&mut pane.cwd);
+        pane.shell_command = None;
+        pane.keep_open = false;
+        pane.send_keys = None;
+        pane.history_file = None;
+        pane.process = None;
+    }
+}
+
+fn redact_popups(popups: &mut [Popup], redact_cwd: impl Fn(&mut Cwd)) {
+    for popup in popups {
+        redact_cwd(&mut popup.cwd);
+        popup.shell_command = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Config, Pane};
+    use std::path::Path;
+
+    #[test]
+    fn test_redact() {
+        let load = |config_str: &str| serde_yaml::from_str::<Config>(config_str).unwrap();
+        let mut config = load(
+            r#"
+            sessions:
+              - name: dev
+                cwd: /home/alice/work/secret-project
+                windows:
+                  - cwd: /srv/logs
+                    left:
+                      shell_command: psql postgres://alice:hunter2@db
+                      keep_open: true
+                    right:
+                      cwd: /home/alice
+                      send_keys: [export TOKEN=abc, Enter]
+                      history_file: /home/alice/.local/share/history/1.txt
+            "#,
+        );
+        config.redact(Some(Path::new("/home/alice")));
+
+        // (Cwds are expanded when loading, so compare paths.)
+        let session = &config.sessions[0];
+        assert_eq!(
+            session.cwd.to_path(),
+            Some(Path::new("~/work/secret-project"))
+        );
+        let window = &session.windows[0];
+        assert_eq!(window.cwd.to_path(), Some(Path::new("/srv/logs")));
+        let panes = window.root_split.pane_iter().collect::<Vec<_>>();
+        assert_eq!(panes[1].cwd.to_path(), Some(Path::new("~")));
+        let empty_pane = Pane {
+            cwd: panes[1].cwd.clone(),
+            ..Default::default()
+        };
+        assert_eq!(*panes[0], Pane::default());
+        assert_eq!(*panes[1], empty_pane);
+    }
+}
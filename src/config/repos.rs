@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use super::{Config, ConfigIncludes, ConfigL, Repo, RepoExpansion, Session, Window};
+use crate::cwd::Cwd;
+
+impl Repo {
+    /// Name of the session or window: `name` or the last component of `path`
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            self.path
+                .to_path()
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().replace(['.', ':'], "_"))
+                .unwrap_or_else(|| "repo".to_string())
+        })
+    }
+
+    fn template_windows(&self) -> Vec<Window> {
+        if self.windows.is_empty() {
+            vec![Window::default()]
+        } else {
+            self.windows.clone()
+        }
+    }
+}
+
+impl<Includes: ConfigIncludes> ConfigL<Includes> {
+    /// Adds a session (or top-level windows) for each entry of `repos`. The
+    /// repos are kept for cloning missing checkouts. Relative paths are
+    /// resolved against the `cwd` of the defaults and then `base_dir` (the
+    /// directory of the config file).
+    pub fn expand_repos(&mut self, base_dir: &Path) {
+        let base_dir = Cwd::from(base_dir.to_path_buf());
+        for repo in &mut self.repos {
+            let path = self.defaults.cwd.joined(&repo.path);
+            repo.path = base_dir.joined(&path).into_owned();
+        }
+        for repo in &self.repos {
+            let name = repo.display_name();
+            match repo.expand_as {
+                RepoExpansion::Session => self.sessions.push(Session {
+                    name,
+                    cwd: repo.path.clone(),
+                    windows: repo.template_windows(),
+                    ..Default::default()
+                }),
+                RepoExpansion::Window => {
+                    let windows = repo.template_windows().into_iter().map(|mut window| {
+                        window.cwd = repo.path.joined(&window.cwd).into_owned();
+                        window.name.get_or_insert_with(|| name.clone());
+                        window
+                    });
+                    self.windows.extend(windows);
+                }
+            }
+        }
+    }
+}
+
+impl Config {
+    /// The repos with a `url` whose checkout is missing and used by one of
+    /// the sessions or windows (which are left after skipping existing ones)
+    pub fn repos_to_clone(&self) -> impl Iterator<Item = &Repo> {
+        let cwds = self
+            .sessions
+            .iter()
+            .flat_map(|session| {
+                let windows = session.windows.iter().map(|window| &window.cwd);
+                std::iter::once(&session.cwd).chain(windows)
+            })
+            .chain(self.windows.iter().map(|window| &window.cwd))
+            .filter_map(Cwd::to_path)
+            .collect::<Vec<_>>();
+
+        self.repos.iter().filter(move |repo| {
+            let Some(path) = repo.path.to_path() else {
+                return false;
+            };
+            repo.url.is_some() && !path.exists() && cwds.iter().any(|cwd| cwd.starts_with(path))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::PartialConfig;
+    use std::path::Path;
+
+    #[test]
+    fn test_expand_repos() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            repos:
+              - path: /src/api.rs
+                url: git@example.com:api.git
+              - path: /src/web
+                name: frontend
+                windows:
+                  - name: editor
+                  - name: server
+                    cwd: server
+              - path: /src/docs
+                as: window
+                windows:
+                  - cwd: book
+                  - name: build
+              - path: tools
+                as: window
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+        config.expand_repos(Path::new("/etc"));
+
+        let sessions = config
+            .sessions
+            .iter()
+            .map(|s| (s.name.as_str(), s.cwd.to_path().unwrap().to_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sessions,
+            [("api_rs", "/src/api.rs"), ("frontend", "/src/web")]
+        );
+        assert_eq!(config.sessions[0].windows.len(), 1);
+        assert_eq!(config.sessions[1].windows[1].cwd, "server");
+
+        let windows = config
+            .windows
+            .iter()
+            .map(|w| {
+                (
+                    w.name.as_deref().unwrap(),
+                    w.cwd.to_path().unwrap().to_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            windows,
+            [
+                ("docs", "/src/docs/book"),
+                ("build", "/src/docs"),
+                ("tools", "/etc/tools")
+            ]
+        );
+        assert_eq!(config.repos.len(), 4);
+        assert_eq!(config.repos[3].path, "/etc/tools");
+    }
+
+    #[test]
+    fn test_repos_to_clone() {
+        let mut config = serde_yaml::from_str::<PartialConfig>(
+            r#"
+            repos:
+              - path: /nonexistent/api
+                url: git@example.com:api.git
+              - path: /nonexistent/web
+                url: git@example.com:web.git
+              - path: /nonexistent/docs
+                as: window
+                url: git@example.com:docs.git
+                windows:
+                  - cwd: book
+              - path: /nonexistent/local
+              - path: /
+                url: git@example.com:root.git
+            "#,
+        )
+        .unwrap()
+        .into_config()
+        .unwrap();
+        config.expand_repos(Path::new("/"));
+        // Skipped, e.g. because the session exists already
+        config.sessions.retain(|s| s.name != "web");
+
+        let paths = config
+            .repos_to_clone()
+            .map(|repo| repo.path.to_path().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/nonexistent/api", "/nonexistent/docs"]);
+    }
+}
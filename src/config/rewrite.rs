@@ -0,0 +1,429 @@
+//! In-place updates of config files (`snapshot`, `edit --tui`).
+//!
+//! Instead of serializing the whole config again, only the changed entry of
+//! a top-level list (`sessions` or `windows`) is replaced (or appended), so
+//! that comments and formatting of everything else in the file survive.
+//! Comments within the replaced entry itself are lost.
+
+use std::ops::Range;
+
+use super::PartialConfig;
+
+/// Updates `contents` (YAML) to match `updated` (the whole config serialized
+/// as YAML) by replacing the entry `name` of the list `key` (or appending it
+/// if it doesn't exist yet or is unnamed). Returns `None` if the file can't
+/// be updated in place (e.g. flow style lists).
+pub fn upsert_yaml(contents: &str, updated: &str, key: &str, name: Option<&str>) -> Option<String> {
+    let lines = lines_of(contents);
+    let updated_lines = lines_of(updated);
+
+    let new_list = find_list(&updated_lines, key).ok()??;
+    let new_range = find_item(&updated_lines, &new_list, name)?;
+    let new_item = updated_lines[new_range]
+        .iter()
+        .map(|line| &line[new_list.indent.min(indent_of(line))..])
+        .collect::<Vec<_>>();
+
+    let mut result = lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    match find_list(&lines, key).ok()? {
+        Some(list) => {
+            let range = name
+                .and_then(|_| find_item(&lines, &list, name))
+                .unwrap_or(list.end..list.end);
+            let indent = " ".repeat(list.indent);
+            let item = new_item.iter().map(|line| match line.trim().is_empty() {
+                true => line.to_string(),
+                false => format!("{}{}", indent, line),
+            });
+            result.splice(range, item);
+        }
+        None => {
+            result.push(format!("{}:\n", key));
+            result.extend(new_item.iter().map(|line| line.to_string()));
+        }
+    }
+
+    let result = result.concat();
+    let parse = |s: &str| serde_yaml::from_str::<PartialConfig>(s).ok();
+    (parse(&result)? == parse(updated)?).then_some(result)
+}
+
+/// Like [`upsert_yaml`] for TOML (`key` is a list of tables)
+pub fn upsert_toml(contents: &str, updated: &str, key: &str, name: Option<&str>) -> Option<String> {
+    use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+    let has_name = |table: &Table| {
+        name.is_some() && table.get("name").and_then(|value| value.as_str()) == name
+    };
+    fn set_position(table: &mut Table, position: usize) {
+        table.set_position(position);
+        for (_, item) in table.iter_mut() {
+            match item {
+                Item::Table(table) => set_position(table, position),
+                Item::ArrayOfTables(tables) => {
+                    for table in tables.iter_mut() {
+                        set_position(table, position)
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fn last_position(table: &Table) -> usize {
+        let nested = table.iter().flat_map(|(_, item)| match item {
+            Item::Table(table) => vec![last_position(table)],
+            Item::ArrayOfTables(tables) => tables.iter().map(last_position).collect(),
+            _ => vec![],
+        });
+        nested.chain(table.position()).max().unwrap_or(0)
+    }
+
+    let mut document = contents.parse::<DocumentMut>().ok()?;
+    let updated_document = updated.parse::<DocumentMut>().ok()?;
+    let new_items = updated_document.get(key)?.as_array_of_tables()?;
+    let mut new_item = match name {
+        Some(_) => new_items.iter().find(|table| has_name(table))?,
+        None => new_items.iter().last()?,
+    }
+    .clone();
+
+    let end_position = last_position(document.as_table());
+    match document.get_mut(key) {
+        Some(item) => {
+            let tables = item.as_array_of_tables_mut()?;
+            let index = tables.iter().position(has_name);
+            match index {
+                Some(index) => {
+                    let existing = tables.get_mut(index)?;
+                    // Keep the comments above the entry.
+                    *new_item.decor_mut() = existing.decor().clone();
+                    set_position(&mut new_item, existing.position().unwrap_or(end_position));
+                    *existing = new_item;
+                }
+                None => {
+                    let position = tables.iter().map(last_position).max();
+                    set_position(&mut new_item, position.unwrap_or(end_position));
+                    tables.push(new_item);
+                }
+            }
+        }
+        None => {
+            set_position(&mut new_item, end_position + 1);
+            let mut tables = ArrayOfTables::new();
+            tables.push(new_item);
+            document.insert(key, Item::ArrayOfTables(tables));
+        }
+    }
+
+    let result = document.to_string();
+    let parse = |s: &str| toml::from_str::<PartialConfig>(s).ok();
+    (parse(&result)? == parse(updated)?).then_some(result)
+}
+
+/// Updates `contents` (the config file `saved` was loaded from, TOML if
+/// `toml`, YAML otherwise) to match `edited` by rewriting only the sessions
+/// and top-level windows that differ, or appending the added windows.
+/// Returns `None` if that isn't possible in place, e.g. because entries were
+/// removed or renamed.
+pub fn update(
+    contents: &str,
+    saved: &PartialConfig,
+    edited: &PartialConfig,
+    toml: bool,
+) -> Option<String> {
+    let upsert = |contents: &str, config: &PartialConfig, key: &str, name: Option<&str>| match toml
+    {
+        true => upsert_toml(contents, &toml::to_string(config).ok()?, key, name),
+        false => upsert_yaml(contents, &serde_yaml::to_string(config).ok()?, key, name),
+    };
+
+    let mut contents = contents.to_string();
+    let mut step = saved.clone();
+    if edited.sessions.len() != saved.sessions.len() || edited.windows.len() < saved.windows.len() {
+        return None;
+    }
+    for (index, session) in edited.sessions.iter().enumerate() {
+        if *session == step.sessions[index] {
+            continue;
+        }
+        let name = &session.name;
+        let is_unique = saved.sessions.iter().filter(|s| s.name == *name).count() == 1;
+        if step.sessions[index].name != *name || !is_unique {
+            return None;
+        }
+        step.sessions[index] = session.clone();
+        contents = upsert(&contents, &step, "sessions", Some(name))?;
+    }
+    for (index, window) in edited.windows.iter().enumerate() {
+        match step.windows.get(index) {
+            Some(existing) if existing == window => continue,
+            Some(existing) => {
+                let name = window.name.as_deref();
+                let is_unique = saved
+                    .windows
+                    .iter()
+                    .filter(|w| w.name.as_deref() == name)
+                    .count()
+                    == 1;
+                if name.is_none() || existing.name.as_deref() != name || !is_unique {
+                    return None;
+                }
+                step.windows[index] = window.clone();
+                contents = upsert(&contents, &step, "windows", name)?;
+            }
+            // Appended (unnamed, so it isn't confused with existing windows)
+            None => {
+                step.windows.push(window.clone());
+                contents = upsert(&contents, &step, "windows", None)?;
+            }
+        }
+    }
+    (step == *edited).then_some(contents)
+}
+
+/// A block style list under a top-level key
+struct List {
+    /// Indentation of the `-` of the items
+    indent: usize,
+    /// Lines of the items (without trailing blank lines and comments)
+    items: Vec<Range<usize>>,
+    /// Line after the last item
+    end: usize,
+}
+
+/// Lines of `contents` including their line breaks (also the last one)
+fn lines_of(contents: &str) -> Vec<String> {
+    contents
+        .split_inclusive('\n')
+        .map(|line| match line.ends_with('\n') {
+            true => line.to_string(),
+            false => format!("{}\n", line),
+        })
+        .collect()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank_or_comment(s: &str) -> bool {
+    let s = s.trim();
+    s.is_empty() || s.starts_with('#')
+}
+
+/// Finds the list under the top-level `key`. Returns `Ok(None)` if there is
+/// no such key and `Err` if its value isn't a block style list.
+fn find_list(lines: &[String], key: &str) -> Result<Option<List>, ()> {
+    let Some(key_line) = lines.iter().position(|line| {
+        line.strip_prefix(key)
+            .is_some_and(|rest| rest.starts_with(':'))
+    }) else {
+        return Ok(None);
+    };
+    if !is_blank_or_comment(&lines[key_line][key.len() + 1..]) {
+        return Err(());
+    }
+
+    let block_end = (key_line + 1..lines.len())
+        .find(|&i| {
+            let line = &lines[i];
+            !line.starts_with([' ', '-', '#']) && !line.trim().is_empty()
+        })
+        .unwrap_or(lines.len());
+
+    let mut indent = None;
+    let mut starts = Vec::new();
+    for (i, line) in lines.iter().enumerate().take(block_end).skip(key_line + 1) {
+        if is_blank_or_comment(line) {
+            continue;
+        }
+        let line_indent = indent_of(line);
+        let is_item = line[line_indent..].starts_with('-');
+        match indent {
+            None if is_item => indent = Some(line_indent),
+            None => return Err(()),
+            Some(indent) if is_item && line_indent == indent => {}
+            Some(indent) if line_indent > indent => continue,
+            Some(_) => return Err(()),
+        }
+        starts.push(i);
+    }
+
+    let items = starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let mut end = starts.get(n + 1).copied().unwrap_or(block_end);
+            while end > start + 1 && is_blank_or_comment(&lines[end - 1]) {
+                end -= 1;
+            }
+            start..end
+        })
+        .collect::<Vec<_>>();
+    let end = items.last().map_or(key_line + 1, |item| item.end);
+    Ok(Some(List {
+        indent: indent.unwrap_or(0),
+        items,
+        end,
+    }))
+}
+
+/// The item named `name` of `list` (or the last item if `name` is `None`)
+fn find_item(lines: &[String], list: &List, name: Option<&str>) -> Option<Range<usize>> {
+    let Some(name) = name else {
+        return list.items.last().cloned();
+    };
+    list.items
+        .iter()
+        .find(|range| {
+            let item = lines[(*range).clone()]
+                .iter()
+                .map(|line| &line[list.indent.min(indent_of(line))..])
+                .collect::<String>();
+            let value = serde_yaml::from_str::<Vec<serde_yaml::Value>>(&item).ok();
+            let item_name = value
+                .as_ref()
+                .and_then(|v| v.first()?.get("name")?.as_str());
+            item_name == Some(name)
+        })
+        .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{Pane, Session, Split, Window};
+
+    #[test]
+    fn test_upsert_keeps_comments() {
+        let contents = "\
+# My sessions
+sessions:
+  # The main one
+  - name: dev # inline
+    windows:
+      - shell_command: nvim
+
+  # Notes
+  - name: notes
+    windows:
+      - {}
+windows:
+  - name: top
+";
+        let mut config: PartialConfig = serde_yaml::from_str(contents).unwrap();
+        let session = |name: &str, command: &str| -> Session {
+            serde_yaml::from_str(&format!(
+                "{{name: {}, windows: [{{shell_command: {}}}]}}",
+                name, command
+            ))
+            .unwrap()
+        };
+        config.upsert_session(session("dev", "htop"));
+        let updated = serde_yaml::to_string(&config).unwrap();
+        let result = upsert_yaml(contents, &updated, "sessions", Some("dev")).unwrap();
+        config.upsert_session(session("new", "top"));
+        let updated = serde_yaml::to_string(&config).unwrap();
+        let result = upsert_yaml(&result, &updated, "sessions", Some("new")).unwrap();
+        assert_eq!(
+            result,
+            "\
+# My sessions
+sessions:
+  # The main one
+  - name: dev
+    windows:
+    - shell_command: htop
+
+  # Notes
+  - name: notes
+    windows:
+      - {}
+  - name: new
+    windows:
+    - shell_command: top
+windows:
+  - name: top
+"
+        );
+
+        let toml_contents = "# My sessions\n\n# The main one\n[[sessions]]\nname = \"dev\"\n\n[[sessions.windows]]\nshell_command = \"nvim\"\n\n# Top\n[[windows]]\nname = \"top\"\n";
+        let mut config: PartialConfig = toml::from_str(toml_contents).unwrap();
+        config.upsert_session(session("dev", "htop"));
+        let updated = toml::to_string(&config).unwrap();
+        let result = upsert_toml(toml_contents, &updated, "sessions", Some("dev")).unwrap();
+        config.upsert_session(session("new", "top"));
+        let updated = toml::to_string(&config).unwrap();
+        let result = upsert_toml(&result, &updated, "sessions", Some("new")).unwrap();
+        assert!(result.starts_with("# My sessions\n\n# The main one\n[[sessions]]\n"));
+        assert!(result.contains("# Top\n[[windows]]"));
+        assert_eq!(
+            toml::from_str::<PartialConfig>(&result).unwrap(),
+            toml::from_str::<PartialConfig>(&updated).unwrap()
+        );
+
+        assert_eq!(
+            upsert_yaml("sessions: []\n", &updated, "sessions", Some("dev")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_update_keeps_comments() {
+        let contents = "\
+# Sessions
+sessions:
+  - name: dev
+    cwd: ~/src # home
+    windows:
+      - shell_command: htop # monitor
+windows:
+  # Editor
+  - name: edit
+    shell_command: nvim
+  # Logs
+  - name: logs
+    shell_command: tail -f log
+";
+        let saved: PartialConfig = serde_yaml::from_str(contents).unwrap();
+
+        let mut edited = saved.clone();
+        edited.windows[1].root_split = Split::Pane(Pane {
+            shell_command: Some("journalctl -f".to_string()),
+            ..Default::default()
+        })
+        .into_root();
+        edited.windows.push(Window::default());
+        let result = update(contents, &saved, &edited, false).unwrap();
+        assert_eq!(
+            result,
+            "\
+# Sessions
+sessions:
+  - name: dev
+    cwd: ~/src # home
+    windows:
+      - shell_command: htop # monitor
+windows:
+  # Editor
+  - name: edit
+    shell_command: nvim
+  # Logs
+  - name: logs
+    shell_command: journalctl -f
+  - {}
+"
+        );
+
+        let mut renamed = saved.clone();
+        renamed.windows[0].name = Some("editor".to_string());
+        assert_eq!(update(contents, &saved, &renamed, false), None);
+
+        let mut removed = saved.clone();
+        removed.windows.pop();
+        assert_eq!(update(contents, &saved, &removed, false), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! Secret values in `send_keys`.
+//!
+//! Entries like `{env: MY_TOKEN}` or `{secret: "op://vault/item/field"}` are
+//! only resolved by `create` (see [`resolve_secrets`]). Everywhere else
+//! (`dump-config`, `dump-command`, logs) they stay references or are
+//! replaced by [`REDACTED`].
+
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+use super::Config;
+use crate::logging;
+
+/// Placeholder for secrets in printed tmux commands
+pub const REDACTED: &str = "<redacted>";
+
+/// Command reading the value of a `secret` reference (passed as its last
+/// argument) unless overridden by `$TMUX_LAYOUT_SECRET_COMMAND`
+const DEFAULT_SECRET_COMMAND: &str = "op read";
+
+/// An entry of `send_keys`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Key {
+    Literal(String),
+    /// Written as `{env: VAR}` in all formats (instead of YAML tags)
+    Secret(#[serde(with = "serde_yaml::with::singleton_map")] Secret),
+}
+
+impl From<&str> for Key {
+    fn from(key: &str) -> Key {
+        Key::Literal(key.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Secret {
+    /// Reference read by the secret command (e.g. `op://vault/item/field`)
+    Secret(String),
+    /// Name of an environment variable
+    Env(String),
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("environment variable '{0}' of secret is not set")]
+    EnvNotSet(String),
+    #[error("failed to read secret '{0}': {1}")]
+    Command(String, String),
+}
+
+impl Secret {
+    fn resolve(&self) -> Result<String, Error> {
+        match self {
+            Secret::Env(var) => std::env::var(var).map_err(|_| Error::EnvNotSet(var.clone())),
+            Secret::Secret(reference) => {
+                let secret_command = std::env::var("TMUX_LAYOUT_SECRET_COMMAND")
+                    .unwrap_or_else(|_| DEFAULT_SECRET_COMMAND.to_string());
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(format!("{} \"$1\"", secret_command))
+                    .arg("sh")
+                    .arg(reference)
+                    .stdin(Stdio::inherit())
+                    .output()
+                    .map_err(|err| Error::Command(reference.clone(), err.to_string()))?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(Error::Command(
+                        reference.clone(),
+                        stderr.trim_end().to_string(),
+                    ));
+                }
+                let value = String::from_utf8_lossy(&output.stdout);
+                Ok(value.trim_end_matches(['\n', '\r']).to_string())
+            }
+        }
+    }
+}
+
+/// Replaces all secrets in `send_keys` of `config` by their values. The
+/// values are redacted from all further log messages.
+///
+/// Note that tmux receives the values as arguments of `send-keys`, so other
+/// users of the machine can see them in the process list while the tmux
+/// command runs.
+pub fn resolve_secrets(config: &mut Config) -> Result<(), Error> {
+    for value in resolve_secrets_with(config, Secret::resolve)? {
+        logging::add_secret(&value);
+    }
+    Ok(())
+}
+
+/// Replaces all secrets in `send_keys` of `config` by their values looked up
+/// by `resolve`. Returns the values.
+fn resolve_secrets_with(
+    config: &mut Config,
+    mut resolve: impl FnMut(&Secret) -> Result<String, Error>,
+) -> Result<Vec<String>, Error> {
+    let windows = config
+        .sessions
+        .iter_mut()
+        .flat_map(|session| &mut session.windows)
+        .chain(&mut config.windows);
+    let mut values = Vec::new();
+    for window in windows {
+        for pane in window.root_split.pane_iter_mut() {
+            for key in pane.send_keys.iter_mut().flatten() {
+                if let Key::Secret(secret) = key {
+                    let value = resolve(secret)?;
+                    values.push(value.clone());
+                    *key = Key::Literal(value);
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve_secrets() {
+        let load = || -> Config {
+            serde_yaml::from_str(
+                r#"
+                windows:
+                  - send_keys:
+                      - "export TOKEN="
+                      - env: TMUX_LAYOUT_TEST_SECRET
+                      - Enter
+                "#,
+            )
+            .unwrap()
+        };
+        let mut config = load();
+        let keys = |config: &Config| {
+            config.windows[0]
+                .root_split
+                .pane_iter()
+                .next()
+                .unwrap()
+                .send_keys
+                .clone()
+        };
+        assert_eq!(
+            keys(&config).unwrap()[1],
+            Key::Secret(Secret::Env("TMUX_LAYOUT_TEST_SECRET".to_string()))
+        );
+
+        let lookup = |secret: &Secret| match secret {
+            Secret::Env(var) if var == "TMUX_LAYOUT_TEST_SECRET" => Ok("hunter2".to_string()),
+            Secret::Env(var) => Err(Error::EnvNotSet(var.clone())),
+            Secret::Secret(reference) => Err(Error::Command(reference.clone(), String::new())),
+        };
+        let mut unset = load();
+        unset.windows[0]
+            .root_split
+            .pane_iter_mut()
+            .next()
+            .unwrap()
+            .send_keys = Some(vec![Key::Secret(Secret::Env("UNSET".to_string()))]);
+        assert!(matches!(
+            resolve_secrets_with(&mut unset, lookup),
+            Err(Error::EnvNotSet(_))
+        ));
+        let values = resolve_secrets_with(&mut config, lookup).unwrap();
+        assert_eq!(values, ["hunter2"]);
+        assert_eq!(
+            keys(&config).unwrap(),
+            ["export TOKEN=".into(), "hunter2".into(), "Enter".into()]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Resolution of window layouts against the size of the client.
+//!
+//! Windows with alternative `layouts` get the widest layout fitting the
+//! client. Sizes are usually passed to tmux as they are (`30%`, `80`).
+//! Splits with `min_*`/`max_*` constraints are resolved into cells for the
+//! size of the client instead, so the constraints can be applied.
+
+use std::fmt;
+
+use super::{Config, Split, Status, Window};
+
+/// Size of the client (terminal) in cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ClientSize {
+    /// Parses `"<width> <height>"` (as printed by `stty size` in reverse or
+    /// `#{client_width} #{client_height}`) or `"<width>x<height>"`.
+    pub fn parse(s: &str) -> Option<ClientSize> {
+        let mut words = s
+            .split(|c: char| c.is_whitespace() || c == 'x')
+            .filter(|w| !w.is_empty())
+            .map(|w| w.parse().ok());
+        let size = ClientSize {
+            width: words.next()??,
+            height: words.next()??,
+        };
+        Some(size).filter(|size| size.width > 0 && size.height > 0)
+    }
+}
+
+impl fmt::Display for ClientSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Whether `config` has windows with alternative layouts or size
+/// constraints (see [`apply_client_size`]).
+pub fn needs_client_size(config: &Config) -> bool {
+    all_windows(config).any(|window| {
+        split_has_constraints(&window.root_split)
+            || window
+                .layouts
+                .iter()
+                .any(|layout| layout.min_width > 0 || split_has_constraints(&layout.root_split))
+    })
+}
+
+fn all_windows(config: &Config) -> impl Iterator<Item = &Window> {
+    config
+        .sessions
+        .iter()
+        .flat_map(|s| &s.windows)
+        .chain(&config.windows)
+}
+
+fn all_windows_mut(config: &mut Config) -> impl Iterator<Item = &mut Window> {
+    config
+        .sessions
+        .iter_mut()
+        .flat_map(|s| &mut s.windows)
+        .chain(&mut config.windows)
+}
+
+/// Selects the layout of every window with alternative `layouts` for
+/// `client` and clamps all constrained splits. If the size of the client is
+/// unknown, the narrowest layouts are used and constraints are ignored.
+pub fn apply_client_size(config: &mut Config, client: Option<ClientSize>) {
+    for window in all_windows_mut(config) {
+        select_layout(window, client.map(|client| client.width));
+    }
+    if let Some(client) = client {
+        apply_size_constraints(config, client);
+    }
+}
+
+/// Replaces the splits of `window` by the widest of its `layouts` fitting
+/// into `width` columns. Windows keep their own splits if none fits.
+fn select_layout(window: &mut Window, width: Option<u32>) {
+    let layouts = std::mem::take(&mut window.layouts);
+    let selected = match width {
+        Some(width) => layouts
+            .into_iter()
+            .filter(|layout| layout.min_width <= width)
+            .max_by_key(|layout| layout.min_width),
+        None => layouts.into_iter().min_by_key(|layout| layout.min_width),
+    };
+    if let Some(layout) = selected {
+        window.root_split = layout.root_split;
+    }
+}
+
+fn split_has_constraints(split: &Split) -> bool {
+    match split {
+        Split::Pane(_) => false,
+        Split::H { left, right } => [left, right].iter().any(|part| {
+            part.min_width.is_some()
+                || part.max_width.is_some()
+                || split_has_constraints(&part.split)
+        }),
+        Split::V { top, bottom } => [top, bottom].iter().any(|part| {
+            part.min_height.is_some()
+                || part.max_height.is_some()
+                || split_has_constraints(&part.split)
+        }),
+    }
+}
+
+/// Clamps the sizes of all constrained splits of `config` for `client` and
+/// warns about constraints which can't be met.
+pub fn apply_size_constraints(config: &mut Config, client: ClientSize) {
+    let session_windows = config.sessions.iter_mut().flat_map(|session| {
+        let height = window_height(client, &session.status);
+        session
+            .windows
+            .iter_mut()
+            .map(move |window| (window, height))
+    });
+    // Top-level windows end up in sessions with an unknown status line.
+    let default_height = window_height(client, &Status::default());
+    let top_level_windows = config
+        .windows
+        .iter_mut()
+        .map(|window| (window, default_height));
+    for (window, height) in session_windows.chain(top_level_windows) {
+        let name = window.name.clone().unwrap_or_else(|| "(unnamed)".into());
+        resolve(&mut window.root_split, client.width, height, &name);
+    }
+}
+
+/// Rows of the windows of a session with `status` on `client`
+pub fn window_height(client: ClientSize, status: &Status) -> u32 {
+    client.height.saturating_sub(status.height())
+}
+
+fn resolve(split: &mut Split, width: u32, height: u32, window_name: &str) {
+    match split {
+        Split::Pane(_) => {}
+        Split::H { left, right } => {
+            let constraints = [
+                (left.min_width, left.max_width),
+                (right.min_width, right.max_width),
+            ];
+            let (left_width, right_width) =
+                resolve_part_sizes(&mut left.width, &mut right.width, constraints, width)
+                    .unwrap_or_else(|infeasible| {
+                        warn_infeasible(window_name, width, "columns", infeasible)
+                    });
+            resolve(&mut left.split, left_width, height, window_name);
+            resolve(&mut right.split, right_width, height, window_name);
+        }
+        Split::V { top, bottom } => {
+            let constraints = [
+                (top.min_height, top.max_height),
+                (bottom.min_height, bottom.max_height),
+            ];
+            let (top_height, bottom_height) =
+                resolve_part_sizes(&mut top.height, &mut bottom.height, constraints, height)
+                    .unwrap_or_else(|infeasible| {
+                        warn_infeasible(window_name, height, "rows", infeasible)
+                    });
+            resolve(&mut top.split, width, top_height, window_name);
+            resolve(&mut bottom.split, width, bottom_height, window_name);
+        }
+    }
+}
+
+fn warn_infeasible(window_name: &str, total: u32, unit: &str, sizes: (u32, u32)) -> (u32, u32) {
+    crate::show_warning(&format!(
+        "size constraints of window '{}' can't be met with {} {}",
+        window_name, total, unit
+    ));
+    sizes
+}
+
+type Constraint = (Option<u32>, Option<u32>);
+
+/// Computes the sizes (in cells) of the two parts of a split of `total`
+/// cells. If the parts have constraints, the clamped size is written back
+/// (in cells) into the part which had a size before (or the second part).
+/// Returns `Err` with the best effort sizes if the constraints conflict.
+fn resolve_part_sizes(
+    first_size: &mut Option<String>,
+    second_size: &mut Option<String>,
+    [(first_min, first_max), (second_min, second_max)]: [Constraint; 2],
+    total: u32,
+) -> Result<(u32, u32), (u32, u32)> {
+    let available = total.saturating_sub(1);
+    let Some(first) = first_part_size(first_size.as_deref(), second_size.as_deref(), total) else {
+        // Unknown size format: leave it to tmux
+        return Ok((available / 2, available - available / 2));
+    };
+
+    if [first_min, first_max, second_min, second_max]
+        .iter()
+        .all(Option::is_none)
+    {
+        return Ok((first, available - first));
+    }
+
+    let min = first_min
+        .unwrap_or(1)
+        .max(available.saturating_sub(second_max.unwrap_or(u32::MAX)));
+    let max = first_max
+        .unwrap_or(u32::MAX)
+        .min(available.saturating_sub(second_min.unwrap_or(1)));
+    let feasible = min <= max;
+    let clamped = if feasible {
+        first.clamp(min, max)
+    } else {
+        // Prefer the constraints of the first part
+        first
+            .max(first_min.unwrap_or(0))
+            .min(first_max.unwrap_or(u32::MAX))
+            .min(available)
+    };
+
+    if clamped != first {
+        if first_size.is_some() {
+            *first_size = Some(clamped.to_string());
+        } else {
+            *second_size = Some((available - clamped).to_string());
+        }
+    }
+
+    let sizes = (clamped, available - clamped);
+    if feasible {
+        Ok(sizes)
+    } else {
+        Err(sizes)
+    }
+}
+
+/// Sizes (in cells) of the two parts of a split of `total` cells with the
+/// configured sizes of the parts. Unknown size formats split evenly.
+pub fn part_sizes(first_size: Option<&str>, second_size: Option<&str>, total: u32) -> (u32, u32) {
+    let available = total.saturating_sub(1);
+    let first = first_part_size(first_size, second_size, total).unwrap_or(available / 2);
+    (first, available - first)
+}
+
+fn first_part_size(first_size: Option<&str>, second_size: Option<&str>, total: u32) -> Option<u32> {
+    // One cell for the border between the parts
+    let available = total.saturating_sub(1);
+    let first = match (first_size, second_size) {
+        (Some(size), _) if !is_even_size(size) => parse_size(size, total)?,
+        (_, Some(size)) if !is_even_size(size) => {
+            available.saturating_sub(parse_size(size, total)?)
+        }
+        // Like tmux, which gives the second part half of the cells
+        // (rounded down)
+        _ => available - available / 2,
+    };
+    Some(first.min(available))
+}
+
+/// Whether `size` is `50%`, which splits like a split without sizes (see
+/// `TmuxCommandBuilder::split_pane`)
+pub fn is_even_size(size: &str) -> bool {
+    size.strip_suffix('%')
+        .is_some_and(|percentage| percentage.trim().parse() == Ok(50))
+}
+
+/// Size in cells of `size` (`N%` or `N`) relative to `total` cells (like
+/// tmux, percentages include the border between the parts)
+fn parse_size(size: &str, total: u32) -> Option<u32> {
+    match size.strip_suffix('%') {
+        Some(percentage) => Some(total * percentage.trim().parse::<u32>().ok()? / 100),
+        None => size.trim().parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    fn load(config_str: &str) -> Config {
+        serde_yaml::from_str(config_str).unwrap()
+    }
+
+    #[test]
+    fn test_apply_size_constraints() {
+        let config_str = r#"
+            windows:
+              - left:
+                  width: 20%
+                  min_width: 40
+                right:
+                  top:
+                    max_height: 10
+                  bottom: {}
+            "#;
+
+        let mut config = load(config_str);
+        assert!(needs_client_size(&config));
+        apply_size_constraints(&mut config, ClientSize::parse("300 81").unwrap());
+        let expected = load(
+            r#"
+            windows:
+              - left:
+                  width: 20%
+                  min_width: 40
+                right:
+                  top:
+                    max_height: 10
+                  bottom:
+                    height: "69"
+            "#,
+        );
+        assert_eq!(config, expected);
+
+        // Constraints already met on a small screen
+        let mut config = load(config_str);
+        apply_size_constraints(&mut config, ClientSize::parse("150 21").unwrap());
+        let expected = load(
+            r#"
+            windows:
+              - left:
+                  width: "40"
+                  min_width: 40
+                right:
+                  top:
+                    max_height: 10
+                  bottom: {}
+            "#,
+        );
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn test_apply_size_constraints_status_height() {
+        let config_str = |status: &str| {
+            format!(
+                r#"
+                sessions:
+                  - name: s
+                    status: {}
+                    windows:
+                      - top:
+                          max_height: 10
+                        bottom: {{}}
+                "#,
+                status
+            )
+        };
+        let bottom_height = |status: &str| {
+            let mut config = load(&config_str(status));
+            apply_size_constraints(&mut config, ClientSize::parse("80x41").unwrap());
+            let Split::V { bottom, .. } = &*config.sessions[0].windows[0].root_split else {
+                panic!("not a vertical split");
+            };
+            bottom.height.clone()
+        };
+
+        assert_eq!(bottom_height("{}").as_deref(), Some("29"));
+        assert_eq!(bottom_height("{ lines: 3 }").as_deref(), Some("27"));
+        assert_eq!(bottom_height("{ enabled: false }").as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn test_select_layout() {
+        let config_str = r#"
+            windows:
+              - name: default
+              - name: responsive
+                layouts:
+                  - min_width: 200
+                    panes: [a, b, c]
+                  - min_width: 120
+                    panes: [a, b]
+                  - panes: [a]
+              - name: ultrawide-only
+                layouts:
+                  - min_width: 300
+                    panes: [a, b, c, d]
+            "#;
+        let pane_counts = |config: &Config| {
+            config
+                .windows
+                .iter()
+                .map(|w| w.root_split.pane_iter().count())
+                .collect::<Vec<_>>()
+        };
+
+        let mut config = load(config_str);
+        assert!(needs_client_size(&config));
+        apply_client_size(&mut config, ClientSize::parse("150 40"));
+        assert_eq!(pane_counts(&config), [1, 2, 1]);
+        assert!(config.windows.iter().all(|w| w.layouts.is_empty()));
+
+        let mut config = load(config_str);
+        apply_client_size(&mut config, ClientSize::parse("400 40"));
+        assert_eq!(pane_counts(&config), [1, 3, 4]);
+
+        let mut config = load(config_str);
+        apply_client_size(&mut config, None);
+        assert_eq!(pane_counts(&config), [1, 1, 4]);
+    }
+
+    #[test]
+    fn test_resolve_part_sizes() {
+        let none = || (None::<String>, None::<String>);
+
+        let (mut first, mut second) = none();
+        let constraints = [(Some(30), None), (Some(30), None)];
+        assert_eq!(
+            resolve_part_sizes(&mut first, &mut second, constraints, 51),
+            Err((30, 20))
+        );
+
+        let (mut first, mut second) = none();
+        let constraints = [(None, None), (None, Some(20))];
+        assert_eq!(
+            resolve_part_sizes(&mut first, &mut second, constraints, 101),
+            Ok((80, 20))
+        );
+        assert_eq!(second.as_deref(), Some("20"));
+        assert_eq!(first, None);
+
+        assert_eq!(part_sizes(None, Some("30%"), 101), (70, 30));
+        // 50% splits like no size, from either part
+        for total in [120, 121] {
+            let even = part_sizes(None, None, total);
+            assert_eq!(part_sizes(Some("50%"), None, total), even);
+            assert_eq!(part_sizes(None, Some("50%"), total), even);
+        }
+        assert_eq!(ClientSize::parse("200x50").unwrap().height, 50);
+        assert_eq!(parse_size("25%", 200), Some(50));
+        assert_eq!(parse_size("25", 200), Some(25));
+        assert_eq!(parse_size("a", 200), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! Starter configs written by `tmux-layout init`.
+
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    contents: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "minimal",
+        description: "a session with a single window",
+        contents: include_str!("templates/minimal.yaml"),
+    },
+    Template {
+        name: "dev",
+        description: "editor, shell and git status side by side",
+        contents: include_str!("templates/dev.yaml"),
+    },
+    Template {
+        name: "monitor",
+        description: "system overview and log tailing with activity alerts",
+        contents: include_str!("templates/monitor.yaml"),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Template> {
+    TEMPLATES.iter().find(|t| t.name == name)
+}
+
+impl Template {
+    /// Renders the template for a session named `session_name` rooted at `cwd`.
+    pub fn render(&self, session_name: &str, cwd: &str) -> String {
+        self.contents
+            .replace("{name}", &yaml_escape(session_name))
+            .replace("{cwd}", &yaml_escape(cwd))
+    }
+}
+
+/// Escapes `s` for use within a double-quoted YAML string.
+fn yaml_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{validate, PartialConfig};
+
+    #[test]
+    fn test_templates_are_valid() {
+        for template in TEMPLATES {
+            let rendered = template.render("my \"project\"", "/tmp");
+            let config = serde_yaml::from_str::<PartialConfig>(&rendered)
+                .unwrap_or_else(|err| panic!("template {}: {}", template.name, err))
+                .into_config()
+                .unwrap();
+
+            assert_eq!(config.sessions[0].name, "my \"project\"");
+            assert_eq!(config.sessions[0].cwd, "/tmp");
+            assert_eq!(validate::validate(&config), vec![], "{}", template.name);
+        }
+    }
+}
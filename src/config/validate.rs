@@ -0,0 +1,473 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{Config, Pane, Window};
+use crate::shell;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Checks a fully loaded config (includes resolved) for conflicts tmux
+/// would either reject or silently resolve in surprising ways.
+pub fn validate(config: &Config) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let mut session_counts = HashMap::<&str, usize>::new();
+    for session in &config.sessions {
+        *session_counts.entry(&session.name).or_default() += 1;
+    }
+    let mut duplicate_sessions = session_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect::<Vec<_>>();
+    duplicate_sessions.sort();
+    for (name, count) in duplicate_sessions {
+        issues.push(Issue::error(format!(
+            "session '{}' is defined {} times (possibly across included files); \
+             session names must be unique (set `merge: true` to extend a session \
+             from an included file)",
+            name, count
+        )));
+    }
+
+    let mut popup_counts = HashMap::<&str, usize>::new();
+    for (popup, _) in config.popups() {
+        *popup_counts.entry(&popup.name).or_default() += 1;
+    }
+    let mut duplicate_popups = popup_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect::<Vec<_>>();
+    duplicate_popups.sort();
+    for (name, count) in duplicate_popups {
+        issues.push(Issue::warning(format!(
+            "popup '{}' is defined {} times; `tmux-layout popup {}` opens the first one",
+            name, count, name
+        )));
+    }
+
+    for session in &config.sessions {
+        let context = format!("session '{}'", session.name);
+        if let Some(lines) = session
+            .status
+            .lines
+            .filter(|lines| !(1..=5).contains(lines))
+        {
+            issues.push(Issue::error(format!(
+                "{}: status lines must be between 1 and 5 (not {})",
+                context, lines
+            )));
+        }
+        validate_raw_commands(&session.raw_commands, &context, &mut issues);
+        validate_windows(&session.windows, &context, &mut issues);
+    }
+    validate_windows(&config.windows, "the top-level windows", &mut issues);
+
+    issues
+}
+
+fn validate_raw_commands(raw_commands: &[String], context: &str, issues: &mut Vec<Issue>) {
+    for raw_command in raw_commands {
+        match shell::split(raw_command) {
+            Ok(args) if args.is_empty() => {
+                issues.push(Issue::error(format!("empty raw command in {}", context)))
+            }
+            Ok(_) => {}
+            Err(err) => issues.push(Issue::error(format!(
+                "raw command `{}` of {} can't be parsed: {}",
+                raw_command, context, err
+            ))),
+        }
+    }
+}
+
+fn validate_windows(windows: &[Window], context: &str, issues: &mut Vec<Issue>) {
+    let active_windows = windows.iter().filter(|w| w.active).count();
+    if active_windows > 1 {
+        issues.push(Issue::warning(format!(
+            "{} active windows in {}; only the first one will be selected",
+            active_windows, context
+        )));
+    }
+    for window in windows.iter().filter(|w| w.active && w.background) {
+        issues.push(Issue::warning(format!(
+            "window '{}' of {} is created in the background and won't be selected",
+            window.name.as_deref().unwrap_or("(unnamed)"),
+            context
+        )));
+    }
+
+    let mut window_counts = HashMap::<&str, usize>::new();
+    for name in windows.iter().filter_map(|w| w.name.as_deref()) {
+        *window_counts.entry(name).or_default() += 1;
+    }
+    let mut duplicate_windows = window_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect::<Vec<_>>();
+    duplicate_windows.sort();
+    for (name, count) in duplicate_windows {
+        issues.push(Issue::warning(format!(
+            "window '{}' is defined {} times in {}; windows can't be told apart by name",
+            name, count, context
+        )));
+    }
+
+    for window in windows {
+        let window_name = window.name.as_deref().unwrap_or("(unnamed)");
+        validate_raw_commands(
+            &window.raw_commands,
+            &format!("window '{}' of {}", window_name, context),
+            issues,
+        );
+
+        if window
+            .root_split
+            .pane_iter()
+            .any(|p| p.keep_open && p.shell_command.is_none())
+        {
+            issues.push(Issue::warning(format!(
+                "`keep_open` without `shell_command` has no effect (window '{}' of {})",
+                window_name, context
+            )));
+        }
+
+        let clears_nothing = |p: &Pane| match &p.shell_command {
+            Some(_) => !p.keep_open && p.send_keys.is_none(),
+            None => p.history_file.is_none() && p.send_keys.is_none(),
+        };
+        if window
+            .root_split
+            .pane_iter()
+            .any(|p| p.clear && clears_nothing(p))
+        {
+            issues.push(Issue::warning(format!(
+                "`clear` needs `send_keys` or a `shell_command` with `keep_open` (window '{}' of {})",
+                window_name, context
+            )));
+        }
+
+        let pane_count = window.root_split.pane_iter().count();
+        for target in window
+            .root_split
+            .pane_iter()
+            .filter_map(|p| p.send_keys_target)
+            .filter(|target| *target as usize >= pane_count)
+        {
+            issues.push(Issue::error(format!(
+                "`send_keys_target` {} doesn't exist, window '{}' of {} has {} panes",
+                target, window_name, context, pane_count
+            )));
+        }
+
+        for wait_for in window
+            .root_split
+            .pane_iter()
+            .filter_map(|p| p.wait_for.as_ref())
+        {
+            if wait_for.port.is_none() && wait_for.text.is_none() {
+                issues.push(Issue::error(format!(
+                    "`wait_for` needs a `port` or `text` (window '{}' of {})",
+                    window_name, context
+                )));
+            }
+            if let Some(pane) = wait_for.pane.filter(|pane| *pane as usize >= pane_count) {
+                issues.push(Issue::error(format!(
+                    "`wait_for` pane {} doesn't exist, window '{}' of {} has {} panes",
+                    pane, window_name, context, pane_count
+                )));
+            }
+        }
+        if window
+            .root_split
+            .pane_iter()
+            .any(|p| p.wait_for.is_some() && p.send_keys.is_none())
+        {
+            issues.push(Issue::warning(format!(
+                "`wait_for` without `send_keys` has no effect (window '{}' of {})",
+                window_name, context
+            )));
+        }
+        if window
+            .root_split
+            .pane_iter()
+            .any(|p| p.delay.is_some() && p.shell_command.is_none() && p.send_keys.is_none())
+        {
+            issues.push(Issue::warning(format!(
+                "`delay` without `shell_command` or `send_keys` has no effect (window '{}' of {})",
+                window_name, context
+            )));
+        }
+
+        let mut role_counts = HashMap::<&str, usize>::new();
+        for role in window
+            .root_split
+            .pane_iter()
+            .filter_map(|p| p.role.as_deref())
+        {
+            *role_counts.entry(role).or_default() += 1;
+        }
+        let mut duplicate_roles = role_counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .collect::<Vec<_>>();
+        duplicate_roles.sort();
+        for (role, count) in duplicate_roles {
+            issues.push(Issue::warning(format!(
+                "role '{}' is given to {} panes of window '{}' of {}; \
+                 `focus` and `swap` can't tell them apart",
+                role, count, window_name, context
+            )));
+        }
+
+        let mut border_colors = window
+            .root_split
+            .pane_iter()
+            .filter_map(|p| p.border_color.as_deref())
+            .collect::<Vec<_>>();
+        border_colors.dedup();
+        if border_colors.len() > 1 {
+            issues.push(Issue::warning(format!(
+                "panes of window '{}' of {} have different `border_color`s; tmux only \
+                 has one border style per window, using '{}'",
+                window_name, context, border_colors[0]
+            )));
+        }
+
+        let active_panes = window.root_split.pane_iter().filter(|p| p.active).count();
+        if active_panes > 1 {
+            issues.push(Issue::warning(format!(
+                "{} active panes in window '{}' of {}; only the first one will be selected",
+                active_panes, window_name, context
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::PartialConfig;
+
+    fn parse(yaml: &str) -> Config {
+        serde_yaml::from_str::<PartialConfig>(yaml)
+            .unwrap()
+            .into_config()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let config = parse(
+            r#"
+            sessions:
+              - name: a
+                windows:
+                  - name: w1
+                    active: true
+                  - name: w2
+              - name: b
+                windows:
+                  - name: w1
+                    active: true
+            "#,
+        );
+        assert_eq!(validate(&config), vec![]);
+    }
+
+    #[test]
+    fn test_duplicate_sessions() {
+        let config = parse(
+            r#"
+            sessions:
+              - name: a
+                windows: [{}]
+              - name: a
+                windows: [{}]
+            "#,
+        );
+        let issues = validate(&config);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].is_error());
+        assert!(issues[0].message.contains("'a' is defined 2 times"));
+    }
+
+    #[test]
+    fn test_window_conflicts() {
+        let config = parse(
+            r#"
+            windows:
+              - name: w
+                active: true
+              - name: w
+                active: true
+                left: { active: true }
+                right: { active: true }
+            "#,
+        );
+        let issues = validate(&config);
+        assert_eq!(issues.len(), 3);
+        assert!(issues.iter().all(|issue| !issue.is_error()));
+        assert!(issues[0].message.starts_with("2 active windows"));
+        assert!(issues[1]
+            .message
+            .starts_with("window 'w' is defined 2 times"));
+        assert!(issues[2]
+            .message
+            .starts_with("2 active panes in window 'w'"));
+    }
+
+    #[test]
+    fn test_clear_without_output() {
+        let config = parse(
+            r#"
+            windows:
+              - name: ok
+                left: { shell_command: motd, keep_open: true, clear: true }
+                right: { send_keys: [neofetch, Enter], clear: true }
+              - name: dashboard
+                shell_command: htop
+                clear: true
+            "#,
+        );
+        let issues = validate(&config);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.starts_with(
+            "`clear` needs `send_keys` or a `shell_command` with `keep_open` (window 'dashboard'"
+        ));
+    }
+
+    #[test]
+    fn test_send_keys_target() {
+        let config = parse(
+            r#"
+            windows:
+              - name: api
+                left: { shell_command: ./serve }
+                right: { send_keys: [curl localhost, Enter], send_keys_target: 2 }
+            "#,
+        );
+        let issues = validate(&config);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].is_error());
+        assert!(issues[0]
+            .message
+            .starts_with("`send_keys_target` 2 doesn't exist, window 'api'"));
+    }
+
+    #[test]
+    fn test_wait_for() {
+        let config = parse(
+            r#"
+            windows:
+              - name: db
+                left: { shell_command: postgres }
+                right:
+                  send_keys: [psql, Enter]
+                  wait_for: { port: 5432, timeout: 10 }
+              - name: broken
+                left: { wait_for: { text: ready, pane: 3 } }
+                right: { send_keys: [ls, Enter], wait_for: { timeout: 10 } }
+              - name: delayed
+                left: { shell_command: make watch, delay: 5 }
+                right: { delay: 5 }
+            "#,
+        );
+        let issues = validate(&config);
+        let messages = issues
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4, "{:?}", messages);
+        assert!(messages[0].starts_with("`wait_for` pane 3 doesn't exist, window 'broken'"));
+        assert!(messages[1].starts_with("`wait_for` needs a `port` or `text` (window 'broken'"));
+        assert!(messages[2].starts_with("`wait_for` without `send_keys` has no effect"));
+        assert!(messages[3].starts_with("`delay` without `shell_command` or `send_keys`"));
+    }
+
+    #[test]
+    fn test_raw_commands() {
+        let config = parse(
+            r#"
+            sessions:
+              - name: main
+                raw_commands: ["set-option mouse on", "display 'unterminated"]
+                windows:
+                  - name: code
+                    raw_commands: ["set-option -w synchronize-panes on", " "]
+            "#,
+        );
+        let issues = validate(&config);
+        let messages = issues
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "raw command `display 'unterminated` of session 'main' can't be parsed: \
+                 unterminated single quote",
+                "empty raw command in window 'code' of session 'main'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_roles() {
+        let config = parse(
+            r#"
+            windows:
+              - name: code
+                left: { role: editor }
+                right:
+                  top: { role: editor }
+                  bottom: { role: shell }
+              - name: logs
+                role: shell
+            "#,
+        );
+        let issues = validate(&config);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(!issues[0].is_error());
+        assert!(issues[0]
+            .message
+            .starts_with("role 'editor' is given to 2 panes of window 'code'"));
+    }
+}
@@ -34,12 +34,56 @@ impl Cwd<'_> {
         self.path.as_ref().map(AsRef::as_ref)
     }
 
-    pub fn shallow_clone(&self) -> Cwd {
+    pub fn shallow_clone(&self) -> Cwd<'_> {
         Cwd {
             path: self.path.as_ref().map(|path| Cow::Borrowed(path.as_ref())),
         }
     }
 
+    pub fn into_owned(self) -> Cwd<'static> {
+        Cwd {
+            path: self.path.map(|path| Cow::Owned(path.into_owned())),
+        }
+    }
+
+    /// The path with all symlinks (and `.`/`..`) resolved. Falls back to the
+    /// path as is if it can't be resolved (e.g. because it doesn't exist).
+    pub fn canonicalized(&self) -> Cwd<'static> {
+        match &self.path {
+            None => Cwd::default(),
+            Some(path) => path
+                .canonicalize()
+                .unwrap_or_else(|_| path.to_path_buf())
+                .into(),
+        }
+    }
+
+    /// `path` relative to this cwd, if it lies below it. Symlinks are resolved
+    /// on both sides if the paths don't match literally (tmux reports pane
+    /// paths resolved, session paths as given).
+    pub fn relative_path_of(&self, path: &Path) -> Option<PathBuf> {
+        let base = self.to_path()?;
+        if let Ok(rel_path) = path.strip_prefix(base) {
+            return Some(rel_path.to_owned());
+        }
+        let base = self.canonicalized();
+        let path = Cwd::from(path.to_path_buf()).canonicalized();
+        path.to_path()?
+            .strip_prefix(base.to_path()?)
+            .ok()
+            .map(Path::to_owned)
+    }
+
+    /// This (absolute) path written as `~` or `~/...` if it lies below `home`
+    pub fn home_relative(&self, home: &Path) -> Option<Cwd<'static>> {
+        let rel_path = Cwd::from(home.to_path_buf()).relative_path_of(self.to_path()?)?;
+        let mut tilde_path = PathBuf::from("~");
+        if rel_path.components().next().is_some() {
+            tilde_path.push(rel_path);
+        }
+        Some(tilde_path.into())
+    }
+
     pub fn is_empty(&self) -> bool {
         match &self.path {
             None => true,
@@ -115,3 +159,33 @@ impl<'de> Deserialize<'de> for Cwd<'static> {
         Ok(expanded_path.into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_relative_path_of() {
+        let cwd = Cwd::from("/srv/app");
+        assert_eq!(
+            cwd.relative_path_of(Path::new("/srv/app/src")),
+            Some(PathBuf::from("src"))
+        );
+        assert_eq!(cwd.relative_path_of(Path::new("/srv/other")), None);
+        assert_eq!(Cwd::default().relative_path_of(Path::new("/srv")), None);
+
+        // Symlinked base dir, pane path reported resolved
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let real_dir = tmp_dir.path().join("real");
+        let link_dir = tmp_dir.path().join("link");
+        fs::create_dir_all(real_dir.join("src")).unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&real_dir, &link_dir).unwrap();
+            let cwd = Cwd::from(link_dir.clone());
+            let pane_path = real_dir.join("src").canonicalize().unwrap();
+            assert_eq!(cwd.relative_path_of(&pane_path), Some(PathBuf::from("src")));
+        }
+    }
+}
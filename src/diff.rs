@@ -0,0 +1,313 @@
+//! Line diffs of serialized configs (`export --diff-against`)
+
+use crate::ui::{self, Role};
+
+/// Lines of context around the changes of a hunk
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Diffs the lines of `old` and `new` along a shortest edit script (Myers'
+/// algorithm in linear space, so large snapshots diff cheaply).
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    diff_slices(&old, &new, &mut lines);
+    lines
+}
+
+fn diff_slices<'a>(old: &[&'a str], new: &[&'a str], lines: &mut Vec<Line<'a>>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old_rest[..old_rest.len() - suffix];
+    let new_middle = &new_rest[..new_rest.len() - suffix];
+
+    lines.extend(old[..prefix].iter().map(|line| Line::Same(line)));
+    if old_middle.is_empty() || new_middle.is_empty() {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        match middle_snake(old_middle, new_middle) {
+            Some((x, y)) => {
+                diff_slices(&old_middle[..x], &new_middle[..y], lines);
+                diff_slices(&old_middle[x..], &new_middle[y..], lines);
+            }
+            None => {
+                lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+                lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+            }
+        }
+    }
+    lines.extend(
+        old_rest[old_rest.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+}
+
+/// The point where the forward and backward searches for the shortest edit
+/// script of `old` and `new` (both non-empty) meet, `None` if they share no
+/// lines. Only needs space linear in the lengths.
+fn middle_snake(old: &[&str], new: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    // Furthest x reached on each diagonal k = x - y (forward) and from the
+    // ends (backward), -1 if not reached yet
+    let mut forward = vec![-1isize; 2 * max_d as usize + 2];
+    let mut backward = forward.clone();
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    let delta = n - m;
+    // With an odd delta, the forward search detects the overlap.
+    let check_forward = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        for k1 in (-d + k1_start..=d - k1_end).step_by(2) {
+            let k1_offset = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[k1_offset - 1] < forward[k1_offset + 1])
+            {
+                forward[k1_offset + 1]
+            } else {
+                forward[k1_offset - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && old[x1 as usize] == new[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[k1_offset] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if check_forward {
+                let k2_offset = offset + delta - k1;
+                if (0..backward.len() as isize).contains(&k2_offset)
+                    && backward[k2_offset as usize] != -1
+                    && x1 >= n - backward[k2_offset as usize]
+                {
+                    return Some((x1 as usize, y1 as usize));
+                }
+            }
+        }
+
+        for k2 in (-d + k2_start..=d - k2_end).step_by(2) {
+            let k2_offset = (offset + k2) as usize;
+            let mut x2 =
+                if k2 == -d || (k2 != d && backward[k2_offset - 1] < backward[k2_offset + 1]) {
+                    backward[k2_offset + 1]
+                } else {
+                    backward[k2_offset - 1] + 1
+                };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && old[(n - x2 - 1) as usize] == new[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[k2_offset] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !check_forward {
+                let k1_offset = offset + delta - k2;
+                if (0..forward.len() as isize).contains(&k1_offset)
+                    && forward[k1_offset as usize] != -1
+                {
+                    let x1 = forward[k1_offset as usize];
+                    let y1 = offset + x1 - k1_offset;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Formats the changes of `lines` as a unified diff of `old_label` and
+/// `new_label` (colored as results), `None` if there are none.
+pub fn unified(lines: &[Line], old_label: &str, new_label: &str) -> Option<String> {
+    let changed = |line: &Line| !matches!(line, Line::Same(_));
+    if !lines.iter().any(changed) {
+        return None;
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    // Line numbers (1-based) in old and new before each line
+    let mut positions = Vec::with_capacity(lines.len());
+    let (mut old_line, mut new_line) = (1, 1);
+    for line in lines {
+        positions.push((old_line, new_line));
+        match line {
+            Line::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Line::Removed(_) => old_line += 1,
+            Line::Added(_) => new_line += 1,
+        }
+    }
+
+    let mut start = 0;
+    while let Some(first_change) = lines[start..].iter().position(changed) {
+        let hunk_start = (start + first_change).saturating_sub(CONTEXT);
+        // The hunk ends once more than two contexts of unchanged lines follow.
+        let mut end = start + first_change;
+        let mut last_change = end;
+        while end < lines.len() && end - last_change <= 2 * CONTEXT {
+            if changed(&lines[end]) {
+                last_change = end;
+            }
+            end += 1;
+        }
+        let hunk_end = (last_change + 1 + CONTEXT).min(lines.len());
+        let hunk = &lines[hunk_start..hunk_end];
+
+        let count = |counted: fn(&Line) -> bool| hunk.iter().filter(|l| counted(l)).count();
+        let (old_start, new_start) = positions[hunk_start];
+        out.push_str(&ui::paint_stdout(
+            &format!(
+                "@@ -{},{} +{},{} @@",
+                old_start,
+                count(|l| !matches!(l, Line::Added(_))),
+                new_start,
+                count(|l| !matches!(l, Line::Removed(_))),
+            ),
+            Role::Title,
+        ));
+        out.push('\n');
+        for line in hunk {
+            let formatted = match line {
+                Line::Same(text) => format!(" {}", text),
+                Line::Removed(text) => ui::paint_stdout(&format!("-{}", text), Role::Error),
+                Line::Added(text) => ui::paint_stdout(&format!("+{}", text), Role::Success),
+            };
+            out.push_str(&formatted);
+            out.push('\n');
+        }
+        start = hunk_end;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Length of the longest common subsequence of the lines of `old` and
+    /// `new` (the quadratic way)
+    fn common_lines(old: &str, new: &str) -> usize {
+        let old = old.lines().collect::<Vec<_>>();
+        let new = new.lines().collect::<Vec<_>>();
+        let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if old[i] == new[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        common[0][0]
+    }
+
+    proptest! {
+        #[test]
+        fn test_diff_lines_minimal(old in "[abc\n]{0,40}", new in "[abc\n]{0,40}") {
+            let lines = diff_lines(&old, &new);
+            let old_side = lines.iter().filter_map(|line| match line {
+                Line::Same(text) | Line::Removed(text) => Some(*text),
+                Line::Added(_) => None,
+            });
+            let new_side = lines.iter().filter_map(|line| match line {
+                Line::Same(text) | Line::Added(text) => Some(*text),
+                Line::Removed(_) => None,
+            });
+            prop_assert_eq!(old_side.collect::<Vec<_>>(), old.lines().collect::<Vec<_>>());
+            prop_assert_eq!(new_side.collect::<Vec<_>>(), new.lines().collect::<Vec<_>>());
+            let same = lines.iter().filter(|line| matches!(line, Line::Same(_))).count();
+            prop_assert_eq!(same, common_lines(&old, &new));
+        }
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\n";
+        assert_eq!(
+            diff_lines(old, new),
+            [
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Same("c"),
+                Line::Same("d"),
+                Line::Added("e"),
+            ]
+        );
+        assert_eq!(unified(&diff_lines(old, old), "old", "new"), None);
+        assert_eq!(
+            unified(&diff_lines(old, new), "old", "new").unwrap(),
+            "--- old\n+++ new\n@@ -1,4 +1,4 @@\n a\n-b\n c\n d\n+e\n"
+        );
+
+        let old = (1..=20).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                i => format!("{}\n", i),
+            })
+            .collect::<String>();
+        let diff = unified(&diff_lines(&old, &new), "old", "new").unwrap();
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n"));
+        assert!(diff.contains("@@ -16,5 +16,5 @@\n 16\n 17\n 18\n-19\n+nineteen\n 20\n"));
+
+        // Large inputs, with a few changes and without much in common
+        let old = (0..20_000).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = old
+            .replace("\n100\n", "\nhundred\n")
+            .replace("\n15000\n", "\n");
+        let changed = diff_lines(&old, &new)
+            .into_iter()
+            .filter(|line| !matches!(line, Line::Same(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changed,
+            [
+                Line::Removed("100"),
+                Line::Added("hundred"),
+                Line::Removed("15000")
+            ]
+        );
+        let old = (0..2_000).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = (0..2_000)
+            .map(|i| format!("{}\n", i * 7 % 2_000))
+            .collect::<String>();
+        let lines = diff_lines(&old, &new);
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| !matches!(l, Line::Added(_)))
+                .count(),
+            2_000
+        );
+    }
+}
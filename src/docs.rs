@@ -0,0 +1,300 @@
+//! Man pages (`man`) generated from the clap definitions of [`crate::cli`],
+//! extended by a reference of the config format, the exit codes and the
+//! environment variables.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Command;
+use clap_mangen::roff::{bold, roman, Roff};
+use clap_mangen::Man;
+
+use crate::ExitCode;
+
+/// Keys of the config file by the section they appear in
+const CONFIG_REFERENCE: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Top level",
+        &[
+            (
+                "includes",
+                "other config files whose sessions and windows are added",
+            ),
+            (
+                "defaults",
+                "cwd, shell_command, send_keys, wrapper, width and height used when unset",
+            ),
+            (
+                "templates",
+                "windows and panes referenced by name (template: <name>)",
+            ),
+            (
+                "selected_session",
+                "session to attach to or switch to after creating",
+            ),
+            (
+                "selected_target",
+                "session:window[.pane] to select after creating",
+            ),
+            ("on_created", "shell command run after creating the layout"),
+            ("detach_others", "detach other clients when attaching"),
+            (
+                "socket",
+                "tmux server socket name (-L) used with this config",
+            ),
+            ("sessions", "list of sessions"),
+            ("windows", "list of windows created in the current session"),
+            ("repos", "git checkouts expanded into sessions or windows"),
+        ],
+    ),
+    (
+        "Sessions",
+        &[
+            (
+                "name",
+                "session name, may contain {date:<format>} and {i} placeholders",
+            ),
+            ("count", "create this many copies of the session"),
+            ("merge", "merge with same-named sessions of included files"),
+            (
+                "attach_if_exists",
+                "select the session instead of creating it if it's running",
+            ),
+            (
+                "match_by",
+                "match running windows by name, index or name-then-index",
+            ),
+            ("when", "only create the session if these conditions hold"),
+            ("cwd", "working directory of the session"),
+            ("windows", "list of windows"),
+            (
+                "popups",
+                "floating windows opened with `tmux-layout popup <name>`",
+            ),
+            (
+                "status",
+                "status line options (enabled, position, left, right)",
+            ),
+            ("default_command", "the default-command session option"),
+            ("default_terminal", "the default-terminal session option"),
+            (
+                "raw_commands",
+                "tmux commands run as they are after creating the windows",
+            ),
+            (
+                "on_kill",
+                "shell commands run before `tmux-layout kill` kills the session",
+            ),
+        ],
+    ),
+    (
+        "Windows",
+        &[
+            ("name", "window name"),
+            (
+                "template",
+                "name of a window template providing unset fields",
+            ),
+            ("when", "only create the window if these conditions hold"),
+            ("cwd", "working directory, relative to the session's"),
+            ("active", "select this window"),
+            ("background", "create the window without selecting it"),
+            ("shell", "shell of all panes without their own"),
+            (
+                "monitor_activity, monitor_silence, monitor_bell",
+                "window monitoring options",
+            ),
+            (
+                "layouts",
+                "alternative splits for terminals at least min_width columns wide",
+            ),
+            (
+                "raw_commands",
+                "tmux commands run as they are once the window is set up",
+            ),
+            (
+                "left, right",
+                "horizontal split, sized by width, min_width and max_width",
+            ),
+            (
+                "top, bottom",
+                "vertical split, sized by height, min_height and max_height",
+            ),
+        ],
+    ),
+    (
+        "Panes",
+        &[
+            ("cwd", "working directory, relative to the window's"),
+            ("active", "select this pane"),
+            ("shell", "shell to start instead of tmux's default-shell"),
+            ("shell_command", "command run in the pane"),
+            ("keep_open", "drop to a shell when shell_command exits"),
+            (
+                "direnv",
+                "run shell_command via direnv exec (also on windows and sessions)",
+            ),
+            (
+                "wrapper",
+                "command prefix running shell_command, e.g. nix develop -c (also on windows and sessions)",
+            ),
+            ("send_keys", "keys sent to the pane after starting it"),
+            (
+                "send_keys_target",
+                "index of the pane of the window to send the keys to instead",
+            ),
+            (
+                "wait_for",
+                "send the keys once port (on host) or text (in pane) appears, or timeout",
+            ),
+            (
+                "delay",
+                "seconds to defer the shell_command and send_keys by",
+            ),
+            (
+                "restart",
+                "when supervise restarts the pane: never, always or on-failure",
+            ),
+            (
+                "read_only",
+                "disable input from clients once set up (read-only --off enables it)",
+            ),
+            (
+                "clear",
+                "clear the screen once the startup output is printed",
+            ),
+            (
+                "history_file",
+                "file with captured contents replayed when the pane starts",
+            ),
+            ("role", "label of the pane for focus and swap"),
+            ("background", "background color of the pane (select-pane -P)"),
+            (
+                "border_color",
+                "color of the pane borders of the window (pane-border-style)",
+            ),
+            ("template", "name of a pane template providing unset fields"),
+        ],
+    ),
+];
+
+/// Definition of the `Aq` string starting every roff document rendered by
+/// `roff`, kept only once when joining documents
+const ROFF_PREAMBLE: &str = ".ie \\n(.g .ds Aq \\(aq\n.el .ds Aq '\n";
+
+/// Environment variables read by tmux-layout
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("TMUX_PATH", "path of the tmux binary (default: tmux)"),
+    (
+        "TMUX",
+        "set inside tmux, where top-level windows go to the current session",
+    ),
+    (
+        "TMUX_LAYOUT_FINDER",
+        "fuzzy finder used by pick (default: fzf)",
+    ),
+    (
+        "TMUX_LAYOUT_SECRET_COMMAND",
+        "command resolving secrets in configs (default: op read)",
+    ),
+    (
+        "TMUX_LAYOUT_COLORS",
+        "colors of the output, e.g. error=magenta,highlight=cyan",
+    ),
+    ("NO_COLOR", "disables colors unless --color always is given"),
+    ("VISUAL, EDITOR", "editor used by edit"),
+];
+
+/// Man page of `app` itself, with the config reference, exit codes and
+/// environment variables.
+pub fn man_page(app: Command) -> io::Result<Vec<u8>> {
+    let man = Man::new(app);
+    let mut sections = Vec::new();
+    man.render_title(&mut sections)?;
+    man.render_name_section(&mut sections)?;
+    man.render_synopsis_section(&mut sections)?;
+    man.render_description_section(&mut sections)?;
+    man.render_options_section(&mut sections)?;
+    man.render_subcommands_section(&mut sections)?;
+    extra_sections().to_writer(&mut sections)?;
+    man.render_version_section(&mut sections)?;
+    man.render_authors_section(&mut sections)?;
+
+    let sections = String::from_utf8_lossy(&sections);
+    Ok(format!("{}{}", ROFF_PREAMBLE, sections.replace(ROFF_PREAMBLE, "")).into_bytes())
+}
+
+/// Writes the man pages of `app` (`<name>.1`) and of its subcommands
+/// (`<name>-<subcommand>.1`) to `dir`. Returns the paths of the pages.
+pub fn write_man_pages(app: Command, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut app = app.disable_help_subcommand(true);
+    app.build();
+
+    let path = dir.join(Man::new(app.clone()).get_filename());
+    std::fs::File::create(&path)?.write_all(&man_page(app.clone())?)?;
+    let mut paths = vec![path];
+    for subcommand in app.get_subcommands().filter(|s| !s.is_hide_set()) {
+        paths.push(Man::new(subcommand.clone()).generate_to(dir)?);
+    }
+    Ok(paths)
+}
+
+fn extra_sections() -> Roff {
+    let mut roff = Roff::new();
+
+    roff.control("SH", ["CONFIGURATION"]);
+    roff.text([roman(
+        "Layouts are described in YAML or TOML files (see the README for examples). \
+         Without --config, tmux-layout looks for .tmux-layout.yaml (or .yml, .toml) in \
+         the current directory, its parents and the home directory.",
+    )]);
+    for (section, keys) in CONFIG_REFERENCE {
+        roff.control("SS", [*section]);
+        for (key, description) in keys.iter() {
+            roff.control("TP", [])
+                .text([bold(*key)])
+                .text([roman(*description)]);
+        }
+    }
+
+    roff.control("SH", ["EXIT STATUS"]);
+    roff.control("TP", [])
+        .text([bold("0")])
+        .text([roman("success")]);
+    for code in ExitCode::ALL {
+        roff.control("TP", [])
+            .text([bold((code as i32).to_string())])
+            .text([roman(code.description())]);
+    }
+
+    roff.control("SH", ["ENVIRONMENT"]);
+    for (name, description) in ENVIRONMENT {
+        roff.control("TP", [])
+            .text([bold(*name)])
+            .text([roman(*description)]);
+    }
+    roff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_man_page() {
+        let page = String::from_utf8(man_page(crate::cli::app()).unwrap()).unwrap();
+        assert!(page.starts_with(ROFF_PREAMBLE));
+        assert_eq!(page.matches(ROFF_PREAMBLE).count(), 1);
+        assert!(page[ROFF_PREAMBLE.len()..].starts_with(".TH tmux-layout 1"));
+        for section in [
+            ".SH OPTIONS",
+            ".SH SUBCOMMANDS",
+            ".SH CONFIGURATION",
+            ".SH \"EXIT STATUS\"",
+        ] {
+            assert!(page.contains(section), "missing {}", section);
+        }
+        assert!(page.contains(".SS Panes\n.TP\n\\fBcwd\\fR"));
+        assert!(!page.contains(".SH EXTRA"));
+    }
+}
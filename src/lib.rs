@@ -1,19 +1,122 @@
 pub mod cli;
 pub mod config;
 pub mod cwd;
+pub mod diff;
+pub mod docs;
+pub mod logging;
+pub mod persist;
+pub mod plugin;
+pub mod preview;
+pub mod process;
+pub mod shell;
+pub mod shell_init;
 pub mod tmux;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod ui;
 
-use colored::Colorize;
+use logging::Level;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Switches to machine-readable output (see `--output json`): fatal errors
+/// are printed as `{"error": "<message>"}` on STDOUT.
+pub fn enable_json_output() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Exit codes telling scripts why tmux-layout failed (listed in `--help`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Any other error
+    Failure = 1,
+    /// Invalid command line arguments (reported by clap)
+    Usage = 2,
+    /// The config file is missing or can't be read or parsed
+    Config = 3,
+    /// The config has validation errors (see `check`)
+    Invalid = 4,
+    /// The tmux binary can't be run
+    TmuxNotFound = 5,
+    /// A tmux command failed
+    TmuxFailed = 6,
+    /// There was nothing to create or respawn (e.g. all sessions exist)
+    NothingToDo = 7,
+    /// Sessions to create already exist (`--on-conflict error`)
+    Conflict = 8,
+    /// The exported config differs from the file (`export --diff-against`)
+    Changed = 9,
+}
+
+impl ExitCode {
+    pub const ALL: [ExitCode; 9] = [
+        ExitCode::Failure,
+        ExitCode::Usage,
+        ExitCode::Config,
+        ExitCode::Invalid,
+        ExitCode::TmuxNotFound,
+        ExitCode::TmuxFailed,
+        ExitCode::NothingToDo,
+        ExitCode::Conflict,
+        ExitCode::Changed,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ExitCode::Failure => "other error",
+            ExitCode::Usage => "invalid command line arguments",
+            ExitCode::Config => "config file missing, unreadable or unparsable",
+            ExitCode::Invalid => "config has validation errors",
+            ExitCode::TmuxNotFound => "tmux can't be run",
+            ExitCode::TmuxFailed => "a tmux command failed",
+            ExitCode::NothingToDo => "nothing to do (e.g. all sessions already exist)",
+            ExitCode::Conflict => "sessions already exist (--on-conflict error)",
+            ExitCode::Changed => "the export differs from the file (export --diff-against)",
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
 
 pub fn exit_with_error(msg: &str) -> ! {
-    eprintln!("{} {}", "error:".red().bold(), msg);
-    std::process::exit(1)
+    exit_with(ExitCode::Failure, msg)
+}
+
+/// Reports the error `msg` and exits with `code`.
+pub fn exit_with(code: ExitCode, msg: &str) -> ! {
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "error": msg, "code": code as i32 })
+        );
+    } else {
+        show_error(msg);
+    }
+    code.exit()
+}
+
+/// Warns that there is nothing to do (silenced by `--quiet`) and exits with
+/// [`ExitCode::NothingToDo`].
+pub fn exit_nothing_to_do(msg: &str) -> ! {
+    show_warning(msg);
+    ExitCode::NothingToDo.exit()
+}
+
+pub fn show_error(msg: &str) {
+    logging::log(Level::Error, msg);
 }
 
 pub fn show_warning(msg: &str) {
-    eprintln!("{} {}", "warning:".yellow().bold(), msg);
+    logging::log(Level::Warning, msg);
 }
 
 pub fn show_info(msg: &str) {
-    eprintln!("{} {}", "info:".green().bold(), msg);
+    logging::log(Level::Info, msg);
 }
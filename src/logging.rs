@@ -0,0 +1,181 @@
+//! Leveled logging to STDERR and (optionally) a log file.
+//!
+//! The level is set once at startup from `-q`/`-v`/`-vv`; messages above it
+//! are dropped. The log file (`--log-file`) receives the same messages with
+//! timestamps and without colors.
+
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::shell;
+use crate::ui::{self, Role};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+    /// Every tmux command as it is executed
+    Debug,
+    /// Exit status and error output of executed commands
+    Trace,
+}
+
+impl Level {
+    /// Level for the number of `-v` flags (or `-q`).
+    pub fn from_verbosity(quiet: bool, verbose: u8) -> Level {
+        match (quiet, verbose) {
+            (true, _) => Level::Error,
+            (false, 0) => Level::Info,
+            (false, 1) => Level::Debug,
+            (false, _) => Level::Trace,
+        }
+    }
+
+    fn from_u8(level: u8) -> Level {
+        match level {
+            0 => Level::Error,
+            1 => Level::Warning,
+            2 => Level::Info,
+            3 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+/// Appends all further log messages to the file at `path`.
+pub fn set_log_file(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOG_FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+pub fn log(level: Level, msg: &str) {
+    if !enabled(level) {
+        return;
+    }
+    let msg = redact(msg);
+
+    let label = format!("{}:", level.label());
+    let label = match level {
+        Level::Error => ui::paint(&label, Role::Error),
+        Level::Warning => ui::paint(&label, Role::Warning),
+        Level::Info => ui::paint(&label, Role::Info),
+        Level::Debug | Level::Trace => ui::paint(&label, Role::Debug),
+    };
+    if level >= Level::Debug {
+        eprintln!("{} {} {}", ui::paint(&timestamp(), Role::Debug), label, msg);
+    } else {
+        eprintln!("{} {}", label, msg);
+    }
+
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        // Logging must never fail the actual operation.
+        let _ = writeln!(file, "{} {}: {}", timestamp(), level.label(), msg);
+    }
+}
+
+/// Replaces `secret` by `***` in all further log messages.
+pub fn add_secret(secret: &str) {
+    if !secret.is_empty() {
+        SECRETS.lock().unwrap().push(secret.to_string());
+    }
+}
+
+/// `msg` with all secrets (see [`add_secret`]) replaced by `***`
+pub fn redact(msg: &str) -> Cow<'_, str> {
+    let secrets = SECRETS.lock().unwrap();
+    let mut msg = Cow::Borrowed(msg);
+    for secret in secrets.iter() {
+        if msg.contains(secret.as_str()) {
+            msg = Cow::Owned(msg.replace(secret.as_str(), "***"));
+        }
+    }
+    msg
+}
+
+pub fn debug(msg: &str) {
+    log(Level::Debug, msg);
+}
+
+pub fn trace(msg: &str) {
+    log(Level::Trace, msg);
+}
+
+/// Logs `command` (shell-quoted) right before it is executed.
+pub fn command(command: &Command) {
+    if !enabled(Level::Debug) {
+        return;
+    }
+    let program = command.get_program().to_string_lossy();
+    let args = command.get_args().map(|arg| arg.to_string_lossy());
+    let words = std::iter::once(program)
+        .chain(args)
+        .map(|word| shell::quote(&word).into_owned())
+        .collect::<Vec<_>>();
+    debug(&format!("running {}", words.join(" ")));
+}
+
+/// UTC time of day with milliseconds, e.g. `13:37:00.042`
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_verbosity() {
+        assert_eq!(Level::from_verbosity(true, 2), Level::Error);
+        assert_eq!(Level::from_verbosity(false, 0), Level::Info);
+        assert_eq!(Level::from_verbosity(false, 1), Level::Debug);
+        assert_eq!(Level::from_verbosity(false, 5), Level::Trace);
+        for level in [Level::Error, Level::Warning, Level::Info, Level::Debug] {
+            assert_eq!(Level::from_u8(level as u8), level);
+        }
+    }
+}
@@ -1,176 +1,1888 @@
-use colored::*;
-use std::collections::HashSet;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::error::Error;
-use std::io::{IsTerminal, Read};
-use std::path::Path;
-use std::process::Command;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
 use tmux_layout::cli::{
-    self, ConfigFormat, CreateOpts, DumpCommandOps, DumpConfigOps, ExportOpts,
-    SessionSelectModeOption,
+    self, CheckOpts, ConfigFormat, ConflictStrategy, ConvertLayoutOpts, CreateOpts, DumpCommandOps,
+    DumpConfigOps, EditOpts, ExportOpts, FocusOpts, InitOpts, KillOpts, ManOpts, PickOpts,
+    PluginOpts, PopupOpts, PreviewOpts, ReadOnlyOpts, RebalanceOpts, RespawnOpts, RestoreOpts,
+    SaveOpts, SessionSelectModeOption, ShellInitOpts, SnapshotOpts, SuperviseOpts, SwapOpts,
+};
+use tmux_layout::config::graph;
+use tmux_layout::config::loader::{self, find_default_config_file, LoadOptions};
+use tmux_layout::config::missing_dirs::{self, MissingDirsPolicy};
+use tmux_layout::config::rewrite;
+use tmux_layout::config::secrets;
+use tmux_layout::config::sizes::{self, ClientSize};
+use tmux_layout::config::validate::{self, Issue, Severity};
+use tmux_layout::config::{
+    self, templates, Config, MatchBy, MergeStrategy, PartialConfig, RestartPolicy, Status,
 };
-use tmux_layout::config::loader::find_default_config_file;
-use tmux_layout::config::{self, Config, PartialConfig, Session};
 use tmux_layout::cwd::Cwd;
-use tmux_layout::tmux::import::TmuxState;
-use tmux_layout::tmux::{import, QueryScope};
-use tmux_layout::tmux::{SessionSelectMode, TmuxCommandBuilder};
-use tmux_layout::{exit_with_error, show_info, show_warning};
+use tmux_layout::tmux::import::{Field, Relativize, StateQuery, TmuxState};
+use tmux_layout::tmux::layout::Sizes;
+use tmux_layout::tmux::version::{self, Feature};
+use tmux_layout::tmux::{exec, import, retry, ExecutionReport, QueryScope};
+use tmux_layout::tmux::{Layout, SessionSelectMode, TmuxCommandBuilder, TmuxSocket};
+use tmux_layout::ui::{self, ColorChoice, Role};
+use tmux_layout::{diff, docs, logging, persist, plugin, preview, process, shell, shell_init};
+use tmux_layout::{exit_nothing_to_do, exit_with, exit_with_error, ExitCode};
+use tmux_layout::{json_output, show_error, show_info, show_warning};
+
+fn main() {
+    let matches = cli::app().get_matches();
+    let mut color_choice = cli::color_choice_from_matches(&matches);
+    if cli::OutputFormat::from_matches(&matches) == cli::OutputFormat::Json {
+        tmux_layout::enable_json_output();
+        // Messages end up in JSON strings on STDOUT.
+        color_choice = ColorChoice::Never;
+    }
+    if let Err(err) = ui::init(color_choice) {
+        exit_with_error(&err);
+    }
+    let log_opts = cli::LogOpts::from_matches(&matches);
+    logging::set_level(log_opts.level);
+    if let Some(log_file) = log_opts.log_file {
+        logging::set_log_file(Path::new(log_file)).unwrap_or_else(|err| {
+            exit_with_error(&format!("failed to open log file '{}': {}", log_file, err))
+        });
+    }
+    let Some(command) = cli::Subcommand::from_matches(&matches) else {
+        eprintln!("{}\n", cli::app().render_usage());
+        exit_with_error("no subcommand given");
+    };
+    match command {
+        cli::Subcommand::Create(opts) => run_create(opts),
+        cli::Subcommand::Export(opts) => run_export(opts),
+        cli::Subcommand::DumpCommand(opts) => run_dump_command(opts),
+        cli::Subcommand::DumpConfig(opts) => run_dump_config(opts),
+        cli::Subcommand::Check(opts) => run_check(opts),
+        cli::Subcommand::Save(opts) => run_save(opts),
+        cli::Subcommand::Restore(opts) => run_restore(opts),
+        cli::Subcommand::Popup(opts) => run_popup(opts),
+        cli::Subcommand::Respawn(opts) => run_respawn(opts),
+        cli::Subcommand::Supervise(opts) => run_supervise(opts),
+        cli::Subcommand::ReadOnly(opts) => run_read_only(opts),
+        cli::Subcommand::Focus(opts) => run_focus(opts),
+        cli::Subcommand::Swap(opts) => run_swap(opts),
+        cli::Subcommand::Kill(opts) => run_kill(opts),
+        cli::Subcommand::Rebalance(opts) => run_rebalance(opts),
+        cli::Subcommand::Plugin(opts) => run_plugin(opts),
+        cli::Subcommand::ShellInit(opts) => run_shell_init(opts),
+        cli::Subcommand::ConvertLayout(opts) => run_convert_layout(opts),
+        cli::Subcommand::Man(opts) => run_man(opts),
+        cli::Subcommand::Edit(opts) => run_edit(opts),
+        cli::Subcommand::Init(opts) => run_init(opts),
+        cli::Subcommand::Snapshot(opts) => run_snapshot(opts),
+        cli::Subcommand::Pick(opts) => run_pick(opts),
+        cli::Subcommand::Preview(opts) => run_preview(opts),
+    }
+}
+
+fn run_create(opts: CreateOpts) {
+    let mut config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let session_select_mode = if opts.here {
+        SessionSelectMode::Detached
+    } else {
+        get_session_select_mode(opts.session_select_mode, &env, &opts.tmux_args, true)
+    };
+    if let Some(target) = opts.select_target {
+        config.selected_target = Some(target.to_string());
+    }
+    if sizes::needs_client_size(&config) {
+        let client_size = query_client_size(&env, &opts.tmux_args);
+        if client_size.is_none() {
+            show_warning(
+                "cannot determine the terminal size, using the narrowest layouts \
+                 and ignoring size constraints",
+            );
+        }
+        sizes::apply_client_size(&mut config, client_size);
+    }
+    // Printed before resolving secrets, which must not end up in the output.
+    if opts.print_config || opts.resolve_only {
+        let format = if json_output() {
+            ConfigFormat::Json
+        } else {
+            ConfigFormat::Yaml
+        };
+        dump_config(&config, format);
+        if opts.resolve_only {
+            return;
+        }
+    }
+    secrets::resolve_secrets(&mut config).unwrap_or_else(|err| exit_with_error(&err.to_string()));
+    let tmux_version = query_tmux_version(&env);
+
+    let attaching =
+        skip_attachable_sessions(&mut config, opts.attach_if_exists, &env, &opts.tmux_args);
+    let replaced_sessions = match opts.on_conflict {
+        Some(strategy) => resolve_session_conflicts(&mut config, strategy, &env, &opts.tmux_args),
+        None => Vec::new(),
+    };
+    // Only once the conflicts are resolved, so no repos are cloned for
+    // sessions which aren't created.
+    clone_missing_repos(&config);
+    missing_dirs::handle_missing_dirs(&mut config, opts.missing_dirs)
+        .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+
+    let tmux_args = &opts.tmux_args;
+    let into_session = if opts.here {
+        build_here(&mut config, &env, tmux_args)
+    } else {
+        opts.into_session
+            .map(|name| resolve_into_session(name, &env, tmux_args))
+    };
+
+    let existing_sessions = if opts.update_existing_sessions {
+        query_existing_sessions(&env, &opts.tmux_args, "--update-existing-sessions")
+    } else {
+        HashMap::new()
+    };
+    if opts.ignore_existing_windows {
+        skip_existing_windows(
+            &mut config,
+            into_session.as_deref(),
+            &existing_sessions,
+            &env,
+            tmux_args,
+        );
+    }
+
+    let detach_others = opts.detach_others || config.detach_others;
+    // Replaced sessions are killed once the client left them.
+    let select = |builder: TmuxCommandBuilder| {
+        builder
+            .detach_others(detach_others)
+            .select_target(config.target_to_select(), session_select_mode)
+            .kill_replaced_sessions(&replaced_sessions)
+    };
+
+    if config.sessions.is_empty() && config.windows.is_empty() {
+        if !attaching {
+            exit_nothing_to_do("no sessions or windows to create");
+        }
+        if matches!(session_select_mode, SessionSelectMode::Detached) {
+            std::process::exit(0);
+        }
+        execute_command(select(env.command_builder(tmux_args)).into_command(), &env);
+    }
+
+    let current_session =
+        background_windows_session(&config, into_session.as_deref(), &env, tmux_args);
+    let builder = env
+        .command_builder(&opts.tmux_args)
+        .tmux_version(tmux_version)
+        .current_session(current_session)
+        .replace_sessions(
+            config
+                .sessions
+                .iter()
+                .filter(|s| replaced_sessions.contains(&s.name)),
+        )
+        .new_windows_into(&config.windows, into_session.as_deref())
+        .new_or_update_sessions(&config.sessions, &existing_sessions);
+
+    let on_created = opts
+        .post_cmd
+        .map(str::to_string)
+        .or(config.on_created.clone());
+    let Some(on_created) = on_created else {
+        let builder = select(builder);
+        if opts.sequential {
+            execute_sequentially(builder, &env.tmux_path);
+        }
+        execute_command(builder.into_command(), &env);
+    };
+
+    // The session is selected after running the hook, as `attach-session`
+    // only returns when the client detaches.
+    if opts.sequential {
+        if !run_sequentially(builder, &env.tmux_path) {
+            ExitCode::TmuxFailed.exit();
+        }
+    } else {
+        let exit_status = run_command(builder.into_command(), &env);
+        if !exit_status.success() {
+            ExitCode::TmuxFailed.exit();
+        }
+    }
+    run_on_created(&on_created, &config);
+
+    if matches!(session_select_mode, SessionSelectMode::Detached) {
+        let builder = env
+            .command_builder(tmux_args)
+            .kill_replaced_sessions(&replaced_sessions);
+        if builder.is_empty() {
+            std::process::exit(0);
+        }
+        execute_command(builder.into_command(), &env);
+    }
+    execute_command(select(env.command_builder(tmux_args)).into_command(), &env);
+}
+
+/// Runs the `on_created` hook (or `--post-cmd`) with the names of the created
+/// sessions (one per line) in `$TMUX_LAYOUT_SESSIONS` and the selected one in
+/// `$TMUX_LAYOUT_SELECTED_SESSION`.
+fn run_on_created(on_created: &str, config: &Config) {
+    let sessions = config
+        .sessions
+        .iter()
+        .filter(|session| !session.windows.is_empty())
+        .map(|session| session.name.as_str())
+        .collect::<Vec<_>>();
+    let selected_session = config
+        .session_to_select()
+        .or(sessions.first().copied())
+        .unwrap_or_default();
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(on_created)
+        .env("TMUX_LAYOUT_SESSIONS", sessions.join("\n"))
+        .env("TMUX_LAYOUT_SELECTED_SESSION", selected_session);
+    logging::command(&command);
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => show_warning(&format!("on_created hook exited with {}", status)),
+        Err(err) => show_warning(&format!("failed to run on_created hook: {}", err)),
+    }
+}
+
+fn run_export(opts: ExportOpts) {
+    // Exporting into a config file talks to the server of its `socket`.
+    let config_socket = opts
+        .append
+        .and_then(|path| loader::load_partial_config_at(Path::new(path)).ok())
+        .and_then(|config| config.socket);
+    let env = EnvOpts::from_env(
+        opts.socket.or(config_socket.map(TmuxSocket::Name)),
+        opts.retries,
+    );
+    let command_builder = env.command_builder(&opts.tmux_args);
+    if opts.windows.is_some()
+        && !matches!(
+            opts.scope,
+            QueryScope::CurrentSession | QueryScope::Session(_)
+        )
+    {
+        exit_with_error("--windows requires the scope to be a single session");
+    }
+    let mut query = StateQuery::new(opts.scope.clone())
+        .field(Field::PaneStyle)
+        .field(Field::PaneBorderStyle);
+    if opts.include_processes {
+        query = query.field(Field::PanePid);
+    }
+    if opts.include_alerts {
+        query = query
+            .field(Field::WindowActivityFlag)
+            .field(Field::WindowBellFlag)
+            .field(Field::WindowSilenceFlag);
+    }
+    let mut tmux_state = query.run(command_builder).unwrap_or_else(|err| {
+        exit_with(
+            ExitCode::TmuxFailed,
+            &format!("failed to query tmux state: {}", err),
+        )
+    });
+    if opts.include_processes {
+        record_processes(&mut tmux_state);
+    }
+    if let Some(names) = &opts.windows {
+        for session in tmux_state.sessions.values_mut() {
+            session
+                .windows
+                .retain(|_, window| names.contains(&window.name.as_str()));
+        }
+    }
+
+    if let Some(history_dir) = opts.history_dir {
+        capture_history(&mut tmux_state, &history_dir, &env, &opts.tmux_args);
+    }
+
+    let mut config = if let Some(names) = &opts.windows {
+        Config {
+            windows: extract_named_windows(tmux_state, names, opts.relativize, opts.sizes),
+            ..Default::default()
+        }
+    } else if opts.scope.is_window() {
+        let window = extract_window(tmux_state)
+            .unwrap_or_else(|| exit_with_error("failed to extract window"));
+
+        Config {
+            windows: vec![window.into_config_window(&Cwd::default(), opts.relativize, opts.sizes)],
+            ..Default::default()
+        }
+    } else {
+        // With a single session, `create` selects it anyway.
+        let selected_session = Some(&tmux_state)
+            .filter(|state| state.sessions.len() > 1)
+            .and_then(|state| state.selected_session())
+            .map(str::to_owned);
+        Config {
+            selected_session,
+            sessions: tmux_state.into_config_sessions(opts.sort, opts.relativize, opts.sizes),
+            ..Default::default()
+        }
+    };
+    if opts.redact {
+        config.redact(dirs::home_dir().as_deref());
+    }
+    if opts.canonical {
+        config.canonicalize();
+    }
+
+    if let Some(path) = opts.append {
+        let path = Path::new(path);
+        let count = config.sessions.len() + config.windows.len();
+        let replaced = upsert_into_config_file(path, config.sessions, config.windows);
+        show_info(&format!(
+            "added {}, replaced {} entries in {}",
+            count - replaced,
+            replaced,
+            path.display()
+        ));
+        return;
+    }
+
+    if let Some(path) = opts.diff_against {
+        diff_against_file(&config, Path::new(path), opts.format, opts.canonical);
+    }
+
+    if opts.canonical {
+        print!(
+            "{}",
+            with_trailing_newline(serialize_config(&config, opts.format))
+        );
+    } else {
+        dump_config(&config, opts.format);
+    }
+}
+
+fn run_dump_command(opts: DumpCommandOps) {
+    let mut config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let session_select_mode = if opts.here {
+        SessionSelectMode::Detached
+    } else {
+        get_session_select_mode(opts.session_select_mode, &env, &opts.tmux_args, false)
+    };
+    if let Some(target) = opts.select_target {
+        config.selected_target = Some(target.to_string());
+    }
+    if sizes::needs_client_size(&config) {
+        let client_size = query_client_size(&env, &opts.tmux_args);
+        if client_size.is_none() {
+            show_warning(
+                "cannot determine the terminal size, using the narrowest layouts \
+                 and ignoring size constraints",
+            );
+        }
+        sizes::apply_client_size(&mut config, client_size);
+    }
+    missing_dirs::handle_missing_dirs(&mut config, opts.missing_dirs)
+        .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+    let tmux_version = query_tmux_version(&env);
+
+    let attaching =
+        skip_attachable_sessions(&mut config, opts.attach_if_exists, &env, &opts.tmux_args);
+    let replaced_sessions = match opts.on_conflict {
+        Some(strategy) => resolve_session_conflicts(&mut config, strategy, &env, &opts.tmux_args),
+        None => Vec::new(),
+    };
+
+    let tmux_args = &opts.tmux_args;
+    let into_session = if opts.here {
+        build_here(&mut config, &env, tmux_args)
+    } else {
+        opts.into_session
+            .map(|name| resolve_into_session(name, &env, tmux_args))
+    };
+
+    let existing_sessions = if opts.update_existing_sessions {
+        query_existing_sessions(&env, &opts.tmux_args, "--update-existing-sessions")
+    } else {
+        HashMap::new()
+    };
+    if opts.ignore_existing_windows {
+        skip_existing_windows(
+            &mut config,
+            into_session.as_deref(),
+            &existing_sessions,
+            &env,
+            tmux_args,
+        );
+    }
+
+    if config.sessions.is_empty() && config.windows.is_empty() && !attaching {
+        show_warning("no sessions or windows to create");
+    }
+
+    let current_session =
+        background_windows_session(&config, into_session.as_deref(), &env, tmux_args);
+    let new_builder = || {
+        env.command_builder(tmux_args)
+            .tmux_version(tmux_version)
+            .current_session(current_session.clone())
+    };
+    let detach_others = opts.detach_others || config.detach_others;
+    // Replaced sessions are killed once the client left them.
+    let select = |builder: TmuxCommandBuilder| {
+        builder
+            .detach_others(detach_others)
+            .select_target(config.target_to_select(), session_select_mode)
+            .kill_replaced_sessions(&replaced_sessions)
+    };
+
+    if !opts.split_per_session {
+        let builder = new_builder()
+            .replace_sessions(
+                config
+                    .sessions
+                    .iter()
+                    .filter(|s| replaced_sessions.contains(&s.name)),
+            )
+            .new_windows_into(&config.windows, into_session.as_deref())
+            .new_or_update_sessions(&config.sessions, &existing_sessions);
+        return dump_command(select(builder).into_command());
+    }
+
+    // Replaced sessions are moved aside on the line creating their
+    // replacement and killed on the last line.
+    let windows = new_builder().new_windows_into(&config.windows, into_session.as_deref());
+    let sessions = config.sessions.iter().map(|session| {
+        let replaced = replaced_sessions.contains(&session.name).then_some(session);
+        new_builder()
+            .replace_sessions(replaced)
+            .new_or_update_sessions([session], &existing_sessions)
+    });
+    let builders = std::iter::once(windows)
+        .chain(sessions)
+        .chain([select(new_builder())]);
+    for builder in builders.filter(|builder| !builder.is_empty()) {
+        dump_command(builder.into_command());
+    }
+}
+
+fn run_dump_config(opts: DumpConfigOps) {
+    let config = load_config(opts.config_path, &opts.load);
+    dump_config(&config, opts.format)
+}
+
+fn run_check(opts: CheckOpts) {
+    let config = load_unvalidated_config(opts.config_path, &opts.load);
+    check_config(&config);
+}
+
+/// Reports all issues of `config` and exits if any of them is an error.
+fn check_config(config: &Config) {
+    let issues = validate::validate(config);
+    let valid = !issues.iter().any(Issue::is_error);
+
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "valid": valid, "issues": issues })
+        );
+    } else {
+        for issue in &issues {
+            report_issue(issue);
+        }
+        if issues.is_empty() {
+            show_info("config is valid");
+        }
+    }
+
+    if !valid {
+        ExitCode::Invalid.exit();
+    }
+}
+
+fn run_preview(opts: PreviewOpts) {
+    let mut config = load_config(opts.config_path, &opts.load);
+    let size = opts.size.or_else(terminal_size).unwrap_or(ClientSize {
+        width: 80,
+        height: 24,
+    });
+    sizes::apply_client_size(&mut config, Some(size));
+
+    let session_windows = config.sessions.iter().flat_map(|session| {
+        session
+            .windows
+            .iter()
+            .map(move |window| (Some(session), window))
+    });
+    let windows = session_windows.chain(config.windows.iter().map(|window| (None, window)));
+    for (index, (session, window)) in windows.enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let window_name = window.name.as_deref().unwrap_or("(unnamed)");
+        let (title, session_cwd, status) = match session {
+            Some(session) => (
+                format!("{}:{}", session.name, window_name),
+                session.cwd.shallow_clone(),
+                session.status.clone(),
+            ),
+            None => (window_name.to_string(), Cwd::default(), Status::default()),
+        };
+        println!("{} ({})", ui::paint_stdout(&title, Role::Title), size);
+        print!(
+            "{}",
+            preview::render_window(window, &session_cwd, size, &status)
+        );
+    }
+}
+
+fn run_init(opts: InitOpts) {
+    let config_path = if opts.global {
+        loader::global_config_path()
+            .unwrap_or_else(|| exit_with_error("cannot determine config directory"))
+    } else {
+        PathBuf::from(".tmux-layout.yaml")
+    };
+    if config_path.exists() && !opts.force {
+        exit_with_error(&format!(
+            "{} already exists (use --force to overwrite it)",
+            config_path.display()
+        ));
+    }
+
+    let cwd = env::current_dir()
+        .unwrap_or_else(|err| exit_with_error(&format!("cannot determine current dir: {}", err)));
+    // tmux doesn't allow '.' and ':' in session names
+    let session_name = cwd
+        .file_name()
+        .map(|name| name.to_string_lossy().replace(['.', ':'], "_"))
+        .unwrap_or_else(|| "main".to_string());
+
+    let contents = if opts.from_current_window {
+        let env = EnvOpts::from_env(opts.socket, opts.retries);
+        let command_builder = env.command_builder(&opts.tmux_args);
+        let tmux_state = import::query_tmux_state(command_builder, &QueryScope::CurrentWindow)
+            .unwrap_or_else(|err| {
+                exit_with(
+                    ExitCode::TmuxFailed,
+                    &format!("failed to query tmux state: {}", err),
+                )
+            });
+        let window = extract_window(tmux_state)
+            .unwrap_or_else(|| exit_with_error("failed to extract window"));
+
+        let config = Config {
+            sessions: vec![config::Session {
+                name: session_name,
+                windows: vec![window.into_config_window(
+                    &Cwd::from(cwd.clone()),
+                    Relativize::Session,
+                    Sizes::default(),
+                )],
+                cwd: cwd.into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        format!(
+            "# tmux-layout config (exported from the current tmux window)\n\n{}",
+            serialize_config(&config, ConfigFormat::Yaml)
+        )
+    } else {
+        let template = templates::find(opts.template).unwrap();
+        template.render(&session_name, &cwd.to_string_lossy())
+    };
+
+    persist::write_atomic(&config_path, contents.as_bytes()).unwrap_or_else(|err| {
+        exit_with_error(&format!(
+            "failed to write {}: {}",
+            config_path.display(),
+            err
+        ))
+    });
+    show_info(&format!("wrote {}", config_path.display()));
+}
+
+fn run_snapshot(opts: SnapshotOpts) {
+    let env = EnvOpts::from_env(opts.socket, opts.retries);
+    let command_builder = env.command_builder(&opts.tmux_args);
+    let tmux_state = import::query_tmux_state(command_builder, &opts.scope).unwrap_or_else(|err| {
+        exit_with(
+            ExitCode::TmuxFailed,
+            &format!("failed to query tmux state: {}", err),
+        )
+    });
+
+    let profile_path = loader::profile_path(opts.profile)
+        .unwrap_or_else(|| exit_with_error("cannot determine profiles directory"));
+
+    let (kind, name, replaced) = if opts.scope.is_window() {
+        let window: config::Window = extract_window(tmux_state)
+            .unwrap_or_else(|| exit_with_error("failed to extract window"))
+            .into();
+        let name = window.name.clone().unwrap_or_default();
+        let replaced = upsert_into_config_file(&profile_path, vec![], vec![window]);
+        ("window", name, replaced)
+    } else {
+        let Some(session) = Vec::<config::Session>::from(tmux_state).pop() else {
+            exit_with_error("failed to extract session")
+        };
+        let name = session.name.clone();
+        let replaced = upsert_into_config_file(&profile_path, vec![session], vec![]);
+        ("session", name, replaced)
+    };
+
+    show_info(&format!(
+        "{} {} '{}' in {}",
+        if replaced > 0 { "replaced" } else { "added" },
+        kind,
+        name,
+        profile_path.display()
+    ));
+}
+
+/// Adds `sessions` and top-level `windows` to the config file at `path`
+/// (created if missing), replacing entries of the same name. The file is
+/// updated in place if possible, so that its comments survive. Returns the
+/// number of replaced entries.
+fn upsert_into_config_file(
+    path: &Path,
+    sessions: Vec<config::Session>,
+    windows: Vec<config::Window>,
+) -> usize {
+    let mut config = if path.exists() {
+        loader::load_partial_config_at(path)
+            .unwrap_or_else(|err| exit_with_error(&format!("{}", err)))
+    } else {
+        PartialConfig::default()
+    };
+    let format = config_format_of(path);
+    let original = fs::read_to_string(path).ok();
+    let mut contents = original.clone();
+    let mut replaced = 0;
+
+    let mut rewrite = |config: &PartialConfig, key: &str, name: Option<&str>| {
+        let updated = serialize_config(config, format);
+        contents = contents.as_deref().and_then(|contents| match format {
+            ConfigFormat::Toml => rewrite::upsert_toml(contents, &updated, key, name),
+            _ => rewrite::upsert_yaml(contents, &updated, key, name),
+        });
+    };
+    // One at a time, so that each entry is rewritten in place
+    for session in sessions {
+        let name = session.name.clone();
+        let added = Config {
+            sessions: vec![session],
+            ..Default::default()
+        };
+        replaced += config.merge(added, MergeStrategy::Overwrite);
+        rewrite(&config, "sessions", Some(&name));
+    }
+    for window in windows {
+        let name = window.name.clone();
+        let added = Config {
+            windows: vec![window],
+            ..Default::default()
+        };
+        replaced += config.merge(added, MergeStrategy::Overwrite);
+        rewrite(&config, "windows", name.as_deref());
+    }
+
+    let contents = contents.unwrap_or_else(|| {
+        if original.is_some() {
+            show_warning(&format!(
+                "can't update {} in place, comments are not preserved",
+                path.display()
+            ));
+        }
+        serialize_config(&config, format)
+    });
+    persist::write_atomic(path, contents.as_bytes()).unwrap_or_else(|err| {
+        exit_with_error(&format!("failed to write {}: {}", path.display(), err))
+    });
+    replaced
+}
+
+fn run_pick(opts: PickOpts) {
+    let env = EnvOpts::from_env(opts.socket, opts.retries);
+    let session_select_mode =
+        get_session_select_mode(opts.session_select_mode, &env, &opts.tmux_args, true);
+
+    let config_path = match opts.profile {
+        Some(name) => loader::find_profile(name)
+            .unwrap_or_else(|| exit_with_error(&format!("unknown profile '{}'", name))),
+        None => {
+            let picked = pick_config_path(
+                opts.finder,
+                &opts.load,
+                opts.detach_others,
+                session_select_mode,
+                &env,
+                &opts.tmux_args,
+            );
+            match picked {
+                Some(config_path) => config_path,
+                // Selection aborted (or a running session was selected)
+                None => return,
+            }
+        }
+    };
+
+    let mut config = load_config(Some(&config_path.to_string_lossy()), &opts.load);
+    let env = env.for_config(&config);
+    let session_select_mode =
+        get_session_select_mode(opts.session_select_mode, &env, &opts.tmux_args, true);
+    if config.target_to_select().is_none() {
+        config.selected_session = config.sessions.first().map(|s| s.name.clone());
+    }
+    // Already running sessions are just selected.
+    resolve_session_conflicts(&mut config, ConflictStrategy::Skip, &env, &opts.tmux_args);
+    if config.sessions.is_empty()
+        && config.windows.is_empty()
+        && matches!(session_select_mode, SessionSelectMode::Detached)
+    {
+        return;
+    }
+
+    let current_session = background_windows_session(&config, None, &env, &opts.tmux_args);
+    let command = env
+        .command_builder(&opts.tmux_args)
+        .tmux_version(query_tmux_version(&env))
+        .current_session(current_session)
+        .new_windows(&config.windows, &Cwd::default())
+        .new_sessions(&config.sessions)
+        .detach_others(opts.detach_others || config.detach_others)
+        .select_target(config.target_to_select(), session_select_mode)
+        .into_command();
+
+    execute_command(command, &env);
+}
+
+/// Lets the user pick a profile, config file or running session in a fuzzy
+/// finder. Running sessions are selected right away; `None` if the selection
+/// was aborted.
+fn pick_config_path(
+    finder: Option<&str>,
+    load: &LoadOptions,
+    detach_others: bool,
+    session_select_mode: SessionSelectMode,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) -> Option<PathBuf> {
+    let mut running_sessions = query_existing_sessions(env, tmux_args, "pick")
+        .into_keys()
+        .collect::<Vec<_>>();
+    running_sessions.sort();
+    let profiles = loader::list_profiles();
+    let default_config = find_default_config_file(load);
+
+    // Candidates are "<kind>\t<name>" lines; the kind disambiguates e.g. a
+    // profile and a running session of the same name.
+    let mut candidates = String::new();
+    for name in &running_sessions {
+        candidates.push_str(&format!("session\t{}\n", name));
+    }
+    for (name, _) in &profiles {
+        candidates.push_str(&format!("profile\t{}\n", name));
+    }
+    if let Some(path) = &default_config {
+        candidates.push_str(&format!("config\t{}\n", path.display()));
+    }
+    if candidates.is_empty() {
+        exit_with_error("no profiles, config files or running sessions found");
+    }
+
+    let finder = finder
+        .map(str::to_string)
+        .or_else(|| env::var("TMUX_LAYOUT_FINDER").ok())
+        .unwrap_or_else(|| "fzf".to_string());
+    let Some(selection) = run_finder(&finder, &candidates) else {
+        // Selection aborted
+        return None;
+    };
+
+    let config_path = match selection.split_once('\t') {
+        Some(("session", _)) if matches!(session_select_mode, SessionSelectMode::Detached) => {
+            return None;
+        }
+        Some(("session", name)) => {
+            let command = env
+                .command_builder(tmux_args)
+                .detach_others(detach_others)
+                .select_session(Some(name), session_select_mode)
+                .into_command();
+            execute_command(command, env);
+        }
+        Some(("profile", name)) => profiles
+            .into_iter()
+            .find(|(profile, _)| profile == name)
+            .map(|(_, path)| path),
+        Some(("config", _)) => default_config,
+        _ => None,
+    };
+    let Some(config_path) = config_path else {
+        exit_with_error(&format!("invalid selection '{}'", selection))
+    };
+    Some(config_path)
+}
+
+/// Size of the tmux client (inside tmux) or of the terminal the session will
+/// be attached to.
+fn query_client_size(env: &EnvOpts, tmux_args: &[&str]) -> Option<ClientSize> {
+    let client_size = env
+        .inside_tmux
+        .then(|| {
+            let command = env
+                .command_builder(tmux_args)
+                .query_client_size()
+                .into_command();
+            ClientSize::parse(&command_stdout(command, env.retries)?)
+        })
+        .flatten();
+    let size = client_size.or_else(terminal_size);
+    logging::debug(&format!("client size: {:?}", size));
+    size
+}
+
+/// Size of the controlling terminal
+fn terminal_size() -> Option<ClientSize> {
+    let mut command = Command::new("stty");
+    command.arg("size").stdin(fs::File::open("/dev/tty").ok()?);
+    // `stty size` prints "<rows> <columns>"
+    let output = command_stdout(command, 0)?;
+    let (rows, columns) = output.trim().split_once(' ')?;
+    ClientSize::parse(&format!("{} {}", columns, rows))
+}
+
+/// STDOUT of `command` if it succeeds (retrying transient tmux errors up
+/// to `retries` times)
+fn command_stdout(mut command: Command, retries: u32) -> Option<String> {
+    logging::command(&command);
+    let output = retry::output(&mut command, retries).ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Clones the repos of the config which have a `url` but no checkout yet
+/// (see [`Config::repos_to_clone`]).
+fn clone_missing_repos(config: &Config) {
+    for repo in config.repos_to_clone() {
+        let (Some(url), Some(path)) = (&repo.url, repo.path.to_path()) else {
+            continue;
+        };
+        show_info(&format!("cloning {} into '{}'", url, path.display()));
+        let mut command = Command::new("git");
+        command.arg("clone").arg("--").arg(url).arg(path);
+        logging::command(&command);
+        let status = command
+            .status()
+            .unwrap_or_else(|err| exit_with_error(&format!("failed to run git: {}", err)));
+        if !status.success() {
+            exit_with_error(&format!("failed to clone {} ({})", url, status));
+        }
+    }
+}
+
+/// Runs the fuzzy finder `finder` (a shell command) with `candidates` on
+/// STDIN and returns the selected line (`None` if the selection was aborted).
+fn run_finder(finder: &str, candidates: &str) -> Option<String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(finder)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    logging::command(&command);
+    let mut child = command
+        .spawn()
+        .unwrap_or_else(|err| exit_with_error(&format!("failed to run {}: {}", finder, err)));
+
+    // The finder may exit before reading all candidates, so ignore write errors.
+    let _ = child.stdin.take().unwrap().write_all(candidates.as_bytes());
+
+    let output = child
+        .wait_with_output()
+        .unwrap_or_else(|err| exit_with_error(&format!("failed to run {}: {}", finder, err)));
+    match output.status.code() {
+        Some(0) => {}
+        // fzf/skim exit with 1 (no match) or 130 (interrupted)
+        Some(1) | Some(130) => return None,
+        _ => exit_with_error(&format!("{} exited with {}", finder, output.status)),
+    }
+
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.lines().next()?.trim_end();
+    (!selection.is_empty()).then(|| selection.to_string())
+}
+
+fn run_edit(opts: EditOpts) {
+    let config_path = match (opts.profile, opts.config_path) {
+        (Some(profile), _) => loader::profile_path(profile)
+            .unwrap_or_else(|| exit_with_error("cannot determine profiles directory")),
+        (None, Some(path)) => PathBuf::from(path),
+        (None, None) => find_default_config_file(&opts.load)
+            .unwrap_or_else(|| exit_with(ExitCode::Config, "no config file found")),
+    };
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir).unwrap_or_else(|err| {
+            exit_with_error(&format!("failed to create {}: {}", dir.display(), err))
+        });
+    }
+
+    if opts.tui {
+        edit_interactively(&config_path);
+    } else {
+        edit_in_editor(&config_path);
+    }
+
+    if !config_path.exists() {
+        return;
+    }
+    let config = load_file_config(&config_path, &opts.load);
+    check_config(&config);
+}
+
+fn edit_in_editor(config_path: &Path) {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Run via the shell, so that editors with arguments (e.g. "code -w") work.
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(config_path);
+    logging::command(&command);
+    let status = command
+        .status()
+        .unwrap_or_else(|err| exit_with_error(&format!("failed to run {}: {}", editor, err)));
+    if !status.success() {
+        exit_with_error(&format!("{} exited with {}", editor, status));
+    }
+}
+
+#[cfg(feature = "tui")]
+fn edit_interactively(config_path: &Path) {
+    let config = if config_path.exists() {
+        loader::load_partial_config_at(config_path)
+            .unwrap_or_else(|err| exit_with_error(&format!("{}", err)))
+    } else {
+        PartialConfig::default()
+    };
+    let format = config_format_of(config_path);
+    let mut saved = config.clone();
+    // Only the edited entries are rewritten, so that comments and shorthands
+    // elsewhere in the file survive (see `rewrite::update`).
+    let save = |config: &PartialConfig, force: bool| {
+        let contents = match fs::read_to_string(config_path) {
+            Ok(contents) => rewrite::update(
+                &contents,
+                &saved,
+                config,
+                matches!(format, ConfigFormat::Toml),
+            ),
+            Err(_) => Some(serialize_config(config, format)),
+        };
+        let contents = match contents {
+            Some(contents) => contents,
+            None if force => serialize_config(config, format),
+            None => {
+                return Err(format!(
+                    "can't update {} in place, press w again to overwrite it (losing comments)",
+                    config_path.display()
+                ))
+            }
+        };
+        persist::write_atomic(config_path, contents.as_bytes())
+            .map_err(|err| format!("failed to write {}: {}", config_path.display(), err))?;
+        saved = config.clone();
+        Ok(())
+    };
+    tmux_layout::tui::run(config, save)
+        .unwrap_or_else(|err| exit_with_error(&format!("editor failed: {}", err)));
+}
+
+#[cfg(not(feature = "tui"))]
+fn edit_interactively(_config_path: &Path) {
+    exit_with_error("tmux-layout was built without the 'tui' feature");
+}
+
+/// TOML for `.toml` files, YAML otherwise
+fn config_format_of(path: &Path) -> ConfigFormat {
+    match path.extension() {
+        Some(ext) if ext == "toml" => ConfigFormat::Toml,
+        _ => ConfigFormat::Yaml,
+    }
+}
+
+fn run_save(opts: SaveOpts) {
+    let env = EnvOpts::from_env(opts.socket, opts.retries);
+    let output = opts
+        .output
+        .map(PathBuf::from)
+        .or_else(persist::default_autosave_path)
+        .unwrap_or_else(|| exit_with_error("can't determine snapshot location, use --output"));
+
+    loop {
+        match save_snapshot(&env, &opts.tmux_args, &output, opts.keep, opts.canonical) {
+            Ok(true) => show_info(&format!("saved snapshot to '{}'", output.display())),
+            Ok(false) => {}
+            Err(err) if opts.once => exit_with_error(&err),
+            Err(err) => show_warning(&err),
+        }
+
+        if opts.once {
+            break;
+        }
+        std::thread::sleep(opts.interval);
+    }
+}
+
+/// Exports all sessions into `output` (`canonical`ly, see
+/// [`Config::canonicalize`]). Returns `false` if nothing changed since the
+/// last snapshot.
+fn save_snapshot(
+    env: &EnvOpts,
+    tmux_args: &[&str],
+    output: &Path,
+    keep: usize,
+    canonical: bool,
+) -> Result<bool, String> {
+    let command_builder = env.command_builder(tmux_args);
+    let tmux_state = import::query_tmux_state(command_builder, &QueryScope::AllSessions)
+        .map_err(|err| format!("failed to query tmux state: {}", err))?;
+
+    let mut config = Config {
+        sessions: tmux_state.into(),
+        ..Default::default()
+    };
+    let contents = if canonical {
+        config.canonicalize();
+        with_trailing_newline(serialize_config(&config, ConfigFormat::Yaml))
+    } else {
+        serialize_config(&config, ConfigFormat::Yaml)
+    };
+
+    persist::write_with_backups(output, contents.as_bytes(), keep)
+        .map_err(|err| format!("failed to write '{}': {}", output.display(), err))
+}
+
+fn run_restore(opts: RestoreOpts) {
+    let snapshot_path = match opts.from {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let autosave_path = persist::default_autosave_path().unwrap_or_else(|| {
+                exit_with_error("can't determine snapshot location, use --from")
+            });
+            let Some(newest) = persist::versions(&autosave_path).into_iter().next() else {
+                exit_with_error(&format!(
+                    "no snapshot found at '{}'",
+                    autosave_path.display()
+                ))
+            };
+            newest
+        }
+    };
+    show_info(&format!("restoring from '{}'", snapshot_path.display()));
+
+    // Restoring is creating from the snapshot, skipping sessions still running.
+    run_create(CreateOpts {
+        config_path: Some(&snapshot_path.to_string_lossy()),
+        // The snapshot is loaded from its path and includes no other files.
+        load: LoadOptions::default(),
+        into_session: None,
+        here: false,
+        socket: opts.socket,
+        retries: opts.retries,
+        session_select_mode: opts.session_select_mode,
+        detach_others: opts.detach_others,
+        on_conflict: Some(ConflictStrategy::Skip),
+        update_existing_sessions: false,
+        ignore_existing_windows: false,
+        attach_if_exists: false,
+        missing_dirs: MissingDirsPolicy::default(),
+        select_target: None,
+        sequential: false,
+        print_config: false,
+        resolve_only: false,
+        post_cmd: None,
+        tmux_args: opts.tmux_args,
+    })
+}
+
+fn run_popup(opts: PopupOpts) {
+    let config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let name = opts.name;
+    let Some((popup, cwd)) = config.popups().find(|(popup, _)| popup.name == name) else {
+        exit_with_error(&format!(
+            "no popup named '{}' in config",
+            ui::paint(name, Role::Highlight)
+        ))
+    };
+
+    if let Some(version) = query_tmux_version(&env) {
+        let feature = if popup.border {
+            Feature::Popups
+        } else {
+            Feature::BorderlessPopups
+        };
+        if !version.supports(feature) {
+            exit_with_error(&format!(
+                "popup '{}' requires tmux >= {} (found {})",
+                popup.name,
+                feature.min_version(),
+                version
+            ));
+        }
+    }
+
+    let command = env
+        .command_builder(&opts.tmux_args)
+        .display_popup(popup, &cwd)
+        .into_command();
+
+    execute_command(command, &env);
+}
+
+/// Respawns the configured panes with a `shell_command` or `send_keys`
+/// (see [`match_configured_panes`]).
+fn run_respawn(opts: RespawnOpts) {
+    let tmux_args = &opts.tmux_args;
+    let (env, config) = load_config_for_running(
+        opts.config_path,
+        &opts.load,
+        opts.socket,
+        opts.retries,
+        tmux_args,
+    );
+    let tmux_state = query_all_sessions(&env, tmux_args);
+
+    let mut builder = env.command_builder(tmux_args);
+    let mut respawned = 0;
+    for matched in match_configured_panes(&config, &tmux_state, &env, tmux_args, opts.window) {
+        let pane = matched.pane;
+        if pane.shell_command.is_none() && pane.send_keys.is_none() {
+            continue;
+        }
+        builder = builder.respawn_pane(
+            &matched.id,
+            &matched.keys_target,
+            matched.keys_target_read_only,
+            pane,
+            &matched.cwd,
+        );
+        respawned += 1;
+    }
+
+    if respawned == 0 {
+        exit_nothing_to_do("no panes with a shell_command or send_keys to respawn");
+    }
+    execute_command(builder.into_command(), &env);
+}
+
+/// Restarts the configured panes whose process exited according to their
+/// restart policy (`restart`, or `--restart` if unset), polling tmux until
+/// none of them is left.
+fn run_supervise(opts: SuperviseOpts) {
+    let tmux_args = &opts.tmux_args;
+    let (env, config) = load_config_for_running(
+        opts.config_path,
+        &opts.load,
+        opts.socket,
+        opts.retries,
+        tmux_args,
+    );
+    let tmux_state = query_all_sessions(&env, tmux_args);
+
+    let default_policy = opts.restart;
+    let mut supervised = match_configured_panes(&config, &tmux_state, &env, tmux_args, None)
+        .into_iter()
+        .map(|matched| (matched.pane.restart.unwrap_or(default_policy), matched))
+        .filter(|(policy, _)| *policy != RestartPolicy::Never)
+        .collect::<Vec<_>>();
+    if supervised.is_empty() {
+        exit_nothing_to_do("no panes to supervise (set `restart` or use --restart)");
+    }
+
+    // Exited panes stay open, so their exit status can be read.
+    let builder = supervised
+        .iter()
+        .fold(env.command_builder(tmux_args), |builder, (_, matched)| {
+            builder.remain_on_exit(&matched.id)
+        });
+    if !run_command(builder.into_command(), &env).success() {
+        ExitCode::TmuxFailed.exit();
+    }
+    show_info(&format!("supervising {} panes", supervised.len()));
+
+    let query = StateQuery::new(QueryScope::AllSessions).field(Field::PaneDeadStatus);
+    loop {
+        std::thread::sleep(opts.interval);
+        let tmux_state = match query.run(env.command_builder(tmux_args)) {
+            Ok(tmux_state) => tmux_state,
+            Err(import::Error::NoServer) => exit_with(ExitCode::TmuxFailed, "tmux server exited"),
+            Err(err) => {
+                show_warning(&format!("failed to query tmux state: {}", err));
+                continue;
+            }
+        };
+        let dead_statuses = tmux_state
+            .sessions
+            .values()
+            .flat_map(|session| session.windows.values())
+            .flat_map(|window| window.panes.values())
+            .map(|pane| (pane.id.to_string(), pane.dead_status))
+            .collect::<HashMap<_, _>>();
+
+        supervised.retain(|(_, matched)| {
+            let open = dead_statuses.contains_key(&matched.id);
+            if !open {
+                show_info(&format!("pane {} was closed", matched.location));
+            }
+            open
+        });
+        if supervised.is_empty() {
+            show_info("no supervised panes left");
+            return;
+        }
+
+        let mut builder = env.command_builder(tmux_args);
+        for (policy, matched) in &supervised {
+            let Some(exit_status) = dead_statuses[&matched.id] else {
+                continue;
+            };
+            if !policy.restarts(exit_status) {
+                continue;
+            }
+            let reason = match exit_status {
+                Some(status) => format!("exited with status {}", status),
+                None => "killed by a signal".to_string(),
+            };
+            show_info(&format!(
+                "restarting pane {} ({})",
+                ui::paint(&matched.location, Role::Highlight),
+                reason
+            ));
+            builder = builder.respawn_pane(
+                &matched.id,
+                &matched.keys_target,
+                matched.keys_target_read_only,
+                matched.pane,
+                &matched.cwd,
+            );
+        }
+        if !builder.is_empty() && !run_command(builder.into_command(), &env).success() {
+            show_warning("failed to restart panes");
+        }
+    }
+}
 
-fn main() {
-    let matches = cli::app().get_matches();
-    let Some(command) = cli::Subcommand::from_matches(&matches) else {
-        eprintln!("{}\n", cli::app().render_usage());
-        exit_with_error("no subcommand given");
-    };
-    match command {
-        cli::Subcommand::Create(opts) => run_create(opts),
-        cli::Subcommand::Export(opts) => run_export(opts),
-        cli::Subcommand::DumpCommand(opts) => run_dump_command(opts),
-        cli::Subcommand::DumpConfig(opts) => run_dump_config(opts),
+/// Disables (or with `--off` enables) the input to the configured panes
+/// with `read_only`.
+fn run_read_only(opts: ReadOnlyOpts) {
+    let tmux_args = &opts.tmux_args;
+    let (env, config) = load_config_for_running(
+        opts.config_path,
+        &opts.load,
+        opts.socket,
+        opts.retries,
+        tmux_args,
+    );
+    let tmux_state = query_all_sessions(&env, tmux_args);
+
+    let mut builder = env.command_builder(tmux_args);
+    for matched in match_configured_panes(&config, &tmux_state, &env, tmux_args, opts.window) {
+        if matched.pane.read_only {
+            builder = builder.set_pane_input(&matched.id, opts.off);
+        }
     }
+
+    if builder.is_empty() {
+        exit_nothing_to_do("no running panes with read_only");
+    }
+    execute_command(builder.into_command(), &env);
 }
 
-fn run_create(opts: CreateOpts) {
-    let env = EnvOpts::from_env();
+/// Selects the configured pane with the role `opts.role` (switching the
+/// client to it inside tmux).
+fn run_focus(opts: FocusOpts) {
+    let tmux_args = &opts.tmux_args;
+    let config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let tmux_state = query_all_sessions(&env, tmux_args);
+
+    let matched = match_configured_panes(&config, &tmux_state, &env, tmux_args, opts.window);
+    let pane = find_role_pane(&matched, opts.role);
+    let command = env
+        .command_builder(tmux_args)
+        .focus_pane(&pane.id, env.inside_tmux)
+        .into_command();
+    execute_command(command, &env);
+}
 
-    let session_select_mode = get_session_select_mode(opts.session_select_mode, &env, true);
-    let mut config = load_config(opts.config_path);
+/// Swaps the configured panes with the roles `opts.roles`.
+fn run_swap(opts: SwapOpts) {
+    let tmux_args = &opts.tmux_args;
+    let config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let tmux_state = query_all_sessions(&env, tmux_args);
 
-    if opts.ignore_existing_sessions {
-        remove_existing_sessions(&mut config.sessions, &env.tmux_path);
+    let matched = match_configured_panes(&config, &tmux_state, &env, tmux_args, opts.window);
+    let [source, target] = opts.roles.map(|role| find_role_pane(&matched, role));
+    if source.id == target.id {
+        exit_nothing_to_do(&format!(
+            "roles belong to the same pane {}",
+            source.location
+        ));
     }
+    let command = env
+        .command_builder(tmux_args)
+        .swap_panes(&source.id, &target.id)
+        .into_command();
+    execute_command(command, &env);
+}
 
-    if config.sessions.is_empty() && config.windows.is_empty() {
-        show_warning("no sessions or windows to create");
-        std::process::exit(0)
+/// Kills the running sessions of the config (or those named in
+/// `opts.sessions`) after running their `on_kill` commands.
+fn run_kill(opts: KillOpts) {
+    let tmux_args = &opts.tmux_args;
+    let config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let names = &opts.sessions;
+    for name in names {
+        if !config.sessions.iter().any(|s| s.name == *name) {
+            exit_with_error(&format!(
+                "no session '{}' in the config",
+                ui::paint(name, Role::Highlight)
+            ));
+        }
     }
 
-    let command = TmuxCommandBuilder::new(&env.tmux_path, opts.tmux_args)
-        .new_windows(&config.windows, &Cwd::default())
-        .new_sessions(&config.sessions)
-        .select_session(config.selected_session.as_deref(), session_select_mode)
-        .into_command();
+    let existing_sessions = query_existing_sessions(&env, tmux_args, "kill");
+    let sessions = config
+        .sessions
+        .iter()
+        .filter(|s| names.is_empty() || names.contains(&s.name.as_str()))
+        .filter(|s| existing_sessions.contains_key(&s.name))
+        .collect::<Vec<_>>();
+    if sessions.is_empty() {
+        exit_nothing_to_do("none of the sessions is running");
+    }
 
-    execute_command(command, &env.tmux_path);
+    let command = env
+        .command_builder(tmux_args)
+        .kill_sessions(sessions)
+        .into_command();
+    execute_command(command, &env);
 }
 
-fn run_export(opts: ExportOpts) {
-    let EnvOpts { tmux_path, .. } = EnvOpts::from_env();
-    let command_builder = TmuxCommandBuilder::new(tmux_path, opts.tmux_args);
-    let tmux_state = import::query_tmux_state(command_builder, opts.scope)
-        .unwrap_or_else(|err| exit_with_error(&format!("failed to query tmux state: {}", err)));
-
-    let config = match opts.scope {
-        QueryScope::CurrentWindow => {
-            let window = extract_active_window(tmux_state)
-                .unwrap_or_else(|| exit_with_error("failed to extract active window"));
-
-            Config {
-                windows: vec![window.into()],
-                ..Default::default()
-            }
+/// The only pane of `matched` with the role `role`. Exits if there is none
+/// or the role is ambiguous.
+fn find_role_pane<'m, 'a>(matched: &'m [MatchedPane<'a>], role: &str) -> &'m MatchedPane<'a> {
+    let panes = matched
+        .iter()
+        .filter(|matched| matched.pane.role.as_deref() == Some(role))
+        .collect::<Vec<_>>();
+    match panes[..] {
+        [pane] => pane,
+        [] => exit_with_error(&format!("no running pane has the role '{}'", role)),
+        _ => {
+            let locations = panes
+                .iter()
+                .map(|pane| pane.location.as_str())
+                .collect::<Vec<_>>();
+            exit_with_error(&format!(
+                "role '{}' belongs to {} panes ({}), select the window with --window",
+                role,
+                panes.len(),
+                locations.join(", ")
+            ))
         }
-        _ => Config {
-            sessions: tmux_state.into(),
+    }
+}
+
+/// Applies the splits of the configured windows to the running ones, sized
+/// for their current size. With `--hook`, installs a `client-resized` hook
+/// running this again in the sessions of the windows.
+fn run_rebalance(opts: RebalanceOpts) {
+    let tmux_args = &opts.tmux_args;
+    let config = load_config(opts.config_path, &opts.load);
+    let env = EnvOpts::from_env(opts.socket, opts.retries).for_config(&config);
+    let tmux_state = query_all_sessions(&env, tmux_args);
+
+    let mut builder = env.command_builder(tmux_args);
+    let matched = match_configured_windows(&config, &tmux_state, &env, tmux_args, opts.window);
+    for matched in &matched {
+        let (width, height) = (matched.live.layout.width(), matched.live.layout.height());
+        // Sized as for a client of the window's size plus its status line
+        let mut sized = Config {
+            windows: vec![matched.window.clone()],
             ..Default::default()
-        },
+        };
+        sizes::apply_client_size(
+            &mut sized,
+            Some(ClientSize {
+                width,
+                height: height + 1,
+            }),
+        );
+        let root_split = &sized.windows[0].root_split;
+        let pane_count = root_split.pane_iter().count();
+        if pane_count != matched.live.panes.len() {
+            show_warning(&format!(
+                "window '{}' of session '{}' has {} panes instead of {}, not rebalancing it",
+                matched.name,
+                matched.session_name,
+                matched.live.panes.len(),
+                pane_count
+            ));
+            continue;
+        }
+        let layout = Layout::from_split(root_split, width, height);
+        builder = builder.select_layout(&matched.live.id.to_string(), &layout.to_string());
+    }
+
+    if opts.hook {
+        let config_path = match opts.config_path {
+            Some("-") => exit_with_error("--hook requires a config file, not STDIN"),
+            Some(path) => fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)),
+            None => find_default_config_file(&opts.load)
+                .unwrap_or_else(|| exit_with(ExitCode::Config, "no config file found")),
+        };
+        let program = env::current_exe()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "tmux-layout".to_string());
+        let mut command = format!(
+            "{} -q rebalance -c {}",
+            shell::quote(&program),
+            shell::quote(&config_path.to_string_lossy())
+        );
+        if let Some(window) = opts.window {
+            command = format!("{} -w {}", command, shell::quote(window));
+        }
+        let session_names = matched
+            .iter()
+            .map(|matched| matched.session_name.as_str())
+            .collect::<BTreeSet<_>>();
+        for session_name in session_names {
+            builder = builder.set_hook(session_name, "client-resized", &command);
+        }
+    }
+
+    if builder.is_empty() {
+        exit_nothing_to_do("no running windows to rebalance");
+    }
+    execute_command(builder.into_command(), &env);
+}
+
+/// Loads the config of running sessions with the sizes of the current
+/// client and resolved secrets.
+fn load_config_for_running(
+    config_path: Option<&str>,
+    load: &LoadOptions,
+    socket: Option<TmuxSocket>,
+    retries: u32,
+    tmux_args: &[&str],
+) -> (EnvOpts, Config) {
+    let mut config = load_config(config_path, load);
+    let env = EnvOpts::from_env(socket, retries).for_config(&config);
+    if sizes::needs_client_size(&config) {
+        sizes::apply_client_size(&mut config, query_client_size(&env, tmux_args));
+    }
+    secrets::resolve_secrets(&mut config).unwrap_or_else(|err| exit_with_error(&err.to_string()));
+    (env, config)
+}
+
+fn query_all_sessions(env: &EnvOpts, tmux_args: &[&str]) -> TmuxState {
+    import::query_tmux_state(env.command_builder(tmux_args), &QueryScope::AllSessions)
+        .unwrap_or_else(|err| {
+            exit_with(
+                ExitCode::TmuxFailed,
+                &format!("failed to query tmux state: {}", err),
+            )
+        })
+}
+
+/// A window of the config matched to a running window
+struct MatchedWindow<'a, 's> {
+    window: &'a config::Window,
+    live: &'s import::Window,
+    session_name: String,
+    /// Name of the window, or `#<position>` if unnamed
+    name: String,
+    cwd: Cwd<'static>,
+}
+
+/// A pane of the config matched to a running pane
+struct MatchedPane<'a> {
+    pane: &'a config::Pane,
+    /// ID of the running pane, e.g. `%3`
+    id: String,
+    /// ID of the running pane its keys are sent to (see `send_keys_target`)
+    keys_target: String,
+    /// Whether the pane its keys are sent to is `read_only`
+    keys_target_read_only: bool,
+    /// Cwd of the pane's window
+    cwd: Cwd<'static>,
+    /// `session:window.pane` for messages
+    location: String,
+}
+
+/// Matches the running windows of the configured sessions (according to their
+/// `match_by`) and their panes (by position) to the config. Only
+/// windows named `window_filter` are matched if given. Panes sending keys to
+/// other panes come after the other panes of their window.
+fn match_configured_windows<'a, 's>(
+    config: &'a Config,
+    tmux_state: &'s TmuxState,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+    window_filter: Option<&str>,
+) -> Vec<MatchedWindow<'a, 's>> {
+    // Top-level windows were created in the session that was current then.
+    let current_session = if config.windows.is_empty() {
+        None
+    } else {
+        query_current_session(env, tmux_args)
+            .map_err(|err| show_warning(&format!("skipping top-level windows: {}", err)))
+            .ok()
     };
+    let default_cwd = Cwd::default();
+    let sessions = config
+        .sessions
+        .iter()
+        .map(|session| {
+            let match_by = session.match_by.unwrap_or_default();
+            (
+                session.name.as_str(),
+                &session.cwd,
+                &session.windows,
+                match_by,
+            )
+        })
+        .chain(
+            current_session
+                .as_deref()
+                .map(|name| (name, &default_cwd, &config.windows, MatchBy::default())),
+        );
+
+    let mut matched = Vec::new();
+    for (session_name, session_cwd, windows, match_by) in sessions {
+        let Some(live_session) = tmux_state
+            .sessions
+            .values()
+            .find(|s| s.name == session_name)
+        else {
+            show_warning(&format!("session '{}' isn't running", session_name));
+            continue;
+        };
+        let mut live_windows = live_session.windows.values().collect::<Vec<_>>();
+        live_windows.sort_by_key(|w| w.index);
+        let live_names = live_windows.iter().map(|w| &w.name).collect::<Vec<_>>();
+        let matches = match_by.match_windows(windows, &live_names);
 
-    dump_config(&config, opts.format);
+        for (position, (window, live_position)) in windows.iter().zip(matches).enumerate() {
+            if window_filter.is_some() && window.name.as_deref() != window_filter {
+                continue;
+            }
+            let window_name = window
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("#{}", position));
+            let Some(live_window) = live_position.map(|p| live_windows[p]) else {
+                show_warning(&format!(
+                    "window '{}' of session '{}' isn't running",
+                    window_name, session_name
+                ));
+                continue;
+            };
+            matched.push(MatchedWindow {
+                window,
+                live: live_window,
+                session_name: session_name.to_string(),
+                name: window_name,
+                cwd: session_cwd.joined(&window.cwd).into_owned(),
+            });
+        }
+    }
+    matched
 }
 
-fn run_dump_command(opts: DumpCommandOps) {
-    let env = EnvOpts::from_env();
-    let session_select_mode = get_session_select_mode(opts.session_select_mode, &env, false);
-    let mut config = load_config(opts.config_path);
+fn match_configured_panes<'a>(
+    config: &'a Config,
+    tmux_state: &TmuxState,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+    window_filter: Option<&str>,
+) -> Vec<MatchedPane<'a>> {
+    let mut matched = Vec::new();
+    for window in match_configured_windows(config, tmux_state, env, tmux_args, window_filter) {
+        let mut live_panes = window.live.panes.values().collect::<Vec<_>>();
+        live_panes.sort_by_key(|p| p.index);
+        let panes = window.window.root_split.pane_iter().collect::<Vec<_>>();
+        if live_panes.len() != panes.len() {
+            show_warning(&format!(
+                "window '{}' of session '{}' has {} panes instead of {}, \
+                 matching the first ones",
+                window.name,
+                window.session_name,
+                live_panes.len(),
+                panes.len()
+            ));
+        }
 
-    if opts.ignore_existing_sessions {
-        remove_existing_sessions(&mut config.sessions, &env.tmux_path);
+        let mut window_panes = live_panes
+            .iter()
+            .zip(&panes)
+            .enumerate()
+            .map(|(index, (live_pane, &pane))| {
+                let (keys_target, keys_target_pane) = pane
+                    .send_keys_target
+                    .map(|index| index as usize)
+                    .and_then(|index| Some((live_panes.get(index)?, panes.get(index)?)))
+                    .unwrap_or((live_pane, &pane));
+                MatchedPane {
+                    pane,
+                    id: live_pane.id.to_string(),
+                    keys_target: keys_target.id.to_string(),
+                    keys_target_read_only: keys_target_pane.read_only,
+                    cwd: window.cwd.clone(),
+                    location: format!("{}:{}.{}", window.session_name, window.name, index),
+                }
+            })
+            .collect::<Vec<_>>();
+        // Keys for other panes are typed after these were (re)started.
+        window_panes.sort_by_key(|matched| matched.pane.send_keys_target.is_some());
+        matched.extend(window_panes);
     }
+    matched
+}
 
-    if config.sessions.is_empty() && config.windows.is_empty() {
-        show_warning("no sessions or windows to create");
+fn run_plugin(opts: PluginOpts) {
+    let env = EnvOpts::from_env(opts.socket, opts.retries);
+    // The absolute path keeps working if the binary isn't in tmux's $PATH.
+    let program = env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "tmux-layout".to_string());
+    let mut keys = opts.keys;
+    if opts.apply {
+        let pick_key = keys
+            .iter_mut()
+            .find(|(action, _)| *action == plugin::Action::Pick);
+        if let Some((_, key @ Some(_))) = pick_key {
+            if query_tmux_version(&env).is_some_and(|v| !v.supports(Feature::Popups)) {
+                show_warning(&format!(
+                    "not binding pick, popups require tmux >= {}",
+                    Feature::Popups.min_version()
+                ));
+                *key = None;
+            }
+        }
     }
+    let bindings = plugin::bindings(&keys, &program, &env.tmux_path);
 
-    let command = TmuxCommandBuilder::new(&env.tmux_path, opts.tmux_args)
-        .new_windows(&config.windows, &Cwd::default())
-        .new_sessions(&config.sessions)
-        .select_session(config.selected_session.as_deref(), session_select_mode)
+    if !opts.apply {
+        print!("{}", plugin::config_lines(&bindings));
+        return;
+    }
+    if bindings.is_empty() {
+        exit_nothing_to_do("no keys to bind");
+    }
+    let command = bindings
+        .iter()
+        .fold(env.command_builder(&opts.tmux_args), |builder, binding| {
+            builder.bind_key(&binding.key, &binding.command)
+        })
         .into_command();
+    execute_command(command, &env);
+}
 
-    dump_command(command)
+fn run_shell_init(opts: ShellInitOpts) {
+    let profiles_dir = loader::profiles_dir()
+        .unwrap_or_else(|| exit_with_error("cannot determine profiles directory"));
+    // The absolute path keeps working if the binary isn't in $PATH.
+    let program = env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "tmux-layout".to_string());
+    print!(
+        "{}",
+        shell_init::script(opts.shell, opts.name, &program, &profiles_dir)
+    );
 }
 
-fn run_dump_config(opts: DumpConfigOps) {
-    let config = load_config(opts.config_path);
-    dump_config(&config, opts.format)
+fn run_convert_layout(opts: ConvertLayoutOpts) {
+    let layout = Layout::parse(opts.layout.trim()).unwrap_or_else(|err| {
+        exit_with_error(&format!("invalid layout '{}': {}", opts.layout, err))
+    });
+    let window = config::Window {
+        root_split: layout.into_split(opts.sizes).into_root(),
+        ..Default::default()
+    };
+    println!("{}", serialize_config(&window, opts.format));
 }
 
-fn execute_command(mut command: Command, tmux_path: &str) -> ! {
-    let exit_status = command
-        .spawn()
-        .unwrap_or_else(|err| {
-            exit_with_error(&format!(
-                "failed to start tmux (at '{}'): {}",
-                tmux_path.yellow(),
-                err
-            ))
-        })
-        .wait()
-        .unwrap_or_else(|err| {
-            exit_with_error(&format!("failed to wait for tmux process: {}", err))
-        });
+fn run_man(opts: ManOpts) {
+    let Some(dir) = opts.dir else {
+        let page = docs::man_page(cli::app())
+            .unwrap_or_else(|err| exit_with_error(&format!("failed to render man page: {}", err)));
+        std::io::stdout().write_all(&page).unwrap();
+        return;
+    };
+    let paths = docs::write_man_pages(cli::app(), Path::new(dir))
+        .unwrap_or_else(|err| exit_with_error(&format!("failed to write man pages: {}", err)));
+    for path in paths {
+        show_info(&format!("wrote {}", path.display()));
+    }
+}
+
+fn query_tmux_version(env: &EnvOpts) -> Option<version::Version> {
+    version::query_version(Command::new(&env.tmux_path)).unwrap_or_else(|err| {
+        show_warning(&format!("failed to determine tmux version: {}", err));
+        None
+    })
+}
+
+fn execute_command(command: Command, env: &EnvOpts) -> ! {
+    if !run_command(command, env).success() {
+        ExitCode::TmuxFailed.exit();
+    }
+    std::process::exit(0)
+}
+
+fn run_command(mut command: Command, env: &EnvOpts) -> ExitStatus {
+    logging::command(&command);
+    let exit_status = retry::status(&mut command, env.retries)
+        .unwrap_or_else(|err| exit_with_tmux_error(&err, &env.tmux_path));
+    logging::trace(&format!("tmux exited with {}", exit_status));
+    exit_status
+}
+
+fn execute_sequentially(builder: TmuxCommandBuilder, tmux_path: &str) -> ! {
+    if !run_sequentially(builder, tmux_path) {
+        ExitCode::TmuxFailed.exit();
+    }
+    std::process::exit(0)
+}
+
+fn exit_with_tmux_error(err: &std::io::Error, tmux_path: &str) -> ! {
+    let code = match err.kind() {
+        std::io::ErrorKind::NotFound => ExitCode::TmuxNotFound,
+        _ => ExitCode::TmuxFailed,
+    };
+    exit_with(
+        code,
+        &format!(
+            "failed to run tmux (at '{}'): {}",
+            ui::paint(tmux_path, Role::Highlight),
+            err
+        ),
+    )
+}
+
+/// Runs the commands of `builder` one by one and prints the report. Returns
+/// whether all commands succeeded.
+fn run_sequentially(builder: TmuxCommandBuilder, tmux_path: &str) -> bool {
+    let report = exec::execute_sequentially(&builder.into_command_sequence())
+        .unwrap_or_else(|err| exit_with_tmux_error(&err, tmux_path));
+
+    if json_output() {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print_execution_report(&report);
+    }
+    report.success()
+}
 
-    std::process::exit(exit_status.code().unwrap_or(1))
+fn print_execution_report(report: &ExecutionReport) {
+    for failure in report.failures() {
+        show_error(&format!(
+            "`{}` failed: {}",
+            failure.command_line(),
+            failure.stderr.trim_end()
+        ));
+    }
+    for unit in report.units() {
+        if unit.failed == 0 {
+            show_info(&format!(
+                "{}: {}",
+                unit.unit,
+                ui::paint("ok", Role::Success)
+            ));
+        } else {
+            show_warning(&format!(
+                "{}: {} of {} commands failed",
+                unit.unit,
+                unit.failed,
+                unit.failed + unit.succeeded
+            ));
+        }
+    }
+    let failed = report.failures().count();
+    show_info(&format!(
+        "{} of {} tmux commands succeeded",
+        report.results.len() - failed,
+        report.results.len()
+    ));
+}
+
+fn load_config(config_path: Option<&str>, load: &LoadOptions) -> Config {
+    let config = load_unvalidated_config(config_path, load);
+    let issues = validate::validate(&config);
+
+    for issue in &issues {
+        report_issue(issue);
+    }
+    if issues.iter().any(Issue::is_error) {
+        exit_with(ExitCode::Invalid, "invalid config (see above)");
+    }
+
+    config
 }
 
-fn load_config(config_path: Option<&str>) -> Config {
+fn report_issue(issue: &Issue) {
+    match issue.severity {
+        Severity::Warning => show_warning(&issue.message),
+        Severity::Error => show_error(&issue.message),
+    }
+}
+
+fn load_unvalidated_config(config_path: Option<&str>, load: &LoadOptions) -> Config {
     match config_path {
         Some("-") => load_stdin_config(),
-        Some(path) => load_file_config(Path::new(path)),
+        Some(path) => load_file_config(Path::new(path), load),
         None => {
-            let Some(default_path) = find_default_config_file() else {
-                exit_with_error("no config file found")
+            let Some(default_path) = find_default_config_file(load) else {
+                exit_with(ExitCode::Config, "no config file found")
             };
             show_info(&format!(
                 "using config file at '{}'",
                 default_path.display()
             ));
-            load_file_config(&default_path)
+            load_file_config(&default_path, load)
         }
     }
 }
 
-fn load_file_config(config_path: &Path) -> Config {
-    config::loader::load_config_at(Path::new(config_path))
-        .unwrap_or_else(|err| exit_with_error(&format!("{}", err)))
+fn load_file_config(config_path: &Path, load: &LoadOptions) -> Config {
+    config::loader::load_config_at(Path::new(config_path), load)
+        .unwrap_or_else(|err| exit_with(ExitCode::Config, &format!("{}", err)))
 }
 
 fn load_stdin_config() -> Config {
-    let mut config_bytes = Vec::new();
-    std::io::stdin()
-        .read_to_end(&mut config_bytes)
-        .unwrap_or_else(|err| exit_with_error(&format!("Reading from STDIN failed: {}", err)));
-
-    // Guess format
-    let partial_config: PartialConfig = if config_bytes.starts_with(b"[[") {
-        let config_str = std::str::from_utf8(&config_bytes)
-            .unwrap_or_else(|err| exit_with_parse_error(&err, "(STDIN)"));
-
-        toml::from_str(config_str).unwrap_or_else(|err| exit_with_parse_error(&err, "(STDIN)"))
-    } else {
-        let config_str = std::str::from_utf8(&config_bytes)
-            .unwrap_or_else(|err| exit_with_parse_error(&err, "(STDIN)"));
-
-        serde_yaml::from_slice(&config_bytes)
-            .or_else(|_| toml::from_str(config_str))
-            .unwrap_or_else(|err| exit_with_parse_error(&err, "(STDIN)"))
-    };
-
-    partial_config
-        .into_config()
-        .unwrap_or_else(|_| exit_with_error("config given to STDIN can't have file includes"))
+    // Relative repo paths are resolved against the current directory.
+    let base_dir = std::env::current_dir().unwrap_or_default();
+    loader::load_config_from_reader(std::io::stdin(), &base_dir)
+        .unwrap_or_else(|err| exit_with(ExitCode::Config, &format!("(STDIN): {}", err)))
 }
 
 fn dump_command(command: Command) {
@@ -179,30 +1891,178 @@ fn dump_command(command: Command) {
 
 fn dump_config(config: &Config, format: ConfigFormat) {
     match format {
-        ConfigFormat::Yaml => println!("{}", serde_yaml::to_string(config).unwrap()),
-        ConfigFormat::Toml => {
-            let toml_str = toml::to_string(config).unwrap_or_else(|err| {
-                show_warning("emitting TOML is unstable. Try using the YAML format instead.");
-                exit_with_error(&format!("failed to emit TOML: {}", err));
+        ConfigFormat::Graph(graph_format) => print!("{}", graph::to_graph(config, graph_format)),
+        _ => println!("{}", serialize_config(config, format)),
+    }
+}
+
+fn serialize_config(config: &impl Serialize, format: ConfigFormat) -> String {
+    match format {
+        ConfigFormat::Yaml => serde_yaml::to_string(config).unwrap(),
+        ConfigFormat::Json => serde_json::to_string_pretty(config).unwrap(),
+        ConfigFormat::Toml => toml::to_string(config).unwrap_or_else(|err| {
+            show_warning("emitting TOML is unstable. Try using the YAML format instead.");
+            exit_with_error(&format!("failed to emit TOML: {}", err));
+        }),
+        ConfigFormat::Graph(_) => unreachable!("graphs can only be dumped"),
+    }
+}
+
+/// Prints the differences between the config in the file at `path` and
+/// `config` (both serialized as `format`, the file's config canonicalized
+/// like `config` if `canonical`) and exits with [`ExitCode::Changed`], or
+/// with success if there are none.
+fn diff_against_file(config: &Config, path: &Path, format: ConfigFormat, canonical: bool) -> ! {
+    let mut existing = loader::load_partial_config_at(path)
+        .unwrap_or_else(|err| exit_with_parse_error(&err, &path.to_string_lossy()));
+    if canonical {
+        existing.canonicalize();
+    }
+    let existing = serialize_config(&existing, format);
+    let exported = serialize_config(config, format);
+    let lines = diff::diff_lines(&existing, &exported);
+    let changes = diff::unified(&lines, &path.to_string_lossy(), "tmux");
+    if json_output() {
+        // Uncolored, as colors are disabled for JSON output
+        let diff = changes.as_deref().unwrap_or_default();
+        println!(
+            "{}",
+            serde_json::json!({ "changed": changes.is_some(), "diff": diff })
+        );
+    } else if let Some(changes) = &changes {
+        print!("{}", changes);
+    }
+    if changes.is_some() {
+        ExitCode::Changed.exit()
+    }
+    std::process::exit(0)
+}
+
+/// `contents` ending with exactly one newline
+fn with_trailing_newline(contents: String) -> String {
+    format!("{}\n", contents.trim_end_matches('\n'))
+}
+
+/// Looks up the foreground command of every pane (with a PID).
+fn record_processes(tmux_state: &mut TmuxState) {
+    let windows = tmux_state
+        .sessions
+        .values_mut()
+        .flat_map(|session| session.windows.values_mut());
+    for pane in windows.flat_map(|window| window.panes.values_mut()) {
+        pane.command_line = pane.pid.and_then(process::foreground_command);
+    }
+}
+
+/// Captures the contents of all panes into files within `history_dir` and
+/// records the files in the state.
+fn capture_history(
+    tmux_state: &mut TmuxState,
+    history_dir: &Path,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) {
+    let history_dir = fs::create_dir_all(history_dir)
+        .and_then(|_| fs::canonicalize(history_dir))
+        .unwrap_or_else(|err| {
+            exit_with_error(&format!(
+                "failed to create history directory '{}': {}",
+                history_dir.display(),
+                err
+            ))
+        });
+
+    for session in tmux_state.sessions.values_mut() {
+        let session_name = session
+            .name
+            .replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "_");
+
+        for pane in session
+            .windows
+            .values_mut()
+            .flat_map(|w| w.panes.values_mut())
+        {
+            let contents = import::capture_pane(env.command_builder(tmux_args), pane.id)
+                .unwrap_or_else(|err| {
+                    exit_with_error(&format!("failed to capture pane {}: {}", pane.id, err))
+                });
+
+            let file_path = history_dir.join(format!(
+                "{}-{}.txt",
+                session_name,
+                pane.id.to_string().trim_start_matches('%')
+            ));
+            fs::write(&file_path, contents).unwrap_or_else(|err| {
+                exit_with_error(&format!(
+                    "failed to write '{}': {}",
+                    file_path.display(),
+                    err
+                ))
             });
-            println!("{}", toml_str);
+            pane.history_file = Some(file_path.to_string_lossy().into_owned());
+        }
+    }
+}
+
+/// Extracts the windows `names` (in this order) of a state queried with a
+/// single-session scope. Windows sharing a name are all extracted (in the
+/// order of their indexes).
+fn extract_named_windows(
+    tmux_state: TmuxState,
+    names: &[&str],
+    relativize: Relativize,
+    sizes: Sizes,
+) -> Vec<config::Window> {
+    let mut windows = tmux_state
+        .sessions
+        .into_values()
+        .flat_map(|session| session.windows.into_values())
+        .collect::<Vec<_>>();
+    windows.sort_by_key(|window| window.index);
+
+    let mut extracted = Vec::new();
+    for name in names {
+        let (named, rest): (Vec<_>, _) =
+            windows.into_iter().partition(|window| window.name == *name);
+        windows = rest;
+        if named.is_empty() {
+            exit_with_error(&format!(
+                "no window named '{}'",
+                ui::paint(name, Role::Highlight)
+            ));
+        }
+        if named.len() > 1 {
+            show_warning(&format!(
+                "{} windows are named '{}'",
+                named.len(),
+                ui::paint(name, Role::Highlight)
+            ));
         }
+        extracted.extend(named);
     }
+
+    extracted
+        .into_iter()
+        .map(|window| window.into_config_window(&Cwd::default(), relativize, sizes))
+        .collect()
 }
 
-fn extract_active_window(tmux_state: TmuxState) -> Option<import::Window> {
+/// Extracts the window of a state queried with a window scope (which
+/// contains a single window only).
+fn extract_window(tmux_state: TmuxState) -> Option<import::Window> {
     tmux_state
         .sessions
         .into_values()
         .next()?
         .windows
         .into_values()
-        .find(|w| w.active)
+        .next()
 }
 
 fn get_session_select_mode(
     opt: SessionSelectModeOption,
     env: &EnvOpts,
+    tmux_args: &[&str],
     allow_overwrite: bool,
 ) -> SessionSelectMode {
     let is_terminal = std::io::stdin().is_terminal();
@@ -210,6 +2070,13 @@ fn get_session_select_mode(
     match opt {
         SessionSelectModeOption::Switch => SessionSelectMode::Switch,
         SessionSelectModeOption::Detached => SessionSelectMode::Detached,
+        SessionSelectModeOption::Attach if env.inside_tmux => {
+            show_warning(
+                "Using 'switch' mode instead of 'attach' mode because tmux refuses \
+                 to nest a client inside a session of the same server.",
+            );
+            SessionSelectMode::Switch
+        }
         SessionSelectModeOption::Attach => {
             if is_terminal || !allow_overwrite {
                 SessionSelectMode::Attach
@@ -223,7 +2090,7 @@ fn get_session_select_mode(
             }
         }
         SessionSelectModeOption::Auto => {
-            if has_tmux_clients(&env.tmux_path) {
+            if env.inside_tmux || has_tmux_clients(env, tmux_args) {
                 SessionSelectMode::Switch
             } else if is_terminal {
                 SessionSelectMode::Attach
@@ -234,8 +2101,84 @@ fn get_session_select_mode(
     }
 }
 
-fn has_tmux_clients(tmux_path: &str) -> bool {
-    match Command::new(tmux_path).arg("list-clients").output() {
+/// Moves all windows of `config` (including those of its sessions) to the
+/// top level for `--here` and returns the session to create them in.
+fn build_here(config: &mut Config, env: &EnvOpts, tmux_args: &[&str]) -> Option<String> {
+    if !env.inside_tmux {
+        exit_with_error("--here requires running inside tmux");
+    }
+
+    for session in config.sessions.drain(..) {
+        let session_cwd = session.cwd;
+        config
+            .windows
+            .extend(session.windows.into_iter().map(|mut window| {
+                window.cwd = session_cwd.joined(&window.cwd).into_owned();
+                window
+            }));
+    }
+    config.selected_session = None;
+    config.selected_target = None;
+
+    Some(resolve_into_session(None, env, tmux_args))
+}
+
+/// Resolves the session given by `--into-session` (`None`: the session of
+/// the current client).
+fn resolve_into_session(name: Option<&str>, env: &EnvOpts, tmux_args: &[&str]) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+    if !env.inside_tmux {
+        exit_with_error("not running inside tmux, pass a session name to --into-session");
+    }
+
+    query_current_session(env, tmux_args).unwrap_or_else(|err| exit_with_error(&err))
+}
+
+/// The session top-level windows are created in if they include background
+/// windows, which are targeted relative to the current window of the session
+/// by its name (see `Window::background`).
+fn background_windows_session(
+    config: &Config,
+    into_session: Option<&str>,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) -> Option<String> {
+    if into_session.is_some() || !config.windows.iter().any(|window| window.background) {
+        return None;
+    }
+    query_current_session(env, tmux_args)
+        .map_err(|err| show_warning(&err))
+        .ok()
+}
+
+fn query_current_session(env: &EnvOpts, tmux_args: &[&str]) -> Result<String, String> {
+    let mut command = env
+        .command_builder(tmux_args)
+        .query_current_session()
+        .into_command();
+    logging::command(&command);
+    match retry::output(&mut command, env.retries) {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string()),
+        Ok(output) => Err(format!(
+            "failed to determine current session: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )),
+        Err(err) => Err(format!("failed to determine current session: {}", err)),
+    }
+}
+
+fn has_tmux_clients(env: &EnvOpts, tmux_args: &[&str]) -> bool {
+    let mut command = env
+        .command_builder(tmux_args)
+        .query_clients()
+        .into_command();
+
+    logging::command(&command);
+    match retry::output(&mut command, env.retries) {
         Err(_) => {
             show_warning("Error while listing tmux clients");
             false
@@ -244,44 +2187,230 @@ fn has_tmux_clients(tmux_path: &str) -> bool {
     }
 }
 
-fn remove_existing_sessions(sessions: &mut Vec<Session>, tmux_path: &str) {
-    let builder = TmuxCommandBuilder::new(tmux_path, std::iter::empty::<String>());
-    let tmux_state =
-        import::query_tmux_state(builder, QueryScope::AllSessions).unwrap_or_else(|err| {
-            exit_with_error(&format!(
-                "failed to query tmux state (needed for --ignore-existing-sessions): {}",
-                err
-            ))
-        });
+/// Applies the conflict strategy to configured sessions whose name is already
+/// taken by a running session. Returns the running sessions to be replaced.
+fn resolve_session_conflicts(
+    config: &mut Config,
+    strategy: ConflictStrategy,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) -> Vec<String> {
+    let existing_sessions = query_existing_sessions(env, tmux_args, "--on-conflict");
+    let conflicting_sessions = config
+        .sessions
+        .iter()
+        .map(|s| s.name.clone())
+        .filter(|name| existing_sessions.contains_key(name))
+        .collect::<Vec<_>>();
+
+    match strategy {
+        ConflictStrategy::Skip => {
+            config
+                .sessions
+                .retain(|s| !existing_sessions.contains_key(&s.name));
+            Vec::new()
+        }
+        ConflictStrategy::Error => {
+            if !conflicting_sessions.is_empty() {
+                exit_with(
+                    ExitCode::Conflict,
+                    &format!(
+                        "sessions already exist: {}",
+                        ui::paint(&conflicting_sessions.join(", "), Role::Highlight)
+                    ),
+                );
+            }
+            Vec::new()
+        }
+        ConflictStrategy::Rename => {
+            let mut taken_names = config
+                .sessions
+                .iter()
+                .map(|s| s.name.clone())
+                .chain(existing_sessions.into_keys())
+                .collect::<HashSet<_>>();
+
+            for session in &mut config.sessions {
+                if !conflicting_sessions.contains(&session.name) {
+                    continue;
+                }
+                let new_name = (2..)
+                    .map(|i| format!("{}-{}", session.name, i))
+                    .find(|name| !taken_names.contains(name))
+                    .unwrap();
+
+                show_info(&format!(
+                    "creating session '{}' as '{}'",
+                    session.name, new_name
+                ));
+                if config.selected_session.as_ref() == Some(&session.name) {
+                    config.selected_session = Some(new_name.clone());
+                }
+                if let Some(target) = &mut config.selected_target {
+                    if let Some(window_pane) = target.strip_prefix(&format!("{}:", session.name)) {
+                        *target = format!("{}:{}", new_name, window_pane);
+                    }
+                }
+                taken_names.insert(new_name.clone());
+                session.name = new_name;
+            }
+            Vec::new()
+        }
+        ConflictStrategy::Replace => conflicting_sessions,
+    }
+}
+
+/// Drops the sessions to select instead of create as they are running
+/// already (see [`Config::skip_attachable_sessions`]). Returns whether there
+/// were any.
+fn skip_attachable_sessions(
+    config: &mut Config,
+    all: bool,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) -> bool {
+    if !all && !config.sessions.iter().any(|s| s.attach_if_exists) {
+        return false;
+    }
+    let existing_sessions = query_existing_sessions(env, tmux_args, "--attach-if-exists");
+    let attached =
+        config.skip_attachable_sessions(all, |name| existing_sessions.contains_key(name));
+    for name in &attached {
+        show_info(&format!(
+            "session '{}' exists already, selecting it",
+            ui::paint(name, Role::Highlight)
+        ));
+    }
+    !attached.is_empty()
+}
+
+/// Drops the top-level windows whose name exists in the session they are
+/// created in: `into_session`, or else the current session.
+fn skip_existing_windows(
+    config: &mut Config,
+    into_session: Option<&str>,
+    existing_sessions: &HashMap<String, Vec<String>>,
+    env: &EnvOpts,
+    tmux_args: &[&str],
+) {
+    if config.windows.is_empty() {
+        return;
+    }
+    let session = match into_session {
+        Some(session) => session.to_string(),
+        None if env.inside_tmux => match query_current_session(env, tmux_args) {
+            Ok(session) => session,
+            Err(err) => return show_warning(&err),
+        },
+        None => return,
+    };
+    let Some(existing_window_names) = existing_sessions.get(&session) else {
+        return;
+    };
+    config.windows.retain(|window| {
+        window
+            .name
+            .as_ref()
+            .is_none_or(|name| !existing_window_names.contains(name))
+    });
+}
 
-    let existing_sessions = tmux_state
+/// Queries the names of all running sessions along with their window names
+/// (ordered by window index).
+fn query_existing_sessions(
+    env: &EnvOpts,
+    tmux_args: &[&str],
+    needed_for: &str,
+) -> HashMap<String, Vec<String>> {
+    let builder = env.command_builder(tmux_args);
+    let tmux_state = match import::query_tmux_state(builder, &QueryScope::AllSessions) {
+        Ok(tmux_state) => tmux_state,
+        Err(import::Error::NoServer) => return HashMap::new(),
+        Err(err) => exit_with(
+            ExitCode::TmuxFailed,
+            &format!(
+                "failed to query tmux state (needed for {}): {}",
+                needed_for, err
+            ),
+        ),
+    };
+
+    tmux_state
         .sessions
         .into_values()
-        .map(|s| s.name)
-        .collect::<HashSet<_>>();
-
-    sessions.retain(|s| !existing_sessions.contains(&s.name));
+        .map(|s| {
+            let mut windows = s.windows.into_values().collect::<Vec<_>>();
+            windows.sort_by_key(|w| w.index);
+            (s.name, windows.into_iter().map(|w| w.name).collect())
+        })
+        .collect()
 }
 
 fn exit_with_parse_error(err: &dyn Error, config_path: &str) -> ! {
-    exit_with_error(&format!(
-        "Parsing config file '{}' failed: {}",
-        config_path.yellow(),
-        err
-    ))
+    exit_with(
+        ExitCode::Config,
+        &format!(
+            "Parsing config file '{}' failed: {}",
+            ui::paint(config_path, Role::Highlight),
+            err
+        ),
+    )
 }
 
 #[derive(Debug)]
 struct EnvOpts {
     tmux_path: String,
+    socket: Option<TmuxSocket>,
+    /// Whether we're running inside a tmux session of the selected server
+    inside_tmux: bool,
+    /// Retries of tmux invocations failing for transient reasons (see
+    /// `retry`)
+    retries: u32,
 }
 
 impl EnvOpts {
-    fn from_env() -> Self {
+    fn from_env(socket: Option<TmuxSocket>, retries: u32) -> Self {
         // Allow overriding path of tmux executable
         let tmux_path = env::var("TMUX_PATH");
         let tmux_path = tmux_path.unwrap_or_else(|_| "tmux".to_string());
 
-        Self { tmux_path }
+        // $TMUX holds the socket path of the server we're running in, which
+        // tmux talks to unless another socket is given.
+        let inside_tmux = match env::var("TMUX") {
+            Ok(tmux) if !tmux.is_empty() => socket
+                .as_ref()
+                .is_none_or(|socket| socket.matches_tmux_env(&tmux)),
+            _ => false,
+        };
+
+        Self {
+            tmux_path,
+            socket,
+            inside_tmux,
+            retries,
+        }
+    }
+
+    /// Talks to the server of the config's `socket` unless a socket was given
+    /// on the command line.
+    fn for_config(self, config: &Config) -> Self {
+        match (&self.socket, &config.socket) {
+            (None, Some(name)) => {
+                Self::from_env(Some(TmuxSocket::Name(name.clone())), self.retries)
+            }
+            _ => self,
+        }
+    }
+
+    fn socket_args(&self) -> impl Iterator<Item = &OsStr> {
+        self.socket.iter().flat_map(TmuxSocket::args)
+    }
+
+    /// Creates a command builder talking to the selected tmux server.
+    fn command_builder(&self, tmux_args: &[impl AsRef<OsStr>]) -> TmuxCommandBuilder {
+        let args = self
+            .socket_args()
+            .chain(tmux_args.iter().map(AsRef::as_ref));
+        TmuxCommandBuilder::new(&self.tmux_path, args).retries(self.retries)
     }
 }
@@ -0,0 +1,141 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Default location of periodic snapshots (see the `save` subcommand).
+pub fn default_autosave_path() -> Option<PathBuf> {
+    let state_dir = dirs::state_dir().or_else(|| Some(dirs::home_dir()?.join(".local/state")))?;
+    Some(state_dir.join("tmux-layout").join("autosave.yaml"))
+}
+
+/// Writes `contents` to `path` by writing a temporary file in the same
+/// directory first and renaming it afterwards, so readers never observe a
+/// partially written file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = file_name.to_owned();
+    tmp_name.push(format!(".tmp{}", std::process::id()));
+    let tmp_path = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Writes `contents` to `path`, keeping up to `keep` previous versions as
+/// `<path>.1` (newest) to `<path>.<keep>` (oldest).
+///
+/// Returns `false` without touching any file if `path` already has the
+/// given contents.
+pub fn write_with_backups(path: &Path, contents: &[u8], keep: usize) -> io::Result<bool> {
+    match fs::read(path) {
+        Ok(existing) if existing == contents => return Ok(false),
+        Ok(_) => rotate_backups(path, keep)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    write_atomic(path, contents)?;
+    Ok(true)
+}
+
+/// Returns `path` followed by its existing backups, newest first.
+pub fn versions(path: &Path) -> Vec<PathBuf> {
+    let mut versions = Vec::new();
+    if path.exists() {
+        versions.push(path.to_owned());
+    }
+    versions.extend(
+        (1..)
+            .map(|i| backup_path(path, i))
+            .take_while(|backup| backup.exists()),
+    );
+    versions
+}
+
+fn rotate_backups(path: &Path, keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    remove_if_exists(&backup_path(path, keep))?;
+    for i in (1..keep).rev() {
+        let backup = backup_path(path, i);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, i + 1))?;
+        }
+    }
+    // The live file stays in place until `write_atomic` replaces it, so a
+    // failed write never leaves `path` missing.
+    let backup = backup_path(path, 1);
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}", index));
+    backup.into()
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_with_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autosave.yaml");
+
+        for contents in ["a", "b", "b", "c", "d"] {
+            write_with_backups(&path, contents.as_bytes(), 2).unwrap();
+        }
+
+        let versions = versions(&path);
+        let contents = versions
+            .iter()
+            .map(|p| fs::read_to_string(p).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["d", "c", "b"]);
+        assert!(!backup_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_rotate_backups_keeps_live_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autosave.yaml");
+        write_atomic(&path, b"a").unwrap();
+
+        rotate_backups(&path, 1).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "a");
+
+        write_atomic(&path, b"b").unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "a");
+    }
+}
@@ -0,0 +1,150 @@
+//! tmux plugin glue (`plugin`): key bindings running tmux-layout from inside
+//! tmux. `tmux-layout.tmux` in the repository root is the entry point for
+//! TPM, which reads the keys from `@tmux-layout-*` options and applies the
+//! bindings with `plugin --apply`.
+
+use crate::shell;
+
+/// What a key binding does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Save a snapshot of all sessions (`save --once`)
+    Save,
+    /// Recreate the sessions of the newest snapshot (`restore`)
+    Restore,
+    /// Prompt for a profile and export the current session into it
+    /// (`snapshot`)
+    Snapshot,
+    /// Pick a layout in a popup (`pick`, requires tmux >= 3.2)
+    Pick,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Save,
+        Action::Restore,
+        Action::Snapshot,
+        Action::Pick,
+    ];
+
+    /// Key bound in the prefix table unless configured otherwise
+    pub fn default_key(self) -> Option<&'static str> {
+        match self {
+            Action::Save => Some("L"),
+            Action::Restore => Some("R"),
+            Action::Snapshot | Action::Pick => None,
+        }
+    }
+}
+
+/// A key of the prefix table and the tmux command (as arguments) it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub key: String,
+    pub command: Vec<String>,
+}
+
+/// Bindings of `keys` (the key of each action, `None` for unbound) running
+/// `program`. `tmux` is the path of the tmux binary used for messages (which
+/// finds the server via `$TMUX` in `run-shell`).
+pub fn bindings(keys: &[(Action, Option<&str>)], program: &str, tmux: &str) -> Vec<Binding> {
+    let program = shell::quote(program);
+    let display = |message: &str| {
+        format!(
+            "{} display-message {}",
+            shell::quote(tmux),
+            shell::quote(message)
+        )
+    };
+    keys.iter()
+        .filter_map(|(action, key)| Some((*action, (*key)?)))
+        .map(|(action, key)| {
+            let command = match action {
+                Action::Save => vec![
+                    "run-shell".to_string(),
+                    format!(
+                        "{} -q save --once && {}",
+                        program,
+                        display("tmux-layout: saved snapshot")
+                    ),
+                ],
+                Action::Restore => vec!["run-shell".to_string(), format!("{} -q restore", program)],
+                Action::Snapshot => {
+                    let snapshot = format!(
+                        "{} -q snapshot %% --scope {} && {}",
+                        program,
+                        shell::quote("session=#{session_name}"),
+                        display("tmux-layout: saved profile %%")
+                    );
+                    vec![
+                        "command-prompt".to_string(),
+                        "-p".to_string(),
+                        "tmux-layout profile:".to_string(),
+                        format!("run-shell {}", shell::quote(&snapshot)),
+                    ]
+                }
+                Action::Pick => vec![
+                    "display-popup".to_string(),
+                    "-E".to_string(),
+                    format!("{} pick", program),
+                ],
+            };
+            Binding {
+                key: key.to_string(),
+                command,
+            }
+        })
+        .collect()
+}
+
+/// `bindings` as `bind-key` lines of a tmux config file
+pub fn config_lines(bindings: &[Binding]) -> String {
+    bindings
+        .iter()
+        .map(|binding| {
+            let args = std::iter::once(&binding.key)
+                .chain(&binding.command)
+                .map(|arg| shell::quote(arg))
+                .collect::<Vec<_>>();
+            format!("bind-key {}\n", args.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bindings() {
+        let keys = Action::ALL.map(|action| (action, action.default_key()));
+        let bindings = bindings(&keys, "/opt/tmux-layout", "tmux");
+        assert_eq!(
+            bindings.iter().map(|b| b.key.as_str()).collect::<Vec<_>>(),
+            ["L", "R"]
+        );
+        assert_eq!(
+            config_lines(&bindings),
+            "bind-key L run-shell '/opt/tmux-layout -q save --once && \
+             tmux display-message '\\''tmux-layout: saved snapshot'\\'''\n\
+             bind-key R run-shell '/opt/tmux-layout -q restore'\n"
+        );
+
+        let bindings = super::bindings(
+            &[(Action::Snapshot, Some("S")), (Action::Pick, Some("C-p"))],
+            "/opt/tmux-layout",
+            "tmux",
+        );
+        assert_eq!(
+            bindings[0].command[..3],
+            ["command-prompt", "-p", "tmux-layout profile:"]
+        );
+        assert!(bindings[0].command[3].starts_with(
+            "run-shell '/opt/tmux-layout -q snapshot %% --scope '\\''session=#{session_name}'\\'' && "
+        ));
+        assert_eq!(
+            bindings[1].command,
+            ["display-popup", "-E", "/opt/tmux-layout pick"]
+        );
+    }
+}
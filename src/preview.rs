@@ -0,0 +1,252 @@
+//! ASCII rendering of window layouts (`tmux-layout preview`).
+//!
+//! Panes are placed like tmux would place them on a client of the given size
+//! (see [`sizes::part_sizes`]) and drawn as boxes sharing their borders.
+
+use crate::config::sizes::{self, ClientSize};
+use crate::config::{Pane, Split, Status, Window};
+use crate::cwd::Cwd;
+
+/// Renders the panes of `window` for a client of `size` (of which the
+/// `status` line takes some rows), labeled with their index (`*` marks the
+/// active pane), command and cwd (resolved against `parent_cwd`). Sizes must
+/// be resolved already (see [`sizes::apply_client_size`]).
+pub fn render_window(
+    window: &Window,
+    parent_cwd: &Cwd,
+    size: ClientSize,
+    status: &Status,
+) -> String {
+    let (width, height) = (size.width, sizes::window_height(size, status));
+    let window_cwd = parent_cwd.joined(&window.cwd);
+    let mut canvas = Canvas::new(width as usize + 2, height as usize + 2);
+    for (index, (rect, pane)) in pane_rects(&window.root_split, width, height)
+        .into_iter()
+        .enumerate()
+    {
+        canvas.frame(rect);
+        let active = if pane.active { "*" } else { "" };
+        let command = pane.shell_command.as_deref().unwrap_or("(shell)");
+        canvas.text(rect, 0, &format!("{}{}: {}", index, active, command));
+        if let Some(cwd) = window_cwd.joined(&pane.cwd).to_path() {
+            canvas.text(rect, 1, &cwd.to_string_lossy());
+        }
+    }
+    canvas.to_string()
+}
+
+/// Area of a pane in cells (without borders). Borders are at `x` and
+/// `x + width + 1` (and `y` and `y + height + 1` respectively), so all panes
+/// of a window fit into `(width + 2) x (height + 2)` cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Areas of the panes of `split` (in tmux index order) for a window of
+/// `width` x `height` cells
+pub fn pane_rects(split: &Split, width: u32, height: u32) -> Vec<(Rect, &Pane)> {
+    let area = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    let mut panes = Vec::new();
+    place_panes(split, area, &mut panes);
+    panes
+}
+
+fn place_panes<'a>(split: &'a Split, rect: Rect, panes: &mut Vec<(Rect, &'a Pane)>) {
+    // Panes without any cells (on tiny clients) take no space, so nested
+    // ones stay within the borders of their parent.
+    if rect.width == 0 || rect.height == 0 {
+        panes.extend(split.pane_iter().map(|pane| (rect, pane)));
+        return;
+    }
+    match split {
+        Split::Pane(pane) => panes.push((rect, pane)),
+        Split::H { left, right } => {
+            let (left_width, right_width) =
+                sizes::part_sizes(left.width.as_deref(), right.width.as_deref(), rect.width);
+            let left_rect = Rect {
+                width: left_width,
+                ..rect
+            };
+            let right_rect = Rect {
+                x: rect.x + left_width + 1,
+                width: right_width,
+                ..rect
+            };
+            place_panes(&left.split, left_rect, panes);
+            place_panes(&right.split, right_rect, panes);
+        }
+        Split::V { top, bottom } => {
+            let (top_height, bottom_height) =
+                sizes::part_sizes(top.height.as_deref(), bottom.height.as_deref(), rect.height);
+            let top_rect = Rect {
+                height: top_height,
+                ..rect
+            };
+            let bottom_rect = Rect {
+                y: rect.y + top_height + 1,
+                height: bottom_height,
+                ..rect
+            };
+            place_panes(&top.split, top_rect, panes);
+            place_panes(&bottom.split, bottom_rect, panes);
+        }
+    }
+}
+
+/// Grid of characters with an outer border of one cell around the panes
+struct Canvas {
+    width: usize,
+    cells: Vec<char>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            cells: vec![' '; width * height],
+        }
+    }
+
+    /// Draws a line character, turning crossings into `+`.
+    fn put_line(&mut self, x: usize, y: usize, c: char) {
+        let cell = &mut self.cells[y * self.width + x];
+        *cell = match *cell {
+            ' ' => c,
+            existing if existing == c => c,
+            _ => '+',
+        };
+    }
+
+    /// Draws the border around the pane at `rect` (unless it has no cells).
+    fn frame(&mut self, rect: Rect) {
+        if rect.width == 0 || rect.height == 0 {
+            return;
+        }
+        let (left, top) = (rect.x as usize, rect.y as usize);
+        let (right, bottom) = (
+            left + rect.width as usize + 1,
+            top + rect.height as usize + 1,
+        );
+        for x in left + 1..right {
+            self.put_line(x, top, '-');
+            self.put_line(x, bottom, '-');
+        }
+        for y in top + 1..bottom {
+            self.put_line(left, y, '|');
+            self.put_line(right, y, '|');
+        }
+        for (x, y) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+            self.cells[y * self.width + x] = '+';
+        }
+    }
+
+    /// Writes `text` into line `line` of the pane at `rect` (truncated).
+    fn text(&mut self, rect: Rect, line: u32, text: &str) {
+        if line >= rect.height {
+            return;
+        }
+        let start = (rect.y + line + 1) as usize * self.width + rect.x as usize + 1;
+        for (i, c) in text.chars().take(rect.width as usize).enumerate() {
+            self.cells[start + i] = c;
+        }
+    }
+}
+
+impl std::fmt::Display for Canvas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.width) {
+            writeln!(f, "{}", row.iter().collect::<String>().trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_render_window() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            windows:
+              - cwd: /src
+                left:
+                  shell_command: nvim
+                  active: true
+                right:
+                  width: "12"
+                  top:
+                    cwd: logs
+                  bottom:
+                    height: "2"
+                    shell_command: cargo watch
+            "#,
+        )
+        .unwrap();
+        let size = ClientSize::parse("30x9").unwrap();
+        let rendered = render_window(
+            &config.windows[0],
+            &Cwd::default(),
+            size,
+            &Status::default(),
+        );
+
+        let expected = "\
++-----------------+------------+
+|0*: nvim         |1: (shell)  |
+|/src             |/src/logs   |
+|                 |            |
+|                 |            |
+|                 |            |
+|                 +------------+
+|                 |2: cargo wat|
+|                 |/src        |
++-----------------+------------+
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_render_window_tiny_size() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            windows:
+              - top: {}
+                bottom:
+                  top: {}
+                  bottom:
+                    top: {}
+                    bottom:
+                      left: {}
+                      right:
+                        width: "40"
+            "#,
+        )
+        .unwrap();
+        let window = &config.windows[0];
+        for size in ["20x1", "20x2", "20x3", "1x5", "2x2"] {
+            let size = ClientSize::parse(size).unwrap();
+            let rendered = render_window(window, &Cwd::default(), size, &Status::default());
+            assert_eq!(rendered.lines().count(), size.height.max(1) as usize + 1);
+
+            let height = sizes::window_height(size, &Status::default());
+            let rects = pane_rects(&window.root_split, size.width, height);
+            assert_eq!(rects.len(), 5);
+            for (rect, _) in rects {
+                assert!(rect.x + rect.width < size.width + 2);
+                assert!(rect.y + rect.height < height + 2);
+            }
+        }
+    }
+}
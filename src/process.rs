@@ -0,0 +1,75 @@
+//! Best-effort inspection of the processes running in panes (Linux only, via
+//! `/proc`), used by `export --include-processes`.
+
+use crate::shell;
+
+/// Shells whose command line isn't worth recording
+const SHELLS: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "dash", "ksh", "mksh", "tcsh", "csh", "nu", "xonsh",
+];
+
+/// Command line (shell-quoted) of the foreground process of the terminal of
+/// the pane whose first process (usually the shell) is `pane_pid`. `None` if
+/// a shell is in the foreground or the process can't be inspected.
+#[cfg(target_os = "linux")]
+pub fn foreground_command(pane_pid: u32) -> Option<String> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pane_pid)).ok()?;
+    let foreground_pid = terminal_foreground_pid(&stat)?;
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", foreground_pid)).ok()?;
+    command_line(&cmdline)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn foreground_command(_pane_pid: u32) -> Option<String> {
+    None
+}
+
+/// The foreground process group of the terminal (`tpgid`) from the contents
+/// of `/proc/<pid>/stat` (see proc(5)). The process group id is the PID of
+/// its leader.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn terminal_foreground_pid(stat: &str) -> Option<u32> {
+    // The command name (2nd field) is in parentheses and may contain spaces.
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    // state, ppid, pgrp, session, tty_nr, tpgid (-1 without a terminal)
+    let tpgid = fields.nth(5)?.parse::<u32>().ok()?;
+    Some(tpgid).filter(|pid| *pid > 0)
+}
+
+/// Joins the NUL-separated arguments of `/proc/<pid>/cmdline`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn command_line(cmdline: &[u8]) -> Option<String> {
+    let cmdline = String::from_utf8_lossy(cmdline);
+    let args = cmdline
+        .trim_end_matches('\0')
+        .split('\0')
+        .collect::<Vec<_>>();
+    // Login shells are started as `-bash`.
+    let program = args[0].rsplit('/').next()?.trim_start_matches('-');
+    if program.is_empty() || SHELLS.contains(&program) {
+        return None;
+    }
+    let args = args.into_iter().map(shell::quote).collect::<Vec<_>>();
+    Some(args.join(" "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_foreground_command() {
+        let stat = "4242 (my (shell)) S 1 4242 4242 34816 5151 4194304 0 0";
+        assert_eq!(terminal_foreground_pid(stat), Some(5151));
+        let stat = "4242 (bash) S 1 4242 4242 0 -1 4194304 0 0";
+        assert_eq!(terminal_foreground_pid(stat), None);
+
+        assert_eq!(
+            command_line(b"vim\0src/main.rs\0my notes.md\0").as_deref(),
+            Some("vim src/main.rs 'my notes.md'")
+        );
+        assert_eq!(command_line(b"-bash\0"), None);
+        assert_eq!(command_line(b"/usr/bin/fish\0-l\0"), None);
+        assert_eq!(command_line(b""), None);
+    }
+}
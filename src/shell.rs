@@ -0,0 +1,77 @@
+use std::borrow::Cow;
+
+/// Quotes `s` for use as a single word in a POSIX shell command line.
+pub fn quote(s: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(format!("'{}'", s.replace('\'', r"'\''")))
+}
+
+/// Splits the command line `s` into words like a POSIX shell, without any
+/// expansions: whitespace separates words unless quoted or escaped.
+pub fn split(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Only these are escaped within double quotes
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"set-option -w  status-style 'bg=red fg=white' "#).unwrap(),
+            ["set-option", "-w", "status-style", "bg=red fg=white"]
+        );
+        assert_eq!(
+            split(r#"bind-key x display "\"hi\" \n" '' it\'s \;"#).unwrap(),
+            ["bind-key", "x", "display", "\"hi\" \\n", "", "it's", ";"]
+        );
+        assert_eq!(split("  ").unwrap(), Vec::<String>::new());
+        assert!(split("display 'unterminated").is_err());
+        assert!(split(r#"display "unterminated"#).is_err());
+        assert!(split("display \\").is_err());
+    }
+}
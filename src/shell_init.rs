@@ -0,0 +1,94 @@
+//! Shell functions for opening profiles (`shell-init`), meant to be evaluated
+//! in the shell's startup file like the hooks of `direnv` or `zoxide`.
+
+use std::path::Path;
+
+use crate::config::loader::EXTS;
+use crate::shell;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Script defining the function `name` which runs `program pick` with its
+/// arguments (creating or attaching to the given profile, or picking one in
+/// a fuzzy finder without), completing the profiles in `profiles_dir`.
+pub fn script(shell: Shell, name: &str, program: &str, profiles_dir: &Path) -> String {
+    let program = shell::quote(program);
+    let dir = profiles_dir.to_string_lossy();
+    let dir = shell::quote(&dir);
+    match shell {
+        Shell::Bash => {
+            let globs = EXTS
+                .iter()
+                .map(|ext| format!("\"$dir\"/*.{}", ext))
+                .collect::<Vec<_>>();
+            format!(
+                r#"{name}() {{
+    {program} pick "$@"
+}}
+
+_{name}_complete() {{
+    local dir={dir} file profiles=()
+    for file in {globs}; do
+        [ -e "$file" ] && profiles+=("$(basename "${{file%.*}}")")
+    done
+    COMPREPLY=($(compgen -W "${{profiles[*]}}" -- "${{COMP_WORDS[COMP_CWORD]}}"))
+}}
+complete -F _{name}_complete {name}
+"#,
+                globs = globs.join(" ")
+            )
+        }
+        Shell::Zsh => format!(
+            r#"{name}() {{
+    {program} pick "$@"
+}}
+
+_{name}() {{
+    local dir={dir}
+    local -a profiles
+    profiles=($dir/*.({exts})(N:t:r))
+    compadd -a profiles
+}}
+(( $+functions[compdef] )) && compdef _{name} {name}
+"#,
+            exts = EXTS.join("|")
+        ),
+        Shell::Fish => format!(
+            r#"function {name} --description 'Create or attach to a tmux-layout profile'
+    {program} pick $argv
+end
+
+complete -c {name} -f -a "(for file in {dir}/*.{{{exts}}}; basename \$file | string replace -r '\.[^.]*\$' ''; end)"
+"#,
+            exts = EXTS.join(",")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let dir = Path::new("/home/me/my profiles");
+        let bash = script(Shell::Bash, "tl", "/usr/bin/tmux-layout", dir);
+        assert!(bash.starts_with("tl() {\n    /usr/bin/tmux-layout pick \"$@\"\n}"));
+        assert!(bash.contains("local dir='/home/me/my profiles' file profiles=()"));
+        assert!(bash.contains(r#"for file in "$dir"/*.yaml "$dir"/*.yml "$dir"/*.toml; do"#));
+        assert!(bash.ends_with("complete -F _tl_complete tl\n"));
+
+        let zsh = script(Shell::Zsh, "work", "tmux-layout", dir);
+        assert!(zsh.contains("profiles=($dir/*.(yaml|yml|toml)(N:t:r))"));
+        assert!(zsh.ends_with("compdef _work work\n"));
+
+        let fish = script(Shell::Fish, "tl", "tmux-layout", dir);
+        assert!(fish.starts_with("function tl "));
+        assert!(fish.contains("for file in '/home/me/my profiles'/*.{yaml,yml,toml};"));
+    }
+}
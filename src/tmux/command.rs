@@ -1,15 +1,32 @@
-use crate::config::{Pane, RootSplit, Session, Split, Window};
+use crate::config::secrets::{self, Key};
+use crate::config::sizes;
+use crate::config::{Alert, Pane, Popup, Session, Split, StatusPosition, Window};
 use crate::cwd::Cwd;
-use crate::show_warning;
+use crate::tmux::exec::CommandSequence;
+use crate::tmux::version::{Feature, Version};
+use crate::tmux::wait;
+use crate::{shell, show_warning};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::{ffi::OsStr, process::Command};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QueryScope {
     AllSessions,
     CurrentSession,
     CurrentWindow,
+    Session(String),
+    Window { session: String, window: String },
+}
+
+impl QueryScope {
+    /// Whether the scope covers a single window only
+    pub fn is_window(&self) -> bool {
+        matches!(self, QueryScope::CurrentWindow | QueryScope::Window { .. })
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -19,13 +36,65 @@ pub enum SessionSelectMode {
     Detached,
 }
 
+/// Selects the tmux server to talk to (`-L` / `-S`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmuxSocket {
+    Name(String),
+    Path(String),
+}
+
+impl TmuxSocket {
+    pub fn args(&self) -> [&OsStr; 2] {
+        match self {
+            TmuxSocket::Name(name) => ["-L".as_ref(), name.as_ref()],
+            TmuxSocket::Path(path) => ["-S".as_ref(), path.as_ref()],
+        }
+    }
+
+    /// Whether this is the socket of the server `tmux_env` (the value of
+    /// `$TMUX`: `<socket path>,<pid>,<session index>`) refers to.
+    pub fn matches_tmux_env(&self, tmux_env: &str) -> bool {
+        let socket_path = Path::new(tmux_env.split(',').next().unwrap_or_default());
+        match self {
+            // Named sockets live in a per-user directory under $TMUX_TMPDIR.
+            TmuxSocket::Name(name) => socket_path.file_name() == Some(name.as_ref()),
+            TmuxSocket::Path(path) => {
+                let path = Path::new(path);
+                path == socket_path
+                    || matches!(
+                        (path.canonicalize(), socket_path.canonicalize()),
+                        (Ok(a), Ok(b)) if a == b
+                    )
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TmuxCommandBuilder {
     command: Command,
     first_command: bool,
     current_session_name: Option<String>,
-    window_count: u32,
-    active_window_index: Option<u32>,
+    /// Name of the session tmux considers current, if known (see
+    /// [`Self::current_session`])
+    current_session: Option<String>,
+    /// Whether the active window of the windows being created was marked
+    /// (see [`Self::mark_active_window`])
+    active_window_marked: bool,
+    /// Target of the window being set up if it isn't the current one (see
+    /// `Window::background`)
+    background_window: Option<String>,
+    /// Panes of the window being set up whose keys go to another pane of
+    /// the window (see `Pane::send_keys_target`), by target pane index
+    deferred_keys: Vec<(u32, Pane)>,
+    tmux_version: Option<Version>,
+    /// Detach other clients when attaching (`attach-session -d`)
+    detach_others: bool,
+    /// Number of arguments (like `-L <socket>`) before the first command
+    global_arg_count: usize,
+    /// Retries of invocations failing for transient reasons (see
+    /// [`retry`](super::retry))
+    retries: u32,
 }
 
 impl TmuxCommandBuilder {
@@ -35,31 +104,222 @@ impl TmuxCommandBuilder {
     ) -> Self {
         let mut command = Command::new(tmux_path);
         command.args(tmux_args);
+        let global_arg_count = command.get_args().len();
 
         Self {
             command,
             first_command: true,
             current_session_name: None,
-            window_count: 0,
-            active_window_index: None,
+            current_session: None,
+            active_window_marked: false,
+            background_window: None,
+            deferred_keys: Vec::new(),
+            tmux_version: None,
+            detach_others: false,
+            global_arg_count,
+            retries: 0,
         }
     }
 
+    /// Names the session tmux considers current, so that windows created in
+    /// it are targeted via its name. Unlike the current session (`:`) of
+    /// chained commands, which follows the last created pane, the current
+    /// window of a named session isn't changed by background windows.
+    pub fn current_session(mut self, name: Option<String>) -> Self {
+        self.current_session = name;
+        self
+    }
+
+    /// Detaches other clients from the session when attaching to it (see
+    /// [`Self::select_session`]). Switching clients isn't affected.
+    pub fn detach_others(mut self, detach_others: bool) -> Self {
+        self.detach_others = detach_others;
+        self
+    }
+
+    /// Makes tmux report non-ASCII characters (and tabs) as they are instead
+    /// of replacing them with `_`, even if the locale isn't UTF-8 (`-u`).
+    /// Must be called before adding commands.
+    pub fn force_utf8(mut self) -> Self {
+        self.command.arg("-u");
+        self.global_arg_count += 1;
+        self
+    }
+
+    /// Adapts emitted flags to the given tmux version. Without a version,
+    /// the latest tmux release is assumed.
+    pub fn tmux_version(mut self, version: Option<Version>) -> Self {
+        self.tmux_version = version;
+        self
+    }
+
+    /// Retries invocations of the commands failing for transient reasons up
+    /// to `retries` times (`--retries`).
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn retry_count(&self) -> u32 {
+        self.retries
+    }
+
+    /// Whether no commands were added yet
+    pub fn is_empty(&self) -> bool {
+        self.first_command
+    }
+
     pub fn into_command(self) -> Command {
         self.command
     }
 
-    pub fn query_panes(mut self, format: impl AsRef<OsStr>, scope: QueryScope) -> Self {
+    /// Splits the chained commands for separate tmux invocations (see
+    /// [`execute_sequentially`](super::exec::execute_sequentially)).
+    pub fn into_command_sequence(self) -> CommandSequence {
+        let mut args = self.command.get_args().map(OsStr::to_os_string);
+        let global_args = args.by_ref().take(self.global_arg_count).collect();
+        let mut commands = vec![Vec::new()];
+        for arg in args {
+            if arg == ";" {
+                commands.push(Vec::new());
+            } else {
+                commands.last_mut().unwrap().push(arg);
+            }
+        }
+        commands.retain(|command| !command.is_empty());
+
+        CommandSequence {
+            program: self.command.get_program().to_os_string(),
+            global_args,
+            commands,
+            retries: self.retries,
+        }
+    }
+
+    pub fn query_panes(mut self, format: impl AsRef<OsStr>, scope: &QueryScope) -> Self {
         self.push_new_command("list-panes").push("-F").push(format);
         self.push_query_scope_arg(scope);
         self
     }
 
+    pub fn capture_pane(mut self, pane_id: impl AsRef<OsStr>) -> Self {
+        self.push_new_command("capture-pane")
+            .push_flag_arg("-t", Some(pane_id))
+            .push("-p")
+            .push("-J")
+            .push_flag_arg("-S", Some("-"));
+        self
+    }
+
+    pub fn display_popup(mut self, popup: &Popup, cwd: &Cwd) -> Self {
+        self.push_new_command("display-popup")
+            .push("-E")
+            .push_flag_arg("-d", cwd.to_path())
+            .push_flag_arg("-w", popup.width.as_deref())
+            .push_flag_arg("-h", popup.height.as_deref());
+        if !popup.border {
+            self.push("-B");
+        }
+        self.push_arg(popup.shell_command.as_deref());
+        self
+    }
+
+    /// Binds `key` in the prefix table to the tmux command given as
+    /// `command` arguments.
+    pub fn bind_key(mut self, key: &str, command: &[String]) -> Self {
+        self.push_new_command("bind-key").push(key);
+        for arg in command {
+            self.push(arg);
+        }
+        self
+    }
+
+    /// Selects the pane `pane_id` and its window, and switches the current
+    /// client to it if `switch_client`.
+    pub fn focus_pane(mut self, pane_id: &str, switch_client: bool) -> Self {
+        self.push_new_command("select-window")
+            .push_flag_arg("-t", Some(pane_id));
+        self.push_new_command("select-pane")
+            .push_flag_arg("-t", Some(pane_id));
+        if switch_client {
+            self.push_new_command("switch-client")
+                .push_flag_arg("-t", Some(pane_id));
+        }
+        self
+    }
+
+    /// Swaps the panes `source_id` and `target_id` (possibly of different
+    /// windows), keeping the active pane.
+    pub fn swap_panes(mut self, source_id: &str, target_id: &str) -> Self {
+        self.push_new_command("swap-pane")
+            .push("-d")
+            .push_flag_arg("-s", Some(source_id))
+            .push_flag_arg("-t", Some(target_id));
+        self
+    }
+
+    /// Applies the layout string `layout` to the window `window_id`.
+    pub fn select_layout(mut self, window_id: &str, layout: &str) -> Self {
+        self.push_new_command("select-layout")
+            .push_flag_arg("-t", Some(window_id))
+            .push(layout);
+        self
+    }
+
+    /// Runs the shell command `command` on `hook` of the session
+    /// `session_name`, replacing the hook's commands.
+    pub fn set_hook(mut self, session_name: &str, hook: &str, command: &str) -> Self {
+        // `run-shell` expands formats in the command.
+        let command = command.replace('#', "##");
+        self.push_new_command("set-hook")
+            .push_target_arg(Target::session(session_name))
+            .push(hook)
+            .push(format!("run-shell -b {}", shell::quote(&command)));
+        self
+    }
+
     pub fn query_clients(mut self) -> Self {
         self.push_new_command("list-clients");
         self
     }
 
+    /// Prints the name of the session of the current client.
+    pub fn query_current_session(mut self) -> Self {
+        self.push_new_command("display-message")
+            .push("-p")
+            .push("#{session_name}");
+        self
+    }
+
+    /// Prints the size of the current client as `<width> <height>`.
+    pub fn query_client_size(mut self) -> Self {
+        self.push_new_command("display-message")
+            .push("-p")
+            .push("#{client_width} #{client_height}");
+        self
+    }
+
+    /// Like [`Self::select_session`], but `target` may also name a window
+    /// (`session:window`) or pane (`session:window.pane`) to select, which
+    /// happens in any mode.
+    pub fn select_target(mut self, target: Option<&str>, mode: SessionSelectMode) -> Self {
+        let Some((session, window_pane)) = target.and_then(|target| target.split_once(':')) else {
+            return self.select_session(target, mode);
+        };
+        if !window_pane.is_empty() {
+            let (window, pane) = match window_pane.split_once('.') {
+                Some((window, pane)) => (window, Some(pane)),
+                None => (window_pane, None),
+            };
+            self.select_window(Target::session(session).window(window));
+            if let Some(pane) = pane {
+                let target = Target::session(session).window(window).pane(pane);
+                self.push_new_command("select-pane").push_target_arg(target);
+            }
+        }
+        self.select_session(Some(session), mode)
+    }
+
     pub fn select_session(mut self, name: Option<&str>, mode: SessionSelectMode) -> Self {
         let select = match mode {
             SessionSelectMode::Detached => return self,
@@ -74,6 +334,102 @@ impl TmuxCommandBuilder {
         self
     }
 
+    /// Restarts the running pane `pane_id` (e.g. `%3`) with the command of
+    /// the config `pane` (`respawn-pane -k`) and sends its keys again to
+    /// `keys_target` (the pane itself or its `send_keys_target`), enabling
+    /// its input meanwhile if it's `keys_target_read_only`. Captured history
+    /// isn't replayed.
+    pub fn respawn_pane(
+        mut self,
+        pane_id: &str,
+        keys_target: &str,
+        keys_target_read_only: bool,
+        pane: &Pane,
+        parent_cwd: &Cwd,
+    ) -> Self {
+        let pane = Pane {
+            history_file: None,
+            ..pane.clone()
+        };
+        let cwd = parent_cwd.joined(&pane.cwd);
+        self.push_new_command("respawn-pane")
+            .push("-k")
+            .push_flag_arg("-t", Some(pane_id))
+            .push_cwd_arg(&cwd)
+            .push_arg(pane_command(&pane).as_deref());
+        let wrap_input = keys_target_read_only
+            && pane.send_keys.is_some()
+            && pane.wait_for.is_none()
+            && pane.delay.is_none();
+        if wrap_input {
+            self.push_pane_input(keys_target.to_string(), true);
+        }
+        self.send_pane_keys(keys_target.to_string(), &pane, keys_target_read_only);
+        if wrap_input {
+            self.push_pane_input(keys_target.to_string(), false);
+        }
+        self
+    }
+
+    /// Enables or disables input from clients to the pane `pane_id` (see
+    /// `Pane::read_only`).
+    pub fn set_pane_input(mut self, pane_id: &str, enabled: bool) -> Self {
+        self.push_pane_input(pane_id.to_string(), enabled);
+        self
+    }
+
+    /// Keeps the pane `pane_id` open after its process exits, so `supervise`
+    /// can tell its exit status and restart it.
+    pub fn remain_on_exit(mut self, pane_id: &str) -> Self {
+        let legacy = self
+            .tmux_version
+            .is_some_and(|version| !version.supports(Feature::PaneOptions));
+        match legacy {
+            // tmux < 3.0 only has the window option
+            true => self.push_new_command("set-window-option"),
+            false => self.push_new_command("set-option").push("-p"),
+        }
+        .push_flag_arg("-t", Some(pane_id))
+        .push("remain-on-exit")
+        .push("on");
+        self
+    }
+
+    /// Kills the running `sessions`, each after running its `on_kill`
+    /// commands (waiting for them to finish).
+    pub fn kill_sessions<'a>(mut self, sessions: impl IntoIterator<Item = &'a Session>) -> Self {
+        for session in sessions {
+            self.push_on_kill_commands(session);
+            self.push_new_command("kill-session")
+                .push_target_arg(Target::session(&session.name));
+        }
+        self
+    }
+
+    /// Moves the running `sessions` aside for the sessions of the same name
+    /// replacing them: runs their `on_kill` commands and renames them (see
+    /// [`replaced_session_name`]). They are only killed once the
+    /// replacements are selected (see [`Self::kill_replaced_sessions`]), so
+    /// the client isn't detached if it's attached to one of them.
+    pub fn replace_sessions<'a>(mut self, sessions: impl IntoIterator<Item = &'a Session>) -> Self {
+        for session in sessions {
+            self.push_on_kill_commands(session);
+            self.push_new_command("rename-session")
+                .push_target_arg(Target::session(&session.name))
+                .push(replaced_session_name(&session.name));
+        }
+        self
+    }
+
+    /// Kills the sessions `names` moved aside by [`Self::replace_sessions`].
+    pub fn kill_replaced_sessions(mut self, names: &[String]) -> Self {
+        for name in names {
+            self.push_new_command("kill-session")
+                .push_target_arg(Target::session(replaced_session_name(name)));
+        }
+        self
+    }
+
     pub fn new_sessions<'a>(self, sessions: impl IntoIterator<Item = &'a Session>) -> Self {
         sessions
             .into_iter()
@@ -91,9 +447,98 @@ impl TmuxCommandBuilder {
             .push_flag_arg("-s", Some(&session.name))
             .push_cwd_arg(&session.cwd)
             .push("-d");
+        self.apply_session_options(session);
+
+        let mut builder = self
+            .create_initial_window(&session.windows[0], &session.cwd)
+            .new_windows(&session.windows[1..], &session.cwd);
+        builder.push_raw_commands(&session.raw_commands);
+        builder
+    }
 
-        self.create_initial_window(&session.windows[0], &session.cwd)
-            .new_windows(&session.windows[1..], &session.cwd)
+    /// Sets the session options before creating any panes, so that they
+    /// apply to all of them.
+    fn apply_session_options(&mut self, session: &Session) {
+        let status = &session.status;
+        let status_value = status.option_value();
+        let options = [
+            ("status", status_value.as_deref()),
+            (
+                "status-position",
+                status.position.map(StatusPosition::as_str),
+            ),
+            ("status-left", status.left.as_deref()),
+            ("status-right", status.right.as_deref()),
+            ("default-command", session.default_command.as_deref()),
+            ("default-terminal", session.default_terminal.as_deref()),
+        ];
+
+        for (option, value) in options {
+            if let Some(value) = value {
+                let target = self.session_target();
+                self.push_new_command("set-option")
+                    .push_target_arg(target)
+                    .push(option)
+                    .push(value);
+            }
+        }
+    }
+
+    /// Creates the given sessions, except for those already running (keyed by
+    /// name in `existing_sessions`), which are updated via [`Self::update_session`].
+    pub fn new_or_update_sessions<'a>(
+        self,
+        sessions: impl IntoIterator<Item = &'a Session>,
+        existing_sessions: &HashMap<String, Vec<String>>,
+    ) -> Self {
+        sessions.into_iter().fold(self, |b, session| {
+            match existing_sessions.get(&session.name) {
+                Some(existing_window_names) => b.update_session(session, existing_window_names),
+                None => b.new_session(session),
+            }
+        })
+    }
+
+    /// Appends the windows of `session` which are missing from the already
+    /// running tmux session of the same name (with the names of its windows
+    /// ordered by index). Windows are matched according to the session's
+    /// `match_by`; existing windows are left untouched.
+    pub fn update_session(self, session: &Session, existing_window_names: &[String]) -> Self {
+        let match_by = session.match_by.unwrap_or_default();
+        let matches = match_by.match_windows(&session.windows, existing_window_names);
+        let missing_windows = session
+            .windows
+            .iter()
+            .zip(matches)
+            .filter(|(_, matched)| matched.is_none())
+            .map(|(window, _)| window)
+            .collect::<Vec<_>>();
+
+        self.append_windows(missing_windows, &session.name, &session.cwd)
+    }
+
+    /// Appends `windows` after the last window of the existing session
+    /// `session_name` (instead of the session tmux considers current).
+    pub fn append_windows<'a>(
+        mut self,
+        windows: impl IntoIterator<Item = &'a Window>,
+        session_name: &str,
+        parent_cwd: &Cwd,
+    ) -> Self {
+        let mut windows = windows.into_iter().peekable();
+        if windows.peek().is_none() {
+            return self;
+        }
+
+        self.current_session_name = Some(session_name.to_string());
+        self.active_window_marked = false;
+
+        let mut builder = windows.fold(self, |b, window| {
+            b.new_window_at(window, parent_cwd, WindowPlacement::Append)
+        });
+
+        builder.select_active_window();
+        builder
     }
 
     pub fn new_windows<'a>(
@@ -101,125 +546,266 @@ impl TmuxCommandBuilder {
         windows: impl IntoIterator<Item = &'a Window>,
         parent_cwd: &Cwd,
     ) -> Self {
-        let mut builder = windows
-            .into_iter()
-            .fold(self, |b, win| b.new_window(win, parent_cwd, None));
+        if self.current_session_name.is_none() {
+            return self.new_windows_after_current(windows.into_iter().collect(), parent_cwd);
+        }
+
+        let mut builder = windows.into_iter().fold(self, |b, win| {
+            b.new_window_at(win, parent_cwd, WindowPlacement::Default)
+        });
 
         builder.select_active_window();
         builder
     }
 
+    /// Creates `windows` in the current session right after its current
+    /// window (whatever windows the session had already).
+    fn new_windows_after_current(mut self, windows: Vec<&Window>, parent_cwd: &Cwd) -> Self {
+        self.current_session_name = self.current_session.clone();
+        self.active_window_marked = false;
+        // Background windows don't become the current window, so they are
+        // created right before the next window in the foreground, or after
+        // the current window (in reverse order) if there is none.
+        let mut background_windows = Vec::new();
+        for window in windows.iter().copied() {
+            if window.background {
+                background_windows.push(window);
+                continue;
+            }
+            self = self.new_window_at(window, parent_cwd, WindowPlacement::AfterCurrent);
+            for window in background_windows.drain(..) {
+                self = self.new_window_at(window, parent_cwd, WindowPlacement::BeforeCurrent);
+            }
+        }
+        for window in background_windows.into_iter().rev() {
+            self = self.new_window_at(window, parent_cwd, WindowPlacement::AfterCurrent);
+        }
+
+        self.select_active_window();
+        self.current_session_name = None;
+        self
+    }
+
+    /// Creates the top-level `windows` of a config: in the current session
+    /// or, if given, appended to the existing session `session_name`.
+    pub fn new_windows_into<'a>(
+        self,
+        windows: impl IntoIterator<Item = &'a Window>,
+        session_name: Option<&str>,
+    ) -> Self {
+        match session_name {
+            Some(session_name) => self.append_windows(windows, session_name, &Cwd::default()),
+            None => self.new_windows(windows, &Cwd::default()),
+        }
+    }
+
     pub fn new_window(
-        mut self,
+        self,
         window: &Window,
         parent_cwd: &Cwd,
         before_target: Option<&str>,
     ) -> Self {
-        if window.active {
-            if self.active_window_index.is_none() {
-                self.active_window_index = Some(self.window_count);
-            } else {
-                let session_name = self.current_session_name.as_deref().unwrap_or("(current)");
-                show_warning(&format!(
-                    "Multiple active windows in session '{}'",
-                    session_name
-                ));
-            }
-        }
-        self.window_count += 1;
+        let placement = match before_target {
+            Some(target) => WindowPlacement::Before(target),
+            None => WindowPlacement::Default,
+        };
+        self.new_window_at(window, parent_cwd, placement)
+    }
 
+    fn new_window_at(
+        mut self,
+        window: &Window,
+        parent_cwd: &Cwd,
+        placement: WindowPlacement,
+    ) -> Self {
+        // Conflicting active flags are reported by `config::validate`.
+        let active = window.active && !window.background && !self.active_window_marked;
+
+        // The window is created with its first pane (the one all splits are
+        // created on) already set up.
         let window_cwd = parent_cwd.joined(&window.cwd);
+        let first_pane = root_pane(&window.root_split);
+        let first_pane_cwd = window_cwd.joined(&first_pane.cwd);
         self.push_new_command("new-window")
             .push_flag_arg("-n", window.name.as_deref())
-            .push_cwd_arg(&window_cwd);
+            .push_cwd_arg(&first_pane_cwd);
+        if window.background {
+            self.push("-d");
+        }
 
-        if let Some(before_target) = before_target {
-            let target = self.session_target().window(before_target);
-            self.push("-b").push_target_arg(target);
-        } else {
-            self.push_target_arg(self.session_target());
+        // Background windows are set up via their index (or their offset
+        // from the current window) instead of as the current window.
+        let window_target = match placement {
+            WindowPlacement::Default => {
+                // No window indexes are skipped, not even with `base-index`
+                // or `renumber-windows` (see `WindowPlacement::Initial`).
+                self.push_target_arg(self.session_target());
+                "{end}".to_string()
+            }
+            WindowPlacement::Initial => {
+                let target = self.session_target().window("{start}");
+                self.push("-b").push_target_arg(target);
+                "{start}".to_string()
+            }
+            WindowPlacement::Before(before_target) => {
+                let target = self.session_target().window(before_target);
+                self.push("-b").push_target_arg(target);
+                before_target.to_string()
+            }
+            WindowPlacement::Append => {
+                let target = self.session_target().window("{end}");
+                self.push("-a").push_target_arg(target);
+                "{end}".to_string()
+            }
+            WindowPlacement::AfterCurrent => {
+                let target = self.session_target().current_window();
+                self.push("-a").push_target_arg(target);
+                "+1".to_string()
+            }
+            WindowPlacement::BeforeCurrent => {
+                let target = self.session_target().current_window();
+                self.push("-b").push_target_arg(target);
+                "-1".to_string()
+            }
+        };
+        self.push_arg(pane_command(first_pane).as_deref());
+        if let WindowPlacement::Initial = placement {
+            // The initial window of the session was moved to the end.
+            let target = self.session_target().window("{end}");
+            self.push_new_command("kill-window").push_target_arg(target);
+        }
+        if active {
+            self.mark_active_window();
+        }
+        if window.background {
+            self.background_window = Some(window_target);
         }
 
-        self.apply_root_split(&window.root_split, &window_cwd);
+        self.apply_split(&window.root_split, &window_cwd);
+        self.send_deferred_keys(window);
+        self.disable_read_only_input(window);
+        self.apply_pane_colors(window);
+        self.apply_window_options(window);
         self.select_active_pane(window);
+        self.push_raw_commands(&window.raw_commands);
+        self.background_window = None;
         self
     }
 
-    fn create_initial_window(mut self, window: &Window, parent_cwd: &Cwd) -> Self {
-        self.active_window_index = None;
-        self.window_count = 0;
-
-        // Create our first window at index 0 (pushing the intial window to index 1).
-        self = self.new_window(window, parent_cwd, Some("0"));
-
-        // Kill the initial window.
-        let target = self.session_target().window("1");
-        self.push_new_command("kill-window").push_target_arg(target);
-
-        self
+    /// Appends the tmux commands of `raw_commands` as they are (split like
+    /// by a shell). Unparsable ones are reported by `config::validate`.
+    fn push_raw_commands(&mut self, raw_commands: &[String]) {
+        for raw_command in raw_commands {
+            let words = match shell::split(raw_command) {
+                Ok(words) if !words.is_empty() => words,
+                _ => {
+                    show_warning(&format!("skipping raw command `{}`", raw_command));
+                    continue;
+                }
+            };
+            self.push_new_command(&words[0]);
+            for arg in &words[1..] {
+                self.push(arg);
+            }
+        }
     }
 
-    fn select_active_pane(&mut self, window: &Window) {
-        let active_panes = window
+    /// Sets the `background` of the panes of `window` and its border color
+    /// (of the first pane with a `border_color`).
+    fn apply_pane_colors(&mut self, window: &Window) {
+        for (index, pane) in window.root_split.pane_iter().enumerate() {
+            if let Some(background) = &pane.background {
+                let target = self.setup_window_target().pane(index.to_string());
+                self.push_new_command("select-pane")
+                    .push_target_arg(target)
+                    .push("-P")
+                    .push(format!("bg={}", background));
+            }
+        }
+        let border_color = window
             .root_split
             .pane_iter()
-            .enumerate()
-            .filter(|(_, pane)| pane.active)
-            .collect::<Vec<_>>();
-
-        if active_panes.len() > 1 {
-            let session_name = self.current_session_name.as_deref().unwrap_or("(current)");
-            show_warning(&format!(
-                "Multiple active panes in window '{}' of session '{}'",
-                window.name.as_deref().unwrap_or("(unnamed)"),
-                session_name
-            ));
+            .find_map(|pane| pane.border_color.as_ref());
+        if let Some(border_color) = border_color {
+            let target = self.setup_window_target();
+            self.push_new_command("set-window-option")
+                .push_target_arg(target)
+                .push("pane-border-style")
+                .push(format!("fg={}", border_color));
         }
+    }
 
-        if let Some(active_pane) = active_panes.first() {
-            let pane_index = active_pane.0;
-            let target = self
-                .session_target()
-                .current_window()
-                .pane(pane_index.to_string());
-
-            self.push_new_command("select-pane").push_target_arg(target);
+    fn apply_window_options(&mut self, window: &Window) {
+        let on_off = |flag: bool| if flag { "on" } else { "off" };
+        // Keeps monitoring windows which had alerts
+        let rearm = |alert: Alert| Some(true).filter(|_| window.alerts.contains(&alert));
+
+        let options = [
+            (
+                "monitor-activity",
+                window
+                    .monitor_activity
+                    .or_else(|| rearm(Alert::Activity))
+                    .map(on_off)
+                    .map(Cow::from),
+            ),
+            (
+                "monitor-silence",
+                window
+                    .monitor_silence
+                    .map(|secs| Cow::from(secs.to_string())),
+            ),
+            (
+                "monitor-bell",
+                window
+                    .monitor_bell
+                    .or_else(|| rearm(Alert::Bell))
+                    .map(on_off)
+                    .map(Cow::from),
+            ),
+        ];
+
+        for (option, value) in options {
+            if let Some(value) = value {
+                let target = self.setup_window_target();
+                self.push_new_command("set-window-option")
+                    .push_target_arg(target)
+                    .push(option)
+                    .push(value.as_ref());
+            }
         }
     }
 
-    fn apply_root_split(&mut self, split: &RootSplit, parent_cwd: &Cwd) -> &mut Self {
-        // We now have a fresh window with a single, unconfigured pane.
-        // To apply our options to the pane, we created a horizontal split
-        // with our designated first pane on the right. Afterwards we kill
-        // the initial placeholder pane.
+    fn create_initial_window(mut self, window: &Window, parent_cwd: &Cwd) -> Self {
+        self.active_window_marked = false;
+        self.new_window_at(window, parent_cwd, WindowPlacement::Initial)
+    }
 
-        let first_pane = root_pane(split);
-        let first_pane_cwd = parent_cwd.joined(&first_pane.cwd);
-        self.split_pane(
-            Axis::Horizontal,
-            SplitFlow::Regular,
-            &first_pane_cwd,
-            first_pane.shell_command.as_deref(),
-            None,
-        );
+    fn select_active_pane(&mut self, window: &Window) {
+        let active_pane = window.root_split.pane_iter().position(|pane| pane.active);
 
-        let first_pane_target = self.session_target().current_window().pane("0");
-        self.push_new_command("kill-pane")
-            .push_target_arg(first_pane_target);
+        if let Some(pane_index) = active_pane {
+            let target = self.setup_window_target().pane(pane_index.to_string());
 
-        self.apply_split(split, parent_cwd)
+            self.push_new_command("select-pane").push_target_arg(target);
+        }
     }
 
     fn apply_split(&mut self, split: &Split, parent_cwd: &Cwd) -> &mut Self {
         let flow = SplitFlow::from(split);
 
         match split {
-            Split::Pane(pane) => {
-                if let Some(keys) = &pane.send_keys {
-                    self.send_keys(keys);
+            Split::Pane(pane) => match pane.send_keys_target {
+                // Pane indexes change while the window is split.
+                Some(index) if pane.send_keys.is_some() => {
+                    self.deferred_keys.push((index, pane.clone()));
+                    self
                 }
-                self
-            }
+                _ => {
+                    let target = self.setup_target();
+                    self.send_pane_keys(target, pane, pane.read_only)
+                }
+            },
             Split::H { left, right } => {
                 let (parent, child) = match flow {
                     SplitFlow::Regular => (left, right),
@@ -232,7 +818,7 @@ impl TmuxCommandBuilder {
                     Axis::Horizontal,
                     flow,
                     &child_pane_cwd,
-                    child_pane.shell_command.as_deref(),
+                    pane_command(child_pane).as_deref(),
                     child.width.as_deref(),
                 )
                 .apply_split(&child.split, parent_cwd)
@@ -251,7 +837,7 @@ impl TmuxCommandBuilder {
                     Axis::Vertical,
                     flow,
                     &child_pane_cwd,
-                    child_pane.shell_command.as_deref(),
+                    pane_command(child_pane).as_deref(),
                     child.height.as_deref(),
                 )
                 .apply_split(&child.split, parent_cwd)
@@ -261,10 +847,69 @@ impl TmuxCommandBuilder {
         }
     }
 
-    fn send_keys(&mut self, keys: impl IntoIterator<Item = impl AsRef<OsStr>>) -> &mut Self {
-        let target = self.session_target();
-        self.push_new_command("send-keys").push_target_arg(target);
-        keys.into_iter().fold(self, |b, key| b.push_arg(Some(key)))
+    /// Sends the keys deferred by [`Self::apply_split`] to their target panes
+    /// of the (completely split) `window` being set up.
+    fn send_deferred_keys(&mut self, window: &Window) {
+        for (index, pane) in std::mem::take(&mut self.deferred_keys) {
+            let target = self.setup_window_target().pane(index.to_string());
+            let target_read_only = window
+                .root_split
+                .pane_iter()
+                .nth(index as usize)
+                .is_some_and(|pane| pane.read_only);
+            self.send_pane_keys(target.to_string(), &pane, target_read_only);
+        }
+    }
+
+    /// Disables input to the `read_only` panes of `window` once their keys
+    /// were sent.
+    fn disable_read_only_input(&mut self, window: &Window) {
+        for (index, pane) in window.root_split.pane_iter().enumerate() {
+            if pane.read_only {
+                let target = self.setup_window_target().pane(index.to_string());
+                self.push_pane_input(target.to_string(), false);
+            }
+        }
+    }
+
+    /// Sends the `send_keys` of `pane` to `target`, followed by clearing the
+    /// screen if the pane should be cleared.
+    /// With `wait_for` or `delay`, the keys are sent by a script tmux runs in
+    /// the background (see [`wait::script`]), which enables the input of a
+    /// `target_read_only` pane meanwhile.
+    fn send_pane_keys(&mut self, target: String, pane: &Pane, target_read_only: bool) -> &mut Self {
+        let Some(keys) = &pane.send_keys else {
+            return self;
+        };
+        let mut key_lists = vec![key_args(keys)];
+        // Typed into the shell after the keys (the leading space keeps it
+        // out of the shell history).
+        let clear = format!(" {}", CLEAR_SCREEN);
+        if pane.clear {
+            key_lists.push(vec![&clear, "Enter"]);
+        }
+
+        match (&pane.wait_for, pane.delay) {
+            (None, None) => {
+                for keys in key_lists {
+                    self.push_new_command("send-keys")
+                        .push_flag_arg("-t", Some(&target));
+                    for key in keys {
+                        self.push(key);
+                    }
+                }
+            }
+            (wait_for, delay) => {
+                let tmux = self.command.get_program().to_string_lossy().into_owned();
+                let wait_for = wait_for.clone().unwrap_or_default();
+                let script = wait::script(&wait_for, delay, &tmux, &key_lists, target_read_only);
+                self.push_new_command("run-shell")
+                    .push("-b")
+                    .push_flag_arg("-t", Some(&target))
+                    .push(script);
+            }
+        }
+        self
     }
 
     fn split_pane(
@@ -275,28 +920,29 @@ impl TmuxCommandBuilder {
         shell_command: Option<&str>,
         size: Option<&str>,
     ) -> &mut Self {
-        let target = self.session_target();
+        let target = self.setup_target();
         self.push_new_command("split-window")
-            .push_target_arg(target)
+            .push_flag_arg("-t", Some(target))
             .push_axis_arg(axis)
             .push_flow_arg(flow)
             .push_cwd_arg(cwd)
-            .push_flag_arg("-l", size)
+            // `50%` is left to tmux, which splits the cells evenly (the
+            // percentage would include the border)
+            .push_size_arg(size.filter(|size| !sizes::is_even_size(size)))
             .push_arg(shell_command)
     }
 
-    fn select_pane_at(&mut self, direction: Direction) -> &mut Self {
-        let target = self.session_target();
+    fn push_pane_input(&mut self, target: String, enabled: bool) -> &mut Self {
         self.push_new_command("select-pane")
-            .push_target_arg(target)
-            .push_direction_arg(direction)
+            .push(if enabled { "-e" } else { "-d" })
+            .push_flag_arg("-t", Some(target))
     }
 
-    fn select_window_at(&mut self, direction: Direction) -> &mut Self {
-        let target = self.session_target();
-        self.push_new_command("select-window")
-            .push_target_arg(target)
-            .push_next_prev_arg(direction)
+    fn select_pane_at(&mut self, direction: Direction) -> &mut Self {
+        let target = self.setup_target();
+        self.push_new_command("select-pane")
+            .push_flag_arg("-t", Some(target))
+            .push_direction_arg(direction)
     }
 
     fn select_window(&mut self, target: Target<Window>) -> &mut Self {
@@ -310,23 +956,79 @@ impl TmuxCommandBuilder {
     }
 
     fn attach_session(&mut self, target: Target<Session>) -> &mut Self {
+        let detach_others = self.detach_others;
         self.push_new_command("attach-session")
+            .push_arg(detach_others.then_some("-d"))
+            .push_target_arg(target)
+    }
+
+    /// Remembers the ID of the window just created (the current window of
+    /// the session) as the one to select once all windows exist (see
+    /// [`Self::select_active_window`]). Unlike its index or its offset from
+    /// other windows, the ID doesn't change while windows are created (also
+    /// by raw commands or hooks).
+    fn mark_active_window(&mut self) {
+        self.active_window_marked = true;
+        let target = self.session_target();
+        self.push_new_command("set-option")
+            .push("-F")
             .push_target_arg(target)
+            .push(ACTIVE_WINDOW_OPTION)
+            .push("#{window_id}");
     }
 
+    /// Selects the window marked by [`Self::mark_active_window`] (if any).
+    /// Its ID is only known to tmux, which expands it in `run-shell`.
     fn select_active_window(&mut self) -> &mut Self {
-        if let Some(index) = self.active_window_index {
-            if let Some(session_name) = self.current_session_name.as_deref() {
-                let target = Target::session(session_name).window(index.to_string());
-                self.select_window(target);
-            } else {
-                let steps = self.window_count - index - 1;
-                for _ in 0..steps {
-                    self.select_window_at(Direction::Left);
-                }
-            }
+        if !std::mem::take(&mut self.active_window_marked) {
+            return self;
+        }
+        let target = self.session_target();
+        let select_window = format!("select-window -t #{{{}}}", ACTIVE_WINDOW_OPTION);
+        let run_commands = self
+            .tmux_version
+            .is_none_or(|version| version.supports(Feature::RunShellCommands));
+        self.push_new_command("run-shell");
+        if run_commands {
+            self.push("-C")
+                .push_target_arg(target.clone())
+                .push(select_window);
+        } else {
+            // `run-shell` expands formats in the command.
+            let tmux = self
+                .command
+                .get_program()
+                .to_string_lossy()
+                .replace('#', "##");
+            self.push_target_arg(target.clone()).push(format!(
+                "{} {}",
+                shell::quote(&tmux),
+                select_window
+            ));
+        }
+        self.push_new_command("set-option")
+            .push("-u")
+            .push_target_arg(target)
+            .push(ACTIVE_WINDOW_OPTION)
+    }
+
+    /// The window being set up: the current window of the session unless it
+    /// was created in the background
+    fn setup_window_target(&self) -> Target<Window> {
+        let target = self.session_target();
+        match &self.background_window {
+            Some(window) => target.window(window.clone()),
+            None => target.current_window(),
+        }
+    }
+
+    /// Like [`Self::setup_window_target`] for commands targeting the active
+    /// pane of the window
+    fn setup_target(&self) -> String {
+        match self.background_window {
+            Some(_) => self.setup_window_target().to_string(),
+            None => self.session_target().to_string(),
         }
-        self
     }
 
     fn session_target(&self) -> Target<Session> {
@@ -338,10 +1040,36 @@ impl TmuxCommandBuilder {
 
     // Primitives
 
+    fn push_on_kill_commands(&mut self, session: &Session) {
+        for command in &session.on_kill {
+            let command = match session.cwd.to_path() {
+                Some(dir) => format!("cd {} && {}", shell::quote(&dir.to_string_lossy()), command),
+                None => command.clone(),
+            };
+            // `run-shell` expands formats in the command.
+            self.push_new_command("run-shell")
+                .push_target_arg(Target::session(&session.name))
+                .push(command.replace('#', "##"));
+        }
+    }
+
     fn push_cwd_arg(&mut self, cwd: &Cwd) -> &mut Self {
         self.push_flag_arg("-c", cwd.to_path())
     }
 
+    fn push_size_arg(&mut self, size: Option<&str>) -> &mut Self {
+        let legacy_percentage = size.and_then(|size| size.strip_suffix('%')).filter(|_| {
+            self.tmux_version
+                .is_some_and(|version| !version.supports(Feature::PercentageSizes))
+        });
+
+        match legacy_percentage {
+            // tmux < 3.1 only knows percentages via `-p`
+            Some(percentage) => self.push_flag_arg("-p", Some(percentage)),
+            None => self.push_flag_arg("-l", size),
+        }
+    }
+
     fn push_target_arg<Scope>(&mut self, target: Target<Scope>) -> &mut Self
     where
         Target<Scope>: fmt::Display,
@@ -365,15 +1093,6 @@ impl TmuxCommandBuilder {
         }
     }
 
-    fn push_next_prev_arg(&mut self, direction: Direction) -> &mut Self {
-        match direction {
-            Direction::Left => self.push("-p"),
-            Direction::Right => self.push("-n"),
-            Direction::Up => self.push("-p"),
-            Direction::Down => self.push("-n"),
-        }
-    }
-
     fn push_flow_arg(&mut self, flow: SplitFlow) -> &mut Self {
         match flow {
             SplitFlow::Regular => self,
@@ -381,11 +1100,17 @@ impl TmuxCommandBuilder {
         }
     }
 
-    fn push_query_scope_arg(&mut self, scope: QueryScope) -> &mut Self {
+    fn push_query_scope_arg(&mut self, scope: &QueryScope) -> &mut Self {
         match scope {
             QueryScope::AllSessions => self.push("-a"),
             QueryScope::CurrentSession => self.push("-s"),
             QueryScope::CurrentWindow => self,
+            QueryScope::Session(session) => {
+                self.push("-s").push_target_arg(Target::session(session))
+            }
+            QueryScope::Window { session, window } => {
+                self.push_target_arg(Target::session(session).window(window))
+            }
         }
     }
 
@@ -422,6 +1147,26 @@ impl TmuxCommandBuilder {
     }
 }
 
+/// Where `new-window` places the created window.
+#[derive(Debug, Clone, Copy)]
+enum WindowPlacement<'a> {
+    /// The next free index of the target session
+    Default,
+    /// Right before the given window index, moving it up
+    Before(&'a str),
+    /// In place of the initial window of a new session: before it, which
+    /// is killed right away. The window thus gets the session's first index
+    /// (`base-index`), whether windows are renumbered or not.
+    Initial,
+    /// Right after the last window of the target session
+    Append,
+    /// Right after the current window of the target session, moving later
+    /// windows up
+    AfterCurrent,
+    /// Right before the current window of the target session, moving it up
+    BeforeCurrent,
+}
+
 /// When splitting the parent pane, the split direction depens on the
 /// location of size information. If we are, for instance, supposed
 /// to give the right pane a width of 25%, we create a new pane on the
@@ -454,14 +1199,22 @@ impl From<&'_ Split> for SplitFlow {
         match split {
             Split::Pane(_) => SplitFlow::Regular,
             Split::H { left, .. } => {
-                if left.width.is_some() {
+                if left
+                    .width
+                    .as_deref()
+                    .is_some_and(|size| !sizes::is_even_size(size))
+                {
                     SplitFlow::Inverted
                 } else {
                     SplitFlow::Regular
                 }
             }
             Split::V { top, .. } => {
-                if top.height.is_some() {
+                if top
+                    .height
+                    .as_deref()
+                    .is_some_and(|size| !sizes::is_even_size(size))
+                {
                     SplitFlow::Inverted
                 } else {
                     SplitFlow::Regular
@@ -471,6 +1224,12 @@ impl From<&'_ Split> for SplitFlow {
     }
 }
 
+/// Name of the running session `name` while it's being replaced (see
+/// [`TmuxCommandBuilder::replace_sessions`])
+pub fn replaced_session_name(name: &str) -> String {
+    format!("{}-replaced", name)
+}
+
 /// Finds the root pane for the given split (i.e. the pane all
 /// rescursive splits are created on).
 ///
@@ -491,6 +1250,100 @@ fn root_pane(split: &Split) -> &Pane {
     }
 }
 
+/// Arguments of `send-keys` for `keys`. Secrets which weren't resolved (see
+/// `config::secrets`) are sent as a placeholder.
+fn key_args(keys: &[Key]) -> Vec<&str> {
+    keys.iter()
+        .map(|key| match key {
+            Key::Literal(key) => key.as_str(),
+            Key::Secret(_) => secrets::REDACTED,
+        })
+        .collect()
+}
+
+/// Session option holding the ID of the window to select once all windows
+/// are created (see [`TmuxCommandBuilder::mark_active_window`])
+const ACTIVE_WINDOW_OPTION: &str = "@tmux_layout_active_window";
+
+/// Clears the screen and (as `CSI 3 J` does in tmux) the scrollback of a
+/// pane from within, so that it's ordered after the pane's earlier output
+/// (unlike `clear-history`).
+const CLEAR_SCREEN: &str = r"printf '\033[H\033[2J\033[3J'";
+
+/// The shell command a pane is started with (if any).
+///
+/// A pane's custom `shell` is started as login shell, running the pane's
+/// `shell_command` if given. With `keep_open`, the command is followed by
+/// an interactive shell so the pane survives the command's exit. Captured
+/// history is printed before anything else, and `clear` clears the screen
+/// after the command. The command starts after the pane's `delay`.
+fn pane_command(pane: &Pane) -> Option<Cow<'_, str>> {
+    let mut steps = Vec::new();
+    if let Some(history_file) = &pane.history_file {
+        steps.push(Cow::Owned(format!("cat {}", shell::quote(history_file))));
+    }
+    if let (Some(delay), Some(_)) = (pane.delay, &pane.shell_command) {
+        steps.push(Cow::Owned(format!("sleep {}", delay)));
+    }
+    steps.extend(wrapped_shell_command(pane));
+    let keep_open = pane.keep_open || pane.shell_command.is_none();
+    if pane.clear && keep_open && !steps.is_empty() {
+        steps.push(Cow::Borrowed(CLEAR_SCREEN));
+    }
+
+    match &pane.shell {
+        None => {
+            if steps.is_empty() {
+                return None;
+            }
+            if steps.len() == 1 && !keep_open {
+                return steps.pop();
+            }
+            let mut script = steps.join("; ");
+            if keep_open {
+                script.push_str("; exec \"${SHELL:-sh}\"");
+            }
+            Some(Cow::Owned(format!("sh -c {}", shell::quote(&script))))
+        }
+        Some(custom_shell) => {
+            let login_shell = format!("{} -l", shell::quote(custom_shell));
+            if steps.is_empty() {
+                return Some(Cow::Owned(login_shell));
+            }
+            let mut script = steps.join("; ");
+            if keep_open {
+                script.push_str(&format!("; exec {}", login_shell));
+            }
+            Some(Cow::Owned(format!(
+                "{} -c {}",
+                login_shell,
+                shell::quote(&script)
+            )))
+        }
+    }
+}
+
+/// The `shell_command` of `pane`, run (in the pane's shell) by its `wrapper`
+/// and via `direnv exec` with `direnv`.
+fn wrapped_shell_command(pane: &Pane) -> Option<Cow<'_, str>> {
+    let command = pane.shell_command.as_deref()?;
+    let mut prefixes = Vec::new();
+    if pane.direnv == Some(true) {
+        prefixes.push("direnv exec .");
+    }
+    prefixes.extend(pane.wrapper.as_deref().filter(|w| !w.is_empty()));
+    if prefixes.is_empty() {
+        return Some(Cow::Borrowed(command));
+    }
+    let shell = pane.shell.as_deref().unwrap_or("sh");
+    Some(Cow::Owned(format!(
+        "{} {} -c {}",
+        prefixes.join(" "),
+        shell::quote(shell),
+        shell::quote(command)
+    )))
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Direction {
     Left,
@@ -612,3 +1465,308 @@ impl Default for Target<Session> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::tmux::{simulate, Layout};
+
+    fn load(config_str: &str) -> Config {
+        serde_yaml::from_str(config_str).unwrap()
+    }
+
+    /// The commands added to `builder`, each with its arguments joined by
+    /// spaces
+    fn commands(builder: TmuxCommandBuilder) -> Vec<String> {
+        builder
+            .into_command_sequence()
+            .commands
+            .iter()
+            .map(|command| {
+                let args = command.iter().map(|arg| arg.to_string_lossy());
+                args.collect::<Vec<_>>().join(" ")
+            })
+            .collect()
+    }
+
+    fn builder() -> TmuxCommandBuilder {
+        TmuxCommandBuilder::new("tmux", Vec::<&str>::new())
+    }
+
+    #[test]
+    fn test_new_session_targets_without_indexes() {
+        let config = load(
+            r#"
+            sessions:
+              - name: dev
+                windows:
+                  - name: editor
+                  - name: build
+                  - name: logs
+                    background: true
+                    top: {}
+                    bottom: {}
+                  - name: shell
+            "#,
+        );
+        // Like `base-index` and `renumber-windows`, killing the initial
+        // window doesn't affect the targets.
+        assert_eq!(
+            commands(builder().new_session(&config.sessions[0])),
+            [
+                "new-session -s dev -d",
+                "new-window -n editor -b -t dev:{start}.",
+                "kill-window -t dev:{end}.",
+                "new-window -n build -t dev:",
+                "new-window -n logs -d -t dev:",
+                "split-window -t dev:{end}. -v",
+                "select-pane -t dev:{end}. -U",
+                "new-window -n shell -t dev:",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_sessions() {
+        let config = load(
+            r#"
+            sessions:
+              - name: dev
+                on_kill: ["docker compose down"]
+                windows:
+                  - name: editor
+            "#,
+        );
+        let replaced = ["dev".to_string()];
+        let commands = commands(
+            builder()
+                .replace_sessions(&config.sessions)
+                .new_session(&config.sessions[0])
+                .select_session(Some("dev"), SessionSelectMode::Switch)
+                .kill_replaced_sessions(&replaced),
+        );
+        // The running session is only killed once the client switched away
+        // from it.
+        assert_eq!(
+            commands,
+            [
+                "run-shell -t dev: docker compose down",
+                "rename-session -t dev: dev-replaced",
+                "new-session -s dev -d",
+                "new-window -n editor -b -t dev:{start}.",
+                "kill-window -t dev:{end}.",
+                "switch-client -t dev:",
+                "kill-session -t dev-replaced:",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_session() {
+        let config = load(
+            r#"
+            sessions:
+              - name: dev
+                windows:
+                  - name: editor
+                  - name: build
+                    active: true
+                  - name: logs
+                    background: true
+                  - {}
+            "#,
+        );
+        let session = &config.sessions[0];
+
+        // Only the missing windows are appended, the active one is selected.
+        let existing = ["editor".to_string(), "shell".to_string()];
+        assert_eq!(
+            commands(builder().update_session(session, &existing)),
+            [
+                "new-window -n build -a -t dev:{end}.",
+                "set-option -F -t dev: @tmux_layout_active_window #{window_id}",
+                "new-window -n logs -d -a -t dev:{end}.",
+                "new-window -a -t dev:{end}.",
+                "run-shell -C -t dev: select-window -t #{@tmux_layout_active_window}",
+                "set-option -u -t dev: @tmux_layout_active_window",
+            ]
+        );
+
+        // Nothing is missing (the unnamed window matches by position)
+        let existing = ["editor", "build", "logs", "shell"].map(String::from);
+        assert!(builder().update_session(session, &existing).is_empty());
+    }
+
+    #[test]
+    fn test_simulate_new_session() {
+        let config = load(
+            r#"
+            sessions:
+              - name: dev
+                windows:
+                  - name: editor
+                  - left: {}
+                    right:
+                      width: "30"
+                      top:
+                        active: true
+                      bottom: {}
+            "#,
+        );
+        let sequence = builder()
+            .new_session(&config.sessions[0])
+            .into_command_sequence();
+        let simulated = simulate::simulate(&sequence, 80, 24).unwrap();
+        assert_eq!(
+            simulated.layout().to_string(),
+            Layout::from_split(&config.sessions[0].windows[1].root_split, 80, 24).to_string()
+        );
+        assert_eq!(simulated.active_pane(), 1);
+    }
+
+    #[test]
+    fn test_select_active_window_by_id() {
+        let config = load(
+            r#"
+            windows:
+              - name: editor
+                active: true
+              - name: server
+                raw_commands: ["new-window -n scratch"]
+            "#,
+        );
+        // The window created by the raw command doesn't shift the active one.
+        fn expected(select_window: &str) -> [&str; 6] {
+            [
+                "new-window -n editor -a -t :.",
+                "set-option -F -t : @tmux_layout_active_window #{window_id}",
+                "new-window -n server -a -t :.",
+                "new-window -n scratch",
+                select_window,
+                "set-option -u -t : @tmux_layout_active_window",
+            ]
+        }
+        assert_eq!(
+            commands(builder().new_windows(&config.windows, &Cwd::default())),
+            expected("run-shell -C -t : select-window -t #{@tmux_layout_active_window}")
+        );
+        // tmux < 3.2 can only run shell commands
+        let legacy_builder = builder().tmux_version(Some(Version::new(3, 1)));
+        assert_eq!(
+            commands(legacy_builder.new_windows(&config.windows, &Cwd::default())),
+            expected("run-shell -t : tmux select-window -t #{@tmux_layout_active_window}")
+        );
+    }
+
+    #[test]
+    fn test_new_window_first_pane() {
+        let config = load(
+            r#"
+            windows:
+              - name: editor
+                cwd: /src
+                shell_command: nvim
+              - cwd: /src
+                left:
+                  width: "30"
+                  shell_command: htop
+                right:
+                  cwd: logs
+                  shell_command: tail -f log
+              - name: bg
+                background: true
+                top:
+                  cwd: /tmp
+                bottom: {}
+            "#,
+        );
+        let expected: [&[&str]; 3] = [
+            &["new-window -n editor -c /src -t : nvim"],
+            // The right pane is the one the left one is split off.
+            &[
+                "new-window -c /src/logs -t : tail -f log",
+                "split-window -t : -h -b -c /src -l 30 htop",
+                "select-pane -t : -R",
+            ],
+            &[
+                "new-window -n bg -c /tmp -d -t :",
+                "split-window -t :{end}. -v",
+                "select-pane -t :{end}. -U",
+            ],
+        ];
+        for (window, expected) in config.windows.iter().zip(expected) {
+            let builder = builder().new_window(window, &Cwd::default(), None);
+            assert_eq!(commands(builder), expected);
+        }
+    }
+
+    /// Like `sequence`, but with the first pane of every window split off a
+    /// placeholder pane, which is killed then (as windows used to be set up)
+    fn with_placeholder_panes(sequence: &CommandSequence) -> CommandSequence {
+        let mut placeholder_sequence = sequence.clone();
+        placeholder_sequence.commands.clear();
+        for command in &sequence.commands {
+            placeholder_sequence.commands.push(command.clone());
+            if command[0] == "new-window" {
+                let split = ["split-window", "-h"];
+                let kill = ["kill-pane", "-t", ":.0"];
+                placeholder_sequence.commands.extend([
+                    split.iter().map(Into::into).collect(),
+                    kill.iter().map(Into::into).collect(),
+                ]);
+            }
+        }
+        placeholder_sequence
+    }
+
+    #[test]
+    fn test_simulate_first_pane() {
+        let config = load(
+            r#"
+            windows:
+              - {}
+              - left:
+                  width: "30"
+                right:
+                  top: {}
+                  bottom:
+                    active: true
+              - top:
+                  height: 25%
+                  left: {}
+                  right:
+                    width: 40%
+                bottom:
+                  left:
+                    width: "20"
+                  right: {}
+            "#,
+        );
+        for window in &config.windows {
+            let sequence = builder()
+                .new_window(window, &Cwd::default(), None)
+                .into_command_sequence();
+            let simulated = simulate::simulate(&sequence, 120, 40).unwrap();
+            let placeholder_simulated =
+                simulate::simulate(&with_placeholder_panes(&sequence), 120, 40).unwrap();
+            assert_eq!(simulated.layout(), placeholder_simulated.layout());
+            assert_eq!(simulated.active_pane(), placeholder_simulated.active_pane());
+            assert_eq!(
+                simulated.layout(),
+                Layout::from_split(&window.root_split, 120, 40)
+            );
+        }
+    }
+
+    #[test]
+    fn test_socket_matches_tmux_env() {
+        let named = TmuxSocket::Name("work".to_string());
+        assert!(named.matches_tmux_env("/tmp/tmux-1000/work,1234,0"));
+        assert!(!named.matches_tmux_env("/tmp/tmux-1000/default,1234,0"));
+
+        let path = TmuxSocket::Path("/run/tmux/work".to_string());
+        assert!(path.matches_tmux_env("/run/tmux/work,1234,2"));
+        assert!(!path.matches_tmux_env("/tmp/tmux-1000/work,1234,2"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Sequential execution of tmux commands.
+//!
+//! Usually all tmux commands are chained with `;` and run by a single tmux
+//! invocation, which stops at the first failing command and doesn't tell
+//! which one it was. The sequential backend (`create --sequential`) runs
+//! every command on its own and collects the results into an
+//! [`ExecutionReport`].
+
+use serde::Serialize;
+use std::ffi::OsString;
+use std::io;
+use std::process::{Command, Stdio};
+
+use super::retry;
+use crate::{logging, shell};
+
+/// tmux commands split into separate invocations of `program`
+#[derive(Debug, Clone)]
+pub struct CommandSequence {
+    pub program: OsString,
+    /// Arguments passed to every invocation (e.g. `-L <socket>`)
+    pub global_args: Vec<OsString>,
+    pub commands: Vec<Vec<OsString>>,
+    /// Retries of invocations failing for transient reasons (see
+    /// [`retry`](super::retry))
+    pub retries: u32,
+}
+
+impl CommandSequence {
+    fn command(&self, args: &[OsString]) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.global_args).args(args);
+        command
+    }
+}
+
+/// Result of a single tmux command
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandResult {
+    pub args: Vec<String>,
+    /// Session or window the command belongs to, e.g. `window 'dev:editor'`
+    pub unit: Option<String>,
+    /// Exit code (`None` if tmux was killed by a signal)
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    /// The command as a shell command line (without program and global args)
+    pub fn command_line(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| shell::quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Number of succeeded and failed commands of a session or window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnitSummary {
+    pub unit: String,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Results of all commands run by [`execute_sequentially`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExecutionReport {
+    pub results: Vec<CommandResult>,
+}
+
+impl ExecutionReport {
+    pub fn success(&self) -> bool {
+        self.results.iter().all(CommandResult::success)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CommandResult> {
+        self.results.iter().filter(|result| !result.success())
+    }
+
+    /// Succeeded and failed commands per session and window (in order of
+    /// creation). Commands before the first session or window are left out.
+    pub fn units(&self) -> Vec<UnitSummary> {
+        let mut units: Vec<UnitSummary> = Vec::new();
+        for result in &self.results {
+            let Some(unit) = &result.unit else {
+                continue;
+            };
+            if units.last().map(|last| &last.unit) != Some(unit) {
+                units.push(UnitSummary {
+                    unit: unit.clone(),
+                    succeeded: 0,
+                    failed: 0,
+                });
+            }
+            let summary = units.last_mut().unwrap();
+            if result.success() {
+                summary.succeeded += 1;
+            } else {
+                summary.failed += 1;
+            }
+        }
+        units
+    }
+}
+
+/// Runs the commands of `sequence` one after another (continuing after
+/// failures) and collects their results. `attach-session` inherits the
+/// terminal, so its output isn't captured.
+pub fn execute_sequentially(sequence: &CommandSequence) -> io::Result<ExecutionReport> {
+    let mut report = ExecutionReport::default();
+    let mut units = UnitTracker::default();
+
+    for args in &sequence.commands {
+        let mut command = sequence.command(args);
+        logging::command(&command);
+        let args = args
+            .iter()
+            .map(|arg| logging::redact(&arg.to_string_lossy()).into_owned())
+            .collect::<Vec<_>>();
+        let unit = units.unit_of(&args);
+
+        let result = if args.first().map(String::as_str) == Some("attach-session") {
+            let status = retry::status(&mut command, sequence.retries)?;
+            CommandResult {
+                args,
+                unit,
+                status: status.code(),
+                stdout: String::new(),
+                stderr: String::new(),
+            }
+        } else {
+            let output = retry::output(command.stdin(Stdio::null()), sequence.retries)?;
+            CommandResult {
+                args,
+                unit,
+                status: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+        };
+        logging::trace(&format!(
+            "tmux exited with {:?}: {}",
+            result.status,
+            result.stderr.trim_end()
+        ));
+        report.results.push(result);
+    }
+
+    Ok(report)
+}
+
+/// Assigns commands to the session or window created last
+#[derive(Default)]
+struct UnitTracker {
+    session: Option<String>,
+    window_count: usize,
+    unit: Option<String>,
+}
+
+impl UnitTracker {
+    fn unit_of(&mut self, args: &[String]) -> Option<String> {
+        let flag_value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|i| args.get(i + 1))
+                .cloned()
+        };
+        match args.first().map(String::as_str) {
+            Some("new-session") => {
+                self.session = flag_value("-s");
+                self.window_count = 0;
+                let name = self.session.as_deref().unwrap_or("(unnamed)");
+                self.unit = Some(format!("session '{}'", name));
+            }
+            Some("new-window") => {
+                self.window_count += 1;
+                let window = flag_value("-n").unwrap_or_else(|| self.window_count.to_string());
+                self.unit = Some(match &self.session {
+                    Some(session) => format!("window '{}:{}'", session, window),
+                    None => format!("window '{}'", window),
+                });
+            }
+            _ => {}
+        }
+        self.unit.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_execute_sequentially() {
+        // `$0` is the tmux command, `$1` its first argument
+        let sequence = CommandSequence {
+            program: "sh".into(),
+            global_args: args(&["-c", r#"echo "$0"; [ "$1" != fail ]"#]),
+            commands: vec![
+                args(&["kill-session", "old"]),
+                args(&["new-session", "-s", "dev", "-d"]),
+                args(&["split-window", "-h"]),
+                args(&["new-window", "-n", "server"]),
+                args(&["split-window", "fail"]),
+                args(&["send-keys", "ok"]),
+            ],
+            retries: 0,
+        };
+
+        let report = execute_sequentially(&sequence).unwrap();
+        assert!(!report.success());
+        assert_eq!(report.results[2].stdout, "split-window\n");
+        let failures = report.failures().collect::<Vec<_>>();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].command_line(), "split-window fail");
+        assert_eq!(
+            report.units(),
+            [
+                UnitSummary {
+                    unit: "session 'dev'".to_string(),
+                    succeeded: 2,
+                    failed: 0,
+                },
+                UnitSummary {
+                    unit: "window 'dev:server'".to_string(),
+                    succeeded: 2,
+                    failed: 1,
+                },
+            ]
+        );
+    }
+}
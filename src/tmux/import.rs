@@ -1,24 +1,242 @@
-use std::{collections::HashMap, path::Path, process::Stdio};
+//! Querying the sessions, windows and panes of a running tmux server.
+//!
+//! ```no_run
+//! use tmux_layout::tmux::import::{Field, StateQuery};
+//! use tmux_layout::tmux::{QueryScope, TmuxCommandBuilder};
+//!
+//! let state = StateQuery::new(QueryScope::AllSessions)
+//!     .field(Field::PanePid)
+//!     .run(TmuxCommandBuilder::new("tmux", Vec::<&str>::new()))
+//!     .unwrap();
+//! for session in state.sessions.values() {
+//!     println!("{}: {} windows", session.name, session.windows.len());
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
 use thiserror::Error;
 
 use crate::{
     config::{self},
     cwd::Cwd,
-    tmux::{self, TmuxCommandBuilder},
+    logging,
+    tmux::{self, layout::Sizes, retry, TmuxCommandBuilder},
 };
 
 pub use parser::Error as ParseError;
 
 use super::command::QueryScope;
 
+/// A value tmux reports per pane (see FORMATS in tmux(1))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    SessionId,
+    WindowId,
+    PaneId,
+    SessionName,
+    SessionPath,
+    SessionAttached,
+    SessionLastAttached,
+    WindowIndex,
+    WindowName,
+    WindowActive,
+    WindowLayout,
+    MonitorActivity,
+    MonitorSilence,
+    MonitorBell,
+    PaneIndex,
+    PaneActive,
+    PaneCurrentPath,
+    /// PID of the first process of the pane (see [`Pane::pid`])
+    PanePid,
+    /// Pseudo terminal of the pane (see [`Pane::tty`])
+    PaneTty,
+    /// Command running in the foreground of the pane (see
+    /// [`Pane::current_command`])
+    PaneCurrentCommand,
+    /// Exit status of the pane's exited process (see [`Pane::dead_status`])
+    PaneDeadStatus,
+    /// Whether the window has an activity alert (see [`Window::alerts`])
+    WindowActivityFlag,
+    /// Whether the window has a bell alert (see [`Window::alerts`])
+    WindowBellFlag,
+    /// Whether the window has a silence alert (see [`Window::alerts`])
+    WindowSilenceFlag,
+    /// Style of the pane (see [`Pane::style`])
+    PaneStyle,
+    /// Border style of the window (see [`Window::border_style`])
+    PaneBorderStyle,
+}
+
+impl Field {
+    /// Fields every [`StateQuery`] queries, as they are needed to build the
+    /// [`TmuxState`]
+    pub const REQUIRED: &'static [Field] = &[
+        Field::SessionId,
+        Field::WindowId,
+        Field::PaneId,
+        Field::SessionName,
+        Field::SessionPath,
+        Field::SessionAttached,
+        Field::SessionLastAttached,
+        Field::WindowIndex,
+        Field::WindowName,
+        Field::WindowActive,
+        Field::WindowLayout,
+        Field::MonitorActivity,
+        Field::MonitorSilence,
+        Field::MonitorBell,
+        Field::PaneIndex,
+        Field::PaneActive,
+        Field::PaneCurrentPath,
+    ];
+
+    /// The tmux format of the field. Values of [text](Self::is_text) fields
+    /// are prefixed with their length in bytes (`4:/src`).
+    pub fn format(self) -> &'static str {
+        match self {
+            Field::SessionId => "#{session_id}",
+            Field::WindowId => "#{window_id}",
+            Field::PaneId => "#{pane_id}",
+            Field::SessionName => "#{n:session_name}:#{session_name}",
+            Field::SessionPath => "#{n:session_path}:#{session_path}",
+            Field::SessionAttached => "#{session_attached}",
+            Field::SessionLastAttached => "#{?session_last_attached,#{session_last_attached},0}",
+            Field::WindowIndex => "#{window_index}",
+            Field::WindowName => "#{n:window_name}:#{window_name}",
+            Field::WindowActive => "#{window_active}",
+            Field::WindowLayout => "#{window_layout}",
+            Field::MonitorActivity => "#{monitor-activity}",
+            Field::MonitorSilence => "#{monitor-silence}",
+            Field::MonitorBell => "#{monitor-bell}",
+            Field::PaneIndex => "#{pane_index}",
+            Field::PaneActive => "#{pane_active}",
+            Field::PaneCurrentPath => "#{n:pane_current_path}:#{pane_current_path}",
+            Field::PanePid => "#{pane_pid}",
+            Field::PaneTty => "#{n:pane_tty}:#{pane_tty}",
+            Field::PaneCurrentCommand => "#{n:pane_current_command}:#{pane_current_command}",
+            Field::PaneDeadStatus => "#{?pane_dead,#{pane_dead_status}:#{pane_dead_signal},}",
+            Field::WindowActivityFlag => "#{window_activity_flag}",
+            Field::WindowBellFlag => "#{window_bell_flag}",
+            Field::WindowSilenceFlag => "#{window_silence_flag}",
+            Field::PaneStyle => "#{n:window-style}:#{window-style}",
+            Field::PaneBorderStyle => "#{n:pane-border-style}:#{pane-border-style}",
+        }
+    }
+
+    /// Whether values of the field may contain any character (tmux allows
+    /// even tabs and newlines in names), as opposed to numbers, IDs and
+    /// layouts
+    pub fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::SessionName
+                | Field::SessionPath
+                | Field::WindowName
+                | Field::PaneCurrentPath
+                | Field::PaneTty
+                | Field::PaneCurrentCommand
+                | Field::PaneStyle
+                | Field::PaneBorderStyle
+        )
+    }
+}
+
+/// Queries the state of the tmux server with [`Field::REQUIRED`] and the
+/// selected extra fields.
+#[derive(Debug, Clone)]
+pub struct StateQuery {
+    scope: QueryScope,
+    fields: Vec<Field>,
+}
+
+impl StateQuery {
+    pub fn new(scope: QueryScope) -> Self {
+        Self {
+            scope,
+            fields: Field::REQUIRED.to_vec(),
+        }
+    }
+
+    /// Additionally queries `field` (ignored if it's queried anyway)
+    pub fn field(mut self, field: Field) -> Self {
+        if !self.fields.contains(&field) {
+            self.fields.push(field);
+        }
+        self
+    }
+
+    /// The format passed to `list-panes -F`: the fields separated by tabs
+    pub fn format(&self) -> String {
+        let formats = self.fields.iter().map(|field| field.format());
+        formats.collect::<Vec<_>>().join("\t")
+    }
+
+    pub fn run(&self, command_builder: TmuxCommandBuilder) -> Result<TmuxState, Error> {
+        // The length prefixes count the bytes before tmux would replace
+        // non-ASCII characters for clients without a UTF-8 locale.
+        let retries = command_builder.retry_count();
+        let mut command = command_builder
+            .force_utf8()
+            .query_panes(self.format(), &self.scope)
+            .into_command();
+
+        logging::command(&command);
+        let command_out = retry::output(command.stderr(Stdio::piped()), retries)?;
+        if !command_out.status.success() {
+            let stderr = String::from_utf8_lossy(&command_out.stderr);
+            logging::trace(&format!(
+                "tmux exited with {}: {}",
+                command_out.status,
+                stderr.trim_end()
+            ));
+            if is_no_server_error(&stderr) {
+                return Err(Error::NoServer);
+            }
+            crate::show_error(stderr.trim_end());
+            return Err(Error::CommandExitCode(
+                command_out.status.code().unwrap_or(1),
+            ));
+        }
+
+        let state_desc = command_out.stdout;
+        let state_desc = std::str::from_utf8(&state_desc)
+            .map_err(|_| Error::ParseError("command output not UTF-8".into()))?;
+
+        Ok(parser::parse_tmux_state(state_desc, &self.fields)?)
+    }
+}
+
+/// Queries the state of the tmux server (see [`StateQuery`] for more fields).
 pub fn query_tmux_state(
     command_builder: TmuxCommandBuilder,
-    scope: QueryScope,
+    scope: &QueryScope,
 ) -> Result<TmuxState, Error> {
+    StateQuery::new(scope.clone()).run(command_builder)
+}
+
+fn is_no_server_error(stderr: &str) -> bool {
+    [
+        "no server running",
+        "error connecting to",
+        "server exited unexpectedly",
+    ]
+    .iter()
+    .any(|message| stderr.starts_with(message))
+}
+
+/// Captures the contents of a pane (including its scrollback).
+pub fn capture_pane(command_builder: TmuxCommandBuilder, pane_id: PaneId) -> Result<String, Error> {
     let mut command = command_builder
-        .query_panes(parser::TMUX_FORMAT, scope)
+        .capture_pane(pane_id.to_string())
         .into_command();
 
+    logging::command(&command);
     let command_out = command.stderr(Stdio::inherit()).output()?;
     if !command_out.status.success() {
         return Err(Error::CommandExitCode(
@@ -26,22 +244,116 @@ pub fn query_tmux_state(
         ));
     }
 
-    let state_desc = command_out.stdout;
-    let state_desc = std::str::from_utf8(&state_desc)
-        .map_err(|_| Error::ParseError("command output not UTF-8".into()))?;
-
-    Ok(parser::parse_tmux_state(state_desc)?)
+    Ok(String::from_utf8_lossy(&command_out.stdout).into_owned())
 }
+
 #[derive(Debug, Clone)]
 pub struct TmuxState {
     pub sessions: HashMap<SessionId, Session>,
 }
 
+impl TmuxState {
+    /// Name of the session a client is attached to (the most recently
+    /// attached one if there are several). Falls back to the most recently
+    /// attached session if no client is attached at all.
+    pub fn selected_session(&self) -> Option<&str> {
+        self.sessions
+            .values()
+            .max_by_key(|s| (s.attached, s.last_attached, s.id))
+            .map(|s| s.name.as_str())
+    }
+}
+
 impl From<TmuxState> for Vec<config::Session> {
     fn from(state: TmuxState) -> Self {
-        let mut sessions = state.sessions.into_values().collect::<Vec<_>>();
-        sessions.sort_by_key(|s| s.id);
-        sessions.into_iter().map(Into::into).collect()
+        state.into_config_sessions(
+            SessionOrder::default(),
+            Relativize::default(),
+            Sizes::default(),
+        )
+    }
+}
+
+impl TmuxState {
+    /// Converts into config sessions ordered by `order` with cwds written
+    /// according to `relativize` and split sizes as `sizes`.
+    pub fn into_config_sessions(
+        self,
+        order: SessionOrder,
+        relativize: Relativize,
+        sizes: Sizes,
+    ) -> Vec<config::Session> {
+        let mut sessions = self.sessions.into_values().collect::<Vec<_>>();
+        order.sort(&mut sessions);
+        sessions
+            .into_iter()
+            .map(|s| s.into_config_session(relativize, sizes))
+            .collect()
+    }
+}
+
+/// Order of exported sessions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionOrder {
+    /// By creation (session ID)
+    #[default]
+    Id,
+    /// By name, then by creation
+    Name,
+    /// Sessions with attached clients first, each by creation
+    AttachedFirst,
+}
+
+impl SessionOrder {
+    fn sort(self, sessions: &mut [Session]) {
+        match self {
+            SessionOrder::Id => sessions.sort_by_key(|s| s.id),
+            SessionOrder::Name => sessions.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id))),
+            SessionOrder::AttachedFirst => sessions.sort_by_key(|s| (!s.attached, s.id)),
+        }
+    }
+}
+
+/// How exported working directories are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Relativize {
+    /// Like `Session`, but absolute paths below $HOME start with `~`
+    Home,
+    /// Window cwds relative to the session cwd (where possible), pane cwds
+    /// relative to the window cwd
+    #[default]
+    Session,
+    /// Absolute paths only
+    None,
+}
+
+impl Relativize {
+    /// Writes the absolute `path` relative to `base` (if given and enabled)
+    /// or to the home directory (for `Home`).
+    fn apply(self, path: String, base: Option<&Cwd>) -> Cwd<'static> {
+        self.apply_with_home(path, base, dirs::home_dir().as_deref())
+    }
+
+    /// Like [`Self::apply`] with the home directory `home`
+    fn apply_with_home(
+        self,
+        path: String,
+        base: Option<&Cwd>,
+        home: Option<&Path>,
+    ) -> Cwd<'static> {
+        let abs_path = Path::new(&path);
+        if self != Relativize::None {
+            if let Some(rel_path) = base.and_then(|base| base.relative_path_of(abs_path)) {
+                return rel_path.into();
+            }
+        }
+        let path = Cwd::from(path);
+        if self == Relativize::Home {
+            if let Some(home_path) = home.and_then(|home| path.home_relative(home)) {
+                return home_path;
+            }
+        }
+        path
     }
 }
 
@@ -50,25 +362,38 @@ pub struct Session {
     pub id: SessionId,
     pub name: String,
     pub cwd: String,
+    /// Whether a client is attached to the session
+    pub attached: bool,
+    /// Time the session was last attached (seconds since the epoch)
+    pub last_attached: u64,
     pub windows: HashMap<WindowId, Window>,
 }
 
 impl From<Session> for config::Session {
     fn from(session: Session) -> Self {
-        let session_cwd = session.cwd.into();
+        session.into_config_session(Relativize::default(), Sizes::default())
+    }
+}
 
-        let mut windows = session.windows.into_values().collect::<Vec<_>>();
+impl Session {
+    /// Converts into a config session with cwds written according to
+    /// `relativize` and split sizes as `sizes`.
+    pub fn into_config_session(self, relativize: Relativize, sizes: Sizes) -> config::Session {
+        let session_cwd = Cwd::from(self.cwd.clone());
+
+        let mut windows = self.windows.into_values().collect::<Vec<_>>();
         windows.sort_by_key(|w| w.index);
 
         let windows = windows
             .into_iter()
-            .map(|w| w.into_config_window(&session_cwd))
+            .map(|w| w.into_config_window(&session_cwd, relativize, sizes))
             .collect();
 
         config::Session {
-            name: session.name,
-            cwd: session_cwd,
+            name: self.name,
+            cwd: relativize.apply(self.cwd, None),
             windows,
+            ..Default::default()
         }
     }
 }
@@ -80,40 +405,119 @@ pub struct Window {
     pub name: String,
     pub layout: tmux::Layout,
     pub active: bool,
+    pub monitor_activity: bool,
+    pub monitor_silence: u32,
+    pub monitor_bell: bool,
+    /// Only queried with [`Field::WindowActivityFlag`] and the like
+    pub alerts: Vec<config::Alert>,
+    /// `pane-border-style` of the window, only queried with
+    /// [`Field::PaneBorderStyle`]
+    pub border_style: Option<String>,
     pub panes: HashMap<PaneId, Pane>,
 }
 
 impl Window {
-    fn into_config_window(self, session_cwd: &Cwd) -> config::Window {
-        let session_cwd_path = session_cwd.to_path();
-
+    /// Converts into a config window with split sizes as `sizes`. Unless
+    /// `relativize` is `None`, the deepest directory containing the cwds of
+    /// all panes becomes the window cwd (relative to `session_cwd` where
+    /// possible) and the pane cwds are written relative to it.
+    pub fn into_config_window(
+        self,
+        session_cwd: &Cwd,
+        relativize: Relativize,
+        sizes: Sizes,
+    ) -> config::Window {
         let mut panes = self.panes.into_values().collect::<Vec<_>>();
         panes.sort_by_key(|p| p.index);
 
-        let mut root_split = config::Split::from(self.layout).into_root();
+        let window_dir = match relativize {
+            Relativize::None => None,
+            _ => common_dir(panes.iter().map(|pane| Path::new(&pane.cwd))),
+        };
+        let pane_base = match &window_dir {
+            Some(dir) => Cwd::from(dir.clone()),
+            None => session_cwd.clone(),
+        };
+
+        let border_color = self
+            .border_style
+            .as_deref()
+            .and_then(|style| style_color(style, "fg"));
+        let mut root_split = self.layout.into_split(sizes).into_root();
+        if let Some(first_pane) = root_split.pane_iter_mut().next() {
+            first_pane.border_color = border_color;
+        }
         root_split
             .pane_iter_mut()
             .zip(panes)
             .for_each(|(config_pane, pane)| {
                 config_pane.active = pane.active;
-                config_pane.cwd = session_cwd_path
-                    .and_then(|root| Path::new(&pane.cwd).strip_prefix(root).ok())
-                    .map(|p| p.to_owned().into())
-                    .unwrap_or_else(|| pane.cwd.into());
+                config_pane.history_file = pane.history_file.clone();
+                config_pane.background = pane
+                    .style
+                    .as_deref()
+                    .and_then(|style| style_color(style, "bg"));
+                config_pane.process = pane.pid.map(|pid| {
+                    Box::new(config::Process {
+                        pid,
+                        command: pane.command_line.clone(),
+                    })
+                });
+                config_pane.cwd = relativize.apply(pane.cwd.clone(), Some(&pane_base));
             });
 
         config::Window {
             name: Some(self.name),
-            cwd: Cwd::new(None),
+            cwd: match window_dir {
+                Some(dir) => {
+                    relativize.apply(dir.to_string_lossy().into_owned(), Some(session_cwd))
+                }
+                None => Cwd::new(None),
+            },
             active: self.active,
+            // Only export monitor options deviating from tmux' defaults
+            monitor_activity: if self.monitor_activity {
+                Some(true)
+            } else {
+                None
+            },
+            monitor_silence: Some(self.monitor_silence).filter(|secs| *secs > 0),
+            monitor_bell: if self.monitor_bell { None } else { Some(false) },
+            alerts: self.alerts,
             root_split,
+            ..Default::default()
+        }
+    }
+}
+
+/// Color of `attribute` (`fg` or `bg`) in the tmux `style`, e.g. `colour236`
+/// for `fg=default,bg=colour236`. `None` if it's missing or `default`.
+fn style_color(style: &str, attribute: &str) -> Option<String> {
+    style
+        .split([',', ' '])
+        .filter_map(|part| part.strip_prefix(attribute)?.strip_prefix('='))
+        .next_back()
+        .filter(|color| !color.is_empty() && *color != "default")
+        .map(String::from)
+}
+
+/// Deepest directory containing all of the absolute `paths`, `None` if they
+/// only share the root directory
+fn common_dir<'p>(mut paths: impl Iterator<Item = &'p Path>) -> Option<PathBuf> {
+    let mut common = paths.next()?.to_path_buf();
+    for path in paths {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                return None;
+            }
         }
     }
+    Some(common).filter(|dir| dir.parent().is_some())
 }
 
 impl From<Window> for config::Window {
     fn from(window: Window) -> Self {
-        window.into_config_window(&Cwd::default())
+        window.into_config_window(&Cwd::default(), Relativize::default(), Sizes::default())
     }
 }
 
@@ -123,6 +527,25 @@ pub struct Pane {
     pub index: PaneIndex,
     pub active: bool,
     pub cwd: String,
+    /// File the pane's contents were captured into (see [`capture_pane`])
+    pub history_file: Option<String>,
+    /// Only queried with [`Field::PanePid`]
+    pub pid: Option<u32>,
+    /// Only queried with [`Field::PaneTty`]
+    pub tty: Option<String>,
+    /// Only queried with [`Field::PaneCurrentCommand`]
+    pub current_command: Option<String>,
+    /// Exit status of the pane's process if it exited and the pane remains
+    /// (`remain-on-exit`), `Some(None)` if it was killed by a signal. `None`
+    /// until tmux reaped the process (and for signals before tmux 3.3). Only
+    /// queried with [`Field::PaneDeadStatus`].
+    pub dead_status: Option<Option<i32>>,
+    /// Command line of the foreground process (see
+    /// [`crate::process::foreground_command`]), exported along with `pid`
+    pub command_line: Option<String>,
+    /// `window-style` of the pane (set by `select-pane -P`), only queried
+    /// with [`Field::PaneStyle`]
+    pub style: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -140,12 +563,26 @@ pub struct PaneId(u32);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PaneIndex(u32);
 
+impl fmt::Display for WindowId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+impl fmt::Display for PaneId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("error while invoking tmux command: {0}")]
     CommandIo(#[from] std::io::Error),
     #[error("non-successful tmux exit code: {0}")]
     CommandExitCode(i32),
+    #[error("no tmux server running")]
+    NoServer,
     #[error("parse error: {0}")]
     ParseError(#[from] ParseError),
 }
@@ -153,7 +590,6 @@ pub enum Error {
 mod parser {
     use crate::tmux::layout;
     use nom::Parser;
-    use shellwords::MismatchedQuotes;
     use std::borrow::Cow;
     use std::collections::{hash_map::Entry, HashMap};
     use std::fmt;
@@ -163,8 +599,14 @@ mod parser {
 
     type Result<A> = std::result::Result<A, Error>;
 
-    pub(super) fn parse_tmux_state(input: &str) -> Result<TmuxState> {
-        let infos = parse_pane_infos(input)?;
+    pub(super) fn parse_tmux_state(input: &str, fields: &[Field]) -> Result<TmuxState> {
+        let mut infos = Vec::new();
+        let mut rest = input;
+        while !rest.is_empty() {
+            let (info, next) = parse_record(rest, fields)?;
+            infos.push(info);
+            rest = next;
+        }
         let mut sessions = HashMap::new();
 
         for info in infos {
@@ -174,6 +616,8 @@ mod parser {
                     id: info.session_id,
                     name: info.session_name,
                     cwd: info.session_cwd,
+                    attached: info.session_attached,
+                    last_attached: info.session_last_attached,
                     windows: Default::default(),
                 }),
             };
@@ -186,6 +630,11 @@ mod parser {
                     name: info.window_name,
                     layout: info.window_layout,
                     active: info.window_active,
+                    monitor_activity: info.monitor_activity,
+                    monitor_silence: info.monitor_silence,
+                    monitor_bell: info.monitor_bell,
+                    alerts: info.alerts,
+                    border_style: info.pane_border_style,
                     panes: Default::default(),
                 }),
             };
@@ -197,6 +646,13 @@ mod parser {
                     index: info.pane_index,
                     active: info.pane_active,
                     cwd: info.pane_cwd,
+                    history_file: None,
+                    pid: info.pane_pid,
+                    tty: info.pane_tty,
+                    current_command: info.pane_current_command,
+                    dead_status: info.pane_dead_status,
+                    command_line: None,
+                    style: info.pane_style,
                 },
             );
         }
@@ -211,59 +667,143 @@ mod parser {
         pane_id: PaneId,
         session_name: String,
         session_cwd: String,
+        session_attached: bool,
+        session_last_attached: u64,
         window_index: WindowIndex,
         window_name: String,
         window_active: bool,
         window_layout: tmux::Layout,
+        monitor_activity: bool,
+        monitor_silence: u32,
+        monitor_bell: bool,
+        alerts: Vec<config::Alert>,
         pane_index: PaneIndex,
         pane_active: bool,
         pane_cwd: String,
+        pane_pid: Option<u32>,
+        pane_tty: Option<String>,
+        pane_current_command: Option<String>,
+        pane_dead_status: Option<Option<i32>>,
+        pane_style: Option<String>,
+        pane_border_style: Option<String>,
     }
 
-    fn parse_pane_infos(input: &str) -> Result<Vec<PaneInfo>> {
-        input.lines().map(parse_line).collect()
-    }
-
-    pub(super) const TMUX_FORMAT: &str = "#{q:session_id} #{q:window_id} #{q:pane_id} \
-        #{q:session_name} #{q:session_path} #{q:window_index} #{q:window_name} \
-        #{q:window_active} #{q:window_layout} #{q:pane_index} #{q:pane_active} \
-        #{q:pane_current_path}";
-
-    fn parse_line(line: &str) -> Result<PaneInfo> {
-        let mut words = shellwords::split(line)?.into_iter();
-        let mut next_word = || words.next().ok_or_else(|| Error::from("missing word"));
+    /// Parses the line of `list-panes -F <format of fields>` at the start of
+    /// `input` (which may span several lines if values contain newlines) and
+    /// returns the rest of the input.
+    fn parse_record<'a>(input: &'a str, fields: &[Field]) -> Result<(PaneInfo, &'a str)> {
+        let mut rest = input;
+        let mut values = HashMap::new();
+        for (i, field) in fields.iter().enumerate() {
+            let (value, after_value) = split_value(rest, *field)?;
+            let separator = if i == fields.len() - 1 { '\n' } else { '\t' };
+            rest = match after_value.strip_prefix(separator) {
+                Some(after_separator) => after_separator,
+                None if after_value.is_empty() && separator == '\n' => after_value,
+                None => return Err(Error::from(format!("unexpected data after {:?}", field))),
+            };
+            values.insert(*field, value.to_string());
+        }
+        let mut value = |field: Field| {
+            values
+                .remove(&field)
+                .ok_or_else(|| Error::from(format!("{:?} not queried", field)))
+        };
 
-        let session_id_desc = next_word()?;
+        let session_id_desc = value(Field::SessionId)?;
         let session_id = all_consuming(session_id).parse(&session_id_desc)?.1;
-        let window_id_desc = next_word()?;
+        let window_id_desc = value(Field::WindowId)?;
         let window_id = all_consuming(window_id).parse(&window_id_desc)?.1;
-        let pane_id_desc = next_word()?;
+        let pane_id_desc = value(Field::PaneId)?;
         let pane_id = all_consuming(pane_id).parse(&pane_id_desc)?.1;
-        let session_name = next_word()?;
-        let session_cwd = next_word()?;
-        let window_index = WindowIndex(next_word()?.parse()?);
-        let window_name = next_word()?;
-        let window_active = next_word()?.parse::<u8>()? != 0;
-        let window_layout_desc = next_word()?;
-        let window_layout = tmux::Layout::parse(&window_layout_desc)?;
-        let pane_index = PaneIndex(next_word()?.parse()?);
-        let pane_active = next_word()?.parse::<u8>()? != 0;
-        let pane_cwd = next_word().unwrap_or_default();
-
-        Ok(PaneInfo {
+        let session_name = value(Field::SessionName)?;
+        let session_cwd = value(Field::SessionPath)?;
+        let session_attached = value(Field::SessionAttached)?.parse::<u32>()? > 0;
+        let session_last_attached = value(Field::SessionLastAttached)?.parse()?;
+        let window_index = WindowIndex(value(Field::WindowIndex)?.parse()?);
+        let window_name = value(Field::WindowName)?;
+        let window_active = value(Field::WindowActive)?.parse::<u8>()? != 0;
+        let window_layout = tmux::Layout::parse(&value(Field::WindowLayout)?)?;
+        let monitor_activity = value(Field::MonitorActivity)?.parse::<u8>()? != 0;
+        let monitor_silence = value(Field::MonitorSilence)?.parse()?;
+        let monitor_bell = value(Field::MonitorBell)?.parse::<u8>()? != 0;
+        let pane_index = PaneIndex(value(Field::PaneIndex)?.parse()?);
+        let pane_active = value(Field::PaneActive)?.parse::<u8>()? != 0;
+        let pane_cwd = value(Field::PaneCurrentPath)?;
+        let pane_pid = value(Field::PanePid)
+            .ok()
+            .map(|pid| pid.parse())
+            .transpose()?;
+        let pane_tty = value(Field::PaneTty).ok();
+        let pane_current_command = value(Field::PaneCurrentCommand).ok();
+        // `<status>:<signal>` of dead panes, both empty until the process
+        // was reaped
+        let pane_dead_status =
+            value(Field::PaneDeadStatus)
+                .ok()
+                .and_then(|value| match value.split_once(':')? {
+                    ("", "") => None,
+                    (status, _) => Some(status.parse().ok()),
+                });
+        let pane_style = value(Field::PaneStyle).ok();
+        let pane_border_style = value(Field::PaneBorderStyle).ok();
+        let mut alerts = Vec::new();
+        for (field, alert) in [
+            (Field::WindowActivityFlag, config::Alert::Activity),
+            (Field::WindowBellFlag, config::Alert::Bell),
+            (Field::WindowSilenceFlag, config::Alert::Silence),
+        ] {
+            if let Ok(flag) = value(field) {
+                if flag.parse::<u8>()? != 0 {
+                    alerts.push(alert);
+                }
+            }
+        }
+
+        let info = PaneInfo {
             session_id,
             window_id,
             pane_id,
             session_name,
             session_cwd,
+            session_attached,
+            session_last_attached,
             window_index,
             window_name,
             window_active,
             window_layout,
+            monitor_activity,
+            monitor_silence,
+            monitor_bell,
+            alerts,
             pane_index,
             pane_active,
             pane_cwd,
-        })
+            pane_pid,
+            pane_tty,
+            pane_current_command,
+            pane_dead_status,
+            pane_style,
+            pane_border_style,
+        };
+        Ok((info, rest))
+    }
+
+    /// Splits the value of `field` (without its length prefix) off the start
+    /// of `input`.
+    fn split_value(input: &str, field: Field) -> Result<(&str, &str)> {
+        if !field.is_text() {
+            let end = input.find(['\t', '\n']).unwrap_or(input.len());
+            return Ok(input.split_at(end));
+        }
+        let (len, rest) = input
+            .split_once(':')
+            .ok_or_else(|| Error::from(format!("missing length of {:?}", field)))?;
+        let len = len.parse::<usize>()?;
+        if rest.len() < len || !rest.is_char_boundary(len) {
+            return Err(Error::from(format!("invalid length of {:?}", field)));
+        }
+        Ok(rest.split_at(len))
     }
 
     use nom::{
@@ -310,12 +850,6 @@ mod parser {
         }
     }
 
-    impl From<MismatchedQuotes> for Error {
-        fn from(_: MismatchedQuotes) -> Self {
-            Error::from("missing quotes")
-        }
-    }
-
     impl<E: std::error::Error> From<nom::Err<E>> for Error {
         fn from(err: nom::Err<E>) -> Self {
             Error::from(format!("{}", err))
@@ -342,3 +876,212 @@ mod parser {
 
     impl std::error::Error for Error {}
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_extra_fields() {
+        let query = StateQuery::new(QueryScope::AllSessions)
+            .field(Field::PanePid)
+            .field(Field::PaneTty)
+            .field(Field::WindowBellFlag)
+            .field(Field::PaneDeadStatus);
+        assert!(query.format().ends_with(
+            "\t#{pane_pid}\t#{n:pane_tty}:#{pane_tty}\t#{window_bell_flag}\t\
+             #{?pane_dead,#{pane_dead_status}:#{pane_dead_signal},}"
+        ));
+
+        let line = "$1\t@2\t%3\t3:dev\t4:/src\t1\t0\t0\t9:my window\t1\tb25d,80x24,0,0,3\t\
+                    0\t0\t1\t0\t1\t0:\t4242\t10:/dev/pts/3\t1\t3:\n";
+        let state = parser::parse_tmux_state(line, &query.fields).unwrap();
+        let window = &state.sessions[&SessionId(1)].windows[&WindowId(2)];
+        assert_eq!(window.name, "my window");
+        assert_eq!(window.alerts, [config::Alert::Bell]);
+        let pane = &window.panes[&PaneId(3)];
+        assert_eq!(pane.pid, Some(4242));
+        assert_eq!(pane.tty.as_deref(), Some("/dev/pts/3"));
+        assert_eq!(pane.current_command, None);
+        assert_eq!(pane.dead_status, Some(Some(3)));
+        assert_eq!(pane.cwd, "");
+
+        let truncated = "$1\t@2\t%3\t30:dev\n";
+        assert!(parser::parse_tmux_state(truncated, &query.fields).is_err());
+    }
+
+    #[test]
+    fn test_active_windows_and_panes() {
+        let split = "0bea,80x24,0,0{60x24,0,0,1,19x24,61,0,2}";
+        let single = "b25d,80x24,0,0,0";
+        let line = |ids: &str, session: &str, window: &str, layout: &str, pane: &str| {
+            format!(
+                "{ids}\t{}:{session}\t4:/src\t0\t0\t{window}\t{layout}\t0\t0\t1\t{pane}\t4:/src\n",
+                session.len()
+            )
+        };
+        // `<index>\t<name>\t<active>` of windows, `<index>\t<active>` of panes
+        let output = [
+            line("$1\t@1\t%0", "a", "0\t2:a0\t0", split, "0\t0"),
+            line("$1\t@1\t%1", "a", "0\t2:a0\t0", split, "1\t1"),
+            line("$1\t@2\t%2", "a", "1\t2:a1\t1", single, "0\t1"),
+            line("$2\t@3\t%3", "b", "0\t2:b0\t0", single, "0\t1"),
+            line("$2\t@4\t%4", "b", "1\t2:b1\t1", split, "0\t1"),
+            line("$2\t@4\t%5", "b", "1\t2:b1\t1", split, "1\t0"),
+            line("$2\t@5\t%6", "b", "2\t2:b2\t0", single, "0\t1"),
+        ]
+        .concat();
+        let state = parser::parse_tmux_state(&output, Field::REQUIRED).unwrap();
+        let sessions = Vec::<config::Session>::from(state);
+
+        // Like after `create` from the exported config
+        let yaml = serde_yaml::to_string(&sessions).unwrap();
+        let sessions = serde_yaml::from_str::<Vec<config::Session>>(&yaml).unwrap();
+
+        let active = |session: &config::Session| {
+            session
+                .windows
+                .iter()
+                .map(|window| window.active)
+                .collect::<Vec<_>>()
+        };
+        let active_panes = |window: &config::Window| {
+            window
+                .root_split
+                .pane_iter()
+                .map(|pane| pane.active)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(active(&sessions[0]), [false, true]);
+        assert_eq!(active(&sessions[1]), [false, true, false]);
+        assert_eq!(active_panes(&sessions[0].windows[0]), [false, true]);
+        assert_eq!(active_panes(&sessions[1].windows[1]), [true, false]);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_parse_names(
+            session_name in "(?s).*",
+            window_name in "(?s).*",
+            path in "(?s)/.*",
+        ) {
+            // Like `#{n:...}:#{...}`
+            let text = |value: &str| format!("{}:{}", value.len(), value);
+            let line = |window_id: &str| {
+                [
+                    "$1", window_id, "%3", &text(&session_name), &text(&path), "1", "0", "0",
+                    &text(&window_name), "1", "b25d,80x24,0,0,3", "0", "0", "1", "0", "1",
+                    &text(&path),
+                ]
+                .join("\t")
+            };
+            let output = format!("{}\n{}\n", line("@2"), line("@4"));
+            let state = parser::parse_tmux_state(&output, Field::REQUIRED).unwrap();
+
+            let session = &state.sessions[&SessionId(1)];
+            proptest::prop_assert_eq!(&session.name, &session_name);
+            proptest::prop_assert_eq!(&session.cwd, &path);
+            proptest::prop_assert_eq!(session.windows.len(), 2);
+            let window = &session.windows[&WindowId(2)];
+            proptest::prop_assert_eq!(&window.name, &window_name);
+            proptest::prop_assert_eq!(&window.panes[&PaneId(3)].cwd, &path);
+        }
+    }
+
+    #[test]
+    fn test_relativize() {
+        let base = Cwd::from("/srv/app");
+        let base = Some(&base);
+        let home = Path::new("/home/me");
+        let apply = |relativize: Relativize, path: &str| {
+            relativize
+                .apply_with_home(path.to_string(), base, Some(home))
+                .to_path()
+                .map(|p| p.to_string_lossy().into_owned())
+        };
+
+        assert_eq!(
+            apply(Relativize::Session, "/srv/app/src"),
+            Some("src".into())
+        );
+        assert_eq!(apply(Relativize::Session, "/srv/app"), None);
+        assert_eq!(
+            apply(Relativize::Session, "/srv/other"),
+            Some("/srv/other".into())
+        );
+        assert_eq!(apply(Relativize::Home, "/srv/app/src"), Some("src".into()));
+        assert_eq!(
+            apply(Relativize::None, "/srv/app/src"),
+            Some("/srv/app/src".into())
+        );
+
+        let project = "/home/me/project";
+        assert_eq!(apply(Relativize::Home, project), Some("~/project".into()));
+        assert_eq!(apply(Relativize::Session, project), Some(project.into()));
+        assert_eq!(apply(Relativize::Home, "/home/me"), Some("~".into()));
+        assert_eq!(
+            apply(Relativize::Home, "/home/meter"),
+            Some("/home/meter".into())
+        );
+    }
+
+    #[test]
+    fn test_common_dir() {
+        let common = |paths: &[&str]| common_dir(paths.iter().map(Path::new));
+        assert_eq!(
+            common(&["/srv/app/src", "/srv/app/tests", "/srv/app"]),
+            Some(PathBuf::from("/srv/app"))
+        );
+        assert_eq!(
+            common(&["/srv/app/src"]),
+            Some(PathBuf::from("/srv/app/src"))
+        );
+        assert_eq!(
+            common(&["/srv/application", "/srv/app"]),
+            Some(PathBuf::from("/srv"))
+        );
+        assert_eq!(common(&["/srv/app", "/home/user"]), None);
+        assert_eq!(common(&["/srv/app", ""]), None);
+        assert_eq!(common(&[]), None);
+    }
+
+    #[test]
+    fn test_session_order() {
+        let session = |id, name: &str, attached| Session {
+            id: SessionId(id),
+            name: name.to_string(),
+            cwd: String::new(),
+            attached,
+            last_attached: 0,
+            windows: HashMap::new(),
+        };
+        let sorted = |order: SessionOrder| {
+            let mut sessions = vec![
+                session(3, "b", false),
+                session(1, "c", true),
+                session(2, "a", false),
+                session(4, "a", true),
+            ];
+            order.sort(&mut sessions);
+            sessions.iter().map(|s| s.id.0).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(SessionOrder::Id), [1, 2, 3, 4]);
+        assert_eq!(sorted(SessionOrder::Name), [2, 4, 3, 1]);
+        assert_eq!(sorted(SessionOrder::AttachedFirst), [1, 4, 2, 3]);
+    }
+
+    #[test]
+    fn test_style_color() {
+        assert_eq!(
+            style_color("fg=default,bg=colour236", "bg").as_deref(),
+            Some("colour236")
+        );
+        assert_eq!(
+            style_color("bg=#202040 fg=red", "fg").as_deref(),
+            Some("red")
+        );
+        assert_eq!(style_color("fg=red,bg=default", "bg"), None);
+        assert_eq!(style_color("default", "bg"), None);
+        assert_eq!(style_color("", "fg"), None);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::config;
+use std::fmt::{self, Write};
+
+use crate::config::{self, sizes};
 
 pub use parser::Error;
 
@@ -29,70 +31,285 @@ impl Layout {
     pub fn height(&self) -> u32 {
         self.geom().height()
     }
+
+    /// Converts into splits with sizes written as `sizes`
+    pub fn into_split(self, sizes: Sizes) -> config::Split {
+        let size = self.geom().size;
+        split_of(&self, size, sizes)
+    }
 }
 
 impl From<Layout> for config::Split {
-    fn from(split: Layout) -> Self {
-        const LINE_WIDTH: f32 = 1.0;
+    fn from(layout: Layout) -> Self {
+        layout.into_split(Sizes::default())
+    }
+}
 
-        match split {
-            Layout::Pane(_) => config::Split::default(),
-            Layout::H(_, mut splits) => {
-                let Some(last_split) = splits.pop() else {
-                    return config::Split::default();
-                };
+impl Layout {
+    /// Layout of the panes of `split` in a window of `width` x `height`
+    /// cells, sized like tmux sizes created panes (see
+    /// [`sizes::part_sizes`]). Chained splits along the same axis become
+    /// parts of one cell, as in the layouts of tmux.
+    pub fn from_split(split: &config::Split, width: u32, height: u32) -> Layout {
+        let geom = PaneGeom {
+            size: Size::new(width, height),
+            ..Default::default()
+        };
+        place(split, geom)
+    }
 
-                let mut acc_width = last_split.width() as f32;
-                let mut acc_split = last_split.into();
-
-                // Build right-associative HSplit by traversing
-                // the splits vector from right-to-left.
-                for left_split in splits.into_iter().rev() {
-                    let new_width = acc_width + left_split.width() as f32 - LINE_WIDTH;
-                    let right_width_percent = (acc_width * 100f32 / new_width).round();
-                    acc_split = config::Split::H {
-                        left: config::HSplitPart {
-                            width: None,
-                            split: Box::new(left_split.into()),
-                        },
-                        right: config::HSplitPart {
-                            width: Some(format!("{:.0}%", right_width_percent)),
-                            split: Box::new(acc_split),
-                        },
-                    };
-                    acc_width = new_width;
-                }
-                acc_split
+    fn write_cell(&self, out: &mut String, pane_count: &mut u32) {
+        let geom = self.geom();
+        write!(
+            out,
+            "{}x{},{},{}",
+            geom.width(),
+            geom.height(),
+            geom.x_offset,
+            geom.y_offset
+        )
+        .unwrap();
+        let (parts, open, close) = match self {
+            Layout::Pane(_) => {
+                write!(out, ",{}", pane_count).unwrap();
+                *pane_count += 1;
+                return;
             }
-            Layout::V(_, mut splits) => {
-                let Some(last_split) = splits.pop() else {
-                    return config::Split::default();
-                };
+            Layout::H(_, parts) => (parts, '{', '}'),
+            Layout::V(_, parts) => (parts, '[', ']'),
+        };
+        out.push(open);
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            part.write_cell(out, pane_count);
+        }
+        out.push(close);
+    }
+}
 
-                let mut acc_height = last_split.height() as f32;
-                let mut acc_split = last_split.into();
-
-                // Build right-associative VSplit by traversing
-                // the splits vector from right-to-left.
-                for top_split in splits.into_iter().rev() {
-                    let new_height = acc_height + top_split.height() as f32 - LINE_WIDTH;
-                    let bottom_height_percent = (acc_height * 100f32 / new_height).round();
-                    acc_split = config::Split::V {
-                        top: config::VSplitPart {
-                            height: None,
-                            split: Box::new(top_split.into()),
-                        },
-                        bottom: config::VSplitPart {
-                            height: Some(format!("{:.0}%", bottom_height_percent)),
-                            split: Box::new(acc_split),
-                        },
-                    };
-                    acc_height = new_height;
+/// The layout string for `select-layout`. Panes are numbered in order
+/// (tmux ignores the pane IDs of layouts).
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cells = String::new();
+        self.write_cell(&mut cells, &mut 0);
+        write!(f, "{:04x},{}", checksum(&cells), cells)
+    }
+}
+
+/// Checksum of the cells of a layout string (`layout_checksum` of tmux)
+fn checksum(cells: &str) -> u16 {
+    cells.bytes().fold(0, |csum: u16, byte| {
+        csum.rotate_right(1).wrapping_add(byte.into())
+    })
+}
+
+fn place(split: &config::Split, geom: PaneGeom) -> Layout {
+    // Parts of the same axis as the split join its parts.
+    let flatten = |parts: Vec<Layout>, same_axis: fn(&Layout) -> Option<&Vec<Layout>>| {
+        parts
+            .into_iter()
+            .flat_map(|part| match same_axis(&part) {
+                Some(nested) => nested.clone(),
+                None => vec![part],
+            })
+            .collect()
+    };
+    match split {
+        config::Split::Pane(_) => Layout::Pane(geom),
+        config::Split::H { left, right } => {
+            let (left_width, right_width) =
+                sizes::part_sizes(left.width.as_deref(), right.width.as_deref(), geom.width());
+            let left_geom = PaneGeom {
+                size: Size::new(left_width, geom.height()),
+                ..geom
+            };
+            let right_geom = PaneGeom {
+                size: Size::new(right_width, geom.height()),
+                x_offset: geom.x_offset + left_width + 1,
+                ..geom
+            };
+            let parts = vec![
+                place(&left.split, left_geom),
+                place(&right.split, right_geom),
+            ];
+            Layout::H(
+                geom,
+                flatten(parts, |part| match part {
+                    Layout::H(_, nested) => Some(nested),
+                    _ => None,
+                }),
+            )
+        }
+        config::Split::V { top, bottom } => {
+            let (top_height, bottom_height) = sizes::part_sizes(
+                top.height.as_deref(),
+                bottom.height.as_deref(),
+                geom.height(),
+            );
+            let top_geom = PaneGeom {
+                size: Size::new(geom.width(), top_height),
+                ..geom
+            };
+            let bottom_geom = PaneGeom {
+                size: Size::new(geom.width(), bottom_height),
+                y_offset: geom.y_offset + top_height + 1,
+                ..geom
+            };
+            let parts = vec![
+                place(&top.split, top_geom),
+                place(&bottom.split, bottom_geom),
+            ];
+            Layout::V(
+                geom,
+                flatten(parts, |part| match part {
+                    Layout::V(_, nested) => Some(nested),
+                    _ => None,
+                }),
+            )
+        }
+    }
+}
+
+/// How the sizes of converted splits are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sizes {
+    /// Percentages of the split pane, adapting to the terminal size
+    #[default]
+    Percent,
+    /// Cells, recreating the layout exactly on terminals of the same size
+    Cells,
+    /// No sizes: every split halves the split pane
+    None,
+}
+
+/// Converts `layout` into the splits of a pane of `size` cells, the size
+/// the pane gets when the splits are created. Sizes are computed from the
+/// exact cell counts of the layout against the created sizes (instead of
+/// the captured ones), so rounding errors don't add up in nested splits.
+fn split_of(layout: &Layout, size: Size, sizes: Sizes) -> config::Split {
+    match layout {
+        Layout::Pane(_) => config::Split::default(),
+        Layout::H(_, parts) => chain_parts(
+            parts,
+            size.width,
+            sizes,
+            Layout::width,
+            &|part, width| split_of(part, Size { width, ..size }, sizes),
+            &|left, right, width| config::Split::H {
+                left: config::HSplitPart {
+                    width: None,
+                    split: Box::new(left),
+                    ..Default::default()
+                },
+                right: config::HSplitPart {
+                    width,
+                    split: Box::new(right),
+                    ..Default::default()
+                },
+            },
+        ),
+        Layout::V(_, parts) => chain_parts(
+            parts,
+            size.height,
+            sizes,
+            Layout::height,
+            &|part, height| split_of(part, Size { height, ..size }, sizes),
+            &|top, bottom, height| config::Split::V {
+                top: config::VSplitPart {
+                    height: None,
+                    split: Box::new(top),
+                    ..Default::default()
+                },
+                bottom: config::VSplitPart {
+                    height,
+                    split: Box::new(bottom),
+                    ..Default::default()
+                },
+            },
+        ),
+    }
+}
+
+/// Builds a right-associative chain of splits of `parts` (placed along one
+/// axis, `len` being their captured size in its direction) within `total`
+/// cells. Every split sizes its second part, i.e. all remaining parts.
+fn chain_parts(
+    parts: &[Layout],
+    total: u32,
+    sizes: Sizes,
+    len: fn(&Layout) -> u32,
+    convert: &dyn Fn(&Layout, u32) -> config::Split,
+    join: &dyn Fn(config::Split, config::Split, Option<String>) -> config::Split,
+) -> config::Split {
+    let Some((first, rest)) = parts.split_first() else {
+        return config::Split::default();
+    };
+    if rest.is_empty() {
+        return convert(first, total);
+    }
+
+    // The remaining parts and the borders between them
+    let rest_cells = rest.iter().map(len).sum::<u32>() + rest.len() as u32 - 1;
+    let (rest_size, created_cells) = PartSize {
+        cells: rest_cells,
+        total,
+    }
+    .render(sizes);
+    // One cell for the border between the parts
+    let first_cells = total.saturating_sub(created_cells + 1);
+    join(
+        convert(first, first_cells),
+        chain_parts(rest, created_cells, sizes, len, convert, join),
+        rest_size,
+    )
+}
+
+/// Size of the second part of a split: `cells` of the `total` cells of the
+/// pane being split (in the direction of the split)
+#[derive(Debug, Clone, Copy)]
+struct PartSize {
+    cells: u32,
+    total: u32,
+}
+
+impl PartSize {
+    /// The size written as `sizes` (if any), along with the size tmux
+    /// creates the part with
+    fn render(self, sizes: Sizes) -> (Option<String>, u32) {
+        let even_cells = self.even_cells();
+        match sizes {
+            Sizes::Percent => {
+                let (percentage, cells) = self.percentage();
+                // Visually even splits (and 50%, see `is_default_size`)
+                // are left to tmux.
+                if even_cells.abs_diff(self.cells) <= 1 || percentage == 50 {
+                    (None, even_cells)
+                } else {
+                    (Some(format!("{}%", percentage)), cells)
                 }
-                acc_split
             }
+            Sizes::Cells if even_cells == self.cells => (None, even_cells),
+            Sizes::Cells => (Some(self.cells.to_string()), self.cells),
+            Sizes::None => (None, even_cells),
         }
     }
+
+    /// The size tmux creates the part with if the split has no size
+    fn even_cells(self) -> u32 {
+        self.total.div_ceil(2).saturating_sub(1)
+    }
+
+    /// The percentage for `split-window -l` giving the size closest to
+    /// `cells`, along with that size (tmux rounds down)
+    fn percentage(self) -> (u32, u32) {
+        (1..100)
+            .map(|percentage| (percentage, self.total * percentage / 100))
+            .min_by_key(|(_, cells)| cells.abs_diff(self.cells))
+            .unwrap()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -218,6 +435,352 @@ mod parser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{sizes, HSplitPart, Pane, Split, VSplitPart};
+    use crate::tmux::{simulate, TmuxCommandBuilder};
+    use proptest::prelude::*;
+
+    /// Sizes of the panes of `split` created in a pane of `size` (like tmux
+    /// does, see `sizes::part_sizes`)
+    fn created_pane_sizes(split: &Split, size: Size, pane_sizes: &mut Vec<Size>) {
+        match split {
+            Split::Pane(_) => pane_sizes.push(size),
+            Split::H { left, right } => {
+                let (left_width, right_width) =
+                    sizes::part_sizes(left.width.as_deref(), right.width.as_deref(), size.width);
+                created_pane_sizes(&left.split, Size::new(left_width, size.height), pane_sizes);
+                created_pane_sizes(
+                    &right.split,
+                    Size::new(right_width, size.height),
+                    pane_sizes,
+                );
+            }
+            Split::V { top, bottom } => {
+                let (top_height, bottom_height) =
+                    sizes::part_sizes(top.height.as_deref(), bottom.height.as_deref(), size.height);
+                created_pane_sizes(&top.split, Size::new(size.width, top_height), pane_sizes);
+                created_pane_sizes(
+                    &bottom.split,
+                    Size::new(size.width, bottom_height),
+                    pane_sizes,
+                );
+            }
+        }
+    }
+
+    fn captured_pane_sizes(layout: &Layout, pane_sizes: &mut Vec<Size>) {
+        match layout {
+            Layout::Pane(geom) => pane_sizes.push(geom.size),
+            Layout::H(_, parts) | Layout::V(_, parts) => parts
+                .iter()
+                .for_each(|part| captured_pane_sizes(part, pane_sizes)),
+        }
+    }
+
+    #[test]
+    fn test_even_splits() {
+        let sizes = |layout: &str, sizes: Sizes| {
+            let split = Layout::parse(layout).unwrap().into_split(sizes);
+            let Split::H { left, right } = split else {
+                panic!("expected a horizontal split")
+            };
+            (left.width, right.width)
+        };
+
+        for layout in [
+            "b25d,80x24,0,0{40x24,0,0,1,39x24,41,0,2}",
+            "b25d,81x24,0,0{40x24,0,0,1,40x24,41,0,2}",
+            "b25d,201x24,0,0{99x24,0,0,1,101x24,100,0,2}",
+        ] {
+            assert_eq!(sizes(layout, Sizes::Percent), (None, None), "{}", layout);
+        }
+        let layout = "b25d,80x24,0,0{41x24,0,0,1,38x24,42,0,2}";
+        assert_eq!(sizes(layout, Sizes::Percent), (None, None));
+        assert_eq!(sizes(layout, Sizes::Cells), (None, Some("38".to_string())));
+        let layout = "b25d,80x24,0,0{50x24,0,0,1,29x24,51,0,2}";
+        assert_eq!(
+            sizes(layout, Sizes::Percent),
+            (None, Some("37%".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_split_round_trip() {
+        let pane = |width, height| {
+            Layout::Pane(PaneGeom {
+                size: Size::new(width, height),
+                ..Default::default()
+            })
+        };
+        let geom = |width, height| PaneGeom {
+            size: Size::new(width, height),
+            ..Default::default()
+        };
+        let columns = |widths: &[u32], height| {
+            let width = widths.iter().sum::<u32>() + widths.len() as u32 - 1;
+            let panes = widths.iter().map(|w| pane(*w, height)).collect();
+            Layout::H(geom(width, height), panes)
+        };
+        let layouts = [
+            Layout::parse("b25d,80x24,0,0{40x24,0,0,1,39x24,41,0,2}").unwrap(),
+            Layout::parse("c3a1,200x50,0,0{30x50,0,0,1,169x50,31,0[169x12,31,0,2,169x37,31,13,3]}")
+                .unwrap(),
+            // Deep chains, where rounding errors used to add up
+            columns(&[23, 31, 17, 29, 19, 27, 21, 26], 50),
+            Layout::V(
+                geom(157, 60),
+                vec![
+                    columns(&[11, 41, 23, 79], 7),
+                    pane(157, 13),
+                    columns(&[3, 5, 7, 11, 13, 17, 19, 23, 51], 38),
+                ],
+            ),
+        ];
+
+        for layout in layouts {
+            let size = layout.geom().size;
+            let mut captured = Vec::new();
+            captured_pane_sizes(&layout, &mut captured);
+
+            for (sizes, max_error) in [(Sizes::Percent, 1), (Sizes::Cells, 0)] {
+                let mut created = Vec::new();
+                let split = layout.clone().into_split(sizes);
+                created_pane_sizes(&split, size, &mut created);
+
+                assert_eq!(created.len(), captured.len());
+                for (created, captured) in created.iter().zip(&captured) {
+                    assert!(
+                        created.width.abs_diff(captured.width) <= max_error
+                            && created.height.abs_diff(captured.height) <= max_error,
+                        "created {:?} instead of {:?} in {:?} ({:?})",
+                        created,
+                        captured,
+                        size,
+                        sizes
+                    );
+                }
+            }
+
+            let split = layout.into_split(Sizes::None);
+            let mut created = Vec::new();
+            created_pane_sizes(&split, size, &mut created);
+            assert_eq!(created.len(), captured.len());
+            assert_eq!(
+                serde_yaml::to_string(&split).unwrap().matches('%').count(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn test_layout_from_split() {
+        assert_eq!(checksum("80x24,0,0,0"), 0xb25d);
+
+        let window =
+            serde_yaml::from_str::<config::Window>("{ panes: [a, b, c], ratio: '1:1:2' }").unwrap();
+        let layout = Layout::from_split(&window.root_split, 80, 24);
+        let Layout::H(_, parts) = &layout else {
+            panic!("expected a horizontal layout")
+        };
+        assert_eq!(
+            parts.iter().map(Layout::width).collect::<Vec<_>>(),
+            [20, 19, 39]
+        );
+        let layout_string = layout.to_string();
+        assert_eq!(
+            &layout_string[5..],
+            "80x24,0,0{20x24,0,0,0,19x24,21,0,1,39x24,41,0,2}"
+        );
+        assert_eq!(Layout::parse(&layout_string).unwrap(), layout);
+
+        let window = serde_yaml::from_str::<config::Window>(
+            "{ left: { width: 30% }, right: { top: {}, bottom: { height: '10' } } }",
+        )
+        .unwrap();
+        let layout = Layout::from_split(&window.root_split, 100, 40);
+        assert_eq!(
+            layout.to_string()[5..],
+            *"100x40,0,0{30x40,0,0,0,69x40,31,0[69x29,31,0,1,69x10,31,30,2]}"
+        );
+    }
+
+    /// Random split trees with sizes and some pane fields
+    fn arb_split() -> impl Strategy<Value = Split> {
+        let size = prop_oneof![
+            Just(None),
+            (1u32..100)
+                .prop_filter("50% isn't written", |p| *p != 50)
+                .prop_map(|p| Some(format!("{}%", p))),
+            (1u32..200).prop_map(|cells| Some(cells.to_string())),
+        ];
+        let pane = (proptest::option::of("[a-z ]{1,8}"), any::<bool>()).prop_map(
+            |(shell_command, active)| {
+                Split::Pane(Pane {
+                    shell_command,
+                    active,
+                    ..Default::default()
+                })
+            },
+        );
+        pane.prop_recursive(4, 16, 2, move |inner| {
+            let parts = (size.clone(), inner.clone(), size.clone(), inner);
+            prop_oneof![
+                parts
+                    .clone()
+                    .prop_map(|(left_width, left, right_width, right)| {
+                        Split::H {
+                            left: HSplitPart {
+                                width: left_width,
+                                split: Box::new(left),
+                                ..Default::default()
+                            },
+                            right: HSplitPart {
+                                width: right_width,
+                                split: Box::new(right),
+                                ..Default::default()
+                            },
+                        }
+                    }),
+                parts.prop_map(|(top_height, top, bottom_height, bottom)| Split::V {
+                    top: VSplitPart {
+                        height: top_height,
+                        split: Box::new(top),
+                        ..Default::default()
+                    },
+                    bottom: VSplitPart {
+                        height: bottom_height,
+                        split: Box::new(bottom),
+                        ..Default::default()
+                    },
+                }),
+            ]
+        })
+    }
+
+    /// Random layouts of `geom` like tmux reports them: 2 to 4 parts per
+    /// cell, split along the other axis than the parent cell's (`parent`
+    /// being `true` for horizontal), each at least one cell wide
+    fn arb_layout(geom: PaneGeom, depth: u32, parent: Option<bool>) -> BoxedStrategy<Layout> {
+        let mut cells = vec![Just(Layout::Pane(geom)).boxed()];
+        for horizontal in [true, false] {
+            let len = if horizontal {
+                geom.width()
+            } else {
+                geom.height()
+            };
+            if depth == 0 || parent == Some(horizontal) || len < 3 {
+                continue;
+            }
+            let max_parts = 4.min(len.div_ceil(2) as usize);
+            let split = (
+                2..=max_parts,
+                proptest::collection::vec(1u32..10, max_parts),
+            )
+                .prop_flat_map(move |(count, weights)| {
+                    part_geoms(geom, horizontal, &weights[..count])
+                        .into_iter()
+                        .map(|part| arb_layout(part, depth - 1, Some(horizontal)))
+                        .collect::<Vec<_>>()
+                        .prop_map(move |parts| match horizontal {
+                            true => Layout::H(geom, parts),
+                            false => Layout::V(geom, parts),
+                        })
+                });
+            cells.push(split.boxed());
+        }
+        proptest::strategy::Union::new(cells).boxed()
+    }
+
+    /// Geometries of the parts of `geom` sized by `weights` (at least one
+    /// cell each) with borders between them
+    fn part_geoms(geom: PaneGeom, horizontal: bool, weights: &[u32]) -> Vec<PaneGeom> {
+        let count = weights.len() as u32;
+        let len = if horizontal {
+            geom.width()
+        } else {
+            geom.height()
+        };
+        let spare = len - (count - 1) - count;
+        let total_weight = weights.iter().sum::<u32>();
+        let mut lens = weights
+            .iter()
+            .map(|weight| 1 + spare * weight / total_weight)
+            .collect::<Vec<_>>();
+        *lens.last_mut().unwrap() += len - (count - 1) - lens.iter().sum::<u32>();
+
+        let mut offset = 0;
+        lens.into_iter()
+            .map(|part_len| {
+                let part = match horizontal {
+                    true => PaneGeom {
+                        size: Size::new(part_len, geom.height()),
+                        x_offset: geom.x_offset + offset,
+                        ..geom
+                    },
+                    false => PaneGeom {
+                        size: Size::new(geom.width(), part_len),
+                        y_offset: geom.y_offset + offset,
+                        ..geom
+                    },
+                };
+                offset += part_len + 1;
+                part
+            })
+            .collect()
+    }
+
+    fn arb_window_layout() -> impl Strategy<Value = Layout> {
+        (20u32..300, 10u32..100).prop_flat_map(|(width, height)| {
+            let geom = PaneGeom {
+                size: Size::new(width, height),
+                ..Default::default()
+            };
+            arb_layout(geom, 4, None)
+        })
+    }
+
+    /// The tree of cells of `layout` without their geometry
+    fn shape(layout: &Layout) -> String {
+        let parts = |parts: &[Layout]| parts.iter().map(shape).collect::<Vec<_>>().join(",");
+        match layout {
+            Layout::Pane(_) => "p".to_string(),
+            Layout::H(_, parts_) => format!("{{{}}}", parts(parts_)),
+            Layout::V(_, parts_) => format!("[{}]", parts(parts_)),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_split_serde_round_trip(split in arb_split()) {
+            let yaml = serde_yaml::to_string(&split).unwrap();
+            prop_assert_eq!(serde_yaml::from_str::<Split>(&yaml).unwrap(), split);
+        }
+
+        #[test]
+        fn test_layout_round_trip(layout in arb_window_layout()) {
+            let (width, height) = (layout.width(), layout.height());
+            prop_assert_eq!(Layout::parse(&layout.to_string()).unwrap(), layout.clone());
+
+            // Cell sizes recreate the layout exactly, also by the commands
+            // creating the window.
+            let split = layout.clone().into_split(Sizes::Cells);
+            prop_assert_eq!(Layout::from_split(&split, width, height), layout.clone());
+            let window = config::Window {
+                root_split: split.into_root(),
+                ..Default::default()
+            };
+            let sequence = TmuxCommandBuilder::new("tmux", Vec::<&str>::new())
+                .new_window(&window, &Default::default(), None)
+                .into_command_sequence();
+            let simulated = simulate::simulate(&sequence, width, height).unwrap();
+            prop_assert_eq!(simulated.layout(), layout.clone());
+            for sizes in [Sizes::Percent, Sizes::None] {
+                let split = layout.clone().into_split(sizes);
+                let yaml = serde_yaml::to_string(&split).unwrap();
+                prop_assert_eq!(serde_yaml::from_str::<Split>(&yaml).unwrap(), split.clone());
+                prop_assert_eq!(shape(&Layout::from_split(&split, width, height)), shape(&layout));
+            }
+        }
+    }
 
     #[test]
     fn sample1() {
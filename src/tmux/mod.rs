@@ -1,7 +1,17 @@
 mod command;
-pub use command::{QueryScope, SessionSelectMode, TmuxCommandBuilder};
+mod wait;
+pub use command::{QueryScope, SessionSelectMode, TmuxCommandBuilder, TmuxSocket};
 
 pub mod layout;
 pub use layout::Layout;
 
 pub mod import;
+
+pub mod exec;
+pub use exec::ExecutionReport;
+
+pub mod retry;
+
+pub mod simulate;
+
+pub mod version;
@@ -0,0 +1,126 @@
+//! Retries of tmux invocations failing for transient reasons.
+//!
+//! Right after `kill-server` (or while another tmux-layout starts the
+//! server), tmux may lose its connection to the server although the next
+//! attempt succeeds. With `--retries N`, such invocations are repeated up to
+//! `N` times with an exponential backoff. Other failures are never retried,
+//! including a server not running at all (the usual state before the first
+//! session is created).
+
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::logging;
+
+/// Delay before the first retry (doubled for every further retry)
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Whether `stderr` of tmux indicates a lost connection to the server which
+/// may go away by retrying.
+pub fn is_transient_error(stderr: &str) -> bool {
+    let connection_lost = ["lost server", "server exited unexpectedly"]
+        .iter()
+        .any(|message| stderr.starts_with(message));
+    // Without a socket file, there is no server to connect to.
+    let connection_failed = stderr.starts_with("error connecting to")
+        && !stderr.trim_end().ends_with("(No such file or directory)");
+    connection_lost || connection_failed
+}
+
+/// Like [`Command::output`], retrying transient errors up to `retries` times.
+pub fn output(command: &mut Command, retries: u32) -> io::Result<Output> {
+    let mut attempt = 0;
+    loop {
+        let output = command.output()?;
+        if output.status.success()
+            || !is_transient_error(&String::from_utf8_lossy(&output.stderr))
+            || !backoff(&mut attempt, retries)
+        {
+            return Ok(output);
+        }
+    }
+}
+
+/// Like [`Command::status`] (keeping STDIN and STDOUT), retrying transient
+/// errors up to `retries` times. STDERR is forwarded after tmux exits if
+/// retries are enabled. Commands attaching a client keep the terminal as
+/// their STDERR and aren't retried.
+pub fn status(command: &mut Command, retries: u32) -> io::Result<ExitStatus> {
+    if retries == 0 || is_interactive(command) {
+        return command.status();
+    }
+
+    command.stderr(Stdio::piped());
+    let mut attempt = 0;
+    loop {
+        let output = command.spawn()?.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success()
+            || !is_transient_error(&stderr)
+            || !backoff(&mut attempt, retries)
+        {
+            io::stderr().write_all(&output.stderr)?;
+            return Ok(output.status);
+        }
+    }
+}
+
+/// Whether the tmux `command` attaches a client to a session
+fn is_interactive(command: &Command) -> bool {
+    command
+        .get_args()
+        .any(|arg| arg == OsStr::new("attach-session"))
+}
+
+/// Sleeps before the next attempt. Returns `false` if there are no retries
+/// left.
+fn backoff(attempt: &mut u32, retries: u32) -> bool {
+    if *attempt >= retries {
+        return false;
+    }
+    let delay = INITIAL_BACKOFF * 2u32.saturating_pow(*attempt);
+    *attempt += 1;
+    logging::debug(&format!(
+        "transient tmux error, retrying in {} ms ({}/{})",
+        delay.as_millis(),
+        attempt,
+        retries
+    ));
+    thread::sleep(delay);
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_transient_error() {
+        assert!(!is_transient_error(
+            "no server running on /tmp/tmux-0/default\n"
+        ));
+        assert!(!is_transient_error(
+            "error connecting to /tmp/tmux-0/default (No such file or directory)\n"
+        ));
+        assert!(is_transient_error(
+            "error connecting to /tmp/tmux-0/default (Connection refused)\n"
+        ));
+        assert!(is_transient_error("lost server\n"));
+        assert!(is_transient_error("server exited unexpectedly\n"));
+        assert!(!is_transient_error("duplicate session: dev\n"));
+        assert!(!is_transient_error("can't find window: 3\n"));
+    }
+
+    #[test]
+    fn test_is_interactive() {
+        let mut command = Command::new("tmux");
+        command.args(["new-session", "-d", ";", "attach-session", "-t", "dev:"]);
+        assert!(is_interactive(&command));
+        let mut command = Command::new("tmux");
+        command.args(["list-sessions"]);
+        assert!(!is_interactive(&command));
+    }
+}
@@ -0,0 +1,678 @@
+//! Offline interpretation of generated tmux commands. A [`Window`] replays
+//! the `split-window`, `kill-pane`, `select-pane`, `swap-pane` and
+//! `select-layout` commands of a window the way tmux applies them to its
+//! layout, so the resulting pane geometry can be checked without a running
+//! tmux server.
+//!
+//! ```
+//! use tmux_layout::tmux::simulate::Window;
+//!
+//! let mut window = Window::new(80, 24);
+//! window.apply(&["split-window", "-h", "-l", "30"]).unwrap();
+//! assert_eq!(window.layout().to_string(), "8ee5,80x24,0,0{49x24,0,0,0,30x24,50,0,1}");
+//! assert_eq!(window.active_pane(), 1);
+//! ```
+
+use std::collections::HashMap;
+use thiserror::Error;
+
+use super::exec::CommandSequence;
+use super::layout::{Layout, PaneGeom, Size};
+
+/// A command tmux would reject (or the simulation doesn't support)
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{command}: {message}")]
+pub struct Error {
+    pub command: String,
+    pub message: String,
+}
+
+/// The panes of a virtual tmux window
+#[derive(Debug, Clone)]
+pub struct Window {
+    size: Size,
+    root: Cell,
+    /// ID of the active pane
+    active: u32,
+    /// When each pane (by ID) was last active, for picking among adjacent
+    /// panes like tmux
+    last_active: HashMap<u32, u64>,
+    clock: u64,
+    next_id: u32,
+}
+
+/// A cell of the layout tree: a pane or a split into parts along one axis
+#[derive(Debug, Clone)]
+enum Cell {
+    Pane {
+        id: u32,
+        size: Size,
+    },
+    Split {
+        horizontal: bool,
+        size: Size,
+        parts: Vec<Cell>,
+    },
+}
+
+impl Cell {
+    fn size(&self) -> Size {
+        match self {
+            Cell::Pane { size, .. } | Cell::Split { size, .. } => *size,
+        }
+    }
+
+    fn size_mut(&mut self) -> &mut Size {
+        match self {
+            Cell::Pane { size, .. } | Cell::Split { size, .. } => size,
+        }
+    }
+
+    fn pane_ids(&self, ids: &mut Vec<u32>) {
+        match self {
+            Cell::Pane { id, .. } => ids.push(*id),
+            Cell::Split { parts, .. } => parts.iter().for_each(|part| part.pane_ids(ids)),
+        }
+    }
+
+    /// Indexes of the parts leading to the pane `id`
+    fn path_of(&self, id: u32) -> Option<Vec<usize>> {
+        match self {
+            Cell::Pane { id: pane_id, .. } => (*pane_id == id).then(Vec::new),
+            Cell::Split { parts, .. } => parts.iter().enumerate().find_map(|(i, part)| {
+                let mut path = part.path_of(id)?;
+                path.insert(0, i);
+                Some(path)
+            }),
+        }
+    }
+
+    fn at_mut(&mut self, path: &[usize]) -> &mut Cell {
+        match (path.split_first(), self) {
+            (None, cell) => cell,
+            (Some((i, rest)), Cell::Split { parts, .. }) => parts[*i].at_mut(rest),
+            (Some(_), Cell::Pane { .. }) => unreachable!("path leads through a pane"),
+        }
+    }
+
+    /// Grows the cell by `change` cells along the axis (`layout_resize_adjust`
+    /// of tmux): parts across it grow alike, parts along it one cell at a
+    /// time, in turn.
+    fn grow(&mut self, horizontal: bool, change: u32) {
+        let size = self.size_mut();
+        match horizontal {
+            true => size.width += change,
+            false => size.height += change,
+        }
+        let Cell::Split {
+            horizontal: split_horizontal,
+            parts,
+            ..
+        } = self
+        else {
+            return;
+        };
+        if *split_horizontal != horizontal {
+            parts
+                .iter_mut()
+                .for_each(|part| part.grow(horizontal, change));
+            return;
+        }
+        let count = parts.len() as u32;
+        for (i, part) in parts.iter_mut().enumerate() {
+            let share = change / count + u32::from((i as u32) < change % count);
+            if share > 0 {
+                part.grow(horizontal, share);
+            }
+        }
+    }
+
+    fn to_layout(&self, x_offset: u32, y_offset: u32) -> Layout {
+        let geom = PaneGeom {
+            size: self.size(),
+            x_offset,
+            y_offset,
+        };
+        let Cell::Split {
+            horizontal, parts, ..
+        } = self
+        else {
+            return Layout::Pane(geom);
+        };
+        let mut offset = 0;
+        let parts = parts
+            .iter()
+            .map(|part| {
+                let layout = match horizontal {
+                    true => part.to_layout(x_offset + offset, y_offset),
+                    false => part.to_layout(x_offset, y_offset + offset),
+                };
+                let size = part.size();
+                offset += 1 + if *horizontal { size.width } else { size.height };
+                layout
+            })
+            .collect();
+        match horizontal {
+            true => Layout::H(geom, parts),
+            false => Layout::V(geom, parts),
+        }
+    }
+
+    /// Cells of `layout` with pane IDs taken from `ids` in order
+    fn from_layout(layout: &Layout, ids: &mut impl Iterator<Item = u32>) -> Option<Cell> {
+        let size = layout.geom().size;
+        let (horizontal, parts) = match layout {
+            Layout::Pane(_) => {
+                return Some(Cell::Pane {
+                    id: ids.next()?,
+                    size,
+                })
+            }
+            Layout::H(_, parts) => (true, parts),
+            Layout::V(_, parts) => (false, parts),
+        };
+        let parts = parts
+            .iter()
+            .map(|part| Cell::from_layout(part, ids))
+            .collect::<Option<_>>()?;
+        Some(Cell::Split {
+            horizontal,
+            size,
+            parts,
+        })
+    }
+}
+
+impl Window {
+    /// A window of `width` x `height` cells with a single pane
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = Size { width, height };
+        Window {
+            size,
+            root: Cell::Pane { id: 0, size },
+            active: 0,
+            last_active: HashMap::from([(0, 0)]),
+            clock: 0,
+            next_id: 1,
+        }
+    }
+
+    /// The panes in their current geometry. Pane IDs are written in index
+    /// order (like [`Layout`]'s `Display` does anyway).
+    pub fn layout(&self) -> Layout {
+        self.root.to_layout(0, 0)
+    }
+
+    /// Index of the active pane
+    pub fn active_pane(&self) -> usize {
+        self.pane_ids()
+            .iter()
+            .position(|id| *id == self.active)
+            .unwrap()
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.pane_ids().len()
+    }
+
+    /// Applies the tmux command `args`. `new-session` and `new-window` start
+    /// over with a single pane (only one window is simulated), window
+    /// targets are ignored, and commands not affecting the layout are
+    /// skipped.
+    pub fn apply(&mut self, args: &[impl AsRef<str>]) -> Result<(), Error> {
+        let args = args.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let Some((command, args)) = args.split_first() else {
+            return Ok(());
+        };
+        let error = |message: String| Error {
+            command: command.to_string(),
+            message,
+        };
+        match *command {
+            "new-session" | "new-window" => {
+                *self = Window::new(self.size.width, self.size.height);
+            }
+            "split-window" => {
+                let flags = Flags::parse(args, "cetlpF").map_err(error)?;
+                let target = self.target_pane(flags.value('t')).map_err(error)?;
+                let size = match (flags.value('l'), flags.value('p')) {
+                    (Some(size), _) => Some(size.to_string()),
+                    (None, Some(percentage)) => Some(format!("{}%", percentage)),
+                    (None, None) => None,
+                };
+                let horizontal = flags.has('h');
+                let before = flags.has('b');
+                self.split(target, horizontal, before, size.as_deref())
+                    .map_err(error)?;
+            }
+            "kill-pane" => {
+                let flags = Flags::parse(args, "t").map_err(error)?;
+                let target = self.target_pane(flags.value('t')).map_err(error)?;
+                self.kill(target).map_err(error)?;
+            }
+            "select-pane" => {
+                let flags = Flags::parse(args, "tTP").map_err(error)?;
+                let target = self.target_pane(flags.value('t')).map_err(error)?;
+                // Only enables or disables the input, or sets the style
+                if flags.has('e') || flags.has('d') || flags.has('P') || flags.has('g') {
+                    return Ok(());
+                }
+                let direction = ['L', 'R', 'U', 'D'].iter().copied().find(|d| flags.has(*d));
+                let pane = match direction {
+                    Some(direction) => self.adjacent_pane(target, direction),
+                    None => Some(target),
+                };
+                if let Some(pane) = pane {
+                    self.activate(pane);
+                }
+            }
+            "swap-pane" => {
+                let flags = Flags::parse(args, "st").map_err(error)?;
+                let source = self.target_pane(flags.value('s')).map_err(error)?;
+                let target = self.target_pane(flags.value('t')).map_err(error)?;
+                self.swap(source, target);
+                if !flags.has('d') {
+                    self.activate(source);
+                }
+            }
+            "select-layout" => {
+                let flags = Flags::parse(args, "t").map_err(error)?;
+                let [layout] = flags.args[..] else {
+                    return Err(error("expected a layout".to_string()));
+                };
+                self.select_layout(layout).map_err(error)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn pane_ids(&self) -> Vec<u32> {
+        let mut ids = Vec::new();
+        self.root.pane_ids(&mut ids);
+        ids
+    }
+
+    fn activate(&mut self, id: u32) {
+        self.clock += 1;
+        self.active = id;
+        self.last_active.insert(id, self.clock);
+    }
+
+    /// ID of the pane of `target` (`[session]:[window].<index>` or the
+    /// active pane)
+    fn target_pane(&self, target: Option<&str>) -> Result<u32, String> {
+        let window = target.map_or("", |target| {
+            target.split_once(':').map_or(target, |(_, window)| window)
+        });
+        let index = match window.rsplit_once('.') {
+            Some((_, index)) if !index.is_empty() => index,
+            _ => return Ok(self.active),
+        };
+        index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| self.pane_ids().get(index).copied())
+            .ok_or_else(|| format!("can't find pane: {}", index))
+    }
+
+    /// Splits the pane `id` like `layout_split_pane` of tmux. The new pane
+    /// (`before` the pane or after it) gets `size` cells (`N` or `N%` of the
+    /// pane), or else half of them (rounded down), and becomes active.
+    fn split(
+        &mut self,
+        id: u32,
+        horizontal: bool,
+        before: bool,
+        size: Option<&str>,
+    ) -> Result<(), String> {
+        let path = self.root.path_of(id).unwrap();
+        let pane_size = self.root.at_mut(&path).size();
+        let len = if horizontal {
+            pane_size.width
+        } else {
+            pane_size.height
+        };
+        if len < 3 {
+            return Err("no space for new pane".to_string());
+        }
+        let size = size
+            .map(|size| {
+                let cells = match size.strip_suffix('%') {
+                    Some(percentage) => percentage.parse::<u32>().map(|p| len * p / 100),
+                    None => size.parse::<u32>(),
+                };
+                cells.map_err(|_| format!("invalid size: {}", size))
+            })
+            .transpose()?;
+        // Cells of the second part
+        let second = match size {
+            None => len.div_ceil(2) - 1,
+            Some(size) if before => (len - 1).saturating_sub(size),
+            Some(size) => size,
+        };
+        let second = second.clamp(1, len - 2);
+        let first = len - 1 - second;
+
+        let part_size = |len: u32| match horizontal {
+            true => Size {
+                width: len,
+                ..pane_size
+            },
+            false => Size {
+                height: len,
+                ..pane_size
+            },
+        };
+        let new_id = self.next_id;
+        self.next_id += 1;
+        let (first_id, second_id) = if before { (new_id, id) } else { (id, new_id) };
+        let parts = vec![
+            Cell::Pane {
+                id: first_id,
+                size: part_size(first),
+            },
+            Cell::Pane {
+                id: second_id,
+                size: part_size(second),
+            },
+        ];
+
+        // Parts along the same axis join the parent's parts.
+        let (index, parent_path) = match path.split_last() {
+            Some((index, parent_path)) => (*index, parent_path),
+            None => (0, &[][..]),
+        };
+        match self.root.at_mut(parent_path) {
+            Cell::Split {
+                horizontal: parent_horizontal,
+                parts: parent_parts,
+                ..
+            } if *parent_horizontal == horizontal && !path.is_empty() => {
+                parent_parts.splice(index..=index, parts);
+            }
+            _ => {
+                *self.root.at_mut(&path) = Cell::Split {
+                    horizontal,
+                    size: pane_size,
+                    parts,
+                };
+            }
+        }
+        self.activate(new_id);
+        Ok(())
+    }
+
+    /// Removes the pane `id`, whose cells go to the previous part (or the
+    /// next one for the first part). The most recently active pane becomes
+    /// active in place of a killed active one.
+    fn kill(&mut self, id: u32) -> Result<(), String> {
+        let path = self.root.path_of(id).unwrap();
+        let Some((index, parent_path)) = path.split_last() else {
+            return Err("can't kill the only pane of the window".to_string());
+        };
+        let parent = self.root.at_mut(parent_path);
+        let Cell::Split {
+            horizontal, parts, ..
+        } = &mut *parent
+        else {
+            unreachable!("parent of a pane is a split")
+        };
+        let removed = parts.remove(*index).size();
+        let freed = 1 + if *horizontal {
+            removed.width
+        } else {
+            removed.height
+        };
+        let neighbor = index.saturating_sub(1);
+        parts[neighbor].grow(*horizontal, freed);
+        let only_part = if parts.len() == 1 { parts.pop() } else { None };
+        if let Some(only_part) = only_part {
+            *parent = only_part;
+        }
+
+        self.last_active.remove(&id);
+        if self.active == id {
+            let (&last, _) = self
+                .last_active
+                .iter()
+                .max_by_key(|(_, time)| **time)
+                .unwrap();
+            self.activate(last);
+        }
+        Ok(())
+    }
+
+    fn swap(&mut self, source: u32, target: u32) {
+        let source_path = self.root.path_of(source).unwrap();
+        let target_path = self.root.path_of(target).unwrap();
+        if let Cell::Pane { id, .. } = self.root.at_mut(&source_path) {
+            *id = target;
+        }
+        if let Cell::Pane { id, .. } = self.root.at_mut(&target_path) {
+            *id = source;
+        }
+    }
+
+    fn select_layout(&mut self, layout: &str) -> Result<(), String> {
+        let layout =
+            Layout::parse(layout).map_err(|_| format!("unsupported layout: {}", layout))?;
+        let ids = self.pane_ids();
+        let mut geoms = Vec::new();
+        pane_geoms(&layout, &mut geoms);
+        if geoms.len() != ids.len() {
+            return Err(format!(
+                "layout has {} panes, window has {}",
+                geoms.len(),
+                ids.len()
+            ));
+        }
+        self.root = Cell::from_layout(&layout, &mut ids.into_iter()).unwrap();
+        Ok(())
+    }
+
+    /// The pane next to `id` in `direction` (`L`, `R`, `U` or `D`),
+    /// wrapping around at the edges of the window. Among several, the most
+    /// recently active one (like `window_pane_find_*` of tmux).
+    fn adjacent_pane(&self, id: u32, direction: char) -> Option<u32> {
+        let mut geoms = Vec::new();
+        pane_geoms(&self.layout(), &mut geoms);
+        let ids = self.pane_ids();
+        let geom = geoms[ids.iter().position(|pane| *pane == id)?];
+        let (width, height) = (self.size.width, self.size.height);
+
+        let horizontal = matches!(direction, 'L' | 'R');
+        let edge = match direction {
+            'L' if geom.x_offset == 0 => width + 1,
+            'L' => geom.x_offset,
+            'R' if geom.x_offset + geom.width() + 1 >= width => 0,
+            'R' => geom.x_offset + geom.width() + 1,
+            'U' if geom.y_offset == 0 => height + 1,
+            'U' => geom.y_offset,
+            _ if geom.y_offset + geom.height() + 1 >= height => 0,
+            _ => geom.y_offset + geom.height() + 1,
+        };
+        // The range of cells along the edge, the end being inclusive like
+        // in tmux
+        let (start, end) = match horizontal {
+            true => (geom.y_offset, geom.y_offset + geom.height()),
+            false => (geom.x_offset, geom.x_offset + geom.width()),
+        };
+        ids.iter()
+            .zip(&geoms)
+            .filter(|(_, other)| match direction {
+                'L' => other.x_offset + other.width() + 1 == edge,
+                'R' => other.x_offset == edge,
+                'U' => other.y_offset + other.height() + 1 == edge,
+                _ => other.y_offset == edge,
+            })
+            .filter(|(_, other)| {
+                let (other_start, other_len) = match horizontal {
+                    true => (other.y_offset, other.height()),
+                    false => (other.x_offset, other.width()),
+                };
+                let other_end = other_start + other_len - 1;
+                (start..=end).contains(&other_start)
+                    || (start..=end).contains(&other_end)
+                    || (other_start < start && other_end > end)
+            })
+            .map(|(id, _)| *id)
+            .max_by_key(|id| self.last_active.get(id).copied().unwrap_or(0))
+    }
+}
+
+fn pane_geoms(layout: &Layout, geoms: &mut Vec<PaneGeom>) {
+    match layout {
+        Layout::Pane(geom) => geoms.push(*geom),
+        Layout::H(_, parts) | Layout::V(_, parts) => {
+            parts.iter().for_each(|part| pane_geoms(part, geoms))
+        }
+    }
+}
+
+/// Replays the commands of `sequence` (see
+/// [`TmuxCommandBuilder::into_command_sequence`](super::TmuxCommandBuilder::into_command_sequence))
+/// in a window of `width` x `height` cells. With several windows, the last
+/// one remains.
+pub fn simulate(sequence: &CommandSequence, width: u32, height: u32) -> Result<Window, Error> {
+    let mut window = Window::new(width, height);
+    for command in &sequence.commands {
+        let args = command
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>();
+        window.apply(&args)?;
+    }
+    Ok(window)
+}
+
+/// Flags (`-x`, possibly combined like `-hb`) and further arguments of a
+/// command
+struct Flags<'a> {
+    flags: Vec<(char, Option<&'a str>)>,
+    args: Vec<&'a str>,
+}
+
+impl<'a> Flags<'a> {
+    /// Parses `args`, where the flags in `with_value` take a value.
+    fn parse(args: &[&'a str], with_value: &str) -> Result<Self, String> {
+        let mut flags = Vec::new();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            let Some(chars) = arg.strip_prefix('-').filter(|chars| !chars.is_empty()) else {
+                return Ok(Flags {
+                    flags,
+                    args: std::iter::once(*arg).chain(rest.copied()).collect(),
+                });
+            };
+            for (i, flag) in chars.char_indices() {
+                if !with_value.contains(flag) {
+                    flags.push((flag, None));
+                    continue;
+                }
+                let value = match &chars[i + flag.len_utf8()..] {
+                    "" => rest
+                        .next()
+                        .copied()
+                        .ok_or_else(|| format!("-{} expects a value", flag))?,
+                    value => value,
+                };
+                flags.push((flag, Some(value)));
+                break;
+            }
+        }
+        Ok(Flags {
+            flags,
+            args: Vec::new(),
+        })
+    }
+
+    fn has(&self, flag: char) -> bool {
+        self.flags.iter().any(|(f, _)| *f == flag)
+    }
+
+    fn value(&self, flag: char) -> Option<&'a str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(f, _)| *f == flag)
+            .and_then(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config;
+    use crate::cwd::Cwd;
+    use crate::preview;
+    use crate::tmux::TmuxCommandBuilder;
+
+    #[test]
+    fn test_split_and_kill() {
+        let mut window = Window::new(80, 24);
+        window.apply(&["split-window", "-t", "dev:", "-h"]).unwrap();
+        window
+            .apply(&["split-window", "-v", "-b", "-l", "25%"])
+            .unwrap();
+        assert_eq!(
+            window.layout().to_string()[5..],
+            *"80x24,0,0{40x24,0,0,0,39x24,41,0[39x6,41,0,1,39x17,41,7,2]}"
+        );
+        assert_eq!(window.active_pane(), 1);
+
+        // Back to the bottom pane, the most recently active one below
+        window.apply(&["select-pane", "-t", "dev:", "-D"]).unwrap();
+        assert_eq!(window.active_pane(), 2);
+        window.apply(&["select-pane", "-L"]).unwrap();
+        assert_eq!(window.active_pane(), 0);
+
+        // The freed cells go to the next part
+        window.apply(&["kill-pane", "-t", "dev:.0"]).unwrap();
+        assert_eq!(
+            window.layout().to_string()[5..],
+            *"80x24,0,0[80x6,0,0,0,80x17,0,7,1]"
+        );
+        assert_eq!(window.active_pane(), 1);
+        window.apply(&["kill-pane", "-t", ":.1"]).unwrap();
+        assert_eq!(window.layout().to_string()[5..], *"80x24,0,0,0");
+        assert!(window.apply(&["kill-pane"]).is_err());
+
+        let mut window = Window::new(2, 24);
+        let err = window.apply(&["split-window", "-h"]).unwrap_err();
+        assert_eq!(err.to_string(), "split-window: no space for new pane");
+    }
+
+    #[test]
+    fn test_simulate_window() {
+        let window = serde_yaml::from_str::<config::Window>(
+            r#"
+            left:
+              width: 30%
+              top: {}
+              bottom: {}
+            right:
+              panes: [a, b, c]
+              ratio: 1:2:1
+            "#,
+        )
+        .unwrap();
+        let sequence = TmuxCommandBuilder::new("tmux", Vec::<&str>::new())
+            .new_window(&window, &Cwd::default(), None)
+            .into_command_sequence();
+        let simulated = simulate(&sequence, 120, 40).unwrap();
+
+        let layout = simulated.layout();
+        assert_eq!(layout, Layout::from_split(&window.root_split, 120, 40));
+        let mut geoms = Vec::new();
+        pane_geoms(&layout, &mut geoms);
+        let rects = preview::pane_rects(&window.root_split, 120, 40);
+        assert_eq!(geoms.len(), rects.len());
+        for (geom, (rect, _)) in geoms.iter().zip(rects) {
+            assert_eq!(
+                (geom.x_offset, geom.y_offset, geom.width(), geom.height()),
+                (rect.x, rect.y, rect.width, rect.height)
+            );
+        }
+    }
+}
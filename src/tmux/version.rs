@@ -0,0 +1,94 @@
+use std::fmt;
+use std::process::Command;
+
+/// A tmux release version (`tmux -V`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parses the output of `tmux -V`, e.g. `tmux 3.3a` or `tmux next-3.4`.
+    ///
+    /// Returns `None` for builds without a release number (like OpenBSD's
+    /// base system tmux), which are assumed to support every feature.
+    pub fn parse(output: &str) -> Option<Version> {
+        let version = output.trim().strip_prefix("tmux ")?;
+        let version = version.strip_prefix("next-").unwrap_or(version);
+
+        let (major, rest) = version.split_once('.')?;
+        let minor_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+
+        Some(Version {
+            major: major.parse().ok()?,
+            minor: rest[..minor_len].parse().ok()?,
+        })
+    }
+
+    pub fn supports(self, feature: Feature) -> bool {
+        self >= feature.min_version()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// tmux features not available in all supported tmux versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Percentage sizes for `split-window -l`
+    PercentageSizes,
+    /// `display-popup`
+    Popups,
+    /// `display-popup -B`
+    BorderlessPopups,
+    /// Pane options (`set-option -p`)
+    PaneOptions,
+    /// tmux commands run by `run-shell -C`
+    RunShellCommands,
+}
+
+impl Feature {
+    pub fn min_version(self) -> Version {
+        match self {
+            Feature::PercentageSizes => Version::new(3, 1),
+            Feature::PaneOptions => Version::new(3, 0),
+            Feature::Popups => Version::new(3, 2),
+            Feature::RunShellCommands => Version::new(3, 2),
+            Feature::BorderlessPopups => Version::new(3, 3),
+        }
+    }
+}
+
+/// Runs `tmux -V`. Returns `Ok(None)` if the version can't be determined
+/// from the output.
+pub fn query_version(mut command: Command) -> std::io::Result<Option<Version>> {
+    command.arg("-V");
+    crate::logging::command(&command);
+    let output = command.output()?;
+    Ok(Version::parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(Version::parse("tmux 3.3a\n"), Some(Version::new(3, 3)));
+        assert_eq!(Version::parse("tmux 2.9"), Some(Version::new(2, 9)));
+        assert_eq!(Version::parse("tmux next-3.4"), Some(Version::new(3, 4)));
+        assert_eq!(Version::parse("tmux openbsd-7.4"), None);
+        assert_eq!(Version::parse(""), None);
+    }
+}
@@ -0,0 +1,128 @@
+//! Deferred `send_keys` of panes with `wait_for` or `delay`: a shell loop
+//! started with `run-shell -b -t <pane>` polls the condition and types the
+//! keys once it holds, so `create` doesn't block.
+
+use crate::config::WaitFor;
+use crate::shell;
+
+/// Seconds to wait if `wait_for` has no `timeout`
+pub const DEFAULT_TIMEOUT: u32 = 30;
+
+/// Script for `run-shell -b -t <pane>` which sleeps `delay` seconds, waits
+/// for `wait_for` and then runs `tmux send-keys` with each of `key_lists`
+/// for the pane. `tmux` is the path of the tmux binary (which finds the
+/// server via `$TMUX`). Input of `read_only` panes is enabled only while
+/// sending the keys.
+///
+/// tmux expands formats (`#{pane_id}`, relative to the target pane) in the
+/// script, so all other `#` are escaped.
+pub fn script(
+    wait_for: &WaitFor,
+    delay: Option<u32>,
+    tmux: &str,
+    key_lists: &[Vec<&str>],
+    read_only: bool,
+) -> String {
+    let tmux = escape_formats(&shell::quote(tmux));
+
+    let mut conditions = Vec::new();
+    if let Some(port) = wait_for.port {
+        let host = wait_for.host.as_deref().unwrap_or("localhost");
+        let probe = format!(": </dev/tcp/{}/{}", host, port);
+        conditions.push(format!("bash -c {}", escape_formats(&shell::quote(&probe))));
+    }
+    if let Some(text) = &wait_for.text {
+        let pane = match wait_for.pane {
+            Some(index) => format!("#{{window_id}}.{}", index),
+            None => "#{pane_id}".to_string(),
+        };
+        conditions.push(format!(
+            "{} capture-pane -p -t '{}' | grep -qF -- {}",
+            tmux,
+            pane,
+            escape_formats(&shell::quote(text))
+        ));
+    }
+    let condition = match conditions.is_empty() {
+        true => "true".to_string(),
+        false => conditions.join(" && "),
+    };
+
+    let timeout = wait_for.timeout.unwrap_or(DEFAULT_TIMEOUT);
+    let mut send_keys = key_lists
+        .iter()
+        .map(|keys| {
+            let keys = keys
+                .iter()
+                .map(|key| escape_formats(&shell::quote(key)))
+                .collect::<Vec<_>>();
+            format!("{} send-keys -t '#{{pane_id}}' {}", tmux, keys.join(" "))
+        })
+        .collect::<Vec<_>>();
+    if read_only {
+        send_keys.insert(0, format!("{} select-pane -e -t '#{{pane_id}}'", tmux));
+        send_keys.push(format!("{} select-pane -d -t '#{{pane_id}}'", tmux));
+    }
+    let sleep = match delay {
+        Some(delay) => format!("sleep {}; ", delay),
+        None => String::new(),
+    };
+    format!(
+        "{{ {sleep}i=0; until {condition}; do \
+         if [ $i -ge {timeout} ]; then \
+         {tmux} display-message -t '#{{pane_id}}' 'wait_for timed out after {timeout}s'; exit 1; \
+         fi; i=$((i+1)); sleep 1; done; {send_keys}; }} >/dev/null 2>&1",
+        send_keys = send_keys.join("; ")
+    )
+}
+
+/// Escapes `#` for format expansion.
+fn escape_formats(s: &str) -> String {
+    s.replace('#', "##")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let wait_for = WaitFor {
+            port: Some(5432),
+            ..Default::default()
+        };
+        assert_eq!(
+            script(&wait_for, None, "tmux", &[vec!["psql", "Enter"]], false),
+            "{ i=0; until bash -c ': </dev/tcp/localhost/5432'; do \
+             if [ $i -ge 30 ]; then \
+             tmux display-message -t '#{pane_id}' 'wait_for timed out after 30s'; exit 1; \
+             fi; i=$((i+1)); sleep 1; done; \
+             tmux send-keys -t '#{pane_id}' psql Enter; } >/dev/null 2>&1"
+        );
+
+        let wait_for = WaitFor {
+            text: Some("ready #1".to_string()),
+            pane: Some(2),
+            timeout: Some(5),
+            ..Default::default()
+        };
+        let script = script(
+            &wait_for,
+            Some(3),
+            "/opt/tmux",
+            &[vec!["echo '#1'", "Enter"], vec![" clear", "Enter"]],
+            true,
+        );
+        assert!(script.starts_with("{ sleep 3; i=0; until "));
+        assert!(script.contains(
+            "until /opt/tmux capture-pane -p -t '#{window_id}.2' | grep -qF -- 'ready ##1'; do"
+        ));
+        assert!(script.contains("if [ $i -ge 5 ]"));
+        assert!(script.contains(
+            "done; /opt/tmux select-pane -e -t '#{pane_id}'; \
+             /opt/tmux send-keys -t '#{pane_id}' 'echo '\\''##1'\\''' Enter; \
+             /opt/tmux send-keys -t '#{pane_id}' ' clear' Enter; \
+             /opt/tmux select-pane -d -t '#{pane_id}'; }"
+        ));
+    }
+}
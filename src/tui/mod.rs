@@ -0,0 +1,415 @@
+//! Interactive layout editor (`tmux-layout edit --tui`).
+//!
+//! Shows one window of the config at a time, with its panes placed by the
+//! same engine as `preview` (see [`preview::pane_rects`]), and edits the
+//! config in place. Saving is left to the caller.
+
+mod ops;
+
+use std::io;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::config::{PartialConfig, Split, Window};
+use crate::cwd::Cwd;
+use crate::preview;
+use ops::Axis;
+
+/// Percent by which panes grow or shrink per key press
+const RESIZE_STEP: i32 = 5;
+
+const HELP: &str = "tab/arrows: select  %/\": split  x: close  </>/+/-: resize  \
+                    e: command  c: cwd  a: active  r: rename  n/p/N: next/prev/new window  \
+                    w: save  q: quit";
+
+/// Position of a window in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowRef {
+    Session(usize, usize),
+    TopLevel(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Command,
+    Cwd,
+    WindowName,
+}
+
+enum Mode {
+    Normal,
+    Input { field: Field, buffer: String },
+}
+
+struct Editor {
+    config: PartialConfig,
+    windows: Vec<WindowRef>,
+    window: usize,
+    pane: usize,
+    mode: Mode,
+    message: String,
+    modified: bool,
+    confirm_quit: bool,
+    confirm_save: bool,
+}
+
+/// Runs the editor on `config` until the user quits. `save` is called with
+/// the edited config whenever the user saves, and whether they insist on it
+/// (by saving again right after `save` failed).
+pub fn run(
+    config: PartialConfig,
+    mut save: impl FnMut(&PartialConfig, bool) -> Result<(), String>,
+) -> io::Result<()> {
+    let mut editor = Editor::new(config);
+    let mut terminal = ratatui::init();
+    let result = editor.run(&mut terminal, &mut save);
+    ratatui::restore();
+    result
+}
+
+impl Editor {
+    fn new(mut config: PartialConfig) -> Editor {
+        if config.sessions.iter().all(|s| s.windows.is_empty()) && config.windows.is_empty() {
+            config.windows.push(Window::default());
+        }
+        let mut editor = Editor {
+            config,
+            windows: Vec::new(),
+            window: 0,
+            pane: 0,
+            mode: Mode::Normal,
+            message: String::new(),
+            modified: false,
+            confirm_quit: false,
+            confirm_save: false,
+        };
+        editor.index_windows();
+        editor
+    }
+
+    fn index_windows(&mut self) {
+        let session_windows = self
+            .config
+            .sessions
+            .iter()
+            .enumerate()
+            .flat_map(|(si, s)| (0..s.windows.len()).map(move |wi| WindowRef::Session(si, wi)));
+        let top_level_windows = (0..self.config.windows.len()).map(WindowRef::TopLevel);
+        self.windows = session_windows.chain(top_level_windows).collect();
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        save: &mut impl FnMut(&PartialConfig, bool) -> Result<(), String>,
+    ) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match &mut self.mode {
+                Mode::Normal => {
+                    if self.handle_key(key.code, save) {
+                        return Ok(());
+                    }
+                }
+                Mode::Input { field, buffer } => match key.code {
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    KeyCode::Enter => {
+                        let (field, value) = (*field, std::mem::take(buffer));
+                        self.mode = Mode::Normal;
+                        self.set_field(field, value);
+                    }
+                    KeyCode::Backspace => {
+                        buffer.pop();
+                    }
+                    KeyCode::Char(c) => buffer.push(c),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Handles a key in normal mode. Returns whether to quit.
+    fn handle_key(
+        &mut self,
+        code: KeyCode,
+        save: &mut impl FnMut(&PartialConfig, bool) -> Result<(), String>,
+    ) -> bool {
+        self.message.clear();
+        let pane_count = ops::pane_count(self.split());
+        let confirm_quit = std::mem::take(&mut self.confirm_quit);
+        let confirm_save = std::mem::take(&mut self.confirm_save);
+        let edited = match code {
+            KeyCode::Char('q') => {
+                if !self.modified || confirm_quit {
+                    return true;
+                }
+                self.confirm_quit = true;
+                self.message = "unsaved changes, press q again to quit".to_string();
+                false
+            }
+            KeyCode::Char('w') => {
+                match save(&self.config, confirm_save) {
+                    Ok(()) => {
+                        self.modified = false;
+                        self.message = "saved".to_string();
+                    }
+                    Err(err) => {
+                        self.confirm_save = true;
+                        self.message = err;
+                    }
+                }
+                false
+            }
+            KeyCode::Tab | KeyCode::Right | KeyCode::Down => {
+                self.pane = (self.pane + 1) % pane_count;
+                false
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Up => {
+                self.pane = (self.pane + pane_count - 1) % pane_count;
+                false
+            }
+            KeyCode::Char('n') => {
+                self.select_window((self.window + 1) % self.windows.len());
+                false
+            }
+            KeyCode::Char('p') => {
+                self.select_window((self.window + self.windows.len() - 1) % self.windows.len());
+                false
+            }
+            KeyCode::Char('N') => {
+                self.new_window();
+                true
+            }
+            KeyCode::Char('%') => self.split_pane(Axis::Horizontal),
+            KeyCode::Char('"') => self.split_pane(Axis::Vertical),
+            KeyCode::Char('x') => {
+                let pane = self.pane;
+                let closed = ops::close_pane(self.split_mut(), pane);
+                self.pane = pane.saturating_sub(1);
+                closed
+            }
+            KeyCode::Char('>') => self.resize_pane(Axis::Horizontal, RESIZE_STEP),
+            KeyCode::Char('<') => self.resize_pane(Axis::Horizontal, -RESIZE_STEP),
+            KeyCode::Char('+') => self.resize_pane(Axis::Vertical, RESIZE_STEP),
+            KeyCode::Char('-') => self.resize_pane(Axis::Vertical, -RESIZE_STEP),
+            KeyCode::Char('a') => {
+                let pane = self.pane;
+                let split = self.split_mut();
+                for (index, p) in split.pane_iter_mut().enumerate() {
+                    p.active = index == pane && !p.active;
+                }
+                true
+            }
+            KeyCode::Char('e') => self.start_input(Field::Command),
+            KeyCode::Char('c') => self.start_input(Field::Cwd),
+            KeyCode::Char('r') => self.start_input(Field::WindowName),
+            _ => false,
+        };
+        self.modified |= edited;
+        false
+    }
+
+    fn window(&self) -> &Window {
+        match self.windows[self.window] {
+            WindowRef::Session(si, wi) => &self.config.sessions[si].windows[wi],
+            WindowRef::TopLevel(wi) => &self.config.windows[wi],
+        }
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        match self.windows[self.window] {
+            WindowRef::Session(si, wi) => &mut self.config.sessions[si].windows[wi],
+            WindowRef::TopLevel(wi) => &mut self.config.windows[wi],
+        }
+    }
+
+    fn split(&self) -> &Split {
+        &self.window().root_split
+    }
+
+    fn split_mut(&mut self) -> &mut Split {
+        &mut self.window_mut().root_split
+    }
+
+    fn select_window(&mut self, window: usize) {
+        self.window = window;
+        self.pane = 0;
+    }
+
+    /// Adds a window after the current one (in the same session).
+    fn new_window(&mut self) {
+        let position = match self.windows[self.window] {
+            WindowRef::Session(si, wi) => {
+                self.config.sessions[si]
+                    .windows
+                    .insert(wi + 1, Window::default());
+                WindowRef::Session(si, wi + 1)
+            }
+            WindowRef::TopLevel(wi) => {
+                self.config.windows.insert(wi + 1, Window::default());
+                WindowRef::TopLevel(wi + 1)
+            }
+        };
+        self.index_windows();
+        let window = self
+            .windows
+            .iter()
+            .position(|w| *w == position)
+            .unwrap_or(0);
+        self.select_window(window);
+    }
+
+    fn split_pane(&mut self, axis: Axis) -> bool {
+        let pane = self.pane;
+        let split = ops::split_pane(self.split_mut(), pane, axis);
+        if split {
+            self.pane = pane + 1;
+        }
+        split
+    }
+
+    fn resize_pane(&mut self, axis: Axis, delta: i32) -> bool {
+        let pane = self.pane;
+        let resized = ops::resize_pane(self.split_mut(), pane, axis, delta);
+        if !resized {
+            self.message = "no split to resize in this direction".to_string();
+        }
+        resized
+    }
+
+    /// Starts editing `field`, prefilled with its current value.
+    fn start_input(&mut self, field: Field) -> bool {
+        let pane = self.split().pane_iter().nth(self.pane);
+        let buffer = match field {
+            Field::Command => pane.and_then(|p| p.shell_command.clone()),
+            Field::Cwd => pane
+                .and_then(|p| p.cwd.to_path())
+                .map(|path| path.to_string_lossy().into_owned()),
+            Field::WindowName => self.window().name.clone(),
+        };
+        self.mode = Mode::Input {
+            field,
+            buffer: buffer.unwrap_or_default(),
+        };
+        false
+    }
+
+    fn set_field(&mut self, field: Field, value: String) {
+        let value = Some(value).filter(|v| !v.is_empty());
+        let pane = self.pane;
+        match field {
+            Field::WindowName => self.window_mut().name = value,
+            Field::Command => {
+                if let Some(p) = ops::pane_mut(self.split_mut(), pane) {
+                    p.shell_command = value;
+                }
+            }
+            Field::Cwd => {
+                if let Some(p) = ops::pane_mut(self.split_mut(), pane) {
+                    p.cwd = value.into();
+                }
+            }
+        }
+        self.modified = true;
+    }
+
+    fn window_label(&self) -> String {
+        let name = self.window().name.as_deref().unwrap_or("(unnamed)");
+        match self.windows[self.window] {
+            WindowRef::Session(si, _) => format!("{}:{}", self.config.sessions[si].name, name),
+            WindowRef::TopLevel(_) => name.to_string(),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [title_area, panes_area, status_area, help_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let modified = if self.modified { " [modified]" } else { "" };
+        let title = format!(
+            "{} ({}/{}){}",
+            self.window_label(),
+            self.window + 1,
+            self.windows.len(),
+            modified
+        );
+        frame.render_widget(
+            Line::styled(title, Style::default().add_modifier(Modifier::BOLD)),
+            title_area,
+        );
+
+        self.draw_panes(frame, panes_area);
+
+        let status = match &self.mode {
+            Mode::Normal => self.message.clone(),
+            Mode::Input { field, buffer } => {
+                let label = match field {
+                    Field::Command => "command",
+                    Field::Cwd => "cwd",
+                    Field::WindowName => "window name",
+                };
+                format!("{}: {}_", label, buffer)
+            }
+        };
+        frame.render_widget(Line::raw(status), status_area);
+        frame.render_widget(
+            Paragraph::new(HELP)
+                .style(Style::default().fg(Color::DarkGray))
+                .wrap(ratatui::widgets::Wrap { trim: true }),
+            help_area,
+        );
+    }
+
+    fn draw_panes(&self, frame: &mut Frame, area: Rect) {
+        let parent_cwd = match self.windows[self.window] {
+            WindowRef::Session(si, _) => self.config.sessions[si].cwd.shallow_clone(),
+            WindowRef::TopLevel(_) => Cwd::default(),
+        };
+        let window_cwd = parent_cwd.joined(&self.window().cwd);
+        let rects = preview::pane_rects(
+            self.split(),
+            u32::from(area.width.saturating_sub(2)),
+            u32::from(area.height.saturating_sub(2)),
+        );
+        // The selected pane is drawn last so that its border is on top.
+        let mut panes = rects.into_iter().enumerate().collect::<Vec<_>>();
+        let selected = panes.remove(self.pane.min(panes.len() - 1));
+        panes.push(selected);
+
+        for (index, (rect, pane)) in panes {
+            let pane_area = Rect {
+                x: area.x + rect.x as u16,
+                y: area.y + rect.y as u16,
+                width: rect.width as u16 + 2,
+                height: rect.height as u16 + 2,
+            };
+            let active = if pane.active { "*" } else { "" };
+            let mut block = Block::bordered().title(format!(" {}{} ", index, active));
+            if index == self.pane {
+                block = block.border_style(Style::default().fg(Color::Yellow));
+            }
+            let command = pane.shell_command.as_deref().unwrap_or("(shell)");
+            let mut lines = vec![Line::raw(command)];
+            if let Some(cwd) = window_cwd.joined(&pane.cwd).to_path() {
+                lines.push(Line::raw(cwd.to_string_lossy().into_owned()));
+            }
+            frame.render_widget(Paragraph::new(lines).block(block), pane_area);
+        }
+    }
+}
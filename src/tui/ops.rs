@@ -0,0 +1,201 @@
+//! Editing operations on split trees. Panes are addressed by their index
+//! (in tmux index order, see [`Split::pane_iter`]).
+
+use crate::config::sizes;
+use crate::config::{HSplitPart, Pane, Split, VSplitPart};
+
+/// Direction of a split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    /// Side by side panes (`left`/`right`)
+    Horizontal,
+    /// Stacked panes (`top`/`bottom`)
+    Vertical,
+}
+
+/// Smallest size of a part (in percent) when resizing
+const MIN_PERCENTAGE: i32 = 5;
+
+pub fn pane_count(split: &Split) -> usize {
+    split.pane_iter().count()
+}
+
+pub fn pane_mut(split: &mut Split, index: usize) -> Option<&mut Pane> {
+    split.pane_iter_mut().nth(index)
+}
+
+fn leaf_mut(split: &mut Split, index: usize) -> Option<&mut Split> {
+    if let Split::Pane(_) = split {
+        return (index == 0).then_some(split);
+    }
+    let (first, second) = match split {
+        Split::H { left, right } => (&mut left.split, &mut right.split),
+        Split::V { top, bottom } => (&mut top.split, &mut bottom.split),
+        Split::Pane(_) => unreachable!(),
+    };
+    let first_count = pane_count(first);
+    if index < first_count {
+        leaf_mut(first, index)
+    } else {
+        leaf_mut(second, index - first_count)
+    }
+}
+
+/// Splits the pane at `index` in halves. The new pane (right or bottom, at
+/// `index + 1`) starts in the same directory.
+pub fn split_pane(split: &mut Split, index: usize, axis: Axis) -> bool {
+    let Some(leaf) = leaf_mut(split, index) else {
+        return false;
+    };
+    let Split::Pane(pane) = std::mem::take(leaf) else {
+        unreachable!()
+    };
+    let new_pane = Split::Pane(Pane {
+        cwd: pane.cwd.clone(),
+        ..Default::default()
+    });
+    let old_pane = Split::Pane(pane);
+    *leaf = match axis {
+        Axis::Horizontal => Split::H {
+            left: HSplitPart {
+                split: Box::new(old_pane),
+                ..Default::default()
+            },
+            right: HSplitPart {
+                split: Box::new(new_pane),
+                ..Default::default()
+            },
+        },
+        Axis::Vertical => Split::V {
+            top: VSplitPart {
+                split: Box::new(old_pane),
+                ..Default::default()
+            },
+            bottom: VSplitPart {
+                split: Box::new(new_pane),
+                ..Default::default()
+            },
+        },
+    };
+    true
+}
+
+/// Removes the pane at `index`; its sibling takes its space. The last pane
+/// of a window can't be closed.
+pub fn close_pane(split: &mut Split, index: usize) -> bool {
+    let (first, second) = match split {
+        Split::Pane(_) => return false,
+        Split::H { left, right } => (&mut left.split, &mut right.split),
+        Split::V { top, bottom } => (&mut top.split, &mut bottom.split),
+    };
+    let first_count = pane_count(first);
+    let (target, sibling, target_index) = if index < first_count {
+        (first, second, index)
+    } else {
+        (second, first, index - first_count)
+    };
+    if let Split::Pane(_) = **target {
+        if target_index != 0 {
+            return false;
+        }
+        *split = std::mem::take(&mut **sibling);
+        true
+    } else {
+        close_pane(target, target_index)
+    }
+}
+
+/// Grows (or shrinks with a negative `delta`) the pane at `index` by
+/// `delta` percent along `axis`, by moving the border of the innermost
+/// split along `axis` containing it.
+pub fn resize_pane(split: &mut Split, index: usize, axis: Axis, delta: i32) -> bool {
+    match split {
+        Split::Pane(_) => false,
+        Split::H { left, right } => {
+            let first_count = pane_count(&left.split);
+            let in_first = index < first_count;
+            let handled = if in_first {
+                resize_pane(&mut left.split, index, axis, delta)
+            } else {
+                resize_pane(&mut right.split, index - first_count, axis, delta)
+            };
+            if handled || axis != Axis::Horizontal {
+                return handled;
+            }
+            let percentage = first_percentage(left.width.as_deref(), right.width.as_deref());
+            let delta = if in_first { delta } else { -delta };
+            left.width = Some(format!("{}%", clamp_percentage(percentage + delta)));
+            right.width = None;
+            true
+        }
+        Split::V { top, bottom } => {
+            let first_count = pane_count(&top.split);
+            let in_first = index < first_count;
+            let handled = if in_first {
+                resize_pane(&mut top.split, index, axis, delta)
+            } else {
+                resize_pane(&mut bottom.split, index - first_count, axis, delta)
+            };
+            if handled || axis != Axis::Vertical {
+                return handled;
+            }
+            let percentage = first_percentage(top.height.as_deref(), bottom.height.as_deref());
+            let delta = if in_first { delta } else { -delta };
+            top.height = Some(format!("{}%", clamp_percentage(percentage + delta)));
+            bottom.height = None;
+            true
+        }
+    }
+}
+
+/// Size of the first part in percent (cell sizes are taken as percentages)
+fn first_percentage(first_size: Option<&str>, second_size: Option<&str>) -> i32 {
+    // With 101 cells, 100 are left for the parts (one for the border).
+    sizes::part_sizes(first_size, second_size, 101).0 as i32
+}
+
+fn clamp_percentage(percentage: i32) -> i32 {
+    percentage.clamp(MIN_PERCENTAGE, 100 - MIN_PERCENTAGE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_edit_operations() {
+        let mut split = Split::default();
+        assert!(split_pane(&mut split, 0, Axis::Horizontal));
+        assert!(split_pane(&mut split, 1, Axis::Vertical));
+        assert_eq!(pane_count(&split), 3);
+        pane_mut(&mut split, 2).unwrap().shell_command = Some("htop".to_string());
+
+        assert!(resize_pane(&mut split, 0, Axis::Horizontal, 10));
+        assert!(resize_pane(&mut split, 2, Axis::Vertical, 20));
+        assert!(!resize_pane(&mut Split::default(), 0, Axis::Vertical, 5));
+
+        let expected: Config = serde_yaml::from_str(
+            r#"
+            windows:
+              - left:
+                  width: 60%
+                right:
+                  top:
+                    height: 30%
+                  bottom:
+                    shell_command: htop
+            "#,
+        )
+        .unwrap();
+        assert_eq!(split, *expected.windows[0].root_split);
+
+        assert!(close_pane(&mut split, 1));
+        assert!(!close_pane(&mut split, 5));
+        let panes = split.pane_iter().collect::<Vec<_>>();
+        assert_eq!(panes.len(), 2);
+        assert_eq!(panes[1].shell_command.as_deref(), Some("htop"));
+        assert!(close_pane(&mut split, 0));
+        assert!(!close_pane(&mut split, 0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Colors of the output. Whether to color is decided once at startup from
+//! `--color`, `NO_COLOR` and whether STDERR (for messages) and STDOUT (for
+//! results) are terminals. The colors of the message parts can be changed
+//! with `TMUX_LAYOUT_COLORS`, e.g. `error=magenta,highlight=bright cyan`.
+
+use colored::{Color, Colorize};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Environment variable overriding the colors of [`Role`]s
+pub const COLORS_ENV: &str = "TMUX_LAYOUT_COLORS";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color output to terminals unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Parts of the output colored alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// `error:` prefix
+    Error,
+    /// `warning:` prefix
+    Warning,
+    /// `info:` prefix
+    Info,
+    /// `debug:`/`trace:` prefixes and timestamps
+    Debug,
+    /// Names and paths quoted in messages
+    Highlight,
+    /// Successful results (e.g. of `check`)
+    Success,
+    /// Headings in results (e.g. of `preview`)
+    Title,
+}
+
+impl Role {
+    const ALL: [Role; 7] = [
+        Role::Error,
+        Role::Warning,
+        Role::Info,
+        Role::Debug,
+        Role::Highlight,
+        Role::Success,
+        Role::Title,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Role::Error => "error",
+            Role::Warning => "warning",
+            Role::Info => "info",
+            Role::Debug => "debug",
+            Role::Highlight => "highlight",
+            Role::Success => "success",
+            Role::Title => "title",
+        }
+    }
+
+    fn default_color(self) -> Option<Color> {
+        match self {
+            Role::Error => Some(Color::Red),
+            Role::Warning | Role::Highlight => Some(Color::Yellow),
+            Role::Info | Role::Success => Some(Color::Green),
+            Role::Debug | Role::Title => None,
+        }
+    }
+}
+
+/// Colors of the roles (indexed like [`Role::ALL`]), `None` for no color
+type Theme = [Option<Color>; Role::ALL.len()];
+
+static STDERR_COLORS: AtomicBool = AtomicBool::new(false);
+static STDOUT_COLORS: AtomicBool = AtomicBool::new(false);
+static THEME: Mutex<Option<Theme>> = Mutex::new(None);
+
+/// Decides whether to color STDERR and STDOUT and loads the theme. Output
+/// isn't colored before.
+pub fn init(choice: ColorChoice) -> Result<(), String> {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = |is_terminal: bool| match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => !no_color && is_terminal,
+    };
+    STDERR_COLORS.store(enabled(io::stderr().is_terminal()), Ordering::Relaxed);
+    STDOUT_COLORS.store(enabled(io::stdout().is_terminal()), Ordering::Relaxed);
+    // Decided per stream above (`colored` only looks at STDOUT)
+    colored::control::set_override(true);
+
+    let theme = match std::env::var(COLORS_ENV) {
+        Ok(spec) => parse_theme(&spec).map_err(|err| format!("invalid {}: {}", COLORS_ENV, err))?,
+        Err(_) => parse_theme("").unwrap(),
+    };
+    *THEME.lock().unwrap() = Some(theme);
+    Ok(())
+}
+
+/// `text` colored as `role` if messages (on STDERR) are colored
+pub fn paint(text: &str, role: Role) -> String {
+    paint_if(STDERR_COLORS.load(Ordering::Relaxed), text, role)
+}
+
+/// `text` colored as `role` if results (on STDOUT) are colored
+pub fn paint_stdout(text: &str, role: Role) -> String {
+    paint_if(STDOUT_COLORS.load(Ordering::Relaxed), text, role)
+}
+
+fn paint_if(enabled: bool, text: &str, role: Role) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+    let color = match THEME.lock().unwrap().as_ref() {
+        Some(theme) => theme[role as usize],
+        None => role.default_color(),
+    };
+    let colored = match color {
+        Some(color) => text.color(color),
+        None if role == Role::Debug => text.dimmed(),
+        None => text.normal(),
+    };
+    match role {
+        Role::Error | Role::Warning | Role::Info | Role::Title => colored.bold().to_string(),
+        _ => colored.to_string(),
+    }
+}
+
+/// Parses comma-separated `<role>=<color>` overrides of the default colors.
+/// The color `none` removes the color of a role.
+fn parse_theme(spec: &str) -> Result<Theme, String> {
+    let mut theme = Role::ALL.map(Role::default_color);
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, color) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected <role>=<color>, got '{}'", entry))?;
+        let role = Role::ALL
+            .iter()
+            .copied()
+            .find(|role| role.name() == name.trim())
+            .ok_or_else(|| format!("unknown role '{}'", name.trim()))?;
+        theme[role as usize] = match color.trim() {
+            "none" => None,
+            color => Some(
+                color
+                    .parse::<Color>()
+                    .map_err(|_| format!("unknown color '{}'", color))?,
+            ),
+        };
+    }
+    Ok(theme)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = parse_theme("").unwrap();
+        assert_eq!(theme[Role::Error as usize], Some(Color::Red));
+        assert_eq!(theme[Role::Title as usize], None);
+
+        let theme = parse_theme("error=magenta, highlight = bright cyan,warning=none").unwrap();
+        assert_eq!(theme[Role::Error as usize], Some(Color::Magenta));
+        assert_eq!(theme[Role::Highlight as usize], Some(Color::BrightCyan));
+        assert_eq!(theme[Role::Warning as usize], None);
+        assert_eq!(theme[Role::Info as usize], Some(Color::Green));
+
+        assert!(parse_theme("error").is_err());
+        assert!(parse_theme("fatal=red").is_err());
+        assert!(parse_theme("error=reddish").is_err());
+    }
+
+    #[test]
+    fn test_paint_disabled() {
+        assert_eq!(paint_if(false, "error:", Role::Error), "error:");
+    }
+}